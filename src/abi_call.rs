@@ -0,0 +1,297 @@
+use crate::error::AppError;
+use alloy::dyn_abi::{DynSolType, DynSolValue, FunctionExt, JsonAbiExt, Specifier};
+use alloy::json_abi::{Function, JsonAbi};
+use alloy_primitives::hex;
+use serde::Serialize;
+use serde_json::Value;
+
+/// An input or output of a function, decoded by its ABI type.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CallOutput {
+    /// Empty when the ABI leaves the output unnamed.
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub value: Value,
+}
+
+/// Finds `name` in `abi` and ABI-encodes the call with `args`.
+///
+/// Overloads are told apart by the number of arguments. Arguments are JSON
+/// strings, numbers or booleans parsed as the parameter type (e.g. `"0x.."`
+/// for an address, `"1000"` for a `uint256`), and JSON arrays for arrays and
+/// tuples.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_encode_call() {
+/// let abi: JsonAbi = serde_json::from_str(ERC20_ABI).unwrap();
+/// let (function, input) = encode_call(&abi, "balanceOf", &[json!(owner)]).unwrap();
+/// # }
+/// ```
+pub fn encode_call<'abi>(
+    abi: &'abi JsonAbi,
+    name: &str,
+    args: &[Value],
+) -> Result<(&'abi Function, Vec<u8>), AppError> {
+    let function = abi
+        .function(name)
+        .and_then(|overloads| {
+            overloads
+                .iter()
+                .find(|function| function.inputs.len() == args.len())
+        })
+        .ok_or_else(|| {
+            AppError::InvalidRequest(format!(
+                "no function {} taking {} arguments in the ABI",
+                name,
+                args.len()
+            ))
+        })?;
+    let values = function
+        .inputs
+        .iter()
+        .zip(args)
+        .map(|(param, arg)| {
+            let ty = param.resolve().map_err(|error| {
+                AppError::InvalidRequest(format!("invalid type of {}: {}", param.name, error))
+            })?;
+            coerce(&ty, arg).map_err(|error| {
+                AppError::InvalidRequest(format!("invalid argument {}: {}", param.name, error))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let input = function
+        .abi_encode_input(&values)
+        .map_err(|error| AppError::InvalidRequest(error.to_string()))?;
+    Ok((function, input))
+}
+
+/// Decodes the output of a call to `function` into typed JSON values.
+pub fn decode_output(function: &Function, output: &[u8]) -> Result<Vec<CallOutput>, AppError> {
+    let values = function.abi_decode_output(output, true).map_err(|error| {
+        AppError::Decode(format!("{} returned {}", function.signature(), error))
+    })?;
+    Ok(function
+        .outputs
+        .iter()
+        .zip(&values)
+        .map(|(param, value)| CallOutput {
+            name: param.name.clone(),
+            kind: param.selector_type().into_owned(),
+            value: to_json(value),
+        })
+        .collect())
+}
+
+/// Finds the function of `abi` whose selector starts `input` and decodes its
+/// arguments; `None` when no function has that selector.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_decode_input() {
+/// let abi: JsonAbi = serde_json::from_str(ERC20_ABI).unwrap();
+/// let (function, args) = decode_input(&abi, &calldata).unwrap().unwrap();
+/// assert_eq!(function.name, "transfer");
+/// # }
+/// ```
+pub fn decode_input<'abi>(
+    abi: &'abi JsonAbi,
+    input: &[u8],
+) -> Result<Option<(&'abi Function, Vec<CallOutput>)>, AppError> {
+    let Some(selector) = input.get(..4) else {
+        return Ok(None);
+    };
+    let Some(function) = abi
+        .functions()
+        .find(|function| function.selector() == selector)
+    else {
+        return Ok(None);
+    };
+    let values = function
+        .abi_decode_input(&input[4..], true)
+        .map_err(|error| {
+            AppError::InvalidRequest(format!(
+                "input does not decode as {}: {}",
+                function.signature(),
+                error
+            ))
+        })?;
+    let args = function
+        .inputs
+        .iter()
+        .zip(&values)
+        .map(|(param, value)| CallOutput {
+            name: param.name.clone(),
+            kind: param.selector_type().into_owned(),
+            value: to_json(value),
+        })
+        .collect();
+    Ok(Some((function, args)))
+}
+
+/// Parses a JSON argument as `ty`.
+fn coerce(ty: &DynSolType, arg: &Value) -> Result<DynSolValue, String> {
+    let items = |inner: &DynSolType, items: &[Value]| {
+        items
+            .iter()
+            .map(|item| coerce(inner, item))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    match (ty, arg) {
+        (DynSolType::Array(inner), Value::Array(values)) => {
+            Ok(DynSolValue::Array(items(inner, values)?))
+        }
+        (DynSolType::FixedArray(inner, len), Value::Array(values)) if values.len() == *len => {
+            Ok(DynSolValue::FixedArray(items(inner, values)?))
+        }
+        (DynSolType::Tuple(types), Value::Array(values)) if values.len() == types.len() => types
+            .iter()
+            .zip(values)
+            .map(|(ty, value)| coerce(ty, value))
+            .collect::<Result<Vec<_>, _>>()
+            .map(DynSolValue::Tuple),
+        (_, Value::String(value)) => ty.coerce_str(value).map_err(|error| error.to_string()),
+        (_, Value::Number(_) | Value::Bool(_)) => ty
+            .coerce_str(&arg.to_string())
+            .map_err(|error| error.to_string()),
+        _ => Err(format!("expected a {}", ty)),
+    }
+}
+
+/// Integers are written as decimal strings, since they may not fit a JSON number.
+fn to_json(value: &DynSolValue) -> Value {
+    if let Some(values) = value.as_fixed_seq().or_else(|| value.as_array()) {
+        return Value::Array(values.iter().map(to_json).collect());
+    }
+    match value {
+        DynSolValue::Bool(value) => Value::Bool(*value),
+        DynSolValue::Int(value, _) => Value::String(value.to_string()),
+        DynSolValue::Uint(value, _) => Value::String(value.to_string()),
+        DynSolValue::Address(address) => Value::String(address.to_checksum(None)),
+        DynSolValue::FixedBytes(word, size) => Value::String(hex::encode_prefixed(&word[..*size])),
+        DynSolValue::Bytes(bytes) => Value::String(hex::encode_prefixed(bytes)),
+        DynSolValue::String(value) => Value::String(value.clone()),
+        DynSolValue::Function(function) => Value::String(function.to_string()),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::balanceOfCall;
+    use alloy::sol_types::{SolCall, SolValue};
+    use alloy_primitives::{address, U256};
+    use serde_json::json;
+
+    fn abi() -> JsonAbi {
+        serde_json::from_value(json!([
+            {
+                "type": "function",
+                "name": "balanceOf",
+                "stateMutability": "view",
+                "inputs": [{ "name": "owner", "type": "address" }],
+                "outputs": [{ "name": "balance", "type": "uint256" }]
+            },
+            {
+                "type": "function",
+                "name": "holders",
+                "stateMutability": "view",
+                "inputs": [{ "name": "ids", "type": "uint64[]" }],
+                "outputs": [
+                    { "name": "", "type": "address[]" },
+                    { "name": "active", "type": "bool" }
+                ]
+            }
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_encode_call_matches_the_static_binding() {
+        let abi = abi();
+        let owner = address!("1111111111111111111111111111111111111111");
+        let (function, input) = encode_call(&abi, "balanceOf", &[json!(owner)]).unwrap();
+
+        assert_eq!(function.name, "balanceOf");
+        assert_eq!(input, balanceOfCall { owner }.abi_encode());
+    }
+
+    #[test]
+    fn test_encode_call_rejects_bad_arguments() {
+        let abi = abi();
+        let invalid = |name, args: &[Value]| {
+            matches!(
+                encode_call(&abi, name, args),
+                Err(AppError::InvalidRequest(_))
+            )
+        };
+        assert!(invalid("transfer", &[]));
+        assert!(invalid("balanceOf", &[]));
+        assert!(invalid("balanceOf", &[json!("not an address")]));
+        assert!(invalid("holders", &[json!(1)]));
+        assert!(encode_call(&abi, "holders", &[json!([1, "2"])]).is_ok());
+    }
+
+    #[test]
+    fn test_decode_output_types_each_value() {
+        let abi = abi();
+        let function = &abi.function("holders").unwrap()[0];
+        let holder = address!("2222222222222222222222222222222222222222");
+        let output = (vec![holder], true).abi_encode_params();
+
+        assert_eq!(
+            decode_output(function, &output).unwrap(),
+            vec![
+                CallOutput {
+                    name: String::new(),
+                    kind: "address[]".to_string(),
+                    value: json!([holder.to_checksum(None)]),
+                },
+                CallOutput {
+                    name: "active".to_string(),
+                    kind: "bool".to_string(),
+                    value: json!(true),
+                },
+            ]
+        );
+
+        let balance_of = &abi.function("balanceOf").unwrap()[0];
+        let output = U256::from(5000).abi_encode();
+        assert_eq!(decode_output(balance_of, &output).unwrap()[0].value, "5000");
+        assert!(matches!(
+            decode_output(balance_of, &[0x01]),
+            Err(AppError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn test_decode_input_by_selector() {
+        let abi = abi();
+        let owner = address!("1111111111111111111111111111111111111111");
+        let input = balanceOfCall { owner }.abi_encode();
+
+        let (function, args) = decode_input(&abi, &input).unwrap().unwrap();
+        assert_eq!(function.signature(), "balanceOf(address)");
+        assert_eq!(
+            args,
+            vec![CallOutput {
+                name: "owner".to_string(),
+                kind: "address".to_string(),
+                value: json!(owner.to_checksum(None)),
+            }]
+        );
+
+        assert!(decode_input(&abi, &[0xde, 0xad, 0xbe, 0xef])
+            .unwrap()
+            .is_none());
+        assert!(decode_input(&abi, &[]).unwrap().is_none());
+        assert!(matches!(
+            decode_input(&abi, &input[..8]),
+            Err(AppError::InvalidRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,234 @@
+use crate::error::AppError;
+use alloy::providers::Provider;
+use alloy_primitives::{Address, U256};
+use serde::Serialize;
+use std::future::IntoFuture;
+
+/// Estimated first and last blocks an address was active in, as returned by
+/// `/activity/{address}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Activity {
+    /// First block after which the address had sent a transaction or held a
+    /// balance; `None` when it has done neither by the head block.
+    pub first_seen: Option<u64>,
+    /// First block after which its nonce and balance stayed as they are at the head.
+    pub last_seen: Option<u64>,
+    /// Head block the search ran against.
+    pub head: u64,
+}
+
+/// Nonce and balance of an address after a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct State {
+    nonce: u64,
+    balance: U256,
+}
+
+impl State {
+    fn is_active(&self) -> bool {
+        self.nonce > 0 || !self.balance.is_zero()
+    }
+}
+
+/// Estimates when `address` was first and last active, by binary search over
+/// its nonce and balance at past blocks; the node must serve historical state.
+///
+/// Each search takes about `log2(head)` pairs of node calls. The search
+/// assumes activity never undoes itself, so it can be off when:
+///
+/// - a balance was emptied without a transaction from the address, e.g. a
+///   contract paying out everything it held, before it became active again;
+/// - the balance later returned to an earlier value with the same nonce, so
+///   `last_seen` is the block where that value was first reached;
+/// - activity left neither trace, such as calls that moved no value.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_activity() {
+/// let activity = activity(provider.as_ref(), address).await.unwrap();
+/// assert!(activity.first_seen <= activity.last_seen);
+/// # }
+/// ```
+pub async fn activity(provider: &dyn Provider, address: Address) -> Result<Activity, AppError> {
+    let head = provider.get_block_number().await?;
+    let current = state_at(provider, address, head).await?;
+    if !current.is_active() {
+        return Ok(Activity {
+            first_seen: None,
+            last_seen: None,
+            head,
+        });
+    }
+
+    let first_seen = first_block(provider, address, 0, head, |state| state.is_active()).await?;
+    let last_seen = first_block(provider, address, first_seen, head, |state| {
+        state == current
+    })
+    .await?;
+    Ok(Activity {
+        first_seen: Some(first_seen),
+        last_seen: Some(last_seen),
+        head,
+    })
+}
+
+/// State of `address` after `block`.
+async fn state_at(
+    provider: &dyn Provider,
+    address: Address,
+    block: u64,
+) -> Result<State, AppError> {
+    let (nonce, balance) = futures::try_join!(
+        provider
+            .get_transaction_count(address)
+            .number(block)
+            .into_future(),
+        provider.get_balance(address).number(block).into_future()
+    )?;
+    Ok(State { nonce, balance })
+}
+
+/// First block in `low..=high` whose state matches `found`, given that `high`'s does
+/// and that once a block matches, every later one does.
+async fn first_block(
+    provider: &dyn Provider,
+    address: Address,
+    mut low: u64,
+    mut high: u64,
+    found: impl Fn(State) -> bool,
+) -> Result<u64, AppError> {
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if found(state_at(provider, address, middle).await?) {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    Ok(low)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloy::eips::{BlockId, BlockNumberOrTag};
+    use alloy::providers::{ProviderCall, RootProvider, RpcWithBlock};
+    use alloy::rpc::client::NoParams;
+    use alloy_primitives::U64;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Provider whose head is the last block with a nonce, and whose nonce and
+    /// balance after block `n` are `nonces[n]` and `balances[n]`.
+    pub(crate) struct HistoryProvider {
+        nonces: Arc<Vec<u64>>,
+        balances: Arc<Vec<u64>>,
+        pub(crate) nonce_calls: AtomicUsize,
+    }
+
+    impl HistoryProvider {
+        pub(crate) fn new(nonces: Vec<u64>, balances: Vec<u64>) -> Self {
+            assert_eq!(nonces.len(), balances.len());
+            HistoryProvider {
+                nonces: Arc::new(nonces),
+                balances: Arc::new(balances),
+                nonce_calls: AtomicUsize::new(0),
+            }
+        }
+
+        fn head(&self) -> u64 {
+            self.nonces.len() as u64 - 1
+        }
+    }
+
+    fn number(block_id: BlockId, head: u64) -> usize {
+        match block_id {
+            BlockId::Number(BlockNumberOrTag::Number(number)) => number.min(head) as usize,
+            _ => head as usize,
+        }
+    }
+
+    impl Provider for HistoryProvider {
+        fn get_block_number(&self) -> ProviderCall<NoParams, U64, u64> {
+            ProviderCall::ready(Ok(self.head()))
+        }
+
+        fn get_transaction_count(&self, _address: Address) -> RpcWithBlock<Address, U64, u64> {
+            self.nonce_calls.fetch_add(1, Ordering::SeqCst);
+            let (nonces, head) = (self.nonces.clone(), self.head());
+            RpcWithBlock::new_provider(move |block_id| {
+                ProviderCall::ready(Ok(nonces[number(block_id, head)]))
+            })
+        }
+
+        fn get_balance(&self, _address: Address) -> RpcWithBlock<Address, U256> {
+            let (balances, head) = (self.balances.clone(), self.head());
+            RpcWithBlock::new_provider(move |block_id| {
+                ProviderCall::ready(Ok(U256::from(balances[number(block_id, head)])))
+            })
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("HistoryProvider does not support `root`")
+        }
+    }
+
+    /// Funded at block 300, first sends at 400 and last at 700, of 1000 blocks.
+    pub(crate) fn wallet() -> HistoryProvider {
+        let nonces = (0..=1000)
+            .map(|block| match block {
+                0..400 => 0,
+                400..700 => 1,
+                _ => 2,
+            })
+            .collect();
+        let balances = (0..=1000)
+            .map(|block| match block {
+                0..300 => 0,
+                300..400 => 100,
+                400..700 => 60,
+                _ => 20,
+            })
+            .collect();
+        HistoryProvider::new(nonces, balances)
+    }
+
+    #[tokio::test]
+    async fn test_finds_first_and_last_activity() {
+        let provider = wallet();
+        let found = activity(&provider, Address::ZERO).await.unwrap();
+        assert_eq!(
+            found,
+            Activity {
+                first_seen: Some(300),
+                last_seen: Some(700),
+                head: 1000,
+            }
+        );
+        // Two binary searches over 1000 blocks, plus the head.
+        assert!(provider.nonce_calls.load(Ordering::SeqCst) <= 1 + 2 * 10);
+    }
+
+    #[tokio::test]
+    async fn test_inactive_and_receive_only_addresses() {
+        let unused = HistoryProvider::new(vec![0; 50], vec![0; 50]);
+        let found = activity(&unused, Address::ZERO).await.unwrap();
+        assert_eq!(found.first_seen, None);
+        assert_eq!(found.last_seen, None);
+
+        // Paid at block 10 and again at 30, never sending anything.
+        let balances = (0..50)
+            .map(|block| match block {
+                0..10 => 0,
+                10..30 => 5,
+                _ => 8,
+            })
+            .collect();
+        let receiver = HistoryProvider::new(vec![0; 50], balances);
+        let found = activity(&receiver, Address::ZERO).await.unwrap();
+        assert_eq!(found.first_seen, Some(10));
+        assert_eq!(found.last_seen, Some(30));
+        assert_eq!(found.head, 49);
+    }
+}
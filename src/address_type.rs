@@ -0,0 +1,188 @@
+use alloy_primitives::{b256, Address, B256, U256};
+use serde::Serialize;
+
+/// EIP-1967 storage slot holding the implementation address:
+/// `bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)`.
+pub const EIP1967_IMPLEMENTATION_SLOT: B256 =
+    b256!("360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc");
+
+/// EIP-1967 storage slot holding the proxy admin address:
+/// `bytes32(uint256(keccak256("eip1967.proxy.admin")) - 1)`.
+pub const EIP1967_ADMIN_SLOT: B256 =
+    b256!("b53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103");
+
+/// Runtime bytecode of an EIP-1167 minimal proxy surrounding the 20-byte implementation.
+const EIP1167_PREFIX: [u8; 10] = [0x36, 0x3d, 0x3d, 0x37, 0x3d, 0x3d, 0x3d, 0x36, 0x3d, 0x73];
+const EIP1167_SUFFIX: [u8; 15] = [
+    0x5a, 0xf4, 0x3d, 0x82, 0x80, 0x3e, 0x90, 0x3d, 0x91, 0x60, 0x2b, 0x57, 0xfd, 0x5b, 0xf3,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressKind {
+    Eoa,
+    Contract,
+    Proxy,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AddressTypeResponse {
+    #[serde(rename = "type")]
+    pub kind: AddressKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub implementation: Option<Address>,
+}
+
+/// Body of `/proxy/{address}`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ProxyResponse {
+    /// Whether the EIP-1967 implementation slot is set; `false` for anything
+    /// that is not a recognized proxy, including EOAs.
+    pub proxy: bool,
+    pub implementation: Option<Address>,
+    /// Set by transparent proxies; UUPS proxies leave it empty.
+    pub admin: Option<Address>,
+}
+
+/// Reads the addresses held by the EIP-1967 implementation and admin slots.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_proxy_slots() {
+/// let response = proxy_slots(U256::ZERO, U256::ZERO);
+/// assert!(!response.proxy);
+/// # }
+/// ```
+pub fn proxy_slots(implementation_slot: U256, admin_slot: U256) -> ProxyResponse {
+    let address = |slot: U256| (!slot.is_zero()).then(|| Address::from_word(B256::from(slot)));
+    let implementation = address(implementation_slot);
+    ProxyResponse {
+        proxy: implementation.is_some(),
+        implementation,
+        admin: address(admin_slot),
+    }
+}
+
+/// Extracts the implementation address from EIP-1167 minimal proxy bytecode.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_eip1167_implementation() {
+/// assert_eq!(eip1167_implementation(&[]), None);
+/// # }
+/// ```
+pub fn eip1167_implementation(code: &[u8]) -> Option<Address> {
+    let body = code.strip_prefix(&EIP1167_PREFIX[..])?;
+    let implementation = body.strip_suffix(&EIP1167_SUFFIX[..])?;
+    (implementation.len() == Address::len_bytes()).then(|| Address::from_slice(implementation))
+}
+
+/// Classifies an address from its bytecode and the value of its EIP-1967 implementation slot.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_classify_address() {
+/// let response = classify_address(&[], U256::ZERO);
+/// assert_eq!(response.kind, AddressKind::Eoa);
+/// # }
+/// ```
+pub fn classify_address(code: &[u8], implementation_slot: U256) -> AddressTypeResponse {
+    if code.is_empty() {
+        return AddressTypeResponse {
+            kind: AddressKind::Eoa,
+            implementation: None,
+        };
+    }
+
+    let implementation = eip1167_implementation(code).or_else(|| {
+        (!implementation_slot.is_zero())
+            .then(|| Address::from_word(B256::from(implementation_slot)))
+    });
+
+    AddressTypeResponse {
+        kind: if implementation.is_some() {
+            AddressKind::Proxy
+        } else {
+            AddressKind::Contract
+        },
+        implementation,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+
+    const IMPLEMENTATION: Address = address!("bebebebebebebebebebebebebebebebebebebebe");
+
+    fn minimal_proxy() -> Vec<u8> {
+        [
+            &EIP1167_PREFIX[..],
+            IMPLEMENTATION.as_slice(),
+            &EIP1167_SUFFIX[..],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_classify_eoa() {
+        let response = classify_address(&[], U256::ZERO);
+        assert_eq!(response.kind, AddressKind::Eoa);
+        assert_eq!(response.implementation, None);
+    }
+
+    #[test]
+    fn test_classify_plain_contract() {
+        let response = classify_address(&[0x60, 0x80, 0x60, 0x40, 0x52], U256::ZERO);
+        assert_eq!(response.kind, AddressKind::Contract);
+        assert_eq!(response.implementation, None);
+    }
+
+    #[test]
+    fn test_classify_eip1167_proxy() {
+        let response = classify_address(&minimal_proxy(), U256::ZERO);
+        assert_eq!(response.kind, AddressKind::Proxy);
+        assert_eq!(response.implementation, Some(IMPLEMENTATION));
+    }
+
+    #[test]
+    fn test_classify_eip1967_proxy() {
+        let slot = U256::from_be_slice(IMPLEMENTATION.as_slice());
+        let response = classify_address(&[0x60, 0x80], slot);
+        assert_eq!(response.kind, AddressKind::Proxy);
+        assert_eq!(response.implementation, Some(IMPLEMENTATION));
+    }
+
+    #[test]
+    fn test_proxy_slots() {
+        let admin = address!("adadadadadadadadadadadadadadadadadadadad");
+        let word = |address: Address| U256::from_be_slice(address.as_slice());
+        assert_eq!(
+            proxy_slots(word(IMPLEMENTATION), word(admin)),
+            ProxyResponse {
+                proxy: true,
+                implementation: Some(IMPLEMENTATION),
+                admin: Some(admin),
+            }
+        );
+        let uups = proxy_slots(word(IMPLEMENTATION), U256::ZERO);
+        assert!(uups.proxy);
+        assert_eq!(uups.admin, None);
+        assert!(!proxy_slots(U256::ZERO, U256::ZERO).proxy);
+    }
+
+    #[test]
+    fn test_truncated_minimal_proxy_is_a_contract() {
+        let mut code = minimal_proxy();
+        code.remove(12);
+        assert_eq!(eip1167_implementation(&code), None);
+        assert_eq!(
+            classify_address(&code, U256::ZERO).kind,
+            AddressKind::Contract
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use crate::config;
+use crate::error::AppError;
+use alloy::eips::{BlockId, BlockNumberOrTag};
+use alloy::providers::Provider;
+use std::env;
+use std::sync::Arc;
+
+/// Response header naming the provider that served the request.
+pub const SERVED_BY_HEADER: &str = "x-served-by";
+
+/// Which provider served a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServedBy {
+    Default,
+    Archive,
+}
+
+impl ServedBy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServedBy::Default => "default",
+            ServedBy::Archive => "archive",
+        }
+    }
+}
+
+/// Archive node settings read from the environment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveConfig {
+    pub url: String,
+    pub threshold: u64,
+}
+
+/// Reads `ARCHIVE_RPC_URL` and `ARCHIVE_BLOCK_THRESHOLD`; returns `None` without an archive node.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_archive_config() {
+/// let config = get_archive_config();
+/// assert!(config.is_none());
+/// # }
+/// ```
+pub fn get_archive_config() -> Option<ArchiveConfig> {
+    let url = env::var("ARCHIVE_RPC_URL").ok()?;
+    Some(ArchiveConfig {
+        url,
+        threshold: config::env_or("ARCHIVE_BLOCK_THRESHOLD", 128),
+    })
+}
+
+/// Sends queries for blocks older than `threshold` to an archive node.
+pub struct ArchiveRouter {
+    provider: Arc<dyn Provider>,
+    threshold: u64,
+}
+
+/// Whether `block_id` is more than `threshold` blocks behind `head`.
+///
+/// Block hashes are always treated as historical, since their age is unknown
+/// and an archive node can serve any block.
+fn is_historical(block_id: BlockId, head: u64, threshold: u64) -> bool {
+    match block_id {
+        BlockId::Hash(_) => true,
+        BlockId::Number(BlockNumberOrTag::Earliest) => true,
+        BlockId::Number(BlockNumberOrTag::Number(number)) => {
+            head.saturating_sub(number) > threshold
+        }
+        BlockId::Number(_) => false,
+    }
+}
+
+impl ArchiveRouter {
+    pub fn new(provider: Arc<dyn Provider>, threshold: u64) -> Self {
+        ArchiveRouter {
+            provider,
+            threshold,
+        }
+    }
+
+    /// Picks the provider for a query at `block_id`, asking `default` for the head when needed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_route() {
+    /// let (provider, served_by) = router.route(&provider, BlockId::latest()).await.unwrap();
+    /// assert_eq!(served_by, ServedBy::Default);
+    /// # }
+    /// ```
+    pub async fn route(
+        &self,
+        default: &Arc<dyn Provider>,
+        block_id: BlockId,
+    ) -> Result<(Arc<dyn Provider>, ServedBy), AppError> {
+        let head = match block_id {
+            BlockId::Number(BlockNumberOrTag::Number(_)) => default.get_block_number().await?,
+            _ => 0,
+        };
+        if is_historical(block_id, head, self.threshold) {
+            Ok((self.provider.clone(), ServedBy::Archive))
+        } else {
+            Ok((default.clone(), ServedBy::Default))
+        }
+    }
+}
+
+/// Routes to the archive node when one is configured, and to `default` otherwise.
+pub async fn route(
+    archive: Option<&ArchiveRouter>,
+    default: &Arc<dyn Provider>,
+    block_id: BlockId,
+) -> Result<(Arc<dyn Provider>, ServedBy), AppError> {
+    match archive {
+        Some(archive) => archive.route(default, block_id).await,
+        None => Ok((default.clone(), ServedBy::Default)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::B256;
+
+    #[test]
+    fn test_is_historical() {
+        assert!(is_historical(BlockId::number(10), 1000, 128));
+        assert!(is_historical(BlockId::earliest(), 1000, 128));
+        assert!(is_historical(BlockId::from(B256::ZERO), 1000, 128));
+        assert!(!is_historical(BlockId::number(872), 1000, 128));
+        assert!(!is_historical(BlockId::number(2000), 1000, 128));
+        assert!(!is_historical(BlockId::latest(), 1000, 128));
+        assert!(!is_historical(BlockId::finalized(), 1000, 128));
+    }
+}
@@ -0,0 +1,96 @@
+use alloy::rlp::Encodable;
+use alloy::rpc::types::Block;
+use alloy_primitives::{Address, Bytes, B256};
+use serde::Serialize;
+
+/// Block summary returned by the `/block` routes.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BlockResponse {
+    pub number: u64,
+    pub hash: B256,
+    pub parent_hash: B256,
+    pub timestamp: u64,
+    pub miner: Address,
+    pub gas_used: u64,
+    pub gas_limit: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_fee_per_gas: Option<u64>,
+    /// Hashes of the transactions in the block, in block order.
+    pub transactions: Vec<B256>,
+}
+
+impl From<&Block> for BlockResponse {
+    fn from(block: &Block) -> Self {
+        let header = &block.header;
+        BlockResponse {
+            number: header.number,
+            hash: header.hash,
+            parent_hash: header.parent_hash,
+            timestamp: header.timestamp,
+            miner: header.beneficiary,
+            gas_used: header.gas_used,
+            gas_limit: header.gas_limit,
+            base_fee_per_gas: header.base_fee_per_gas,
+            transactions: block.transactions.hashes().collect(),
+        }
+    }
+}
+
+/// RLP encoding of a block fetched with full transactions.
+///
+/// The RPC block only carries the hashes of its uncles, so they are encoded as an empty list.
+pub fn encode_block(block: Block) -> Bytes {
+    let block = block.map_transactions(|tx| tx.inner).into_consensus();
+    let mut out = Vec::with_capacity(block.length());
+    block.encode(&mut out);
+    out.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::consensus::{self, TxEnvelope};
+    use alloy::rlp::Decodable;
+    use alloy::rpc::types::{BlockTransactions, Header};
+
+    #[test]
+    fn test_block_response_from_block() {
+        let tx = B256::repeat_byte(0x11);
+        let block = Block {
+            header: Header::new(consensus::Header {
+                number: 42,
+                timestamp: 1_700_000_000,
+                gas_used: 21_000,
+                gas_limit: 30_000_000,
+                base_fee_per_gas: Some(7),
+                ..Default::default()
+            }),
+            transactions: BlockTransactions::Hashes(vec![tx]),
+            ..Default::default()
+        };
+
+        let response = BlockResponse::from(&block);
+        assert_eq!(response.number, 42);
+        assert_eq!(response.hash, block.header.hash);
+        assert_eq!(response.base_fee_per_gas, Some(7));
+        assert_eq!(response.transactions, vec![tx]);
+    }
+
+    #[test]
+    fn test_encode_block_round_trips() {
+        let tx = crate::transaction::tests::dummy_transaction();
+        let block = Block {
+            header: Header::new(consensus::Header {
+                number: 1000,
+                ..Default::default()
+            }),
+            transactions: BlockTransactions::Full(vec![tx.clone()]),
+            ..Default::default()
+        };
+
+        let encoded = encode_block(block.clone());
+        let decoded = consensus::Block::<TxEnvelope>::decode(&mut encoded.as_ref()).unwrap();
+        assert_eq!(decoded.header, block.header.inner);
+        assert_eq!(decoded.body.transactions, vec![tx.inner]);
+    }
+}
@@ -0,0 +1,169 @@
+use alloy::eips::BlockId;
+use alloy_primitives::B256;
+use log::error;
+use std::fmt;
+use warp::{Filter, Rejection};
+
+/// Rejection raised when a block identifier cannot be parsed.
+#[derive(Debug)]
+pub struct InvalidBlock(pub String);
+impl warp::reject::Reject for InvalidBlock {}
+
+impl fmt::Display for InvalidBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid block identifier: {}", self.0)
+    }
+}
+
+/// Parses a block identifier as accepted by every read endpoint.
+///
+/// Accepts the tags `latest`, `earliest`, `pending`, `safe` and `finalized`,
+/// decimal block numbers and `0x`-prefixed 32-byte block hashes.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_parse_block_id() {
+/// let block = parse_block_id("safe").unwrap();
+/// assert_eq!(block, BlockId::safe());
+/// assert!(parse_block_id("tomorrow").is_err());
+/// # }
+/// ```
+pub fn parse_block_id(s: &str) -> Result<BlockId, InvalidBlock> {
+    match s {
+        "latest" => Ok(BlockId::latest()),
+        "earliest" => Ok(BlockId::earliest()),
+        "pending" => Ok(BlockId::pending()),
+        "safe" => Ok(BlockId::safe()),
+        "finalized" => Ok(BlockId::finalized()),
+        _ if s.starts_with("0x") => {
+            if s.len() != 66 {
+                return Err(InvalidBlock(s.to_string()));
+            }
+            s.parse::<B256>()
+                .map(BlockId::hash)
+                .map_err(|_| InvalidBlock(s.to_string()))
+        }
+        _ if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) => s
+            .parse::<u64>()
+            .map(BlockId::number)
+            .map_err(|_| InvalidBlock(s.to_string())),
+        _ => Err(InvalidBlock(s.to_string())),
+    }
+}
+
+/// Extracts the block of the `?block=` query parameter, `latest` when absent,
+/// rejecting unparsable values with [`InvalidBlock`].
+///
+/// Other query parameters are left to the route's own query struct.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_block_id_query() {
+/// let route = warp::path!("nonce" / String)
+///     .and(block_id_query())
+///     .and_then(|address: String, block_id: BlockId| async move { ... });
+/// # }
+/// ```
+pub fn block_id_query() -> impl Filter<Extract = (BlockId,), Error = Rejection> + Clone {
+    warp::query::raw()
+        .or(warp::any().map(String::new))
+        .unify()
+        .and_then(|query: String| async move {
+            let block = url::form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == "block")
+                .map(|(_, value)| value.into_owned());
+            match block {
+                Some(block) => parse_block_id(&block).map_err(|error| {
+                    error!("{}", error);
+                    warp::reject::custom(error)
+                }),
+                None => Ok(BlockId::latest()),
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "0x88e96d4537bea4d9c05d12549907b32561d3bf31f45aae734cdc119f13406cb6";
+
+    #[test]
+    fn test_parse_block_id_accepts_tags() {
+        assert_eq!(parse_block_id("latest").unwrap(), BlockId::latest());
+        assert_eq!(parse_block_id("earliest").unwrap(), BlockId::earliest());
+        assert_eq!(parse_block_id("pending").unwrap(), BlockId::pending());
+        assert_eq!(parse_block_id("safe").unwrap(), BlockId::safe());
+        assert_eq!(parse_block_id("finalized").unwrap(), BlockId::finalized());
+    }
+
+    #[test]
+    fn test_parse_block_id_accepts_numbers() {
+        assert_eq!(parse_block_id("0").unwrap(), BlockId::number(0));
+        assert_eq!(
+            parse_block_id("19000000").unwrap(),
+            BlockId::number(19_000_000)
+        );
+        assert_eq!(
+            parse_block_id(&u64::MAX.to_string()).unwrap(),
+            BlockId::number(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn test_parse_block_id_accepts_hashes() {
+        let expected = BlockId::hash(HASH.parse::<B256>().unwrap());
+        assert_eq!(parse_block_id(HASH).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_block_id_rejects_invalid_forms() {
+        let too_large = format!("{}0", u64::MAX);
+        let short_hash = &HASH[..40];
+        let bad_hex = format!("0x{}", "g".repeat(64));
+        for input in [
+            "", "Latest", "head", "-1", "1.5", "1e3", " 1", "0x", "0x10", short_hash, &bad_hex,
+            &too_large,
+        ] {
+            assert!(parse_block_id(input).is_err(), "accepted {:?}", input);
+        }
+    }
+
+    async fn extract(path: &str) -> Result<BlockId, Rejection> {
+        warp::test::request()
+            .path(path)
+            .filter(&block_id_query())
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_block_id_query_defaults_to_latest() {
+        assert_eq!(extract("/").await.unwrap(), BlockId::latest());
+        assert_eq!(extract("/?unit=ether").await.unwrap(), BlockId::latest());
+    }
+
+    #[tokio::test]
+    async fn test_block_id_query_parses_the_block() {
+        let hash = BlockId::hash(HASH.parse::<B256>().unwrap());
+        for (query, expected) in [
+            ("block=safe", BlockId::safe()),
+            ("block=finalized&unit=ether", BlockId::finalized()),
+            ("unit=ether&block=pending", BlockId::pending()),
+            ("block=19000000", BlockId::number(19_000_000)),
+            (&format!("block={}", HASH), hash),
+        ] {
+            let block_id = extract(&format!("/?{}", query)).await.unwrap();
+            assert_eq!(block_id, expected, "{}", query);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_id_query_rejects_invalid_blocks() {
+        for query in ["block=tomorrow", "block=", "block=0x10", "block=-1"] {
+            let rejection = extract(&format!("/?{}", query)).await.unwrap_err();
+            assert!(rejection.find::<InvalidBlock>().is_some(), "{}", query);
+        }
+    }
+}
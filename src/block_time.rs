@@ -0,0 +1,238 @@
+use crate::error::AppError;
+use alloy::eips::BlockId;
+use alloy::providers::Provider;
+use alloy::rpc::types::BlockTransactionsKind;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Blocks closer than this to the head may still be reorged, so their
+/// timestamps are not remembered.
+const REORG_DEPTH: u64 = 64;
+
+/// Most block timestamps remembered; the cache starts over once full.
+const MAX_KNOWN_BLOCKS: usize = 10_000;
+
+/// Resolves timestamps to blocks by binary search over block numbers.
+///
+/// Timestamps seen during earlier searches narrow the initial bounds of the
+/// next one, so repeated queries over the same period take few node calls.
+pub struct BlockTimestamps {
+    known: Mutex<BTreeMap<u64, u64>>,
+}
+
+/// A block resolved from a timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedBlock {
+    pub number: u64,
+    pub timestamp: u64,
+}
+
+impl BlockTimestamps {
+    pub fn new() -> Self {
+        BlockTimestamps {
+            known: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// The last block at or before `timestamp`; `None` when it predates the genesis block.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_block_at() {
+    /// let timestamps = BlockTimestamps::new();
+    /// let block = timestamps.block_at(provider.as_ref(), 1_700_000_000).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn block_at(
+        &self,
+        provider: &dyn Provider,
+        timestamp: u64,
+    ) -> Result<Option<ResolvedBlock>, AppError> {
+        let head = provider.get_block_number().await.map_err(AppError::from)?;
+        let head_timestamp = self.timestamp(provider, head, head).await?;
+        if head_timestamp <= timestamp {
+            return Ok(Some(ResolvedBlock {
+                number: head,
+                timestamp: head_timestamp,
+            }));
+        }
+        let genesis_timestamp = self.timestamp(provider, 0, head).await?;
+        if genesis_timestamp > timestamp {
+            return Ok(None);
+        }
+
+        // `low` is at or before `timestamp`, `high` after it.
+        let (mut low, mut high) = self.bounds(timestamp, head);
+        let mut low_timestamp = self.timestamp(provider, low, head).await?;
+        while high - low > 1 {
+            let middle = low + (high - low) / 2;
+            let middle_timestamp = self.timestamp(provider, middle, head).await?;
+            if middle_timestamp <= timestamp {
+                (low, low_timestamp) = (middle, middle_timestamp);
+            } else {
+                high = middle;
+            }
+        }
+        Ok(Some(ResolvedBlock {
+            number: low,
+            timestamp: low_timestamp,
+        }))
+    }
+
+    /// Closest remembered blocks around `timestamp`, defaulting to genesis and the head.
+    fn bounds(&self, timestamp: u64, head: u64) -> (u64, u64) {
+        let known = self.known.lock().expect("block timestamps poisoned");
+        let low = known
+            .iter()
+            .rev()
+            .find(|(_, &known)| known <= timestamp)
+            .map_or(0, |(&number, _)| number);
+        let high = known
+            .range(low..)
+            .find(|(_, &known)| known > timestamp)
+            .map_or(head, |(&number, _)| number);
+        (low, high)
+    }
+
+    async fn timestamp(
+        &self,
+        provider: &dyn Provider,
+        number: u64,
+        head: u64,
+    ) -> Result<u64, AppError> {
+        if let Some(timestamp) = self
+            .known
+            .lock()
+            .expect("block timestamps poisoned")
+            .get(&number)
+        {
+            return Ok(*timestamp);
+        }
+        let block = provider
+            .get_block(BlockId::number(number), BlockTransactionsKind::Hashes)
+            .await
+            .map_err(AppError::from)?
+            .ok_or_else(|| AppError::NotFound(format!("block {}", number)))?;
+        let timestamp = block.header.timestamp;
+        if number + REORG_DEPTH <= head {
+            let mut known = self.known.lock().expect("block timestamps poisoned");
+            if known.len() >= MAX_KNOWN_BLOCKS {
+                known.clear();
+            }
+            known.insert(number, timestamp);
+        }
+        Ok(timestamp)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloy::consensus;
+    use alloy::eips::BlockNumberOrTag;
+    use alloy::providers::{ProviderCall, RootProvider, RpcWithBlock};
+    use alloy::rpc::client::NoParams;
+    use alloy::rpc::types::{Block, Header};
+    use alloy::transports::TransportResult;
+    use alloy_primitives::{Address, U256, U64};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Chain of 1000 blocks, block `n` mined at `GENESIS_TIME + 12 * n`, whose
+    /// balances equal the block number they are read at.
+    #[derive(Default)]
+    pub(crate) struct TimestampProvider {
+        pub(crate) block_calls: AtomicUsize,
+    }
+
+    pub(crate) const GENESIS_TIME: u64 = 1_600_000_000;
+    const HEAD: u64 = 1000;
+
+    #[async_trait::async_trait]
+    impl Provider for TimestampProvider {
+        fn get_block_number(&self) -> ProviderCall<NoParams, U64, u64> {
+            ProviderCall::ready(Ok(HEAD))
+        }
+
+        async fn get_block(
+            &self,
+            block: BlockId,
+            _kind: BlockTransactionsKind,
+        ) -> TransportResult<Option<Block>> {
+            self.block_calls.fetch_add(1, Ordering::SeqCst);
+            let BlockId::Number(BlockNumberOrTag::Number(number)) = block else {
+                return Ok(None);
+            };
+            Ok((number <= HEAD).then(|| Block {
+                header: Header::new(consensus::Header {
+                    number,
+                    timestamp: GENESIS_TIME + 12 * number,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }))
+        }
+
+        fn get_balance(&self, _address: Address) -> RpcWithBlock<Address, U256> {
+            RpcWithBlock::new_provider(|block_id| {
+                let number = match block_id {
+                    BlockId::Number(BlockNumberOrTag::Number(number)) => number,
+                    _ => HEAD,
+                };
+                ProviderCall::ready(Ok(U256::from(number)))
+            })
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("TimestampProvider does not support `root`")
+        }
+    }
+
+    async fn block_at(timestamps: &BlockTimestamps, provider: &TimestampProvider, at: u64) -> u64 {
+        let block = timestamps.block_at(provider, at).await.unwrap().unwrap();
+        assert!(block.timestamp <= at);
+        block.number
+    }
+
+    #[tokio::test]
+    async fn test_resolves_the_block_at_or_before_a_timestamp() {
+        let provider = TimestampProvider::default();
+        let timestamps = BlockTimestamps::new();
+
+        assert_eq!(
+            block_at(&timestamps, &provider, GENESIS_TIME + 12 * 500).await,
+            500
+        );
+        assert_eq!(
+            block_at(&timestamps, &provider, GENESIS_TIME + 12 * 500 + 11).await,
+            500
+        );
+        assert_eq!(block_at(&timestamps, &provider, GENESIS_TIME).await, 0);
+        assert_eq!(
+            block_at(&timestamps, &provider, GENESIS_TIME + 1_000_000).await,
+            HEAD
+        );
+        assert_eq!(
+            timestamps
+                .block_at(&provider, GENESIS_TIME - 1)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_known_timestamps_narrow_later_searches() {
+        let provider = TimestampProvider::default();
+        let timestamps = BlockTimestamps::new();
+
+        block_at(&timestamps, &provider, GENESIS_TIME + 12 * 300).await;
+        let first = provider.block_calls.swap(0, Ordering::SeqCst);
+        assert_eq!(
+            block_at(&timestamps, &provider, GENESIS_TIME + 12 * 301).await,
+            301
+        );
+        let second = provider.block_calls.load(Ordering::SeqCst);
+        assert!(second < first, "{} calls, then {}", first, second);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::error::AppError;
+use crate::request_body;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+use warp::{Filter, Rejection};
+
+/// One problem with a request body, as listed in the `errors` of a `400`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    /// Path of the offending field, e.g. `addresses[2]`; empty for the body itself.
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.field.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.field, self.message)
+        }
+    }
+}
+
+/// Problems found so far in a request body.
+#[derive(Debug, Default)]
+pub struct Problems(Vec<FieldError>);
+
+impl Problems {
+    pub fn push(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.0.push(FieldError {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    /// The value of `field`, recording a problem when it is missing.
+    pub fn require<'a>(&mut self, body: &'a Map<String, Value>, field: &str) -> Option<&'a Value> {
+        let value = body.get(field);
+        if value.is_none() {
+            self.push(field, "missing field");
+        }
+        value
+    }
+}
+
+/// A JSON request body whose fields are checked all at once, so a `400`
+/// lists every problem instead of only the first serde stops at.
+pub trait Validate: DeserializeOwned {
+    /// Top-level fields the body accepts; any other is reported as unknown.
+    const FIELDS: &'static [&'static str];
+
+    /// Records the problems of the known fields of `body`.
+    fn validate(body: &Map<String, Value>, problems: &mut Problems);
+}
+
+/// Deserializes `body` into `T` once [`Validate::validate`] finds nothing wrong with it.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_parse_body() {
+/// let body = serde_json::json!({ "addresses": ["0x123"], "blokc": "latest" });
+/// let Err(AppError::Validation(errors)) = parse_body::<BatchBalanceRequest>(body) else { panic!() };
+/// assert_eq!(errors.len(), 2);
+/// # }
+/// ```
+pub fn parse_body<T: Validate>(body: Value) -> Result<T, AppError> {
+    let Value::Object(fields) = &body else {
+        return Err(AppError::Validation(vec![FieldError {
+            field: String::new(),
+            message: "expected a JSON object".to_string(),
+        }]));
+    };
+    let mut problems = Problems::default();
+    for field in fields.keys() {
+        if !T::FIELDS.contains(&field.as_str()) {
+            problems.push(field.as_str(), format!("unknown field `{}`", field));
+        }
+    }
+    T::validate(fields, &mut problems);
+    if !problems.0.is_empty() {
+        return Err(AppError::Validation(problems.0));
+    }
+    serde_json::from_value(body).map_err(|error| {
+        AppError::Validation(vec![FieldError {
+            field: String::new(),
+            message: error.to_string(),
+        }])
+    })
+}
+
+/// JSON body of at most `limit` bytes, read by [`request_body::json`] and
+/// deserialized with [`parse_body`].
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_validated_json() {
+/// let filter = validated_json::<BatchBalanceRequest>(MAX_BODY_BYTES);
+/// # }
+/// ```
+pub fn validated_json<T: Validate + Send>(
+    limit: u64,
+) -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    request_body::json::<Value>(limit)
+        .and_then(|body| async move { parse_body::<T>(body).map_err(warp::reject::custom) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Range {
+        from: u64,
+        to: u64,
+    }
+
+    impl Validate for Range {
+        const FIELDS: &'static [&'static str] = &["from", "to"];
+
+        fn validate(body: &Map<String, Value>, problems: &mut Problems) {
+            for field in Self::FIELDS {
+                if let Some(value) = problems.require(body, field) {
+                    if !value.is_u64() {
+                        problems.push(*field, "expected a block number");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_collects_every_problem() {
+        let body = serde_json::json!({ "from": -1, "form": 1 });
+        let Err(AppError::Validation(errors)) = parse_body::<Range>(body) else {
+            panic!("expected validation errors");
+        };
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "form: unknown field `form`",
+                "from: expected a block number",
+                "to: missing field",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_valid_bodies_deserialize() {
+        let range: Range = parse_body(serde_json::json!({ "from": 1, "to": 2 })).unwrap();
+        assert_eq!((range.from, range.to), (1, 2));
+        assert!(matches!(
+            parse_body::<Range>(serde_json::json!([1, 2])),
+            Err(AppError::Validation(errors)) if errors[0].message == "expected a JSON object"
+        ));
+    }
+}
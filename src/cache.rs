@@ -0,0 +1,372 @@
+use crate::config;
+use alloy_primitives::{Address, B256, U256};
+use anyhow::bail;
+use futures::future::BoxFuture;
+use log::warn;
+use std::collections::HashMap;
+use std::env;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Entries kept per cache before expired ones are dropped.
+const MAX_CACHE_ENTRIES: usize = 10_000;
+
+/// Response header telling whether the balance cache served the response (`HIT`) or not (`MISS`).
+pub const CACHE_HEADER: &str = "x-cache";
+
+/// A cache whose entries expire a TTL after they were inserted, wherever
+/// they are stored.
+///
+/// Every backend treats a zero TTL as disabled, and reads an entry as
+/// expired once it is older than the current TTL.
+pub trait Cache<K, V>: Send + Sync {
+    fn ttl(&self) -> Duration;
+
+    /// Changes the TTL of current and future entries.
+    fn set_ttl(&self, ttl: Duration);
+
+    fn is_enabled(&self) -> bool {
+        !self.ttl().is_zero()
+    }
+
+    /// The value cached for `key`, unless it has expired.
+    fn get<'a>(&'a self, key: &'a K) -> BoxFuture<'a, Option<V>>;
+
+    /// Caches `value` for `key`; does nothing while the cache is disabled.
+    fn insert(&self, key: K, value: V) -> BoxFuture<'_, ()>;
+}
+
+/// Balances by address and block, as returned by `/balance/{address}`.
+///
+/// Blocks are keyed by their display form since `BlockId` is not hashable.
+pub type BalanceCache = dyn Cache<(Address, String), U256>;
+
+/// Builds the balance cache with the TTL of `CACHE_TTL_MS`, kept in memory or,
+/// with `CACHE_BACKEND=redis`, in the Redis server at `REDIS_URL`.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_balance_cache() {
+/// let cache = get_balance_cache().await.unwrap();
+/// assert!(!cache.is_enabled());
+/// # }
+/// ```
+pub async fn get_balance_cache() -> anyhow::Result<Arc<BalanceCache>> {
+    let ttl = get_cache_ttl();
+    match env::var("CACHE_BACKEND").as_deref() {
+        Err(_) | Ok("memory") => Ok(Arc::new(TtlCache::new(ttl))),
+        Ok("redis") => redis_balance_cache(ttl).await,
+        Ok(other) => bail!(
+            "Unknown CACHE_BACKEND {:?}, expected memory or redis",
+            other
+        ),
+    }
+}
+
+#[cfg(feature = "redis")]
+async fn redis_balance_cache(ttl: Duration) -> anyhow::Result<Arc<BalanceCache>> {
+    use anyhow::Context as _;
+    use log::info;
+
+    let url = env::var("REDIS_URL").context("CACHE_BACKEND=redis needs REDIS_URL")?;
+    let cache = crate::redis_cache::RedisCache::connect(&url, ttl).await?;
+    info!("Caching balances in Redis");
+    Ok(Arc::new(cache))
+}
+
+#[cfg(not(feature = "redis"))]
+async fn redis_balance_cache(_ttl: Duration) -> anyhow::Result<Arc<BalanceCache>> {
+    bail!("CACHE_BACKEND=redis needs the server built with the `redis` feature")
+}
+
+/// Hashes recently looked up without a result, by route label (`tx`, `receipt`).
+pub type NotFoundCache = TtlCache<(&'static str, B256), ()>;
+
+/// How long a hash that was not found is answered locally, unless overridden
+/// by `NOT_FOUND_CACHE_TTL_MS`.
+const DEFAULT_NOT_FOUND_TTL_MS: u64 = 2_000;
+
+/// Longest accepted not-found TTL; anything longer would hide freshly mined transactions.
+const MAX_NOT_FOUND_TTL_MS: u64 = 10_000;
+
+/// Retrieves the not-found cache TTL from `NOT_FOUND_CACHE_TTL_MS`; zero disables it.
+pub fn get_not_found_ttl() -> Duration {
+    let ttl_ms = config::env_or("NOT_FOUND_CACHE_TTL_MS", DEFAULT_NOT_FOUND_TTL_MS);
+    if ttl_ms > MAX_NOT_FOUND_TTL_MS {
+        warn!(
+            "NOT_FOUND_CACHE_TTL_MS is capped at {}ms, got {}ms",
+            MAX_NOT_FOUND_TTL_MS, ttl_ms
+        );
+    }
+    Duration::from_millis(ttl_ms.min(MAX_NOT_FOUND_TTL_MS))
+}
+
+/// Broadcasts made under an `Idempotency-Key`: the hash of the raw
+/// transaction sent and the resulting tx hash, remembered for a TTL.
+///
+/// A key is reserved while its transaction is being broadcast, so requests
+/// reusing it wait for the first one instead of broadcasting again.
+pub struct IdempotencyCache {
+    sent: TtlCache<String, (B256, B256)>,
+    reserved: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+/// Holds an idempotency key until dropped; see [`IdempotencyCache::reserve`].
+pub struct KeyReservation {
+    cache: Arc<IdempotencyCache>,
+    key: String,
+    guard: Option<tokio::sync::OwnedMutexGuard<()>>,
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl: Duration) -> Self {
+        IdempotencyCache {
+            sent: TtlCache::new(ttl),
+            reserved: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sent.is_enabled()
+    }
+
+    /// The transaction fingerprint and hash broadcast under `key`, unless expired.
+    pub fn get(&self, key: &str) -> Option<(B256, B256)> {
+        self.sent.get(&key.to_string())
+    }
+
+    /// Remembers the broadcast made under `key`.
+    pub fn insert(&self, key: String, sent: (B256, B256)) {
+        self.sent.insert(key, sent)
+    }
+
+    /// Waits until no other request holds `key`, then holds it until the
+    /// reservation is dropped. A holder that stores no broadcast, e.g. because
+    /// it failed, leaves the next one to try again.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_reserve() {
+    /// let cache = Arc::new(IdempotencyCache::new(Duration::from_secs(60)));
+    /// let reservation = cache.reserve("order-42").await;
+    /// # }
+    /// ```
+    pub async fn reserve(self: &Arc<Self>, key: &str) -> KeyReservation {
+        let lock = self
+            .reserved
+            .lock()
+            .expect("idempotency reservations poisoned")
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+        KeyReservation {
+            cache: self.clone(),
+            key: key.to_string(),
+            guard: Some(lock.lock_owned().await),
+        }
+    }
+}
+
+impl Drop for KeyReservation {
+    fn drop(&mut self) {
+        self.guard.take();
+        let mut reserved = self
+            .cache
+            .reserved
+            .lock()
+            .expect("idempotency reservations poisoned");
+        // Only forget the key once nobody else holds or awaits it.
+        if reserved
+            .get(&self.key)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            reserved.remove(&self.key);
+        }
+    }
+}
+
+/// Retrieves how long idempotency keys are remembered from `IDEMPOTENCY_TTL_SECS`.
+pub fn get_idempotency_ttl() -> Duration {
+    Duration::from_secs(config::env_or("IDEMPOTENCY_TTL_SECS", 600))
+}
+
+/// Retrieves the response cache TTL from `CACHE_TTL_MS`; zero disables caching.
+pub fn get_cache_ttl() -> Duration {
+    Duration::from_millis(config::env_or("CACHE_TTL_MS", 0))
+}
+
+/// A map whose entries expire `ttl` after they were inserted.
+///
+/// The TTL can be changed while the cache is shared; a zero TTL disables it.
+pub struct TtlCache<K, V> {
+    ttl_millis: AtomicU64,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K, V> TtlCache<K, V> {
+    pub fn new(ttl: Duration) -> Self {
+        TtlCache {
+            ttl_millis: AtomicU64::new(ttl.as_millis() as u64),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        Duration::from_millis(self.ttl_millis.load(Ordering::Relaxed))
+    }
+
+    /// Changes the TTL of current and future entries.
+    pub fn set_ttl(&self, ttl: Duration) {
+        self.ttl_millis
+            .store(ttl.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.ttl().is_zero()
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> TtlCache<K, V> {
+    /// Returns the value cached for `key`, unless it has expired.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_get() {
+    /// let cache = TtlCache::new(Duration::from_secs(1));
+    /// cache.insert("key", 1);
+    /// assert_eq!(cache.get(&"key"), Some(1));
+    /// # }
+    /// ```
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.get_at(key, Instant::now())
+    }
+
+    fn get_at(&self, key: &K, now: Instant) -> Option<V> {
+        let ttl = self.ttl();
+        let entries = self.entries.lock().expect("cache poisoned");
+        entries
+            .get(key)
+            .filter(|(inserted, _)| now.saturating_duration_since(*inserted) < ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Caches `value` for `key`; does nothing while the cache is disabled.
+    pub fn insert(&self, key: K, value: V) {
+        self.insert_at(key, value, Instant::now())
+    }
+
+    fn insert_at(&self, key: K, value: V, now: Instant) {
+        let ttl = self.ttl();
+        if ttl.is_zero() {
+            return;
+        }
+        let mut entries = self.entries.lock().expect("cache poisoned");
+        if entries.len() >= MAX_CACHE_ENTRIES {
+            entries.retain(|_, (inserted, _)| now.saturating_duration_since(*inserted) < ttl);
+            if entries.len() >= MAX_CACHE_ENTRIES {
+                entries.clear();
+            }
+        }
+        entries.insert(key, (now, value));
+    }
+
+    /// Drops the entry for `key`, if any.
+    pub fn remove(&self, key: &K) {
+        self.entries.lock().expect("cache poisoned").remove(key);
+    }
+}
+
+impl<K, V> Cache<K, V> for TtlCache<K, V>
+where
+    K: Hash + Eq + Send + Sync,
+    V: Clone + Send + Sync,
+{
+    fn ttl(&self) -> Duration {
+        TtlCache::ttl(self)
+    }
+
+    fn set_ttl(&self, ttl: Duration) {
+        TtlCache::set_ttl(self, ttl)
+    }
+
+    fn get<'a>(&'a self, key: &'a K) -> BoxFuture<'a, Option<V>> {
+        Box::pin(std::future::ready(TtlCache::get(self, key)))
+    }
+
+    fn insert(&self, key: K, value: V) -> BoxFuture<'_, ()> {
+        TtlCache::insert(self, key, value);
+        Box::pin(std::future::ready(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_expire_after_ttl() {
+        let cache = TtlCache::new(Duration::from_secs(2));
+        let start = Instant::now();
+        cache.insert_at("a", 1, start);
+
+        assert_eq!(cache.get_at(&"a", start + Duration::from_secs(1)), Some(1));
+        assert_eq!(cache.get_at(&"a", start + Duration::from_secs(2)), None);
+        assert_eq!(cache.get_at(&"b", start), None);
+    }
+
+    #[test]
+    fn test_zero_ttl_disables_cache() {
+        let cache = TtlCache::new(Duration::ZERO);
+        cache.insert("a", 1);
+        assert!(!cache.is_enabled());
+        assert_eq!(cache.get(&"a"), None);
+
+        cache.set_ttl(Duration::from_secs(60));
+        cache.insert("a", 1);
+        assert_eq!(cache.get(&"a"), Some(1));
+        cache.remove(&"a");
+        assert_eq!(cache.get(&"a"), None);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_balance_cache() {
+        let cache: Arc<BalanceCache> = Arc::new(TtlCache::new(Duration::from_millis(50)));
+        let key = (Address::ZERO, "latest".to_string());
+        cache.insert(key.clone(), U256::from(1000)).await;
+        assert_eq!(cache.get(&key).await, Some(U256::from(1000)));
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(cache.get(&key).await, None);
+
+        cache.set_ttl(Duration::ZERO);
+        assert!(!cache.is_enabled());
+        cache.insert(key.clone(), U256::from(1)).await;
+        assert_eq!(cache.get(&key).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_released_key_can_be_reserved_again() {
+        let cache = Arc::new(IdempotencyCache::new(Duration::from_secs(60)));
+        let first = cache.reserve("order-42").await;
+
+        // A second holder waits for the first to let go.
+        let waiting = tokio::spawn({
+            let cache = cache.clone();
+            async move { cache.reserve("order-42").await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiting.is_finished());
+
+        // Dropped without a broadcast, as after a failed one.
+        drop(first);
+        let second = waiting.await.unwrap();
+        assert_eq!(cache.get("order-42"), None);
+        drop(second);
+        assert!(cache.reserved.lock().unwrap().is_empty());
+    }
+}
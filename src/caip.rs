@@ -0,0 +1,103 @@
+use crate::error::AppError;
+use alloy_primitives::Address;
+use std::fmt;
+use std::str::FromStr;
+
+/// The only CAIP-2 namespace served: EVM chains identified by their chain id.
+const EIP155: &str = "eip155";
+
+/// A CAIP-10 account id, `eip155:<chain id>:<address>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountId {
+    pub chain_id: u64,
+    pub address: Address,
+}
+
+impl FromStr for AccountId {
+    type Err = AppError;
+
+    /// Parses an account id, rejecting namespaces other than `eip155`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_parse_account_id() {
+    /// let account: AccountId = "eip155:1:0x0000000000000000000000000000000000000000".parse().unwrap();
+    /// assert_eq!(account.chain_id, 1);
+    /// # }
+    /// ```
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || AppError::InvalidRequest(format!("{:?} is not a CAIP-10 account id", value));
+        let mut parts = value.splitn(3, ':');
+        let (Some(namespace), Some(reference), Some(address)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        if namespace != EIP155 {
+            return Err(AppError::InvalidRequest(format!(
+                "unsupported chain namespace {:?}, only {} is served",
+                namespace, EIP155
+            )));
+        }
+        // CAIP-2 references of eip155 chains are decimal chain ids.
+        if reference.is_empty() || !reference.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let chain_id = reference.parse().map_err(|_| invalid())?;
+        let address = address
+            .parse()
+            .map_err(|_| AppError::InvalidAddress(address.to_string()))?;
+        Ok(AccountId { chain_id, address })
+    }
+}
+
+impl fmt::Display for AccountId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            EIP155,
+            self.chain_id,
+            self.address.to_checksum(None)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+
+    #[test]
+    fn test_parses_eip155_account_ids() {
+        let account: AccountId = "eip155:1:0xab5801a7d398351b8be11c439e05c5b3259aec9b"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            account,
+            AccountId {
+                chain_id: 1,
+                address: address!("ab5801a7d398351b8be11c439e05c5b3259aec9b"),
+            }
+        );
+        assert_eq!(
+            account.to_string(),
+            "eip155:1:0xAb5801a7D398351b8bE11C439e05C5B3259aeC9B"
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_account_ids() {
+        let address = "0xab5801a7d398351b8be11c439e05c5b3259aec9b";
+        for value in [
+            format!("cosmos:cosmoshub-3:{}", address),
+            format!("eip155:{}", address),
+            format!("eip155:0x1:{}", address),
+            "eip155:1:0x1234".to_string(),
+        ] {
+            assert!(value.parse::<AccountId>().is_err(), "{}", value);
+        }
+    }
+}
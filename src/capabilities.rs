@@ -0,0 +1,137 @@
+use crate::index::{Endpoint, Index};
+use crate::route_prefix::RoutePrefix;
+use serde::Serialize;
+
+/// Optional features of the server, switched on by its configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Features {
+    /// `/balance/{address}/stream`, fed by a subscription to new heads over `WS_RPC_URL`.
+    pub balance_streams: bool,
+    /// `/pending/poll`, enabled by `ENABLE_PENDING_POLL`.
+    pub pending_transactions: bool,
+    /// `/trace-filter`, enabled by `ENABLE_TRACE_ROUTES`.
+    pub trace_routes: bool,
+    /// `/sign-typed`, with the key of `SIGNER_PRIVATE_KEY`.
+    pub signing: bool,
+    /// `/balance/{address}/fiat`, priced by `PRICE_FEED_URL`.
+    pub fiat_prices: bool,
+    /// Historical reads are sent to `ARCHIVE_RPC_URL`.
+    pub archive: bool,
+    /// The `/admin` routes, guarded by `ADMIN_TOKEN`.
+    pub admin: bool,
+    /// `/admin/reload`, re-reading `CONFIG_FILE`.
+    pub config_reload: bool,
+    /// The web UI at `/ui`, served from `WEB_ROOT`.
+    pub ui: bool,
+}
+
+impl Features {
+    /// Whether the endpoint at `path`, a template without the route prefix,
+    /// is backed by a feature that is switched off.
+    fn disables(&self, path: &str) -> bool {
+        match path {
+            "/balance/{address}/stream" => !self.balance_streams,
+            "/pending/poll" => !self.pending_transactions,
+            "/trace-filter" => !self.trace_routes,
+            "/sign-typed" => !self.signing,
+            "/balance/{address}/fiat" => !self.fiat_prices,
+            "/admin/maintenance" => !self.admin,
+            "/admin/reload" => !(self.admin && self.config_reload),
+            _ => false,
+        }
+    }
+}
+
+/// Body of `/capabilities`: what this instance serves, for clients to adapt to.
+#[derive(Debug, Serialize)]
+pub struct Capabilities {
+    pub version: &'static str,
+    /// Names of the configured chains, the default one first.
+    pub chains: Vec<String>,
+    /// More than one chain is configured, so `/multichain` and `/caip` can reach several.
+    pub multichain: bool,
+    #[serde(flatten)]
+    pub features: Features,
+    /// Endpoints of the index that are actually served with these features.
+    pub endpoints: Vec<Endpoint>,
+}
+
+impl Capabilities {
+    /// Describes the server from its `features`, its `chains` and the
+    /// endpoints listed by `index` under `prefix`, built once when the routes
+    /// are set up.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_capabilities_new() {
+    /// let index = Index::new(&RouteFlags::default(), false, &RoutePrefix::default());
+    /// let chains = vec!["default".to_string()];
+    /// let capabilities = Capabilities::new(Features::default(), chains, &index, &RoutePrefix::default());
+    /// assert!(!capabilities.multichain);
+    /// # }
+    /// ```
+    pub fn new(
+        features: Features,
+        chains: Vec<String>,
+        index: &Index,
+        prefix: &RoutePrefix,
+    ) -> Self {
+        let endpoints = index
+            .endpoints
+            .iter()
+            .filter(|endpoint| !features.disables(prefix.strip(&endpoint.path)))
+            .cloned()
+            .collect();
+        Capabilities {
+            version: env!("CARGO_PKG_VERSION"),
+            multichain: chains.len() > 1,
+            chains,
+            features,
+            endpoints,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::route_flags::RouteFlags;
+
+    fn paths(capabilities: &Capabilities) -> Vec<&str> {
+        capabilities
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.path.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_endpoints_follow_features() {
+        let prefix = RoutePrefix::new("api", true).unwrap();
+        let index = Index::new(&RouteFlags::default(), false, &prefix);
+        let chains = || vec!["default".to_string()];
+        let capabilities = Capabilities::new(Features::default(), chains(), &index, &prefix);
+        let served = paths(&capabilities);
+        assert!(served.contains(&"/api/balance/{address}"));
+        for path in [
+            "/api/sign-typed",
+            "/api/trace-filter",
+            "/api/pending/poll",
+            "/api/admin/reload",
+        ] {
+            assert!(!served.contains(&path), "{}", path);
+        }
+
+        let features = Features {
+            signing: true,
+            admin: true,
+            ..Features::default()
+        };
+        let capabilities = Capabilities::new(features, chains(), &index, &prefix);
+        let served = paths(&capabilities);
+        assert!(served.contains(&"/api/sign-typed"));
+        assert!(served.contains(&"/api/admin/maintenance"));
+        assert!(!served.contains(&"/api/admin/reload"));
+    }
+}
@@ -0,0 +1,300 @@
+use crate::config;
+use alloy::providers::Provider;
+use anyhow::{bail, Context};
+use log::{info, warn};
+use serde::Serialize;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::{interval, timeout};
+
+/// Name under which the provider built from `ETHEREUM_RPC_URL` is registered.
+pub const DEFAULT_CHAIN: &str = "default";
+
+/// How long a single per-chain health check may take.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reachability of a chain as seen by the last background health check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChainStatus {
+    /// No health check has completed yet.
+    Unknown,
+    Reachable,
+    Unreachable,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct HealthState {
+    chain_id: Option<u64>,
+    status: ChainStatus,
+}
+
+/// A configured chain with its provider and cached health.
+pub struct Chain {
+    pub name: String,
+    pub provider: Arc<dyn Provider>,
+    health: Mutex<HealthState>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChainInfo {
+    pub name: String,
+    pub chain_id: Option<u64>,
+    pub status: ChainStatus,
+}
+
+/// How many chains must be reachable for `/ready` to succeed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadyPolicy {
+    /// Every configured chain.
+    All,
+    /// At least this many chains.
+    Quorum(usize),
+}
+
+impl ReadyPolicy {
+    /// Number of reachable chains needed out of `total`.
+    pub fn required(self, total: usize) -> usize {
+        match self {
+            ReadyPolicy::All => total,
+            ReadyPolicy::Quorum(quorum) => quorum.min(total),
+        }
+    }
+}
+
+/// Reads the `/ready` policy: every chain, unless `READY_REQUIRE_ALL=false`, in
+/// which case `READY_QUORUM` chains (default: a majority) are enough.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_ready_policy() {
+/// assert_eq!(get_ready_policy(3), ReadyPolicy::All);
+/// # }
+/// ```
+pub fn get_ready_policy(chains: usize) -> ReadyPolicy {
+    if config::env_or("READY_REQUIRE_ALL", true) {
+        return ReadyPolicy::All;
+    }
+    ReadyPolicy::Quorum(config::env_or("READY_QUORUM", chains / 2 + 1).max(1))
+}
+
+/// Reads how often every chain is health-checked from `CHAIN_HEALTH_CHECK_INTERVAL_SECS`,
+/// rejecting zero.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_health_check_interval() {
+/// assert_eq!(get_health_check_interval().unwrap(), Duration::from_secs(15));
+/// # }
+/// ```
+pub fn get_health_check_interval() -> anyhow::Result<Duration> {
+    config::nonzero_interval(
+        "CHAIN_HEALTH_CHECK_INTERVAL_SECS",
+        Duration::from_secs(config::env_or("CHAIN_HEALTH_CHECK_INTERVAL_SECS", 15)),
+    )
+}
+
+/// Health of every chain against the `/ready` policy.
+#[derive(Debug, Serialize)]
+pub struct ReadyReport {
+    pub ready: bool,
+    pub reachable: usize,
+    pub required: usize,
+    pub chains: Vec<ChainInfo>,
+}
+
+impl Chain {
+    pub fn new(name: impl Into<String>, provider: Arc<dyn Provider>) -> Self {
+        Chain {
+            name: name.into(),
+            provider,
+            health: Mutex::new(HealthState {
+                chain_id: None,
+                status: ChainStatus::Unknown,
+            }),
+        }
+    }
+
+    /// Chain id and status from the last health check.
+    pub fn info(&self) -> ChainInfo {
+        let health = *self.health.lock().expect("chain health poisoned");
+        ChainInfo {
+            name: self.name.clone(),
+            chain_id: health.chain_id,
+            status: health.status,
+        }
+    }
+
+    /// Queries the chain id once and records whether the chain answered.
+    pub async fn check_health(&self) {
+        let result = timeout(HEALTH_CHECK_TIMEOUT, self.provider.get_chain_id()).await;
+        let mut health = self.health.lock().expect("chain health poisoned");
+        match result {
+            Ok(Ok(chain_id)) => {
+                health.chain_id = Some(chain_id);
+                health.status = ChainStatus::Reachable;
+            }
+            Ok(Err(error)) => {
+                warn!("Health check for chain {} failed: {}", self.name, error);
+                health.status = ChainStatus::Unreachable;
+            }
+            Err(_) => {
+                warn!("Health check for chain {} timed out", self.name);
+                health.status = ChainStatus::Unreachable;
+            }
+        }
+    }
+}
+
+/// All chains the server can route to, in configuration order.
+pub struct ChainRegistry {
+    chains: Vec<Chain>,
+}
+
+impl ChainRegistry {
+    pub fn new(chains: Vec<Chain>) -> Self {
+        ChainRegistry { chains }
+    }
+
+    pub fn chains(&self) -> &[Chain] {
+        &self.chains
+    }
+
+    /// The chain whose last health check reported `chain_id`.
+    pub fn by_chain_id(&self, chain_id: u64) -> Option<&Chain> {
+        self.chains
+            .iter()
+            .find(|chain| chain.info().chain_id == Some(chain_id))
+    }
+
+    /// Compares the last health checks with `policy`; chains not checked yet count as down.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_ready_report() {
+    /// let report = registry.ready_report(ReadyPolicy::All);
+    /// assert!(!report.ready);
+    /// # }
+    /// ```
+    pub fn ready_report(&self, policy: ReadyPolicy) -> ReadyReport {
+        let chains: Vec<ChainInfo> = self.chains.iter().map(Chain::info).collect();
+        let reachable = chains
+            .iter()
+            .filter(|chain| chain.status == ChainStatus::Reachable)
+            .count();
+        let required = policy.required(chains.len());
+        ReadyReport {
+            ready: reachable >= required,
+            reachable,
+            required,
+            chains,
+        }
+    }
+
+    /// Runs one health check on every chain concurrently.
+    pub async fn check_health(&self) {
+        futures::future::join_all(self.chains.iter().map(Chain::check_health)).await;
+    }
+
+    /// Re-checks every chain forever, at the given interval.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_run_health_checks() {
+    /// tokio::spawn(registry.clone().run_health_checks(Duration::from_secs(15)));
+    /// # }
+    /// ```
+    pub async fn run_health_checks(self: Arc<Self>, every: Duration) {
+        let mut ticker = interval(every);
+        loop {
+            ticker.tick().await;
+            self.check_health().await;
+        }
+    }
+}
+
+/// Parses `CHAINS` entries of the form `name=url,name=url`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_parse_chains() {
+/// let chains = parse_chains("sepolia=https://rpc.sepolia.org").unwrap();
+/// assert_eq!(chains[0].0, "sepolia");
+/// # }
+/// ```
+pub fn parse_chains(value: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mut chains: Vec<(String, String)> = Vec::new();
+    for entry in value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let (name, url) = entry
+            .split_once('=')
+            .with_context(|| format!("Chain entry {:?} is not of the form name=url", entry))?;
+        let (name, url) = (name.trim(), url.trim());
+        if name.is_empty() || url.is_empty() {
+            bail!("Chain entry {:?} is missing a name or url", entry);
+        }
+        if name == DEFAULT_CHAIN || chains.iter().any(|(existing, _)| existing == name) {
+            bail!("Chain {:?} is configured more than once", name);
+        }
+        chains.push((name.to_string(), url.to_string()));
+    }
+    Ok(chains)
+}
+
+/// Retrieves the additional chains from the `CHAINS` environment variable.
+pub fn get_chains_config() -> anyhow::Result<Vec<(String, String)>> {
+    match env::var("CHAINS") {
+        Ok(value) => {
+            let chains = parse_chains(&value)?;
+            for (name, _) in &chains {
+                info!("Configured chain: {}", name);
+            }
+            Ok(chains)
+        }
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chains() {
+        let chains =
+            parse_chains("mainnet=https://eth.example, sepolia=https://sep.example").unwrap();
+        assert_eq!(
+            chains,
+            vec![
+                ("mainnet".to_string(), "https://eth.example".to_string()),
+                ("sepolia".to_string(), "https://sep.example".to_string()),
+            ]
+        );
+        assert!(parse_chains("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_ready_policy_required() {
+        assert_eq!(ReadyPolicy::All.required(3), 3);
+        assert_eq!(ReadyPolicy::Quorum(2).required(3), 2);
+        // A quorum above the number of chains means all of them.
+        assert_eq!(ReadyPolicy::Quorum(5).required(3), 3);
+    }
+
+    #[test]
+    fn test_parse_chains_rejects_malformed_entries() {
+        assert!(parse_chains("mainnet").is_err());
+        assert!(parse_chains("=https://eth.example").is_err());
+        assert!(parse_chains("a=https://x,a=https://y").is_err());
+        assert!(parse_chains("default=https://x").is_err());
+    }
+}
@@ -0,0 +1,382 @@
+use crate::config;
+use alloy::rpc::json_rpc::{RequestPacket, ResponsePacket};
+use alloy::transports::{TransportError, TransportErrorKind, TransportFut};
+use log::{info, warn};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower::{Layer, Service};
+
+/// State of a circuit breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Requests flow to the node.
+    Closed,
+    /// Requests fail fast until the cooldown elapses.
+    Open,
+    /// A single trial request is let through to test recovery.
+    HalfOpen,
+}
+
+/// Circuit breaker settings read from the environment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+/// Reads the circuit breaker settings from `CIRCUIT_BREAKER_FAILURES` and
+/// `CIRCUIT_BREAKER_COOLDOWN_SECS`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_circuit_breaker_config() {
+/// let config = get_circuit_breaker_config();
+/// assert_eq!(config.failure_threshold, 5);
+/// # }
+/// ```
+pub fn get_circuit_breaker_config() -> CircuitBreakerConfig {
+    CircuitBreakerConfig {
+        failure_threshold: config::env_or("CIRCUIT_BREAKER_FAILURES", 5),
+        cooldown: Duration::from_secs(config::env_or("CIRCUIT_BREAKER_COOLDOWN_SECS", 30)),
+    }
+}
+
+/// How [`CircuitBreaker::admit`] lets a request through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Admission {
+    Allowed,
+    /// The single trial request of a half-open breaker.
+    Trial,
+}
+
+struct BreakerInner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Instant,
+}
+
+/// Counts consecutive upstream failures and opens after too many of them.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<BreakerInner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        CircuitBreaker {
+            failure_threshold: config.failure_threshold.max(1),
+            cooldown: config.cooldown,
+            inner: Mutex::new(BreakerInner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                opened_at: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.inner.lock().expect("circuit breaker poisoned").state
+    }
+
+    /// Whether a request may be sent now, and whether it is the trial of a
+    /// half-open breaker; half-opens the breaker once the cooldown elapsed.
+    fn admit(&self) -> Option<Admission> {
+        self.admit_at(Instant::now())
+    }
+
+    fn admit_at(&self, now: Instant) -> Option<Admission> {
+        let mut inner = self.inner.lock().expect("circuit breaker poisoned");
+        match inner.state {
+            BreakerState::Closed => Some(Admission::Allowed),
+            BreakerState::Open
+                if now.saturating_duration_since(inner.opened_at) >= self.cooldown =>
+            {
+                info!("Circuit breaker half-open, sending a trial request");
+                inner.state = BreakerState::HalfOpen;
+                Some(Admission::Trial)
+            }
+            // Only the trial request goes through while half-open.
+            BreakerState::Open | BreakerState::HalfOpen => None,
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().expect("circuit breaker poisoned");
+        if inner.state != BreakerState::Closed {
+            info!("Circuit breaker closed, upstream recovered");
+        }
+        inner.state = BreakerState::Closed;
+        inner.consecutive_failures = 0;
+    }
+
+    pub fn record_failure(&self) {
+        self.record_failure_at(Instant::now())
+    }
+
+    fn record_failure_at(&self, now: Instant) {
+        let mut inner = self.inner.lock().expect("circuit breaker poisoned");
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        let should_open = inner.state == BreakerState::HalfOpen
+            || inner.consecutive_failures >= self.failure_threshold;
+        if should_open && inner.state != BreakerState::Open {
+            warn!(
+                "Circuit breaker open after {} consecutive failures",
+                inner.consecutive_failures
+            );
+            inner.state = BreakerState::Open;
+            inner.opened_at = now;
+        }
+    }
+}
+
+/// Fails the trial request of a half-open breaker unless it is disarmed.
+///
+/// A trial dropped before it completes, because the client went away, the
+/// request deadline passed or a layer above gave up, would otherwise leave
+/// the breaker half-open and rejecting everything for good.
+struct TrialGuard(Option<Arc<CircuitBreaker>>);
+
+impl TrialGuard {
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for TrialGuard {
+    fn drop(&mut self) {
+        if let Some(breaker) = self.0.take() {
+            warn!("Circuit breaker trial request was cancelled, counting it as failed");
+            breaker.record_failure();
+        }
+    }
+}
+
+/// Transport layer failing requests fast while the breaker is open.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_circuit_breaker_layer() {
+/// let breaker = Arc::new(CircuitBreaker::new(get_circuit_breaker_config()));
+/// let client = ClientBuilder::default()
+///     .layer(CircuitBreakerLayer::new(breaker))
+///     .transport(transport, is_local);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CircuitBreakerLayer {
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl CircuitBreakerLayer {
+    pub fn new(breaker: Arc<CircuitBreaker>) -> Self {
+        CircuitBreakerLayer { breaker }
+    }
+}
+
+impl<S> Layer<S> for CircuitBreakerLayer {
+    type Service = CircuitBreakerService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CircuitBreakerService {
+            inner,
+            breaker: self.breaker.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CircuitBreakerService<S> {
+    inner: S,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl<S> Service<RequestPacket> for CircuitBreakerService<S>
+where
+    S: Service<
+            RequestPacket,
+            Response = ResponsePacket,
+            Error = TransportError,
+            Future = TransportFut<'static>,
+        > + Send
+        + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        let Some(admission) = self.breaker.admit() else {
+            return Box::pin(async {
+                Err(TransportErrorKind::custom_str("circuit breaker is open"))
+            });
+        };
+
+        // JSON-RPC error responses arrive as `Ok`; only transport failures trip the breaker.
+        let breaker = self.breaker.clone();
+        let trial = (admission == Admission::Trial).then(|| TrialGuard(Some(breaker.clone())));
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let result = response.await;
+            if let Some(trial) = trial {
+                trial.disarm();
+            }
+            match &result {
+                Ok(_) => breaker.record_success(),
+                Err(_) => breaker.record_failure(),
+            }
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::rpc::json_rpc::Request;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Transport that always fails, counting the requests reaching it.
+    #[derive(Clone, Default)]
+    struct DownTransport(Arc<AtomicUsize>);
+
+    impl Service<RequestPacket> for DownTransport {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = TransportFut<'static>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _request: RequestPacket) -> Self::Future {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Box::pin(async { Err(TransportErrorKind::custom_str("connection refused")) })
+        }
+    }
+
+    /// Transport whose requests never complete.
+    #[derive(Clone, Default)]
+    struct HangingTransport;
+
+    impl Service<RequestPacket> for HangingTransport {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = TransportFut<'static>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _request: RequestPacket) -> Self::Future {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    fn packet() -> RequestPacket {
+        Request::new("eth_chainId", 1.into(), ())
+            .serialize()
+            .unwrap()
+            .into()
+    }
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(30),
+        })
+    }
+
+    #[test]
+    fn test_breaker_opens_rejects_and_recovers() {
+        let breaker = breaker();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(breaker.admit_at(start).is_some());
+            breaker.record_failure_at(start);
+        }
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(breaker.admit_at(start + Duration::from_secs(10)).is_none());
+
+        // After the cooldown a single trial request is let through.
+        let later = start + Duration::from_secs(30);
+        assert_eq!(breaker.admit_at(later), Some(Admission::Trial));
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(breaker.admit_at(later).is_none());
+
+        breaker.record_success();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert_eq!(breaker.admit_at(later), Some(Admission::Allowed));
+    }
+
+    #[test]
+    fn test_failed_trial_reopens_the_breaker() {
+        let breaker = breaker();
+        let start = Instant::now();
+        for _ in 0..3 {
+            breaker.record_failure_at(start);
+        }
+
+        let later = start + Duration::from_secs(30);
+        assert!(breaker.admit_at(later).is_some());
+        breaker.record_failure_at(later);
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(breaker.admit_at(later + Duration::from_secs(29)).is_none());
+        assert!(breaker.admit_at(later + Duration::from_secs(30)).is_some());
+    }
+
+    #[test]
+    fn test_success_resets_the_failure_count() {
+        let breaker = breaker();
+        let start = Instant::now();
+        breaker.record_failure_at(start);
+        breaker.record_failure_at(start);
+        breaker.record_success();
+        breaker.record_failure_at(start);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_trial_does_not_wedge_the_breaker() {
+        let breaker = Arc::new(CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 1,
+            cooldown: Duration::ZERO,
+        }));
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+        let mut service = CircuitBreakerLayer::new(breaker.clone()).layer(HangingTransport);
+
+        let trial = service.call(packet());
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        drop(trial);
+
+        // The cancelled trial reopened the breaker, so the next cooldown allows another.
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert_eq!(breaker.admit(), Some(Admission::Trial));
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+    }
+
+    #[tokio::test]
+    async fn test_open_breaker_short_circuits_the_transport() {
+        let breaker = Arc::new(breaker());
+        let transport = DownTransport::default();
+        let mut service = CircuitBreakerLayer::new(breaker.clone()).layer(transport.clone());
+
+        for _ in 0..5 {
+            assert!(service.call(packet()).await.is_err());
+        }
+
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert_eq!(transport.0.load(Ordering::Relaxed), 3);
+    }
+}
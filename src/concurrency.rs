@@ -0,0 +1,269 @@
+use crate::config;
+use alloy::rpc::json_rpc::{RequestPacket, ResponsePacket};
+use alloy::transports::{TransportError, TransportErrorKind, TransportFut};
+use anyhow::{bail, Context as _};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::Semaphore;
+use tower::{Layer, Service};
+
+/// Most node requests each provider has in flight, read from the environment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConcurrencyConfig {
+    /// Limit of providers without their own, `None` for unlimited.
+    pub default: Option<usize>,
+    /// Limits by chain name.
+    pub chains: HashMap<String, usize>,
+}
+
+impl ConcurrencyConfig {
+    /// The limit of the provider serving `chain`, `None` for unlimited.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_limit_for() {
+    /// let config = ConcurrencyConfig { default: Some(8), chains: parse_chain_limits("sepolia=2").unwrap() };
+    /// assert_eq!(config.limit_for("sepolia"), Some(2));
+    /// assert_eq!(config.limit_for("default"), Some(8));
+    /// # }
+    /// ```
+    pub fn limit_for(&self, chain: &str) -> Option<usize> {
+        match self.chains.get(chain) {
+            Some(0) => None,
+            Some(limit) => Some(*limit),
+            None => self.default,
+        }
+    }
+}
+
+/// Parses `CHAIN_MAX_CONCURRENCY` entries of the form `name=limit,name=limit`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_parse_chain_limits() {
+/// let limits = parse_chain_limits("default=16, sepolia=2").unwrap();
+/// assert_eq!(limits["sepolia"], 2);
+/// # }
+/// ```
+pub fn parse_chain_limits(value: &str) -> anyhow::Result<HashMap<String, usize>> {
+    let mut limits = HashMap::new();
+    for entry in value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let (name, limit) = entry
+            .split_once('=')
+            .with_context(|| format!("Limit entry {:?} is not of the form name=limit", entry))?;
+        let limit = limit
+            .trim()
+            .parse()
+            .with_context(|| format!("Limit entry {:?} is not a number", entry))?;
+        if limits.insert(name.trim().to_string(), limit).is_some() {
+            bail!("Chain {:?} is limited more than once", name.trim());
+        }
+    }
+    Ok(limits)
+}
+
+/// Reads `RPC_MAX_CONCURRENCY`, the limit of every provider (unset or zero for
+/// unlimited), and `CHAIN_MAX_CONCURRENCY`, overriding it by chain.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_concurrency_config() {
+/// let config = get_concurrency_config().unwrap();
+/// assert_eq!(config.limit_for("default"), None);
+/// # }
+/// ```
+pub fn get_concurrency_config() -> anyhow::Result<ConcurrencyConfig> {
+    let default: usize = config::env_or("RPC_MAX_CONCURRENCY", 0);
+    let chains = match env::var("CHAIN_MAX_CONCURRENCY") {
+        Ok(value) => parse_chain_limits(&value).context("Invalid CHAIN_MAX_CONCURRENCY")?,
+        Err(_) => HashMap::new(),
+    };
+    Ok(ConcurrencyConfig {
+        default: (default > 0).then_some(default),
+        chains,
+    })
+}
+
+/// Transport layer letting at most `limit` node requests of a provider run at
+/// once; further requests wait for a slot without holding up other providers.
+///
+/// Each layer owns its semaphore, so every provider built with its own layer
+/// queues independently.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_concurrency_layer() {
+/// let client = ClientBuilder::default()
+///     .layer(ConcurrencyLayer::new(config.limit_for("sepolia")))
+///     .transport(transport, is_local);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ConcurrencyLayer {
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl ConcurrencyLayer {
+    pub fn new(limit: Option<usize>) -> Self {
+        ConcurrencyLayer {
+            semaphore: limit.map(|limit| Arc::new(Semaphore::new(limit))),
+        }
+    }
+}
+
+impl<S> Layer<S> for ConcurrencyLayer {
+    type Service = ConcurrencyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConcurrencyService {
+            inner,
+            semaphore: self.semaphore.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ConcurrencyService<S> {
+    inner: S,
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl<S> Service<RequestPacket> for ConcurrencyService<S>
+where
+    S: Service<
+            RequestPacket,
+            Response = ResponsePacket,
+            Error = TransportError,
+            Future = TransportFut<'static>,
+        > + Clone
+        + Send
+        + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        let Some(semaphore) = self.semaphore.clone() else {
+            return self.inner.call(request);
+        };
+        let mut inner = self.inner.clone();
+        Box::pin(async move {
+            // The permit is held until the node has answered.
+            let _permit = semaphore
+                .acquire_owned()
+                .await
+                .map_err(|error| TransportErrorKind::custom_str(&error.to_string()))?;
+            futures::future::poll_fn(|cx| inner.poll_ready(cx)).await?;
+            inner.call(request).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry::tests::FlakyTransport;
+    use alloy::rpc::json_rpc::Request;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Transport whose requests never get an answer, like a saturated node.
+    #[derive(Clone)]
+    struct StalledTransport {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl Service<RequestPacket> for StalledTransport {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = TransportFut<'static>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _request: RequestPacket) -> Self::Future {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(std::future::pending())
+        }
+    }
+
+    fn packet() -> RequestPacket {
+        Request::new("eth_chainId", 1.into(), ())
+            .serialize()
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn test_parse_chain_limits() {
+        let limits = parse_chain_limits("default=16, sepolia = 2").unwrap();
+        assert_eq!(limits.len(), 2);
+        assert_eq!(limits["default"], 16);
+        assert_eq!(limits["sepolia"], 2);
+        assert!(parse_chain_limits("").unwrap().is_empty());
+        assert!(parse_chain_limits("sepolia").is_err());
+        assert!(parse_chain_limits("sepolia=two").is_err());
+        assert!(parse_chain_limits("sepolia=1,sepolia=2").is_err());
+
+        let config = ConcurrencyConfig {
+            default: Some(8),
+            chains: parse_chain_limits("sepolia=2,holesky=0").unwrap(),
+        };
+        assert_eq!(config.limit_for("sepolia"), Some(2));
+        assert_eq!(config.limit_for("holesky"), None);
+        assert_eq!(config.limit_for("default"), Some(8));
+    }
+
+    #[tokio::test]
+    async fn test_saturated_provider_does_not_block_another() {
+        let stalled = StalledTransport {
+            calls: Arc::new(AtomicUsize::new(0)),
+        };
+        let mut saturated = ConcurrencyLayer::new(Some(1)).layer(stalled.clone());
+        let mut other = ConcurrencyLayer::new(Some(1)).layer(FlakyTransport::new(0));
+
+        // The only slot of the saturated provider is taken by a request that never ends.
+        tokio::spawn(saturated.call(packet()));
+        let queued = tokio::spawn(saturated.call(packet()));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(stalled.calls.load(Ordering::SeqCst), 1);
+        assert!(!queued.is_finished());
+
+        for _ in 0..3 {
+            let response = tokio::time::timeout(Duration::from_secs(1), other.call(packet()))
+                .await
+                .expect("the other provider was blocked");
+            assert!(response.is_ok());
+        }
+        assert_eq!(stalled.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_layer_passes_requests_through() {
+        let stalled = StalledTransport {
+            calls: Arc::new(AtomicUsize::new(0)),
+        };
+        let mut service = ConcurrencyLayer::new(None).layer(stalled.clone());
+        for _ in 0..3 {
+            tokio::spawn(service.call(packet()));
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(stalled.calls.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,74 @@
+use log::error;
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Reads a boolean flag from the environment; only `true` and `1` enable it.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_env_flag() {
+/// assert!(!env_flag("SOME_UNSET_FLAG"));
+/// # }
+/// ```
+pub fn env_flag(name: &str) -> bool {
+    env::var(name)
+        .map(|value| matches!(value.trim(), "true" | "1"))
+        .unwrap_or(false)
+}
+
+/// Reads and parses a value from the environment, falling back to `default`
+/// when it is unset or invalid.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_env_or() {
+/// assert_eq!(env_or("SOME_UNSET_NUMBER", 5u64), 5);
+/// # }
+/// ```
+pub fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            error!("{} has an invalid value {:?}, using default", name, value);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+/// Rejects a zero `interval` read from `name`, which `tokio::time::interval`
+/// would panic on.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_nonzero_interval() {
+/// assert!(nonzero_interval("SOME_INTERVAL_SECS", Duration::ZERO).is_err());
+/// # }
+/// ```
+pub fn nonzero_interval(name: &str, interval: Duration) -> anyhow::Result<Duration> {
+    if interval.is_zero() {
+        anyhow::bail!("{} must be greater than zero", name);
+    }
+    Ok(interval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_intervals_are_rejected() {
+        let error = nonzero_interval("RATE_LIMIT_SWEEP_INTERVAL_SECS", Duration::ZERO).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "RATE_LIMIT_SWEEP_INTERVAL_SECS must be greater than zero"
+        );
+        assert_eq!(
+            nonzero_interval("PENDING_POLL_INTERVAL_MS", Duration::from_millis(1)).unwrap(),
+            Duration::from_millis(1)
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use crate::config;
+use crate::error::AppError;
+use crate::server::{client_addr, ClientAddr};
+use futures::{Stream, TryStreamExt};
+use hyper::service::Service;
+use log::{info, warn};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::task::{self, JoinError, JoinHandle};
+use warp::filters::path::FullPath;
+use warp::filters::BoxedFilter;
+use warp::http::{HeaderMap, Method};
+use warp::hyper::body::Buf;
+use warp::reply::Response;
+use warp::Filter;
+
+tokio::task_local! {
+    /// Task of the outer request a nested request task serves.
+    static REQUEST_TASK: task::Id;
+}
+
+/// Id of the task serving the current request, which the per-request node
+/// timings and retry counters are keyed by: inside a nested request task it
+/// is the outer request's task, where they were registered.
+pub fn request_task_id() -> Option<task::Id> {
+    REQUEST_TASK.try_with(|id| *id).ok().or_else(task::try_id)
+}
+
+/// Reads `REQUEST_DEADLINE_MS`; `None`, for no deadline, when unset or zero.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_request_deadline() {
+/// let deadline = get_request_deadline();
+/// assert!(deadline.is_none());
+/// # }
+/// ```
+pub fn get_request_deadline() -> Option<Duration> {
+    let millis: u64 = config::env_or("REQUEST_DEADLINE_MS", 0);
+    if millis == 0 {
+        return None;
+    }
+    info!("Aborting requests still running after {}ms", millis);
+    Some(Duration::from_millis(millis))
+}
+
+/// Runs `api` under `deadline`, however many node calls it makes, rejecting
+/// with [`AppError::DeadlineExceeded`] once it passes; `api` is returned as is
+/// without a deadline.
+///
+/// A filter cannot bound the future of another, so `api` is served as a
+/// nested service with the method, path, query, headers, body and client
+/// address of the request, on a task of its own since warp cannot poll one
+/// service inside another. Node calls made there still count towards the
+/// outer request, see [`request_task_id`]. The task is aborted on the
+/// deadline, or when the outer request is dropped, cancelling its pending
+/// node calls. Rejections `api` leaves unhandled would get warp's default
+/// replies there, so it should recover its own.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_deadline() {
+/// let filter = with_deadline(Some(Duration::from_secs(10)), api);
+/// # }
+/// ```
+pub fn with_deadline(
+    deadline: Option<Duration>,
+    api: BoxedFilter<(Response,)>,
+) -> BoxedFilter<(Response,)> {
+    let Some(deadline) = deadline else {
+        return api;
+    };
+    let service = warp::service(api);
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+        .and(warp::header::headers_cloned())
+        .and(client_addr())
+        .and(warp::body::stream().map(into_body))
+        .and_then(
+            move |method: Method,
+                  path: FullPath,
+                  query: String,
+                  headers: HeaderMap,
+                  client: Option<SocketAddr>,
+                  body: hyper::Body| {
+                let mut service = service.clone();
+                async move {
+                    let uri = if query.is_empty() {
+                        path.as_str().to_string()
+                    } else {
+                        format!("{}?{}", path.as_str(), query)
+                    };
+                    let mut request = hyper::Request::builder()
+                        .method(method)
+                        .uri(uri)
+                        .body(body)
+                        .map_err(|error| {
+                            warp::reject::custom(AppError::Internal(error.to_string()))
+                        })?;
+                    *request.headers_mut() = headers;
+                    if let Some(addr) = client {
+                        request.extensions_mut().insert(ClientAddr(addr));
+                    }
+                    let outer = request_task_id();
+                    let task = AbortOnDrop(tokio::spawn(async move {
+                        match outer {
+                            Some(outer) => REQUEST_TASK.scope(outer, service.call(request)).await,
+                            None => service.call(request).await,
+                        }
+                    }));
+                    match tokio::time::timeout(deadline, task).await {
+                        Ok(Ok(Ok(response))) => Ok(response),
+                        Ok(Ok(Err(infallible))) => match infallible {},
+                        Ok(Err(error)) => {
+                            Err(warp::reject::custom(AppError::Internal(error.to_string())))
+                        }
+                        Err(_) => {
+                            warn!("Request to {} exceeded its deadline", path.as_str());
+                            Err(warp::reject::custom(AppError::DeadlineExceeded(deadline)))
+                        }
+                    }
+                }
+            },
+        )
+        .boxed()
+}
+
+/// Nested request task, aborted once nothing awaits it any more.
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Future for AbortOnDrop<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Turns the body of the outer request into one for the nested request.
+fn into_body<S, B>(stream: S) -> hyper::Body
+where
+    S: Stream<Item = Result<B, warp::Error>> + Send + 'static,
+    B: Buf,
+{
+    hyper::Body::wrap_stream(stream.map_ok(|mut buf| buf.copy_to_bytes(buf.remaining())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::http::StatusCode;
+    use warp::hyper::body::Bytes;
+    use warp::test::request;
+    use warp::Reply;
+
+    fn echo() -> BoxedFilter<(Response,)> {
+        warp::post()
+            .and(warp::path!("echo" / String))
+            .and(warp::query::raw())
+            .and(warp::header::<String>("x-tag"))
+            .and(client_addr())
+            .and(warp::body::bytes())
+            .map(
+                |name: String,
+                 query: String,
+                 tag: String,
+                 addr: Option<SocketAddr>,
+                 body: Bytes| {
+                    let addr = addr.map(|addr| addr.to_string()).unwrap_or_default();
+                    let body = String::from_utf8_lossy(&body).to_string();
+                    format!("{} {} {} {} {}", name, query, tag, addr, body).into_response()
+                },
+            )
+            .boxed()
+    }
+
+    #[tokio::test]
+    async fn test_nested_requests_keep_their_parts() {
+        let api = with_deadline(Some(Duration::from_secs(10)), echo());
+        let resp = request()
+            .method("POST")
+            .path("/echo/alice?block=latest")
+            .header("x-tag", "blue")
+            .remote_addr("10.0.0.1:4000".parse().unwrap())
+            .body("hello")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.body(), "alice block=latest blue 10.0.0.1:4000 hello");
+    }
+}
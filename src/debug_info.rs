@@ -0,0 +1,169 @@
+use crate::archive::SERVED_BY_HEADER;
+use serde::Serialize;
+use std::time::Duration;
+use warp::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use warp::hyper::body::{self, Body};
+use warp::reply::Response;
+use warp::{Filter, Rejection};
+
+/// Node timing of a request that asked for it with `?debug=true`.
+///
+/// Attached to the response as an extension until [`append_debug`] writes it
+/// into the body as the `_debug` field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DebugInfo {
+    /// Time spent in node calls; concurrent calls are summed.
+    pub rpc_latency_ms: u64,
+    /// `default`, `archive`, or `upstream` for an `X-RPC-URL` node.
+    pub provider: String,
+}
+
+/// Whether `query` holds `debug=true`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_debug_requested() {
+/// assert!(debug_requested("block=latest&debug=true"));
+/// assert!(!debug_requested("debug=1"));
+/// # }
+/// ```
+pub fn debug_requested(query: &str) -> bool {
+    url::form_urlencoded::parse(query.as_bytes())
+        .any(|(key, value)| key == "debug" && value == "true")
+}
+
+/// Provides the provider label of requests asking for `?debug=true`, and
+/// `None` for every other request.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_debug() {
+/// let filter = with_debug();
+/// # }
+/// ```
+pub fn with_debug() -> impl Filter<Extract = (Option<&'static str>,), Error = Rejection> + Clone {
+    warp::query::raw()
+        .or(warp::any().map(String::new))
+        .unify()
+        .and(warp::header::optional::<String>("x-rpc-url"))
+        .map(|query: String, url: Option<String>| {
+            debug_requested(&query).then_some(if url.is_some() { "upstream" } else { "default" })
+        })
+}
+
+/// Attaches the debug information of a response, preferring the node named
+/// by its `X-Served-By` header over `provider`.
+pub fn attach(response: &mut Response, rpc: Duration, provider: &str) {
+    let provider = response
+        .headers()
+        .get(SERVED_BY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or(provider)
+        .to_string();
+    response.extensions_mut().insert(DebugInfo {
+        rpc_latency_ms: rpc.as_millis() as u64,
+        provider,
+    });
+}
+
+/// Adds the attached [`DebugInfo`] to successful JSON object bodies as `_debug`;
+/// other responses, such as MessagePack or event streams, are left as they are.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_append_debug() {
+/// let response = append_debug(response).await;
+/// # }
+/// ```
+pub async fn append_debug(mut response: Response) -> Response {
+    let Some(info) = response.extensions_mut().remove::<DebugInfo>() else {
+        return response;
+    };
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if !response.status().is_success() || !is_json {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = match body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(_) => return Response::from_parts(parts, Body::empty()),
+    };
+    let body = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(serde_json::Value::Object(mut object)) => {
+            object.insert(
+                "_debug".to_string(),
+                serde_json::to_value(&info).unwrap_or_default(),
+            );
+            parts.headers.remove(CONTENT_LENGTH);
+            serde_json::to_vec(&object).unwrap_or_default().into()
+        }
+        _ => bytes.into(),
+    };
+    Response::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::http::StatusCode;
+    use warp::Reply;
+
+    async fn body_of(response: Response) -> serde_json::Value {
+        let bytes = body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_debug_requested() {
+        assert!(debug_requested("debug=true"));
+        assert!(debug_requested("block=safe&debug=true"));
+        assert!(!debug_requested(""));
+        assert!(!debug_requested("debug=false"));
+        assert!(!debug_requested("debug"));
+    }
+
+    #[tokio::test]
+    async fn test_append_debug_to_json_objects() {
+        let mut response =
+            warp::reply::json(&serde_json::json!({ "balance": "1" })).into_response();
+        response
+            .headers_mut()
+            .insert(SERVED_BY_HEADER, "archive".parse().unwrap());
+        attach(&mut response, Duration::from_millis(12), "default");
+        assert_eq!(
+            body_of(append_debug(response).await).await,
+            serde_json::json!({
+                "balance": "1",
+                "_debug": { "rpc_latency_ms": 12, "provider": "archive" }
+            })
+        );
+
+        // Without the extension, and for arrays or failures, bodies are untouched.
+        let plain = warp::reply::json(&serde_json::json!({ "balance": "1" })).into_response();
+        let body = body_of(append_debug(plain).await).await;
+        assert!(body.get("_debug").is_none());
+
+        let mut array = warp::reply::json(&serde_json::json!([1, 2])).into_response();
+        attach(&mut array, Duration::ZERO, "default");
+        assert_eq!(
+            body_of(append_debug(array).await).await,
+            serde_json::json!([1, 2])
+        );
+
+        let mut failed = warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": "no" })),
+            StatusCode::BAD_GATEWAY,
+        )
+        .into_response();
+        attach(&mut failed, Duration::ZERO, "default");
+        let body = body_of(append_debug(failed).await).await;
+        assert!(body.get("_debug").is_none());
+    }
+}
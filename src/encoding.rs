@@ -0,0 +1,40 @@
+use alloy_primitives::Bytes;
+use serde::{Deserialize, Serialize};
+
+/// How an object is returned by the routes accepting `?encoding=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// The decoded object as JSON.
+    #[default]
+    Json,
+    /// The object's raw encoding as a hex string.
+    Rlp,
+}
+
+/// Query parameters accepted by the routes returning raw encodings.
+#[derive(Debug, Default, Deserialize)]
+pub struct EncodingQuery {
+    #[serde(default)]
+    pub encoding: Encoding,
+}
+
+/// Raw encoding of an object, returned for `?encoding=rlp`.
+#[derive(Debug, Serialize)]
+pub struct RlpResponse {
+    pub rlp: Bytes,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding_query() {
+        let query: EncodingQuery = serde_json::from_str("{}").unwrap();
+        assert_eq!(query.encoding, Encoding::Json);
+        let query: EncodingQuery = serde_json::from_str(r#"{"encoding":"rlp"}"#).unwrap();
+        assert_eq!(query.encoding, Encoding::Rlp);
+        assert!(serde_json::from_str::<EncodingQuery>(r#"{"encoding":"xml"}"#).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use log::error;
+use serde::Serialize;
+use std::env;
+use std::str::FromStr;
+use warp::http::StatusCode;
+
+/// Shape wrapped around every JSON response body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Envelope {
+    /// The handler result as-is.
+    #[default]
+    Bare,
+    /// `{ "data": <result> }`.
+    Data,
+    /// `{ "jsonrpc": "2.0", "id": 1, "result": <result> }`.
+    JsonRpc,
+}
+
+impl FromStr for Envelope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bare" => Ok(Envelope::Bare),
+            "data" => Ok(Envelope::Data),
+            "jsonrpc" => Ok(Envelope::JsonRpc),
+            other => Err(format!("Unknown response envelope: {}", other)),
+        }
+    }
+}
+
+impl Envelope {
+    /// `JsonRpc` when the query string asks for it with `jsonrpc=2.0`, `default` otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_from_query() {
+    /// assert_eq!(Envelope::from_query("block=latest&jsonrpc=2.0", Envelope::Bare), Envelope::JsonRpc);
+    /// assert_eq!(Envelope::from_query("", Envelope::Data), Envelope::Data);
+    /// # }
+    /// ```
+    pub fn from_query(query: &str, default: Envelope) -> Envelope {
+        let requested = url::form_urlencoded::parse(query.as_bytes())
+            .any(|(key, value)| key == "jsonrpc" && value == "2.0");
+        if requested {
+            Envelope::JsonRpc
+        } else {
+            default
+        }
+    }
+}
+
+/// A handler result wrapped in the configured envelope.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Wrapped<T> {
+    Bare(T),
+    Data {
+        data: T,
+    },
+    JsonRpc {
+        jsonrpc: &'static str,
+        id: u64,
+        result: T,
+    },
+}
+
+/// Upstream JSON-RPC code of a failed request, attached to the response
+/// for `wrap_error`.
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorCode(pub i64);
+
+/// Error object of a failed request under the JSON-RPC envelope.
+#[derive(Debug, Serialize)]
+pub struct JsonRpcErrorObject {
+    pub code: i64,
+    pub message: String,
+}
+
+/// Body of a failed request under the JSON-RPC envelope.
+#[derive(Debug, Serialize)]
+pub struct JsonRpcError {
+    jsonrpc: &'static str,
+    id: u64,
+    error: JsonRpcErrorObject,
+}
+
+/// Wraps an error answered with `status` in the JSON-RPC envelope.
+///
+/// The code is the node's own when it returned one (`upstream`), otherwise
+/// the closest standard or EIP-1474 code for the status.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_wrap_error() {
+/// let body = wrap_error(StatusCode::BAD_REQUEST, None, "Invalid address: 0x12".to_string());
+/// let reply = warp::reply::json(&body);
+/// # }
+/// ```
+pub fn wrap_error(status: StatusCode, upstream: Option<i64>, message: String) -> JsonRpcError {
+    let code = upstream.unwrap_or(match status {
+        // Invalid params.
+        StatusCode::BAD_REQUEST
+        | StatusCode::PAYLOAD_TOO_LARGE
+        | StatusCode::UNSUPPORTED_MEDIA_TYPE => -32602,
+        // Method not found.
+        StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED => -32601,
+        // Resource not found.
+        StatusCode::NOT_FOUND => -32001,
+        // Resource unavailable.
+        StatusCode::SERVICE_UNAVAILABLE => -32002,
+        // Limit exceeded.
+        StatusCode::TOO_MANY_REQUESTS => -32005,
+        // Internal error.
+        _ => -32603,
+    });
+    JsonRpcError {
+        jsonrpc: "2.0",
+        id: 1,
+        error: JsonRpcErrorObject { code, message },
+    }
+}
+
+/// Retrieves the response envelope from the `RESPONSE_ENVELOPE` environment variable.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_response_envelope() {
+/// let envelope = get_response_envelope();
+/// assert_eq!(envelope, Envelope::Bare);
+/// # }
+/// ```
+pub fn get_response_envelope() -> Envelope {
+    match env::var("RESPONSE_ENVELOPE") {
+        Ok(value) => value.parse().unwrap_or_else(|message| {
+            error!("{}, using bare", message);
+            Envelope::Bare
+        }),
+        Err(_) => Envelope::Bare,
+    }
+}
+
+/// Wraps a handler result in the given envelope before serialization.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_wrap_response() {
+/// let body = wrap_response("1000", &Envelope::Data);
+/// let reply = warp::reply::json(&body);
+/// # }
+/// ```
+pub fn wrap_response<T: Serialize>(result: T, envelope: &Envelope) -> Wrapped<T> {
+    match envelope {
+        Envelope::Bare => Wrapped::Bare(result),
+        Envelope::Data => Wrapped::Data { data: result },
+        Envelope::JsonRpc => Wrapped::JsonRpc {
+            jsonrpc: "2.0",
+            id: 1,
+            result,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Serialize)]
+    struct Body {
+        balance: &'static str,
+    }
+
+    fn wrapped(envelope: Envelope) -> serde_json::Value {
+        serde_json::to_value(wrap_response(Body { balance: "1000" }, &envelope)).unwrap()
+    }
+
+    #[test]
+    fn test_wrap_response_bare() {
+        assert_eq!(wrapped(Envelope::Bare), json!({ "balance": "1000" }));
+    }
+
+    #[test]
+    fn test_wrap_response_data() {
+        assert_eq!(
+            wrapped(Envelope::Data),
+            json!({ "data": { "balance": "1000" } })
+        );
+    }
+
+    #[test]
+    fn test_wrap_response_jsonrpc() {
+        assert_eq!(
+            wrapped(Envelope::JsonRpc),
+            json!({ "jsonrpc": "2.0", "id": 1, "result": { "balance": "1000" } })
+        );
+    }
+
+    #[test]
+    fn test_envelope_from_query() {
+        let from_query = |query| Envelope::from_query(query, Envelope::Data);
+        assert_eq!(from_query("jsonrpc=2.0"), Envelope::JsonRpc);
+        assert_eq!(from_query("unit=ether&jsonrpc=2.0"), Envelope::JsonRpc);
+        assert_eq!(from_query("jsonrpc=1.0"), Envelope::Data);
+        assert_eq!(from_query(""), Envelope::Data);
+    }
+
+    #[test]
+    fn test_wrap_error_jsonrpc() {
+        let wrapped = |status, upstream| {
+            serde_json::to_value(wrap_error(status, upstream, "failed".to_string())).unwrap()
+        };
+        assert_eq!(
+            wrapped(StatusCode::BAD_REQUEST, None),
+            json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": -32602, "message": "failed" } })
+        );
+        assert_eq!(
+            wrapped(StatusCode::NOT_FOUND, None)["error"]["code"],
+            -32001
+        );
+        assert_eq!(
+            wrapped(StatusCode::BAD_GATEWAY, None)["error"]["code"],
+            -32603
+        );
+        assert_eq!(
+            wrapped(StatusCode::BAD_GATEWAY, Some(3))["error"]["code"],
+            3
+        );
+    }
+
+    #[test]
+    fn test_envelope_from_str() {
+        assert_eq!("bare".parse::<Envelope>(), Ok(Envelope::Bare));
+        assert_eq!("data".parse::<Envelope>(), Ok(Envelope::Data));
+        assert_eq!("jsonrpc".parse::<Envelope>(), Ok(Envelope::JsonRpc));
+        assert!("xml".parse::<Envelope>().is_err());
+    }
+}
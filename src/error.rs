@@ -0,0 +1,453 @@
+use crate::body_validation::FieldError;
+use alloy::transports::{RpcError, TransportError, TransportErrorKind};
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+use warp::http::StatusCode;
+
+/// `Retry-After` sent while the server is still warming up.
+const NOT_READY_RETRY_AFTER_SECS: u64 = 1;
+
+/// `Retry-After` sent while the server is in maintenance mode.
+const MAINTENANCE_RETRY_AFTER_SECS: u64 = 30;
+
+/// `Retry-After` sent with requests shed by `MAX_INFLIGHT_REQUESTS`.
+const OVERLOADED_RETRY_AFTER_SECS: u64 = 1;
+
+/// Stable classification of an error, sent as the `error_type` of error
+/// responses so clients can branch without parsing messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorType {
+    /// The request itself is wrong; retrying it unchanged fails again.
+    InvalidInput,
+    /// The node cannot answer right now, or this server is not serving it
+    /// yet, as while starting up, in maintenance or overloaded.
+    UpstreamUnavailable,
+    /// A request rate, quota or stream limit was hit.
+    RateLimited,
+    NotFound,
+    /// The node, or the request as a whole, took too long.
+    Timeout,
+    Internal,
+}
+
+/// Errors surfaced by the handlers, each mapped to its own HTTP status.
+#[derive(Debug)]
+pub enum AppError {
+    /// The request named an address that could not be parsed.
+    InvalidAddress(String),
+    /// The request body or parameters are malformed.
+    InvalidRequest(String),
+    /// The request body has one or more invalid fields, all listed.
+    Validation(Vec<FieldError>),
+    /// The request body is larger than the route accepts once decompressed.
+    PayloadTooLarge,
+    /// The request body has a `Content-Type` or `Content-Encoding` the route does not accept.
+    UnsupportedMediaType(String),
+    /// The requested object does not exist on the node.
+    NotFound(String),
+    /// The request lacks valid admin credentials.
+    Unauthorized,
+    /// The request selected an upstream that is not allowlisted.
+    UpstreamNotAllowed(String),
+    /// The client exceeded its request rate.
+    RateLimited,
+    /// The API key used up its daily quota, which resets at `resets_at`, in
+    /// Unix seconds, `retry_after` seconds from now.
+    QuotaExceeded { resets_at: u64, retry_after: u64 },
+    /// The server has not reached the node since it started.
+    NotReady,
+    /// The server is in maintenance mode; carries the message shown to clients.
+    Maintenance(String),
+    /// The server already has `MAX_INFLIGHT_REQUESTS` requests in flight.
+    Overloaded,
+    /// The maximum number of balance streams is already open.
+    TooManyStreams,
+    /// The node answered with a JSON-RPC error object.
+    Rpc { code: i64, message: String },
+    /// The node could not be reached or the connection failed.
+    Transport(String),
+    /// The node did not answer in time.
+    Timeout(String),
+    /// The request as a whole ran past `REQUEST_DEADLINE_MS`.
+    DeadlineExceeded(Duration),
+    /// The node answered over HTTP with a non-success status.
+    UpstreamHttp { status: u16, body: String },
+    /// The node answered with a payload that could not be decoded.
+    Decode(String),
+    /// Anything else that went wrong while serving the request.
+    Internal(String),
+}
+impl warp::reject::Reject for AppError {}
+
+impl AppError {
+    /// HTTP status returned to the client for this error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_status() {
+    /// let error = AppError::Rpc { code: -32601, message: "method not found".into() };
+    /// assert_eq!(error.status(), StatusCode::NOT_IMPLEMENTED);
+    /// # }
+    /// ```
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::InvalidAddress(_) | AppError::InvalidRequest(_) | AppError::Validation(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            AppError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::UpstreamNotAllowed(_) => StatusCode::FORBIDDEN,
+            AppError::RateLimited | AppError::QuotaExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Rpc { code, .. } => match code {
+                // Method not found.
+                -32601 => StatusCode::NOT_IMPLEMENTED,
+                // Invalid request / invalid params.
+                -32600 | -32602 => StatusCode::BAD_REQUEST,
+                // Limit exceeded.
+                -32005 => StatusCode::TOO_MANY_REQUESTS,
+                _ => StatusCode::BAD_GATEWAY,
+            },
+            AppError::NotReady
+            | AppError::Maintenance(_)
+            | AppError::Overloaded
+            | AppError::TooManyStreams
+            | AppError::Transport(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Timeout(_) | AppError::DeadlineExceeded(_) => StatusCode::GATEWAY_TIMEOUT,
+            AppError::UpstreamHttp { status: 429, .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::UpstreamHttp { .. } | AppError::Decode(_) => StatusCode::BAD_GATEWAY,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Classification of this error, sent as `error_type`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_error_type() {
+    /// assert_eq!(AppError::RateLimited.error_type(), ErrorType::RateLimited);
+    /// # }
+    /// ```
+    pub fn error_type(&self) -> ErrorType {
+        match self {
+            AppError::InvalidAddress(_)
+            | AppError::InvalidRequest(_)
+            | AppError::Validation(_)
+            | AppError::PayloadTooLarge
+            | AppError::UnsupportedMediaType(_)
+            | AppError::Unauthorized
+            | AppError::UpstreamNotAllowed(_) => ErrorType::InvalidInput,
+            AppError::NotFound(_) => ErrorType::NotFound,
+            AppError::RateLimited | AppError::QuotaExceeded { .. } | AppError::TooManyStreams => {
+                ErrorType::RateLimited
+            }
+            AppError::Rpc { code, .. } => match code {
+                -32600 | -32602 => ErrorType::InvalidInput,
+                -32005 => ErrorType::RateLimited,
+                _ => ErrorType::UpstreamUnavailable,
+            },
+            AppError::UpstreamHttp { status: 429, .. } => ErrorType::RateLimited,
+            AppError::UpstreamHttp {
+                status: 408 | 504, ..
+            }
+            | AppError::Timeout(_)
+            | AppError::DeadlineExceeded(_) => ErrorType::Timeout,
+            AppError::NotReady
+            | AppError::Maintenance(_)
+            | AppError::Overloaded
+            | AppError::Transport(_)
+            | AppError::UpstreamHttp { .. }
+            | AppError::Decode(_) => ErrorType::UpstreamUnavailable,
+            AppError::Internal(_) => ErrorType::Internal,
+        }
+    }
+
+    /// Seconds the client should wait before retrying, sent as `Retry-After`.
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            AppError::NotReady => Some(NOT_READY_RETRY_AFTER_SECS),
+            AppError::Maintenance(_) => Some(MAINTENANCE_RETRY_AFTER_SECS),
+            AppError::Overloaded => Some(OVERLOADED_RETRY_AFTER_SECS),
+            AppError::QuotaExceeded { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+
+    /// Problems with the request body, listed in the `errors` of the response.
+    pub fn field_errors(&self) -> &[FieldError] {
+        match self {
+            AppError::Validation(errors) => errors,
+            _ => &[],
+        }
+    }
+
+    /// Upstream JSON-RPC error code, when the node returned one.
+    pub fn code(&self) -> Option<i64> {
+        match self {
+            AppError::Rpc { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::InvalidAddress(address) => write!(f, "Invalid address: {}", address),
+            AppError::InvalidRequest(message) => write!(f, "Invalid request: {}", message),
+            AppError::Validation(errors) => {
+                let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                write!(f, "Invalid request: {}", errors.join("; "))
+            }
+            AppError::PayloadTooLarge => write!(f, "Request body too large"),
+            AppError::UnsupportedMediaType(what) => write!(f, "Unsupported media type: {}", what),
+            AppError::NotFound(what) => write!(f, "Not found: {}", what),
+            AppError::Unauthorized => write!(f, "Invalid or missing admin token"),
+            AppError::UpstreamNotAllowed(url) => write!(f, "Upstream not allowed: {}", url),
+            AppError::RateLimited => write!(f, "Rate limit exceeded"),
+            AppError::QuotaExceeded { resets_at, .. } => {
+                write!(
+                    f,
+                    "Daily quota exceeded; it resets at Unix time {}",
+                    resets_at
+                )
+            }
+            AppError::NotReady => write!(f, "Service is starting up"),
+            AppError::Maintenance(message) => write!(f, "{}", message),
+            AppError::Overloaded => write!(f, "Server is overloaded"),
+            AppError::TooManyStreams => write!(f, "Too many open balance streams"),
+            AppError::Rpc { code, message } => write!(f, "Upstream error {}: {}", code, message),
+            AppError::Transport(message) => write!(f, "Upstream unavailable: {}", message),
+            AppError::Timeout(message) => write!(f, "Upstream timed out: {}", message),
+            AppError::DeadlineExceeded(deadline) => {
+                write!(f, "Request deadline of {}ms exceeded", deadline.as_millis())
+            }
+            AppError::UpstreamHttp { status, body } => {
+                write!(f, "Upstream returned HTTP {}: {}", status, body)
+            }
+            AppError::Decode(message) => write!(f, "Invalid upstream response: {}", message),
+            AppError::Internal(message) => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl From<TransportError> for AppError {
+    fn from(error: TransportError) -> Self {
+        match error {
+            RpcError::ErrorResp(payload) => AppError::Rpc {
+                code: payload.code,
+                message: payload.message.into_owned(),
+            },
+            RpcError::NullResp => AppError::Decode("null response".to_string()),
+            RpcError::DeserError { err, .. } => AppError::Decode(err.to_string()),
+            RpcError::Transport(TransportErrorKind::HttpError(http)) => AppError::UpstreamHttp {
+                status: http.status,
+                body: http.body,
+            },
+            RpcError::Transport(TransportErrorKind::Custom(error))
+                if error
+                    .downcast_ref::<reqwest::Error>()
+                    .is_some_and(reqwest::Error::is_timeout) =>
+            {
+                AppError::Timeout(error.to_string())
+            }
+            RpcError::Transport(kind) => AppError::Transport(kind.to_string()),
+            other => AppError::Internal(other.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::rpc::json_rpc::ErrorPayload;
+    use alloy::transports::HttpError;
+    use std::borrow::Cow;
+
+    fn rpc_error(code: i64, message: &'static str) -> TransportError {
+        RpcError::ErrorResp(ErrorPayload {
+            code,
+            message: Cow::Borrowed(message),
+            data: None,
+        })
+    }
+
+    #[test]
+    fn test_method_not_found_maps_to_not_implemented() {
+        let error = AppError::from(rpc_error(-32601, "the method eth_foo does not exist"));
+        assert_eq!(error.status(), StatusCode::NOT_IMPLEMENTED);
+        assert_eq!(error.code(), Some(-32601));
+        assert!(error.to_string().contains("eth_foo"));
+    }
+
+    #[test]
+    fn test_rpc_error_codes_map_to_statuses() {
+        let cases = [
+            (-32602, StatusCode::BAD_REQUEST),
+            (-32600, StatusCode::BAD_REQUEST),
+            (-32005, StatusCode::TOO_MANY_REQUESTS),
+            (-32000, StatusCode::BAD_GATEWAY),
+            (3, StatusCode::BAD_GATEWAY),
+        ];
+        for (code, status) in cases {
+            assert_eq!(AppError::from(rpc_error(code, "boom")).status(), status);
+        }
+    }
+
+    #[test]
+    fn test_transport_errors_map_to_service_unavailable() {
+        let error = AppError::from(TransportErrorKind::custom_str("connection refused"));
+        assert!(matches!(error, AppError::Transport(_)));
+        assert_eq!(error.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let error = AppError::from(RpcError::Transport(TransportErrorKind::BackendGone));
+        assert_eq!(error.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[test]
+    fn test_upstream_http_errors_keep_status() {
+        let http = |status| {
+            RpcError::Transport(TransportErrorKind::HttpError(HttpError {
+                status,
+                body: "nope".to_string(),
+            }))
+        };
+        assert_eq!(
+            AppError::from(http(429)).status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(AppError::from(http(500)).status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[test]
+    fn test_decode_errors_map_to_bad_gateway() {
+        let err = serde_json::from_str::<u64>("\"nope\"").unwrap_err();
+        let error = AppError::from(TransportError::DeserError {
+            err,
+            text: "\"nope\"".to_string(),
+        });
+        assert!(matches!(error, AppError::Decode(_)));
+        assert_eq!(error.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            AppError::from(TransportError::NullResp).status(),
+            StatusCode::BAD_GATEWAY
+        );
+    }
+
+    #[test]
+    fn test_invalid_address_is_a_bad_request() {
+        let error = AppError::InvalidAddress("0x123".to_string());
+        assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(error.code(), None);
+    }
+
+    #[test]
+    fn test_unlisted_upstream_is_forbidden() {
+        let error = AppError::UpstreamNotAllowed("http://elsewhere:8545".to_string());
+        assert_eq!(error.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn test_not_ready_asks_clients_to_retry() {
+        assert_eq!(AppError::NotReady.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(AppError::NotReady.retry_after(), Some(1));
+        assert_eq!(AppError::RateLimited.retry_after(), None);
+        let exhausted = AppError::QuotaExceeded {
+            resets_at: 1_704_153_600,
+            retry_after: 60,
+        };
+        assert_eq!(exhausted.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(exhausted.retry_after(), Some(60));
+        assert_eq!(
+            AppError::Overloaded.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(AppError::Overloaded.retry_after(), Some(1));
+    }
+
+    #[test]
+    fn test_errors_map_to_error_types() {
+        let http = |status| AppError::UpstreamHttp {
+            status,
+            body: String::new(),
+        };
+        let rpc = |code| AppError::Rpc {
+            code,
+            message: String::new(),
+        };
+        let cases = [
+            (
+                AppError::InvalidAddress("0x123".into()),
+                ErrorType::InvalidInput,
+            ),
+            (
+                AppError::InvalidRequest("bad".into()),
+                ErrorType::InvalidInput,
+            ),
+            (AppError::Validation(Vec::new()), ErrorType::InvalidInput),
+            (AppError::PayloadTooLarge, ErrorType::InvalidInput),
+            (AppError::Unauthorized, ErrorType::InvalidInput),
+            (rpc(-32602), ErrorType::InvalidInput),
+            (AppError::NotFound("tx".into()), ErrorType::NotFound),
+            (AppError::RateLimited, ErrorType::RateLimited),
+            (
+                AppError::QuotaExceeded {
+                    resets_at: 0,
+                    retry_after: 0,
+                },
+                ErrorType::RateLimited,
+            ),
+            (AppError::TooManyStreams, ErrorType::RateLimited),
+            (rpc(-32005), ErrorType::RateLimited),
+            (http(429), ErrorType::RateLimited),
+            (AppError::NotReady, ErrorType::UpstreamUnavailable),
+            (AppError::Overloaded, ErrorType::UpstreamUnavailable),
+            (
+                AppError::Transport("refused".into()),
+                ErrorType::UpstreamUnavailable,
+            ),
+            (rpc(-32000), ErrorType::UpstreamUnavailable),
+            (http(500), ErrorType::UpstreamUnavailable),
+            (
+                AppError::Decode("null".into()),
+                ErrorType::UpstreamUnavailable,
+            ),
+            (AppError::Timeout("slow".into()), ErrorType::Timeout),
+            (
+                AppError::DeadlineExceeded(Duration::from_secs(1)),
+                ErrorType::Timeout,
+            ),
+            (http(504), ErrorType::Timeout),
+            (AppError::Internal("oops".into()), ErrorType::Internal),
+        ];
+        for (error, error_type) in cases {
+            assert_eq!(error.error_type(), error_type, "{:?}", error);
+        }
+        assert_eq!(
+            serde_json::to_value(ErrorType::UpstreamUnavailable).unwrap(),
+            "upstream_unavailable"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_timeouts_map_to_timeout() {
+        // Accepts connections but never answers.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let error = reqwest::Client::new()
+            .get(url)
+            .timeout(std::time::Duration::from_millis(20))
+            .send()
+            .await
+            .unwrap_err();
+        let error = AppError::from(TransportErrorKind::custom(error));
+        assert!(matches!(error, AppError::Timeout(_)), "{:?}", error);
+        assert_eq!(error.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(error.error_type(), ErrorType::Timeout);
+    }
+}
@@ -0,0 +1,151 @@
+use log::{info, warn};
+use serde::Serialize;
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use warp::http::header::CONTENT_TYPE;
+use warp::{Filter, Rejection};
+
+/// Header carrying the request id, echoed on every response.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-supplied request id that is kept; longer ones are replaced.
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// How long a single report may take before it is abandoned.
+const REPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Error message of a failed request, attached to the response for the reporter.
+#[derive(Debug, Clone)]
+pub struct ErrorMessage(pub String);
+
+/// Event posted to the error sink for every `5xx` response.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorEvent {
+    pub message: String,
+    pub route: String,
+    pub status: u16,
+    pub request_id: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// Posts error events to an external sink without delaying the response.
+pub struct ErrorReporter {
+    url: String,
+    client: reqwest::Client,
+}
+
+/// Retrieves the error sink from the `ERROR_REPORT_URL` environment variable.
+pub fn get_error_report_url() -> Option<String> {
+    let url = env::var("ERROR_REPORT_URL")
+        .ok()
+        .filter(|url| !url.is_empty())?;
+    info!("Reporting server errors to {}", url);
+    Some(url)
+}
+
+impl ErrorReporter {
+    pub fn new(url: impl Into<String>) -> Self {
+        ErrorReporter {
+            url: url.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Sends `event` in the background; failures are only logged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_report() {
+    /// let reporter = ErrorReporter::new("https://errors.example/events");
+    /// reporter.report(event);
+    /// # }
+    /// ```
+    pub fn report(&self, event: ErrorEvent) {
+        let request = self
+            .client
+            .post(&self.url)
+            .timeout(REPORT_TIMEOUT)
+            .header(CONTENT_TYPE.as_str(), "application/json")
+            .body(serde_json::to_vec(&event).unwrap_or_default());
+        tokio::spawn(async move {
+            match request.send().await {
+                Ok(response) if !response.status().is_success() => {
+                    warn!("Error sink answered {}", response.status())
+                }
+                Ok(_) => {}
+                Err(error) => warn!("Failed to report error: {}", error),
+            }
+        });
+    }
+}
+
+/// Milliseconds since the Unix epoch.
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Generates a process-unique request id.
+fn next_request_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "{:x}-{:x}",
+        unix_millis(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Provides the request id: the client's `X-Request-Id` when it is short and
+/// printable, otherwise a generated one.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_request_id() {
+/// let filter = request_id();
+/// # }
+/// ```
+pub fn request_id() -> impl Filter<Extract = (String,), Error = Rejection> + Clone {
+    warp::header::optional::<String>(REQUEST_ID_HEADER).map(|id: Option<String>| {
+        id.filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.bytes().all(|byte| byte.is_ascii_graphic())
+        })
+        .unwrap_or_else(next_request_id)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::test::request;
+
+    #[tokio::test]
+    async fn test_request_id_keeps_valid_client_ids() {
+        let filter = request_id();
+        let id = request()
+            .header(REQUEST_ID_HEADER, "abc-123")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(id, "abc-123");
+
+        let long = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+        let id = request()
+            .header(REQUEST_ID_HEADER, long.as_str())
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_ne!(id, long);
+
+        let first = request().filter(&filter).await.unwrap();
+        let second = request().filter(&filter).await.unwrap();
+        assert_ne!(first, second);
+    }
+}
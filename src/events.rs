@@ -0,0 +1,189 @@
+use alloy::dyn_abi::{DynSolValue, EventExt};
+use alloy::json_abi::Event;
+use alloy::primitives::LogData;
+use alloy_primitives::B256;
+use anyhow::Context;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::env;
+
+/// Events decoded out of the box.
+const BUILTIN_EVENTS: &[&str] = &[
+    "Transfer(address indexed from, address indexed to, uint256 value)",
+    "Approval(address indexed owner, address indexed spender, uint256 value)",
+];
+
+/// A log decoded against a known event.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DecodedLog {
+    pub event: String,
+    pub args: Map<String, Value>,
+}
+
+/// Known events, looked up by their signature hash.
+pub struct EventRegistry {
+    events: HashMap<B256, Vec<Event>>,
+}
+
+impl EventRegistry {
+    /// A registry with the built-in events and the given human-readable signatures.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_event_registry_new() {
+    /// let registry = EventRegistry::new(&["Deposit(address indexed dst, uint256 wad)"]).unwrap();
+    /// # }
+    /// ```
+    pub fn new(signatures: &[&str]) -> anyhow::Result<Self> {
+        let mut events: HashMap<B256, Vec<Event>> = HashMap::new();
+        for signature in BUILTIN_EVENTS.iter().chain(signatures) {
+            let event = Event::parse(signature)
+                .with_context(|| format!("Invalid event signature {:?}", signature))?;
+            events.entry(event.selector()).or_default().push(event);
+        }
+        Ok(EventRegistry { events })
+    }
+
+    /// Decodes `log` with the first known event whose signature and indexed
+    /// arguments match; `None` for anything else.
+    pub fn decode(&self, log: &LogData) -> Option<DecodedLog> {
+        let candidates = self.events.get(log.topics().first()?)?;
+        candidates.iter().find_map(|event| {
+            let decoded = event.decode_log(log, true).ok()?;
+            let (mut indexed, mut body) = (decoded.indexed.into_iter(), decoded.body.into_iter());
+            let args = event
+                .inputs
+                .iter()
+                .enumerate()
+                .map(|(position, input)| {
+                    let value = if input.indexed {
+                        indexed.next()
+                    } else {
+                        body.next()
+                    };
+                    let name = if input.name.is_empty() {
+                        format!("arg{}", position)
+                    } else {
+                        input.name.clone()
+                    };
+                    value.map(|value| (name, to_json(&value)))
+                })
+                .collect::<Option<Map<_, _>>>()?;
+            Some(DecodedLog {
+                event: event.name.clone(),
+                args,
+            })
+        })
+    }
+}
+
+/// Renders an ABI value as JSON, with integers as decimal strings.
+fn to_json(value: &DynSolValue) -> Value {
+    match value {
+        DynSolValue::Bool(value) => Value::Bool(*value),
+        DynSolValue::Int(value, _) => Value::String(value.to_string()),
+        DynSolValue::Uint(value, _) => Value::String(value.to_string()),
+        DynSolValue::Address(address) => Value::String(address.to_checksum(None)),
+        DynSolValue::FixedBytes(word, size) => {
+            Value::String(alloy_primitives::hex::encode_prefixed(&word[..*size]))
+        }
+        DynSolValue::Bytes(bytes) => Value::String(alloy_primitives::hex::encode_prefixed(bytes)),
+        DynSolValue::String(value) => Value::String(value.clone()),
+        DynSolValue::Array(values)
+        | DynSolValue::FixedArray(values)
+        | DynSolValue::Tuple(values)
+        // Event ABIs decode structs as tuples; only typed data builds these.
+        | DynSolValue::CustomStruct { tuple: values, .. } => {
+            Value::Array(values.iter().map(to_json).collect())
+        }
+        DynSolValue::Function(function) => {
+            Value::String(alloy_primitives::hex::encode_prefixed(function.as_slice()))
+        }
+    }
+}
+
+/// Builds the registry, adding the `;`-separated signatures of `LOG_EVENT_SIGNATURES`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_event_registry() {
+/// let registry = get_event_registry().unwrap();
+/// # }
+/// ```
+pub fn get_event_registry() -> anyhow::Result<EventRegistry> {
+    let value = env::var("LOG_EVENT_SIGNATURES").unwrap_or_default();
+    let signatures: Vec<&str> = value
+        .split(';')
+        .map(str::trim)
+        .filter(|signature| !signature.is_empty())
+        .collect();
+    EventRegistry::new(&signatures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, keccak256, Bytes, U256};
+    use serde_json::json;
+
+    #[test]
+    fn test_decodes_erc20_transfer() {
+        let registry = EventRegistry::new(&[]).unwrap();
+        let from = address!("1111111111111111111111111111111111111111");
+        let to = address!("2222222222222222222222222222222222222222");
+        let log = LogData::new_unchecked(
+            vec![
+                keccak256("Transfer(address,address,uint256)"),
+                from.into_word(),
+                to.into_word(),
+            ],
+            Bytes::from(U256::from(1000).to_be_bytes::<32>().to_vec()),
+        );
+
+        let decoded = registry.decode(&log).unwrap();
+        assert_eq!(decoded.event, "Transfer");
+        assert_eq!(
+            Value::Object(decoded.args),
+            json!({
+                "from": from.to_checksum(None),
+                "to": to.to_checksum(None),
+                "value": "1000",
+            })
+        );
+    }
+
+    #[test]
+    fn test_unknown_and_mismatched_logs_are_not_decoded() {
+        let registry = EventRegistry::new(&[]).unwrap();
+        let unknown = LogData::new_unchecked(vec![keccak256("Unknown()")], Bytes::new());
+        assert_eq!(registry.decode(&unknown), None);
+
+        // An ERC-721 Transfer indexes the token id, so the ERC-20 layout does not fit.
+        let token_id = U256::from(7).into();
+        let erc721 = LogData::new_unchecked(
+            vec![
+                keccak256("Transfer(address,address,uint256)"),
+                B256::ZERO,
+                B256::ZERO,
+                token_id,
+            ],
+            Bytes::new(),
+        );
+        assert_eq!(registry.decode(&erc721), None);
+    }
+
+    #[test]
+    fn test_registry_accepts_configured_events() {
+        let registry = EventRegistry::new(&["Deposit(address indexed dst, uint256 wad)"]).unwrap();
+        let log = LogData::new_unchecked(
+            vec![keccak256("Deposit(address,uint256)"), B256::ZERO],
+            Bytes::from(U256::from(5).to_be_bytes::<32>().to_vec()),
+        );
+        assert_eq!(registry.decode(&log).unwrap().event, "Deposit");
+
+        assert!(EventRegistry::new(&["not an event"]).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::error::AppError;
+use alloy::rpc::types::FeeHistory;
+use serde::{Deserialize, Serialize};
+
+/// Most blocks a single `/fee-history` request may cover, as allowed by `eth_feeHistory`.
+pub const MAX_FEE_HISTORY_BLOCKS: u64 = 1024;
+
+/// Most reward percentiles a single request may ask for.
+const MAX_PERCENTILES: usize = 100;
+
+/// Query parameters of `/fee-history`.
+#[derive(Debug, Deserialize)]
+pub struct FeeHistoryQuery {
+    pub blocks: u64,
+    /// Comma-separated reward percentiles, e.g. `10,50,90`.
+    pub percentiles: Option<String>,
+}
+
+impl FeeHistoryQuery {
+    /// Checks the block count and parses the percentiles, which must be
+    /// between 0 and 100 and in ascending order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_percentiles() {
+    /// let query = FeeHistoryQuery { blocks: 4, percentiles: Some("10,50,90".to_string()) };
+    /// assert_eq!(query.validate().unwrap(), vec![10.0, 50.0, 90.0]);
+    /// # }
+    /// ```
+    pub fn validate(&self) -> Result<Vec<f64>, AppError> {
+        if self.blocks == 0 || self.blocks > MAX_FEE_HISTORY_BLOCKS {
+            return Err(AppError::InvalidRequest(format!(
+                "blocks must be between 1 and {}",
+                MAX_FEE_HISTORY_BLOCKS
+            )));
+        }
+        let Some(percentiles) = &self.percentiles else {
+            return Ok(Vec::new());
+        };
+        let parsed = percentiles
+            .split(',')
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|value| (0.0..=100.0).contains(value))
+                    .ok_or_else(|| {
+                        AppError::InvalidRequest(format!(
+                            "percentile {} is not between 0 and 100",
+                            value.trim()
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<f64>, _>>()?;
+        if parsed.len() > MAX_PERCENTILES {
+            return Err(AppError::InvalidRequest(format!(
+                "at most {} percentiles are allowed",
+                MAX_PERCENTILES
+            )));
+        }
+        if parsed.windows(2).any(|pair| pair[0] > pair[1]) {
+            return Err(AppError::InvalidRequest(
+                "percentiles must be in ascending order".to_string(),
+            ));
+        }
+        Ok(parsed)
+    }
+}
+
+/// Fee history with every amount in wei as a decimal string.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FeeHistoryResponse {
+    pub oldest_block: u64,
+    /// One entry per block plus the base fee of the next block.
+    pub base_fee_per_gas: Vec<String>,
+    pub gas_used_ratio: Vec<f64>,
+    /// Per block, the priority fee at each requested percentile.
+    pub reward: Vec<Vec<String>>,
+}
+
+impl From<FeeHistory> for FeeHistoryResponse {
+    fn from(history: FeeHistory) -> Self {
+        let to_strings = |values: Vec<u128>| values.into_iter().map(|v| v.to_string()).collect();
+        FeeHistoryResponse {
+            oldest_block: history.oldest_block,
+            base_fee_per_gas: to_strings(history.base_fee_per_gas),
+            gas_used_ratio: history.gas_used_ratio,
+            reward: history
+                .reward
+                .unwrap_or_default()
+                .into_iter()
+                .map(to_strings)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(blocks: u64, percentiles: &str) -> FeeHistoryQuery {
+        FeeHistoryQuery {
+            blocks,
+            percentiles: Some(percentiles.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_validate_parses_percentiles() {
+        assert_eq!(
+            query(4, "10, 50,90").validate().unwrap(),
+            vec![10.0, 50.0, 90.0]
+        );
+        let none = FeeHistoryQuery {
+            blocks: 4,
+            percentiles: None,
+        };
+        assert!(none.validate().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_input() {
+        assert!(query(0, "50").validate().is_err());
+        assert!(query(MAX_FEE_HISTORY_BLOCKS + 1, "50").validate().is_err());
+        assert!(query(4, "101").validate().is_err());
+        assert!(query(4, "-1").validate().is_err());
+        assert!(query(4, "fifty").validate().is_err());
+        assert!(query(4, "90,10").validate().is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use crate::error::AppError;
+use serde::Serialize;
+use warp::http::header::CONTENT_TYPE;
+use warp::http::HeaderValue;
+use warp::reply::Response;
+use warp::{Filter, Rejection};
+
+/// Serialization of a response body, negotiated through the `Accept` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl Format {
+    /// The first supported media type listed in an `Accept` header, JSON otherwise.
+    ///
+    /// Quality values are ignored: clients list the format they want first.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_from_accept() {
+    /// assert_eq!(Format::from_accept(Some("application/cbor")), Format::Cbor);
+    /// assert_eq!(Format::from_accept(None), Format::Json);
+    /// # }
+    /// ```
+    pub fn from_accept(accept: Option<&str>) -> Self {
+        accept
+            .into_iter()
+            .flat_map(|accept| accept.split(','))
+            .filter_map(|media_type| {
+                let media_type = media_type.split(';').next().unwrap_or_default().trim();
+                match media_type.to_ascii_lowercase().as_str() {
+                    "application/json" => Some(Format::Json),
+                    "application/msgpack" | "application/x-msgpack" => Some(Format::MessagePack),
+                    "application/cbor" => Some(Format::Cbor),
+                    _ => None,
+                }
+            })
+            .next()
+            .unwrap_or_default()
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::MessagePack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+        }
+    }
+
+    fn serialize<T: Serialize>(&self, body: &T) -> Result<Vec<u8>, AppError> {
+        let internal =
+            |error: String| AppError::Internal(format!("Failed to serialize: {}", error));
+        match self {
+            Format::Json => serde_json::to_vec(body).map_err(|error| internal(error.to_string())),
+            // Named fields keep the same keys as the JSON body.
+            Format::MessagePack => {
+                rmp_serde::to_vec_named(body).map_err(|error| internal(error.to_string()))
+            }
+            Format::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(body, &mut bytes)
+                    .map_err(|error| internal(error.to_string()))?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+/// Media type of newline-delimited JSON, one item per line.
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Whether NDJSON comes first among the media types of an `Accept` header
+/// that a streaming route can answer in.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_accepts_ndjson() {
+/// assert!(accepts_ndjson(Some("application/x-ndjson")));
+/// assert!(!accepts_ndjson(Some("application/json, application/x-ndjson")));
+/// # }
+/// ```
+pub fn accepts_ndjson(accept: Option<&str>) -> bool {
+    accept
+        .into_iter()
+        .flat_map(|accept| accept.split(','))
+        .map(|media_type| {
+            let media_type = media_type.split(';').next().unwrap_or_default().trim();
+            media_type.to_ascii_lowercase()
+        })
+        .find(|media_type| media_type == NDJSON_CONTENT_TYPE || media_type == "application/json")
+        .is_some_and(|media_type| media_type == NDJSON_CONTENT_TYPE)
+}
+
+/// Provides the response format requested by the `Accept` header.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_format() {
+/// let filter = with_format();
+/// # }
+/// ```
+pub fn with_format() -> impl Filter<Extract = (Format,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("accept")
+        .map(|accept: Option<String>| Format::from_accept(accept.as_deref()))
+}
+
+/// Serializes `body` in the negotiated format, labelled with its content type.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_negotiate_and_serialize() {
+/// let reply = negotiate_and_serialize(&wrap_response(body, &envelope), Format::Cbor).unwrap();
+/// assert_eq!(reply.headers()["content-type"], "application/cbor");
+/// # }
+/// ```
+pub fn negotiate_and_serialize<T: Serialize>(
+    body: &T,
+    format: Format,
+) -> Result<Response, AppError> {
+    let mut response = Response::new(format.serialize(body)?.into());
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_accept() {
+        assert_eq!(Format::from_accept(None), Format::Json);
+        assert_eq!(Format::from_accept(Some("*/*")), Format::Json);
+        assert_eq!(
+            Format::from_accept(Some("application/msgpack")),
+            Format::MessagePack
+        );
+        assert_eq!(
+            Format::from_accept(Some("text/html, application/x-msgpack;q=0.9")),
+            Format::MessagePack
+        );
+        assert_eq!(
+            Format::from_accept(Some("Application/CBOR, application/json")),
+            Format::Cbor
+        );
+        assert_eq!(
+            Format::from_accept(Some("application/json, application/cbor")),
+            Format::Json
+        );
+    }
+
+    #[test]
+    fn test_accepts_ndjson() {
+        assert!(accepts_ndjson(Some("application/x-ndjson")));
+        assert!(accepts_ndjson(Some(
+            "text/html, Application/X-NDJSON;q=0.9"
+        )));
+        assert!(!accepts_ndjson(Some(
+            "application/json, application/x-ndjson"
+        )));
+        assert!(!accepts_ndjson(Some("*/*")));
+        assert!(!accepts_ndjson(None));
+    }
+
+    #[test]
+    fn test_serialized_bodies_decode_to_the_same_value() {
+        let body = json!({ "balance": "1000", "unit": "wei" });
+        for format in [Format::Json, Format::MessagePack, Format::Cbor] {
+            let bytes = format.serialize(&body).unwrap();
+            let decoded: serde_json::Value = match format {
+                Format::Json => serde_json::from_slice(&bytes).unwrap(),
+                Format::MessagePack => rmp_serde::from_slice(&bytes).unwrap(),
+                Format::Cbor => ciborium::from_reader(bytes.as_slice()).unwrap(),
+            };
+            assert_eq!(decoded, body, "{:?}", format);
+        }
+    }
+}
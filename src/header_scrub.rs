@@ -0,0 +1,139 @@
+use anyhow::Context as _;
+use std::env;
+use std::fmt;
+use warp::http::header::{HeaderMap, HeaderName};
+
+/// Written in place of the value of a sensitive header.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Headers redacted when `SENSITIVE_HEADERS` is unset.
+const DEFAULT_SENSITIVE_HEADERS: &str = "authorization,x-api-key";
+
+/// Headers whose values never reach the logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SensitiveHeaders(Vec<HeaderName>);
+
+impl SensitiveHeaders {
+    /// Parses a comma-separated list of header names, e.g. `authorization,x-api-key`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_sensitive_headers_parse() {
+    /// let sensitive = SensitiveHeaders::parse("Authorization, X-API-Key").unwrap();
+    /// assert!(sensitive.contains(&HeaderName::from_static("x-api-key")));
+    /// # }
+    /// ```
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("{:?} is not a header name", name))
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(SensitiveHeaders)
+    }
+
+    pub fn contains(&self, name: &HeaderName) -> bool {
+        self.0.contains(name)
+    }
+
+    /// `headers` as `name: value` pairs, with sensitive values replaced by [`REDACTED`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_scrub() {
+    /// let line = SensitiveHeaders::default().scrub(&headers).to_string();
+    /// assert_eq!(line, "authorization: [REDACTED], accept: */*");
+    /// # }
+    /// ```
+    pub fn scrub<'a>(&'a self, headers: &'a HeaderMap) -> Scrubbed<'a> {
+        Scrubbed {
+            headers,
+            sensitive: self,
+        }
+    }
+}
+
+impl Default for SensitiveHeaders {
+    fn default() -> Self {
+        SensitiveHeaders::parse(DEFAULT_SENSITIVE_HEADERS).expect("valid header names")
+    }
+}
+
+/// Headers displayed with their sensitive values redacted.
+pub struct Scrubbed<'a> {
+    headers: &'a HeaderMap,
+    sensitive: &'a SensitiveHeaders,
+}
+
+impl fmt::Display for Scrubbed<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (name, value)) in self.headers.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            if self.sensitive.contains(name) {
+                write!(f, "{}: {}", name, REDACTED)?;
+            } else {
+                write!(f, "{}: {}", name, String::from_utf8_lossy(value.as_bytes()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the headers to redact from the logs from `SENSITIVE_HEADERS`, a
+/// comma-separated list replacing the default `authorization,x-api-key`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_sensitive_headers() {
+/// let sensitive = get_sensitive_headers().unwrap();
+/// assert_eq!(sensitive, SensitiveHeaders::default());
+/// # }
+/// ```
+pub fn get_sensitive_headers() -> anyhow::Result<SensitiveHeaders> {
+    match env::var("SENSITIVE_HEADERS") {
+        Ok(value) => SensitiveHeaders::parse(&value).context("Invalid SENSITIVE_HEADERS"),
+        Err(_) => Ok(SensitiveHeaders::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrub_redacts_sensitive_values() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer secret".parse().unwrap());
+        headers.insert("x-api-key", "key-123".parse().unwrap());
+        headers.insert("accept", "*/*".parse().unwrap());
+
+        let line = SensitiveHeaders::default().scrub(&headers).to_string();
+        assert_eq!(
+            line,
+            "authorization: [REDACTED], x-api-key: [REDACTED], accept: */*"
+        );
+
+        let custom = SensitiveHeaders::parse(" Accept ").unwrap();
+        let line = custom.scrub(&headers).to_string();
+        assert!(line.contains("authorization: Bearer secret"));
+        assert!(line.contains("accept: [REDACTED]"));
+    }
+
+    #[test]
+    fn test_parse_sensitive_headers() {
+        assert!(SensitiveHeaders::parse("").unwrap().0.is_empty());
+        assert!(SensitiveHeaders::parse("x-api-key,")
+            .unwrap()
+            .contains(&HeaderName::from_static("x-api-key")));
+        assert!(SensitiveHeaders::parse("bad header").is_err());
+    }
+}
@@ -0,0 +1,403 @@
+use anyhow::{bail, Context};
+use log::info;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::redirect;
+use std::env;
+use std::fmt;
+use std::fs;
+
+/// Header carrying `RPC_CLIENT_ID` on every upstream request.
+pub const CLIENT_ID_HEADER: &str = "x-client-id";
+
+/// Redirects followed per upstream request unless `RPC_MAX_REDIRECTS` says otherwise.
+pub const DEFAULT_MAX_REDIRECTS: usize = 3;
+
+/// Settings of the reqwest client used to reach the upstream node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpClientConfig {
+    pub tls: Option<ClientTlsConfig>,
+    /// Identifier sent as `X-Client-Id`, for providers grouping usage by application.
+    pub client_id: Option<String>,
+    /// Extra headers from `RPC_HEADERS`, such as `Authorization`.
+    pub headers: RpcHeaders,
+    /// Redirects followed per request, from `RPC_MAX_REDIRECTS`; `0` follows none.
+    pub max_redirects: usize,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        HttpClientConfig {
+            tls: None,
+            client_id: None,
+            headers: RpcHeaders::default(),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+        }
+    }
+}
+
+/// Custom headers sent to the upstream node; values are redacted from `Debug` output.
+#[derive(Clone, Default, PartialEq)]
+pub struct RpcHeaders(pub Vec<(String, String)>);
+
+impl RpcHeaders {
+    /// Header names, safe to log.
+    pub fn names(&self) -> Vec<&str> {
+        self.0.iter().map(|(name, _)| name.as_str()).collect()
+    }
+}
+
+impl fmt::Debug for RpcHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(name, _)| (name, "<redacted>")))
+            .finish()
+    }
+}
+
+/// Parses `RPC_HEADERS`, either a JSON object of strings or `name:value;name:value`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_parse_rpc_headers() {
+/// let headers = parse_rpc_headers("Authorization: Bearer abc").unwrap();
+/// assert_eq!(headers.names(), vec!["Authorization"]);
+/// # }
+/// ```
+pub fn parse_rpc_headers(value: &str) -> anyhow::Result<RpcHeaders> {
+    let value = value.trim();
+    if value.starts_with('{') {
+        let map: std::collections::BTreeMap<String, String> =
+            serde_json::from_str(value).context("RPC_HEADERS is not a JSON object of strings")?;
+        return Ok(RpcHeaders(map.into_iter().collect()));
+    }
+
+    let mut headers = Vec::new();
+    for entry in value
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let Some((name, header_value)) = entry.split_once(':') else {
+            bail!(
+                "RPC_HEADERS entry {:?} is not of the form name:value",
+                name_only(entry)
+            );
+        };
+        headers.push((name.trim().to_string(), header_value.trim().to_string()));
+    }
+    Ok(RpcHeaders(headers))
+}
+
+/// The part of a malformed entry that is safe to show in an error.
+fn name_only(entry: &str) -> &str {
+    entry.split_whitespace().next().unwrap_or("")
+}
+
+/// Reads the HTTP client settings from the environment.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_http_client_config() {
+/// let config = get_http_client_config().unwrap();
+/// assert!(config.client_id.is_none());
+/// # }
+/// ```
+pub fn get_http_client_config() -> anyhow::Result<HttpClientConfig> {
+    Ok(HttpClientConfig {
+        tls: get_client_tls_config()?,
+        client_id: env::var("RPC_CLIENT_ID").ok(),
+        headers: match env::var("RPC_HEADERS") {
+            Ok(value) => parse_rpc_headers(&value)?,
+            Err(_) => RpcHeaders::default(),
+        },
+        max_redirects: match env::var("RPC_MAX_REDIRECTS") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .context("RPC_MAX_REDIRECTS must be a number of redirects")?,
+            Err(_) => DEFAULT_MAX_REDIRECTS,
+        },
+    })
+}
+
+/// PEM files holding the client certificate and private key used for mutual TLS.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientTlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+/// Reads the mutual TLS settings from `RPC_CLIENT_CERT_PATH` and `RPC_CLIENT_KEY_PATH`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_client_tls_config() {
+/// let tls = get_client_tls_config().unwrap();
+/// assert!(tls.is_none());
+/// # }
+/// ```
+pub fn get_client_tls_config() -> anyhow::Result<Option<ClientTlsConfig>> {
+    client_tls_config(
+        env::var("RPC_CLIENT_CERT_PATH").ok(),
+        env::var("RPC_CLIENT_KEY_PATH").ok(),
+    )
+}
+
+/// Combines the certificate and key paths, requiring both or neither.
+fn client_tls_config(
+    cert_path: Option<String>,
+    key_path: Option<String>,
+) -> anyhow::Result<Option<ClientTlsConfig>> {
+    match (cert_path, key_path) {
+        (Some(cert_path), Some(key_path)) => Ok(Some(ClientTlsConfig {
+            cert_path,
+            key_path,
+        })),
+        (None, None) => Ok(None),
+        (Some(_), None) => bail!("RPC_CLIENT_CERT_PATH is set but RPC_CLIENT_KEY_PATH is not"),
+        (None, Some(_)) => bail!("RPC_CLIENT_KEY_PATH is set but RPC_CLIENT_CERT_PATH is not"),
+    }
+}
+
+/// Headers sent on every upstream request.
+fn default_headers(config: &HttpClientConfig) -> anyhow::Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    if let Some(client_id) = &config.client_id {
+        let value = HeaderValue::from_str(client_id).context("Invalid RPC_CLIENT_ID")?;
+        headers.insert(CLIENT_ID_HEADER, value);
+    }
+    for (name, value) in &config.headers.0 {
+        let header = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("Invalid RPC_HEADERS name {:?}", name))?;
+        let mut value = HeaderValue::from_str(value)
+            .with_context(|| format!("Invalid RPC_HEADERS value for {}", name))?;
+        value.set_sensitive(true);
+        headers.insert(header, value);
+    }
+    Ok(headers)
+}
+
+/// Redirect policy following at most `max_redirects` redirects, logging each one.
+///
+/// Only origins are logged, since RPC URLs often carry an API key in their path.
+fn redirect_policy(max_redirects: usize) -> redirect::Policy {
+    if max_redirects == 0 {
+        return redirect::Policy::none();
+    }
+    redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() > max_redirects {
+            return attempt.error(format!("more than {} redirects", max_redirects));
+        }
+        let from = attempt
+            .previous()
+            .last()
+            .map(|url| url.origin().ascii_serialization())
+            .unwrap_or_default();
+        info!(
+            "Following a {} redirect of the RPC request from {} to {}",
+            attempt.status(),
+            from,
+            attempt.url().origin().ascii_serialization()
+        );
+        attempt.follow()
+    })
+}
+
+/// Builds the reqwest client used by the HTTP transport of the upstream provider.
+///
+/// When a TLS configuration is given the client presents that identity to the server.
+/// Redirects are followed up to `max_redirects` times.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_build_http_client() {
+/// let client = build_http_client(&HttpClientConfig::default()).unwrap();
+/// # }
+/// ```
+pub fn build_http_client(config: &HttpClientConfig) -> anyhow::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .default_headers(default_headers(config)?)
+        .redirect(redirect_policy(config.max_redirects));
+
+    if let Some(tls) = &config.tls {
+        let cert = fs::read(&tls.cert_path)
+            .with_context(|| format!("Failed to read client certificate {}", tls.cert_path))?;
+        let key = fs::read(&tls.key_path)
+            .with_context(|| format!("Failed to read client key {}", tls.key_path))?;
+        let identity = reqwest::Identity::from_pkcs8_pem(&cert, &key)
+            .context("Failed to load the client certificate and key")?;
+        builder = builder.identity(identity);
+    }
+
+    builder.build().context("Failed to build the HTTP client")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/client.crt");
+    const KEY_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/client.key");
+
+    #[test]
+    fn test_client_tls_config_requires_both_paths() {
+        assert_eq!(client_tls_config(None, None).unwrap(), None);
+        assert!(client_tls_config(Some(CERT_PATH.into()), None).is_err());
+        assert!(client_tls_config(None, Some(KEY_PATH.into())).is_err());
+
+        let tls = client_tls_config(Some(CERT_PATH.into()), Some(KEY_PATH.into())).unwrap();
+        assert_eq!(tls.unwrap().cert_path, CERT_PATH);
+    }
+
+    #[test]
+    fn test_build_http_client_loads_identity() {
+        let tls = ClientTlsConfig {
+            cert_path: CERT_PATH.into(),
+            key_path: KEY_PATH.into(),
+        };
+        let config = HttpClientConfig {
+            tls: Some(tls),
+            ..Default::default()
+        };
+        assert!(build_http_client(&config).is_ok());
+    }
+
+    #[test]
+    fn test_build_http_client_rejects_bad_identity() {
+        let missing = ClientTlsConfig {
+            cert_path: "/does/not/exist.crt".into(),
+            key_path: KEY_PATH.into(),
+        };
+        let config = |tls| HttpClientConfig {
+            tls: Some(tls),
+            ..Default::default()
+        };
+        assert!(build_http_client(&config(missing)).is_err());
+
+        let swapped = ClientTlsConfig {
+            cert_path: KEY_PATH.into(),
+            key_path: CERT_PATH.into(),
+        };
+        assert!(build_http_client(&config(swapped)).is_err());
+    }
+
+    #[test]
+    fn test_default_headers_include_client_id() {
+        let config = HttpClientConfig {
+            client_id: Some("treasury-monitor".into()),
+            ..Default::default()
+        };
+        let headers = default_headers(&config).unwrap();
+        assert_eq!(headers[CLIENT_ID_HEADER], "treasury-monitor");
+
+        assert!(default_headers(&HttpClientConfig::default())
+            .unwrap()
+            .is_empty());
+        let invalid = HttpClientConfig {
+            client_id: Some("line\nbreak".into()),
+            ..Default::default()
+        };
+        assert!(default_headers(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_parse_rpc_headers() {
+        let pairs = parse_rpc_headers("Authorization: Bearer abc; X-Api-Key:k:1").unwrap();
+        assert_eq!(
+            pairs.0,
+            vec![
+                ("Authorization".to_string(), "Bearer abc".to_string()),
+                ("X-Api-Key".to_string(), "k:1".to_string()),
+            ]
+        );
+        let json = parse_rpc_headers(r#"{"Authorization": "Bearer abc"}"#).unwrap();
+        assert_eq!(json.names(), vec!["Authorization"]);
+
+        assert!(parse_rpc_headers("Bearer").is_err());
+        assert!(parse_rpc_headers(r#"{"Authorization": 1}"#).is_err());
+    }
+
+    #[test]
+    fn test_rpc_headers_are_redacted() {
+        let headers = parse_rpc_headers("Authorization: Bearer secret").unwrap();
+        let debug = format!("{:?}", headers);
+        assert!(debug.contains("Authorization"));
+        assert!(!debug.contains("secret"));
+    }
+
+    #[tokio::test]
+    async fn test_rpc_headers_are_sent_upstream() {
+        use warp::Filter;
+
+        let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+        let mock = warp::header::headers_cloned().map(move |headers: warp::http::HeaderMap| {
+            sender.send(headers).unwrap();
+            "ok"
+        });
+        let (addr, server) = warp::serve(mock).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let config = HttpClientConfig {
+            client_id: Some("treasury-monitor".into()),
+            headers: parse_rpc_headers("Authorization: Bearer abc;X-Api-Key: key").unwrap(),
+            ..Default::default()
+        };
+        let client = build_http_client(&config).unwrap();
+        client
+            .post(format!("http://{}/", addr))
+            .send()
+            .await
+            .unwrap();
+
+        let headers = received.recv().await.unwrap();
+        assert_eq!(headers["authorization"], "Bearer abc");
+        assert_eq!(headers["x-api-key"], "key");
+        assert_eq!(headers[CLIENT_ID_HEADER], "treasury-monitor");
+    }
+
+    #[tokio::test]
+    async fn test_rpc_requests_follow_redirects() {
+        use alloy::providers::{Provider, ProviderBuilder};
+        use alloy::rpc::client::ClientBuilder;
+        use alloy::transports::http::Http;
+        use warp::Filter;
+
+        // The old path moves the node with a 307, so the POST body is kept.
+        let moved = warp::path("old").map(|| {
+            warp::reply::with_header(
+                warp::reply::with_status("", warp::http::StatusCode::TEMPORARY_REDIRECT),
+                "location",
+                "/rpc",
+            )
+        });
+        let node = warp::path("rpc")
+            .and(warp::body::json())
+            .map(|request: serde_json::Value| {
+                assert_eq!(request["method"], "eth_blockNumber");
+                warp::reply::json(&serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": "0x2a",
+                }))
+            });
+        let (addr, server) = warp::serve(moved.or(node)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let url: reqwest::Url = format!("http://{}/old", addr).parse().unwrap();
+
+        let provider = |max_redirects| {
+            let config = HttpClientConfig {
+                max_redirects,
+                ..Default::default()
+            };
+            let client = build_http_client(&config).unwrap();
+            let transport = Http::with_client(client, url.clone());
+            ProviderBuilder::new().on_client(ClientBuilder::default().transport(transport, true))
+        };
+        assert_eq!(provider(1).get_block_number().await.unwrap(), 42);
+        assert!(provider(0).get_block_number().await.is_err());
+    }
+}
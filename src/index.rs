@@ -0,0 +1,182 @@
+use crate::route_flags::RouteFlags;
+use crate::route_prefix::RoutePrefix;
+use serde::Serialize;
+
+/// Every endpoint served, by method and path, in the order of the README.
+const ENDPOINTS: &[(&str, &str)] = &[
+    ("GET", "/health"),
+    ("GET", "/ready"),
+    ("GET", "/balance/{address}"),
+    ("GET", "/balance-by-pubkey/{pubkey}"),
+    ("GET", "/nonce/{address}"),
+    ("GET", "/nonce/{address}/gap"),
+    ("GET", "/gas-price"),
+    ("GET", "/chain-id"),
+    ("GET", "/node-status"),
+    ("GET", "/fee-history"),
+    ("GET", "/balance/{address}/stream"),
+    ("GET", "/balance/{address}/tags"),
+    ("GET", "/balance/{address}/fiat"),
+    ("GET", "/balance/{address}/at"),
+    ("POST", "/balance/batch"),
+    ("GET", "/xpub/{xpub}/balances"),
+    ("GET", "/contract/{address}"),
+    ("POST", "/call-fn"),
+    ("GET", "/block/{block}"),
+    ("GET", "/tx/{hash}"),
+    ("GET", "/tx/{hash}/status"),
+    ("POST", "/tx/{hash}/decode"),
+    ("GET", "/receipt/{hash}"),
+    ("POST", "/receipts"),
+    ("GET", "/validate/{address}"),
+    ("GET", "/logs"),
+    ("POST", "/trace-filter"),
+    ("POST", "/send-raw"),
+    ("POST", "/sign-typed"),
+    ("GET", "/type/{address}"),
+    ("GET", "/proxy/{address}"),
+    ("GET", "/activity/{address}"),
+    ("GET", "/pending/poll"),
+    ("GET", "/chains"),
+    ("GET", "/capabilities"),
+    ("GET", "/caip/balance/{account}"),
+    ("GET", "/multichain/balance/{address}"),
+    ("GET", "/stats"),
+    ("GET", "/stats/top-addresses"),
+    ("GET", "/metrics"),
+    ("POST", "/admin/reload"),
+    ("POST", "/admin/maintenance"),
+    ("GET", "/ui"),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Endpoint {
+    pub method: &'static str,
+    /// Including the `ROUTE_PREFIX`, if any.
+    pub path: String,
+}
+
+/// Body of `/`: the service, its version, and the endpoints it serves.
+#[derive(Debug, Serialize)]
+pub struct Index {
+    pub service: &'static str,
+    pub version: &'static str,
+    pub endpoints: Vec<Endpoint>,
+}
+
+impl Index {
+    /// Lists the endpoints left enabled by `flags`, mounted under `prefix`,
+    /// built once when the routes are set up.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_index_new() {
+    /// let index = Index::new(&RouteFlags::default(), false, &RoutePrefix::default());
+    /// assert_eq!(index.endpoints[0].path, "/health");
+    /// # }
+    /// ```
+    pub fn new(flags: &RouteFlags, ui: bool, prefix: &RoutePrefix) -> Self {
+        let endpoints = ENDPOINTS
+            .iter()
+            .filter(|(_, path)| {
+                let route = path.trim_start_matches('/').split('/').next().unwrap_or("");
+                flags.is_enabled(route) && (ui || route != "ui")
+            })
+            .map(|&(method, path)| Endpoint {
+                method,
+                path: prefix.mounted(path),
+            })
+            .collect();
+        Index {
+            service: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            endpoints,
+        }
+    }
+
+    /// Methods of the endpoints whose path template matches `path`, where a
+    /// `{name}` segment matches any one segment.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_allowed_methods() {
+    /// let index = Index::new(&RouteFlags::default(), false, &RoutePrefix::default());
+    /// assert_eq!(index.allowed_methods("/nonce/0x00"), vec!["GET"]);
+    /// # }
+    /// ```
+    pub fn allowed_methods(&self, path: &str) -> Vec<&'static str> {
+        let segments: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+        let mut methods: Vec<&'static str> = self
+            .endpoints
+            .iter()
+            .filter(|endpoint| {
+                let template: Vec<&str> = endpoint.path.split('/').collect();
+                template.len() == segments.len()
+                    && template.iter().zip(&segments).all(|(template, segment)| {
+                        template == segment || (template.starts_with('{') && !segment.is_empty())
+                    })
+            })
+            .map(|endpoint| endpoint.method)
+            .collect();
+        methods.sort_unstable();
+        methods.dedup();
+        methods
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_follows_route_flags() {
+        let index = Index::new(
+            &RouteFlags::only(["balance"]).unwrap(),
+            false,
+            &RoutePrefix::default(),
+        );
+        let paths: Vec<&str> = index.endpoints.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/health",
+                "/balance/{address}",
+                "/balance/{address}/stream",
+                "/balance/{address}/tags",
+                "/balance/{address}/fiat",
+                "/balance/{address}/at",
+                "/balance/batch",
+            ]
+        );
+
+        let index = Index::new(&RouteFlags::default(), true, &RoutePrefix::default());
+        assert_eq!(index.endpoints.len(), ENDPOINTS.len());
+    }
+
+    #[test]
+    fn test_allowed_methods() {
+        let index = Index::new(&RouteFlags::default(), false, &RoutePrefix::default());
+        assert_eq!(index.allowed_methods("/balance/0x00"), vec!["GET"]);
+        assert_eq!(index.allowed_methods("/balance/0x00/"), vec!["GET"]);
+        assert_eq!(index.allowed_methods("/call-fn"), vec!["POST"]);
+        // `batch` is also a valid `{address}` segment as far as the paths go.
+        assert_eq!(index.allowed_methods("/balance/batch"), vec!["GET", "POST"]);
+        assert!(index.allowed_methods("/balance").is_empty());
+        assert!(index.allowed_methods("/unknown").is_empty());
+
+        let prefix = RoutePrefix::new("api", true).unwrap();
+        let index = Index::new(&RouteFlags::default(), false, &prefix);
+        assert_eq!(index.allowed_methods("/api/send-raw"), vec!["POST"]);
+        assert!(index.allowed_methods("/send-raw").is_empty());
+    }
+
+    #[test]
+    fn test_index_lists_prefixed_paths() {
+        let prefix = RoutePrefix::new("api/v1", false).unwrap();
+        let index = Index::new(&RouteFlags::default(), false, &prefix);
+        assert_eq!(index.endpoints[0].path, "/health");
+        assert_eq!(index.endpoints[1].path, "/api/v1/ready");
+    }
+}
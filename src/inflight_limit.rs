@@ -0,0 +1,78 @@
+use crate::config;
+use crate::error::AppError;
+use log::info;
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Cap on the requests the whole server handles at once, from `MAX_INFLIGHT_REQUESTS`.
+///
+/// Unlike the per-provider limit on node requests, requests over the cap are
+/// not queued: they are shed at once with `503`, so an overloaded server
+/// answers quickly instead of piling up work.
+pub struct InflightLimit {
+    permits: Arc<Semaphore>,
+}
+
+impl InflightLimit {
+    pub fn new(max: usize) -> Self {
+        InflightLimit {
+            permits: Arc::new(Semaphore::new(max)),
+        }
+    }
+
+    /// Admits a request until the returned permit is dropped, or rejects it
+    /// with [`AppError::Overloaded`] when `max` requests are already in flight.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_try_acquire() {
+    /// let limit = InflightLimit::new(1);
+    /// let permit = limit.try_acquire().unwrap();
+    /// assert!(matches!(limit.try_acquire(), Err(AppError::Overloaded)));
+    /// drop(permit);
+    /// assert!(limit.try_acquire().is_ok());
+    /// # }
+    /// ```
+    pub fn try_acquire(&self) -> Result<OwnedSemaphorePermit, AppError> {
+        self.permits
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| AppError::Overloaded)
+    }
+}
+
+/// Reads `MAX_INFLIGHT_REQUESTS`; `None`, for no cap, when unset or zero.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_inflight_limit() {
+/// let limit = get_inflight_limit();
+/// assert!(limit.is_none());
+/// # }
+/// ```
+pub fn get_inflight_limit() -> Option<InflightLimit> {
+    let max: usize = config::env_or("MAX_INFLIGHT_REQUESTS", 0);
+    if max == 0 {
+        return None;
+    }
+    info!("Shedding requests beyond {} in flight", max);
+    Some(InflightLimit::new(max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permits_are_returned_on_drop() {
+        let limit = InflightLimit::new(2);
+        let first = limit.try_acquire().unwrap();
+        let _second = limit.try_acquire().unwrap();
+        assert!(matches!(limit.try_acquire(), Err(AppError::Overloaded)));
+
+        drop(first);
+        assert!(limit.try_acquire().is_ok());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::config;
+use anyhow::bail;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use warp::http::StatusCode;
+
+/// Decides which requests are logged: every failed one, and a random
+/// fraction of the successful ones.
+///
+/// Draws come from a xorshift generator shared by all requests; concurrent
+/// draws may repeat a number, which is harmless for sampling.
+pub struct LogSampler {
+    rate: f64,
+    state: AtomicU64,
+}
+
+impl LogSampler {
+    /// Logs `rate` (0.0 to 1.0) of the successful requests.
+    pub fn new(rate: f64) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        LogSampler {
+            rate,
+            // Xorshift never leaves zero.
+            state: AtomicU64::new(seed | 1),
+        }
+    }
+
+    /// Whether a request answered with `status` should be logged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_should_log() {
+    /// let sampler = LogSampler::new(0.0);
+    /// assert!(!sampler.should_log(StatusCode::OK));
+    /// assert!(sampler.should_log(StatusCode::BAD_GATEWAY));
+    /// # }
+    /// ```
+    pub fn should_log(&self, status: StatusCode) -> bool {
+        if !status.is_success() || self.rate >= 1.0 {
+            return true;
+        }
+        if self.rate <= 0.0 {
+            return false;
+        }
+        // The top 53 bits make a uniform float in [0, 1).
+        let draw = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        draw < self.rate
+    }
+
+    fn next(&self) -> u64 {
+        let mut x = self.state.load(Ordering::Relaxed);
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state.store(x, Ordering::Relaxed);
+        x
+    }
+}
+
+impl Default for LogSampler {
+    fn default() -> Self {
+        LogSampler::new(1.0)
+    }
+}
+
+/// Reads the fraction of successful requests to log from `LOG_SAMPLE_RATE` (default 1.0).
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_log_sampler() {
+/// let sampler = get_log_sampler().unwrap();
+/// # }
+/// ```
+pub fn get_log_sampler() -> anyhow::Result<LogSampler> {
+    let rate: f64 = config::env_or("LOG_SAMPLE_RATE", 1.0);
+    if !(0.0..=1.0).contains(&rate) {
+        bail!("LOG_SAMPLE_RATE must be between 0.0 and 1.0, got {}", rate);
+    }
+    Ok(LogSampler::new(rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_rate_logs_only_errors() {
+        let sampler = LogSampler::new(0.0);
+        for _ in 0..1000 {
+            assert!(!sampler.should_log(StatusCode::OK));
+        }
+        assert!(sampler.should_log(StatusCode::BAD_REQUEST));
+        assert!(sampler.should_log(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    #[test]
+    fn test_rate_logs_a_fraction_of_successes() {
+        assert!((0..1000).all(|_| LogSampler::new(1.0).should_log(StatusCode::OK)));
+
+        let sampler = LogSampler::new(0.25);
+        let logged = (0..10_000)
+            .filter(|_| sampler.should_log(StatusCode::OK))
+            .count();
+        assert!((2000..3000).contains(&logged), "{} logged", logged);
+    }
+}
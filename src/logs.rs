@@ -0,0 +1,338 @@
+use crate::config;
+use crate::error::AppError;
+use crate::events::{DecodedLog, EventRegistry};
+use alloy::providers::Provider;
+use alloy::rpc::types::{Filter, Log};
+use alloy_primitives::{Address, Bytes, B256};
+use futures::stream::{self, Stream, StreamExt};
+use log::debug;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Limits on the block ranges served by `/logs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogsConfig {
+    /// Widest range sent to the node in one `eth_getLogs` call.
+    pub max_range: u64,
+    /// Whether wider ranges are split into chunks instead of rejected.
+    pub auto_chunk: bool,
+    /// Blocks per chunk when auto-chunking, at most `max_range`.
+    pub chunk_size: u64,
+    /// Most chunks a single request may be split into.
+    pub max_chunks: u64,
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self {
+        LogsConfig {
+            max_range: 1000,
+            auto_chunk: false,
+            chunk_size: 1000,
+            max_chunks: 100,
+        }
+    }
+}
+
+/// Reads the `/logs` limits from `LOGS_MAX_BLOCK_RANGE`, `LOGS_AUTO_CHUNK`,
+/// `LOGS_CHUNK_SIZE` and `LOGS_MAX_CHUNKS`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_logs_config() {
+/// let config = get_logs_config();
+/// assert!(!config.auto_chunk);
+/// # }
+/// ```
+pub fn get_logs_config() -> LogsConfig {
+    let defaults = LogsConfig::default();
+    let max_range = config::env_or("LOGS_MAX_BLOCK_RANGE", defaults.max_range).max(1);
+    LogsConfig {
+        max_range,
+        auto_chunk: config::env_flag("LOGS_AUTO_CHUNK"),
+        chunk_size: config::env_or("LOGS_CHUNK_SIZE", max_range).clamp(1, max_range),
+        max_chunks: config::env_or("LOGS_MAX_CHUNKS", defaults.max_chunks),
+    }
+}
+
+/// A log as returned by `/logs`, decoded when it matches a known event.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LogEntry {
+    pub address: Address,
+    pub topics: Vec<B256>,
+    pub data: Bytes,
+    pub block_number: Option<u64>,
+    pub transaction_hash: Option<B256>,
+    pub log_index: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<DecodedLog>,
+}
+
+impl LogEntry {
+    pub fn new(log: &Log, events: &EventRegistry) -> Self {
+        LogEntry {
+            address: log.address(),
+            topics: log.topics().to_vec(),
+            data: log.data().data.clone(),
+            block_number: log.block_number,
+            transaction_hash: log.transaction_hash,
+            log_index: log.log_index,
+            decoded: events.decode(log.data()),
+        }
+    }
+}
+
+/// Splits `from..=to` into consecutive ranges of at most `size` blocks.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_chunk_ranges() {
+/// assert_eq!(chunk_ranges(0, 4, 2), vec![(0, 1), (2, 3), (4, 4)]);
+/// # }
+/// ```
+pub fn chunk_ranges(from: u64, to: u64, size: u64) -> Vec<(u64, u64)> {
+    let size = size.max(1);
+    let mut ranges = Vec::new();
+    let mut start = from;
+    while start <= to {
+        let end = start.saturating_add(size - 1).min(to);
+        ranges.push((start, end));
+        if end == u64::MAX {
+            break;
+        }
+        start = end + 1;
+    }
+    ranges
+}
+
+/// The block ranges to query for the logs between `from` and `to`, inclusive.
+///
+/// Ranges wider than `max_range` are rejected, or with `auto_chunk` split
+/// into chunks of `chunk_size` blocks, oldest first.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_plan_ranges() {
+/// assert_eq!(plan_ranges(0, 5, &LogsConfig::default()).unwrap(), vec![(0, 5)]);
+/// # }
+/// ```
+pub fn plan_ranges(from: u64, to: u64, config: &LogsConfig) -> Result<Vec<(u64, u64)>, AppError> {
+    if from > to {
+        return Err(AppError::InvalidRequest(format!(
+            "from_block {} is after to_block {}",
+            from, to
+        )));
+    }
+    // `0..=u64::MAX` has one block more than a `u64` can count.
+    let span = (to - from).saturating_add(1);
+    if span <= config.max_range {
+        return Ok(vec![(from, to)]);
+    }
+    if !config.auto_chunk {
+        return Err(AppError::InvalidRequest(format!(
+            "block range of {} blocks exceeds the maximum of {}",
+            span, config.max_range
+        )));
+    }
+    // Checked before chunking, so a huge range is never materialized.
+    let chunk_size = config.chunk_size.min(config.max_range).max(1);
+    if span.div_ceil(chunk_size) > config.max_chunks {
+        return Err(AppError::InvalidRequest(format!(
+            "block range of {} blocks exceeds the maximum of {}",
+            span,
+            config.chunk_size.saturating_mul(config.max_chunks)
+        )));
+    }
+    Ok(chunk_ranges(from, to, chunk_size))
+}
+
+/// Fetches the logs matching `filter` between `from` and `to`, inclusive,
+/// in block and log index order.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_fetch_logs() {
+/// let logs = fetch_logs(provider.as_ref(), Filter::new(), 0, 5000, &get_logs_config()).await.unwrap();
+/// # }
+/// ```
+pub async fn fetch_logs(
+    provider: &dyn Provider,
+    filter: Filter,
+    from: u64,
+    to: u64,
+    config: &LogsConfig,
+) -> Result<Vec<Log>, AppError> {
+    let ranges = plan_ranges(from, to, config)?;
+    let mut logs = Vec::new();
+    for (start, end) in ranges {
+        debug!("Querying logs from block {} to {}", start, end);
+        let chunk = filter.clone().from_block(start).to_block(end);
+        logs.extend(provider.get_logs(&chunk).await.map_err(AppError::from)?);
+    }
+    // Sequential chunks are already ordered; sorting also covers nodes that aren't.
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+    Ok(logs)
+}
+
+/// Streams the logs matching `filter` over `ranges` from [`plan_ranges`], one
+/// chunk at a time, so only one chunk is held in memory.
+///
+/// Each chunk is sorted by block and log index; the stream ends after the
+/// first chunk that fails.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_stream_logs() {
+/// let ranges = plan_ranges(0, 5000, &get_logs_config()).unwrap();
+/// let mut chunks = stream_logs(provider, Filter::new(), ranges);
+/// # }
+/// ```
+pub fn stream_logs(
+    provider: Arc<dyn Provider>,
+    filter: Filter,
+    ranges: Vec<(u64, u64)>,
+) -> impl Stream<Item = Result<Vec<Log>, AppError>> + Send {
+    stream::iter(ranges)
+        .then(move |(start, end)| {
+            let (provider, chunk) = (
+                provider.clone(),
+                filter.clone().from_block(start).to_block(end),
+            );
+            async move {
+                debug!("Streaming logs from block {} to {}", start, end);
+                let mut logs = provider.get_logs(&chunk).await.map_err(AppError::from)?;
+                logs.sort_by_key(|log| (log.block_number, log.log_index));
+                Ok(logs)
+            }
+        })
+        .scan(false, |failed, chunk: Result<Vec<Log>, AppError>| {
+            let item = (!*failed).then(|| {
+                *failed = chunk.is_err();
+                chunk
+            });
+            futures::future::ready(item)
+        })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloy::providers::RootProvider;
+    use alloy::transports::TransportResult;
+    use std::sync::Mutex;
+
+    /// Provider with one log per block, recording the ranges it was asked for.
+    #[derive(Default)]
+    pub(crate) struct BlockLogsProvider {
+        pub(crate) ranges: Mutex<Vec<(u64, u64)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for BlockLogsProvider {
+        async fn get_logs(&self, filter: &Filter) -> TransportResult<Vec<Log>> {
+            let from = filter.get_from_block().unwrap();
+            let to = filter.get_to_block().unwrap();
+            self.ranges.lock().unwrap().push((from, to));
+            // Newest first, to check the merged output is reordered.
+            Ok((from..=to)
+                .rev()
+                .map(|block_number| Log {
+                    block_number: Some(block_number),
+                    log_index: Some(0),
+                    ..Default::default()
+                })
+                .collect())
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("BlockLogsProvider does not support `root`")
+        }
+    }
+
+    fn config(auto_chunk: bool) -> LogsConfig {
+        LogsConfig {
+            max_range: 4,
+            auto_chunk,
+            chunk_size: 3,
+            max_chunks: 5,
+        }
+    }
+
+    #[test]
+    fn test_chunk_ranges() {
+        assert_eq!(chunk_ranges(0, 9, 3), vec![(0, 2), (3, 5), (6, 8), (9, 9)]);
+        assert_eq!(chunk_ranges(5, 5, 3), vec![(5, 5)]);
+        assert_eq!(
+            chunk_ranges(u64::MAX, u64::MAX, 3),
+            vec![(u64::MAX, u64::MAX)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auto_chunked_logs_are_merged_in_order() {
+        let provider = BlockLogsProvider::default();
+        let logs = fetch_logs(&provider, Filter::new(), 0, 9, &config(true))
+            .await
+            .unwrap();
+
+        let blocks: Vec<u64> = logs.iter().map(|log| log.block_number.unwrap()).collect();
+        assert_eq!(blocks, (0..=9).collect::<Vec<_>>());
+        assert_eq!(
+            *provider.ranges.lock().unwrap(),
+            vec![(0, 2), (3, 5), (6, 8), (9, 9)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streamed_logs_arrive_one_chunk_at_a_time() {
+        let provider = Arc::new(BlockLogsProvider::default());
+        let ranges = plan_ranges(0, 9, &config(true)).unwrap();
+        let mut chunks = Box::pin(stream_logs(provider.clone(), Filter::new(), ranges));
+
+        let first = chunks.next().await.unwrap().unwrap();
+        let blocks: Vec<u64> = first.iter().map(|log| log.block_number.unwrap()).collect();
+        assert_eq!(blocks, vec![0, 1, 2]);
+        // Later chunks are only queried once asked for.
+        assert_eq!(*provider.ranges.lock().unwrap(), vec![(0, 2)]);
+
+        let rest: Vec<_> = chunks.collect().await;
+        assert_eq!(rest.len(), 3);
+        assert_eq!(provider.ranges.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_wide_ranges_are_rejected_without_auto_chunk() {
+        let provider = BlockLogsProvider::default();
+        let error = fetch_logs(&provider, Filter::new(), 0, 9, &config(false))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::InvalidRequest(_)));
+        assert!(provider.ranges.lock().unwrap().is_empty());
+
+        // Even chunked, a range is capped at `max_chunks` chunks.
+        let error = fetch_logs(&provider, Filter::new(), 0, 15, &config(true))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("maximum of 15"));
+    }
+
+    #[test]
+    fn test_huge_ranges_are_rejected_without_chunking() {
+        let error = plan_ranges(0, u64::MAX, &config(false)).unwrap_err();
+        assert!(error.to_string().contains("maximum of 4"));
+        let error = plan_ranges(0, u64::MAX, &config(true)).unwrap_err();
+        assert!(error.to_string().contains("maximum of 15"));
+
+        // Would be about 3 * 10^14 chunks if split before checking.
+        let error = plan_ranges(0, 1_000_000_000_000_000, &config(true)).unwrap_err();
+        assert!(matches!(error, AppError::InvalidRequest(_)));
+        assert_eq!(
+            plan_ranges(u64::MAX - 1, u64::MAX, &config(true)).unwrap(),
+            vec![(u64::MAX - 1, u64::MAX)]
+        );
+    }
+}
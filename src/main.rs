@@ -1,20 +1,677 @@
-use log::{error, info};
-use opentelemetry::global;
+mod abi_call;
+mod activity;
+mod address_type;
+mod archive;
+mod block;
+mod block_id;
+mod block_time;
+mod body_validation;
+mod cache;
+mod caip;
+mod capabilities;
+mod chains;
+mod circuit_breaker;
+mod concurrency;
+mod config;
+mod deadline;
+mod debug_info;
+mod encoding;
+mod envelope;
+mod error;
+mod error_report;
+mod events;
+mod fee_history;
+mod format;
+mod header_scrub;
+mod http_client;
+mod index;
+mod inflight_limit;
+mod log_sampling;
+mod logs;
+mod maintenance;
+mod metrics;
+mod node_cache;
+mod node_status;
+mod number_format;
+mod pending;
+mod price_feed;
+mod provider_cache;
+mod query_limits;
+mod quota;
+mod rate_limit;
+#[cfg(feature = "redis")]
+mod redis_cache;
+mod reload;
+mod request_body;
+mod retry;
+mod route_flags;
+mod route_prefix;
+mod runtime;
+mod server;
+mod server_timing;
+mod signing;
+mod startup;
+mod stream;
+mod supervisor;
+mod telemetry;
+#[cfg(any(test, feature = "testing"))]
+mod testing;
+mod token;
+mod trace;
+mod transaction;
+mod ui;
+mod upstream;
+mod validate;
+mod xpub;
+
+use futures::{StreamExt, TryStreamExt};
+use log::{debug, error, info, warn};
+use opentelemetry::global::{self, BoxedTracer};
 use opentelemetry::trace::{Span, Tracer};
 use opentelemetry::KeyValue;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::Infallible;
 use std::env;
+use std::future::IntoFuture;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OwnedSemaphorePermit;
+use warp::http::StatusCode;
 use warp::{Filter, Rejection, Reply};
 
+use abi_call::CallOutput;
+use address_type::{
+    classify_address, eip1167_implementation, proxy_slots, EIP1967_ADMIN_SLOT,
+    EIP1967_IMPLEMENTATION_SLOT,
+};
+use alloy::consensus::Transaction as _;
+use alloy::dyn_abi::TypedData;
+use alloy::eips::BlockId;
+use alloy::json_abi::JsonAbi;
 use alloy::providers::Provider;
-use alloy::providers::ProviderBuilder;
-use alloy_primitives::Address;
+use alloy::providers::{ProviderBuilder, WsConnect};
+use alloy::rpc::client::ClientBuilder;
+use alloy::rpc::types::{BlockTransactionsKind, TransactionRequest};
+use alloy::signers::local::PrivateKeySigner;
+use alloy::transports::http::Http;
+use alloy::transports::TransportError;
+use alloy_primitives::utils::format_units;
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
+use archive::{ArchiveRouter, SERVED_BY_HEADER};
+use block::{encode_block, BlockResponse};
+use block_id::{block_id_query, parse_block_id, InvalidBlock};
+use block_time::BlockTimestamps;
+use body_validation::{validated_json, FieldError, Problems, Validate};
+use cache::{BalanceCache, IdempotencyCache, NotFoundCache, TtlCache, CACHE_HEADER};
+use caip::AccountId;
+use capabilities::{Capabilities, Features};
+use chains::{Chain, ChainRegistry, ReadyPolicy, DEFAULT_CHAIN};
+use circuit_breaker::{CircuitBreaker, CircuitBreakerLayer};
+use concurrency::ConcurrencyLayer;
+use encoding::{Encoding, EncodingQuery, RlpResponse};
+use envelope::{wrap_error, wrap_response, Envelope, ErrorCode};
+use error::{AppError, ErrorType};
+use error_report::{ErrorEvent, ErrorMessage, ErrorReporter, REQUEST_ID_HEADER};
+use events::EventRegistry;
+use fee_history::{FeeHistoryQuery, FeeHistoryResponse};
+use format::{negotiate_and_serialize, with_format, Format};
+use header_scrub::SensitiveHeaders;
+use index::Index;
+use inflight_limit::InflightLimit;
+use log_sampling::LogSampler;
+use logs::{LogEntry, LogsConfig};
+use maintenance::Maintenance;
+use metrics::{InflightGuard, Metrics, RouteGuard};
+use node_cache::NodeCache;
+use number_format::{AmountQuery, NumberFormat, Unit};
+use pending::{PendingBuffer, PendingPoller};
+use price_feed::PriceFeed;
+use provider_cache::{ProviderCache, ProviderCacheLayer};
+use query_limits::QueryLimits;
+use quota::DailyQuotas;
+use rate_limit::RateLimiter;
+use reload::Reloader;
+use retry::RetryLayer;
+use route_flags::RouteFlags;
+use route_prefix::RoutePrefix;
+use server_timing::{RequestTimer, RpcTimings, ServerTimingLayer, SERVER_TIMING_HEADER};
+use signing::BalanceSignature;
+use startup::Readiness;
+use stream::{BalanceStreams, HeadTracker};
+use subtle::ConstantTimeEq;
+use supervisor::Supervisor;
+use telemetry::{RequestMetrics, RequestSpan};
+use trace::{TraceConfig, TraceFilterRequest};
+use transaction::{encode_transaction, ReceiptResponse, TransactionResponse, TxStatusResponse};
+use upstream::UpstreamAllowlist;
 use url::Url;
 
 #[derive(Serialize)]
 struct BalanceResponse {
     balance: String,
+    unit: Unit,
+    /// Present with `?sign=true`.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    signature: Option<BalanceSignature>,
+}
+
+#[derive(Serialize)]
+struct CaipBalanceResponse {
+    account: String,
+    chain: String,
+    balance: String,
+}
+
+/// Balance on one chain of `/multichain/balance/{address}`, or why that chain failed.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ChainBalance {
+    Balance(String),
+    Error { error: String },
+}
+
+/// Query parameters accepted by the routes returning a quantity at a block.
+#[derive(Debug, Deserialize)]
+struct QuantityQuery {
+    #[serde(default)]
+    format: NumberFormat,
+}
+
+/// Query parameters accepted by the routes returning a balance at a block.
+#[derive(Debug, Deserialize)]
+struct BalanceQuery {
+    #[serde(flatten)]
+    amount: AmountQuery,
+    /// Sign the balance with the configured signer key.
+    #[serde(default)]
+    sign: bool,
+}
+
+/// Balance sent by `/balance/{address}/stream` for each new block.
+#[derive(Serialize)]
+struct BalanceEvent {
+    block_number: u64,
+    balance: String,
+}
+
+/// Transaction count of an address.
+#[derive(Serialize)]
+struct NonceResponse {
+    nonce: String,
+}
+
+/// Transaction counts of an address at `latest` and `pending`.
+#[derive(Serialize)]
+struct NonceGapResponse {
+    latest: u64,
+    pending: u64,
+    /// `pending - latest`: transactions the node holds but has not mined yet.
+    gap: i64,
+}
+
+/// Current gas price of the node.
+#[derive(Serialize)]
+struct GasPriceResponse {
+    gas_price: String,
+    unit: Unit,
+}
+
+/// Chain id of the node.
+#[derive(Serialize)]
+struct ChainIdResponse {
+    chain_id: u64,
+}
+
+/// Query parameters accepted by `/contract/{address}`.
+#[derive(Debug, Deserialize)]
+struct ContractQuery {
+    /// Comma-separated ERC-20 token addresses.
+    tokens: Option<String>,
+}
+
+/// ERC-20 balance of one token, in `?tokens=` order.
+#[derive(Serialize)]
+struct TokenBalance {
+    token: Address,
+    /// Raw integer amount.
+    balance: String,
+    decimals: u8,
+    /// `balance` scaled by `decimals`, e.g. `"1.500000"` for 1500000 of a 6-decimal token.
+    formatted: String,
+    /// Set when the token has no `decimals()` and 18 were assumed.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    decimals_defaulted: bool,
+}
+
+/// Native balance, code and token holdings of an address.
+#[derive(Serialize)]
+struct ContractResponse {
+    is_contract: bool,
+    code_size: usize,
+    balance: String,
+    tokens: Vec<TokenBalance>,
+}
+
+/// Longest `?wait=` accepted by `/pending/poll`, in seconds.
+const MAX_PENDING_WAIT_SECS: u64 = 30;
+
+/// Query parameters accepted by `/pending/poll`.
+#[derive(Debug, Deserialize)]
+struct PendingQuery {
+    cursor: Option<u64>,
+    /// Seconds to wait for new hashes when there are none yet.
+    wait: Option<u64>,
+}
+
+/// Maximum number of addresses accepted by `/balance/batch`.
+const MAX_BATCH_ADDRESSES: usize = 100;
+
+/// Largest request body accepted by the JSON endpoints.
+const MAX_BODY_BYTES: u64 = 16 * 1024;
+
+/// Body of `/balance/batch`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchBalanceRequest {
+    addresses: Vec<String>,
+}
+
+impl Validate for BatchBalanceRequest {
+    const FIELDS: &'static [&'static str] = &["addresses"];
+
+    fn validate(body: &serde_json::Map<String, serde_json::Value>, problems: &mut Problems) {
+        let Some(addresses) = problems.require(body, "addresses") else {
+            return;
+        };
+        let Some(addresses) = addresses.as_array() else {
+            problems.push("addresses", "expected an array of addresses");
+            return;
+        };
+        if addresses.len() > MAX_BATCH_ADDRESSES {
+            problems.push(
+                "addresses",
+                format!("at most {} addresses per batch", MAX_BATCH_ADDRESSES),
+            );
+        }
+        for (index, address) in addresses.iter().enumerate() {
+            check_address(address, format!("addresses[{}]", index), problems);
+        }
+    }
+}
+
+/// Maximum number of hashes accepted by `/receipts`.
+const MAX_BATCH_RECEIPTS: usize = 100;
+
+/// Receipts of a `/receipts` batch fetched from the node at the same time.
+const RECEIPT_CONCURRENCY: usize = 10;
+
+/// Body of `/receipts`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchReceiptsRequest {
+    hashes: Vec<String>,
+}
+
+impl Validate for BatchReceiptsRequest {
+    const FIELDS: &'static [&'static str] = &["hashes"];
+
+    fn validate(body: &serde_json::Map<String, serde_json::Value>, problems: &mut Problems) {
+        let Some(hashes) = problems.require(body, "hashes") else {
+            return;
+        };
+        let Some(hashes) = hashes.as_array() else {
+            problems.push("hashes", "expected an array of transaction hashes");
+            return;
+        };
+        if hashes.len() > MAX_BATCH_RECEIPTS {
+            problems.push(
+                "hashes",
+                format!("at most {} hashes per batch", MAX_BATCH_RECEIPTS),
+            );
+        }
+        for (index, hash) in hashes.iter().enumerate() {
+            if hash
+                .as_str()
+                .is_none_or(|hash| hash.parse::<B256>().is_err())
+            {
+                problems.push(
+                    format!("hashes[{}]", index),
+                    format!("invalid transaction hash: {}", hash),
+                );
+            }
+        }
+    }
+}
+
+/// Largest body accepted by `/call-fn`, leaving room for whole contract ABIs.
+const MAX_CALL_FN_BODY_BYTES: u64 = 256 * 1024;
+
+/// Body of `/call-fn`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CallFnRequest {
+    to: String,
+    abi: JsonAbi,
+    function: String,
+    #[serde(default)]
+    args: Vec<serde_json::Value>,
+}
+
+impl Validate for CallFnRequest {
+    const FIELDS: &'static [&'static str] = &["to", "abi", "function", "args"];
+
+    fn validate(body: &serde_json::Map<String, serde_json::Value>, problems: &mut Problems) {
+        if let Some(to) = problems.require(body, "to") {
+            check_address(to, "to".to_string(), problems);
+        }
+        if let Some(abi) = problems.require(body, "abi") {
+            if let Err(error) = JsonAbi::deserialize(abi) {
+                problems.push("abi", format!("invalid ABI: {}", error));
+            }
+        }
+        if let Some(function) = problems.require(body, "function") {
+            if !function.is_string() {
+                problems.push("function", "expected a function name or signature");
+            }
+        }
+        if body.get("args").is_some_and(|args| !args.is_array()) {
+            problems.push("args", "expected an array of arguments");
+        }
+    }
+}
+
+/// Records a problem at `field` unless `value` is a string holding an address.
+fn check_address(value: &serde_json::Value, field: String, problems: &mut Problems) {
+    if value
+        .as_str()
+        .is_none_or(|address| address.parse::<Address>().is_err())
+    {
+        problems.push(field, format!("invalid address: {}", value));
+    }
+}
+
+/// Body of `/tx/{hash}/decode`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DecodeInputRequest {
+    abi: JsonAbi,
+}
+
+impl Validate for DecodeInputRequest {
+    const FIELDS: &'static [&'static str] = &["abi"];
+
+    fn validate(body: &serde_json::Map<String, serde_json::Value>, problems: &mut Problems) {
+        if let Some(abi) = problems.require(body, "abi") {
+            if let Err(error) = JsonAbi::deserialize(abi) {
+                problems.push("abi", format!("invalid ABI: {}", error));
+            }
+        }
+    }
+}
+
+/// Input of a transaction decoded by `/tx/{hash}/decode`.
+#[derive(Serialize)]
+struct DecodedInputResponse {
+    hash: B256,
+    /// Name of the called function; `null` when the ABI has no function with its selector.
+    function: Option<String>,
+    /// Signature of the called function, e.g. `transfer(address,uint256)`.
+    signature: Option<String>,
+    /// Arguments decoded by the ABI, `null` along with `function`.
+    args: Option<Vec<CallOutput>>,
+    /// Calldata as sent.
+    input: Bytes,
+}
+
+/// Decoded outputs of a `/call-fn` call.
+#[derive(Serialize)]
+struct CallFnResponse {
+    /// Signature of the called function, e.g. `balanceOf(address)`.
+    function: String,
+    outputs: Vec<CallOutput>,
+}
+
+/// Largest body accepted by `/send-raw`, leaving room for blob transactions.
+const MAX_RAW_TX_BODY_BYTES: u64 = 1024 * 1024;
+
+/// Longest accepted `Idempotency-Key` header.
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Body of `/send-raw`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SendRawRequest {
+    /// Signed transaction, EIP-2718 encoded.
+    tx: Bytes,
+}
+
+/// Hash of a broadcast transaction.
+#[derive(Serialize)]
+struct SendRawResponse {
+    tx_hash: B256,
+}
+
+/// Balance of one address of a batch, in request order.
+#[derive(Serialize)]
+struct BatchBalanceEntry {
+    address: Address,
+    balance: String,
+}
+
+/// Balance of the address derived from a public key.
+#[derive(Serialize)]
+struct PubkeyBalanceResponse {
+    address: Address,
+    balance: String,
+    unit: Unit,
+}
+
+/// Balances of an address at the `latest`, `safe` and `finalized` tags.
+#[derive(Serialize)]
+struct BalanceTagsResponse {
+    latest: String,
+    /// `null` when the chain does not support the tag.
+    safe: Option<String>,
+    /// `null` when the chain does not support the tag.
+    finalized: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+    error_type: ErrorType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<i64>,
+    /// Methods served at the path of a `405` response.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    allowed: Vec<&'static str>,
+    /// Every problem found in the request body of a `400`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
+}
+
+/// Query parameters of `/balance/:address/fiat`.
+#[derive(Debug, Deserialize)]
+struct FiatQuery {
+    #[serde(default = "default_currency")]
+    currency: String,
+}
+
+fn default_currency() -> String {
+    "usd".to_string()
+}
+
+/// Query parameters of `/balance/:address/at`.
+#[derive(Debug, Deserialize)]
+struct TimestampQuery {
+    /// Unix time in seconds.
+    timestamp: u64,
+}
+
+/// Balance at the last block mined at or before the requested timestamp.
+#[derive(Serialize)]
+struct TimestampBalanceResponse {
+    block: u64,
+    block_timestamp: u64,
+    balance: String,
+}
+
+/// Balance in ether with its value in a fiat currency; `price` and
+/// `fiat_value` are null when the price feed is unavailable.
+#[derive(Debug, Serialize)]
+struct FiatBalanceResponse {
+    balance_eth: String,
+    price: Option<f64>,
+    fiat_value: Option<f64>,
+    currency: String,
+}
+
+/// Query parameters of `/admin/maintenance`; without `enabled` the mode is flipped.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaintenanceQuery {
+    enabled: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+struct MaintenanceResponse {
+    maintenance: bool,
+}
+
+/// Query parameters accepted by `/logs`; both block bounds are inclusive.
+#[derive(Debug, Deserialize)]
+struct LogsQuery {
+    from_block: u64,
+    to_block: u64,
+    address: Option<String>,
+    topic0: Option<String>,
+}
+
+/// Number of addresses returned by `/stats/top-addresses` without `?limit=`.
+const DEFAULT_TOP_ADDRESSES: usize = 10;
+
+/// Query parameters of `/xpub/:xpub/balances`.
+#[derive(Debug, Deserialize)]
+struct XpubQuery {
+    /// Number of addresses to derive, at most `xpub::MAX_XPUB_ADDRESSES`.
+    count: Option<u32>,
+}
+
+/// Balance of an address derived from an extended public key.
+#[derive(Serialize)]
+struct XpubBalanceEntry {
+    /// Derivation index, the `i` of `m/44'/60'/0'/0/i`.
+    index: u32,
+    address: Address,
+    balance: String,
+}
+
+/// Query parameters accepted by `/stats/top-addresses`.
+#[derive(Debug, Deserialize)]
+struct TopAddressesQuery {
+    limit: Option<usize>,
+}
+
+/// Shared state handed to the route filters.
+#[derive(Clone)]
+struct AppState {
+    provider: Arc<dyn Provider>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+    chains: Arc<ChainRegistry>,
+    ready_policy: ReadyPolicy,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    daily_quotas: Option<Arc<DailyQuotas>>,
+    inflight_limit: Option<Arc<InflightLimit>>,
+    /// Longest a request may take as a whole, from `REQUEST_DEADLINE_MS`.
+    request_deadline: Option<Duration>,
+    query_limits: QueryLimits,
+    route_flags: RouteFlags,
+    route_prefix: RoutePrefix,
+    ui: Option<ui::UiConfig>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    upstreams: Option<Arc<UpstreamAllowlist>>,
+    archive: Option<Arc<ArchiveRouter>>,
+    pending: Option<Arc<PendingBuffer>>,
+    readiness: Arc<Readiness>,
+    balance_cache: Arc<BalanceCache>,
+    node_cache: Arc<NodeCache>,
+    not_found_cache: Arc<NotFoundCache>,
+    idempotency_cache: Arc<IdempotencyCache>,
+    admin_token: Option<String>,
+    reloader: Option<Arc<Reloader>>,
+    maintenance: Arc<Maintenance>,
+    error_reporter: Option<Arc<ErrorReporter>>,
+    streams: Option<Arc<BalanceStreams>>,
+    logs: LogsConfig,
+    events: Arc<EventRegistry>,
+    trace: Option<TraceConfig>,
+    timings: Arc<RpcTimings>,
+    signer: Option<Arc<PrivateKeySigner>>,
+    price_feed: Option<Arc<PriceFeed>>,
+    block_timestamps: Arc<BlockTimestamps>,
+    /// Tracer of the per-request spans.
+    tracer: Arc<BoxedTracer>,
+    /// OpenTelemetry request instruments, with `OTEL_METRICS_ENABLED`.
+    request_metrics: Option<Arc<RequestMetrics>>,
+    /// Background tasks, reported by `/stats`.
+    supervisor: Arc<Supervisor>,
+    /// Picks the successful requests that get logged.
+    log_sampler: Arc<LogSampler>,
+    /// Headers whose values are redacted from the request log.
+    sensitive_headers: Arc<SensitiveHeaders>,
+}
+
+impl AppState {
+    /// Default settings serving a single chain backed by `provider`.
+    fn new(provider: Arc<dyn Provider>) -> Self {
+        let chains = ChainRegistry::new(vec![Chain::new(DEFAULT_CHAIN, provider.clone())]);
+        AppState {
+            provider,
+            metrics: Arc::new(Metrics::new()),
+            envelope: Envelope::default(),
+            chains: Arc::new(chains),
+            ready_policy: ReadyPolicy::All,
+            rate_limiter: None,
+            daily_quotas: None,
+            inflight_limit: None,
+            request_deadline: None,
+            query_limits: QueryLimits::default(),
+            route_flags: RouteFlags::default(),
+            route_prefix: RoutePrefix::default(),
+            ui: None,
+            breaker: None,
+            upstreams: None,
+            archive: None,
+            pending: None,
+            readiness: Arc::new(Readiness::new(true)),
+            balance_cache: Arc::new(TtlCache::new(Duration::ZERO)),
+            node_cache: Arc::new(NodeCache::default()),
+            not_found_cache: Arc::new(NotFoundCache::new(Duration::ZERO)),
+            idempotency_cache: Arc::new(IdempotencyCache::new(Duration::ZERO)),
+            admin_token: None,
+            reloader: None,
+            maintenance: Arc::new(Maintenance::default()),
+            error_reporter: None,
+            streams: None,
+            logs: LogsConfig::default(),
+            events: Arc::new(EventRegistry::new(&[]).expect("built-in events parse")),
+            trace: None,
+            timings: Arc::new(RpcTimings::new()),
+            signer: None,
+            price_feed: None,
+            block_timestamps: Arc::new(BlockTimestamps::new()),
+            tracer: Arc::new(global::tracer("rust-alloy")),
+            request_metrics: None,
+            supervisor: Arc::new(Supervisor::new(
+                supervisor::INITIAL_RESTART_BACKOFF,
+                supervisor::MAX_RESTART_BACKOFF,
+            )),
+            log_sampler: Arc::new(LogSampler::default()),
+            sensitive_headers: Arc::new(SensitiveHeaders::default()),
+        }
+    }
 }
 
 /// Get the balance for a given Ethereum address.
@@ -30,31 +687,84 @@ struct BalanceResponse {
 /// # let provider = Arc::new(provider);
 /// # async_std::task::block_on(async {
 /// let address = "0x0000000000000000000000000000000000000000".to_string();
-/// let response = get_balance(address, provider.clone()).await.unwrap();
+/// let query = BalanceQuery { amount: AmountQuery::default(), sign: false };
+/// let metrics = Arc::new(Metrics::new());
+/// let response = get_balance(address, BlockId::safe(), query, provider.clone(), None, None, None, metrics, Envelope::Bare, Format::Json).await.unwrap();
 /// println!("{:?}", response);
 /// # });
 /// ```
+// One argument per warp filter of the route.
+#[allow(clippy::too_many_arguments)]
 async fn get_balance(
     address: String,
+    mut block_id: BlockId,
+    query: BalanceQuery,
     provider: Arc<dyn Provider>,
+    archive: Option<Arc<ArchiveRouter>>,
+    cache: Option<Arc<BalanceCache>>,
+    signer: Option<Arc<PrivateKeySigner>>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+    format: Format,
 ) -> Result<impl Reply, Rejection> {
     // Get the global tracer (avoid passing it around)
     let tracer = global::tracer("example");
     let mut span = tracer.start("get_balance");
 
-    // Parse the address string into an Ethereum Address.
-    info!("Parsing address: {}", address);
-    let address_parsed = address.parse::<Address>().map_err(|error| {
-        error!("Failed to parse address: {}", error);
-        warp::reject::custom(ServerError)
-    })?;
+    let address_parsed = parse_address(&address)?;
+    metrics.record_address(address_parsed);
 
-    // Query the balance via the alloy provider.
-    info!("Querying balance for address: {}", address_parsed);
-    let balance = provider
-        .get_balance(address_parsed)
-        .await
-        .map_err(|_| warp::reject::custom(ServerError))?;
+    // A signature commits to a block number, so tags and hashes are pinned first.
+    let signer = match (query.sign, signer) {
+        (false, _) => None,
+        (true, Some(signer)) => {
+            let block_number = resolve_block_number(provider.as_ref(), block_id).await?;
+            block_id = BlockId::number(block_number);
+            Some((signer, block_number))
+        }
+        (true, None) => {
+            return Err(AppError::InvalidRequest(
+                "signing is not configured on this server".to_string(),
+            )
+            .into())
+        }
+    };
+    let (provider, served_by) = archive::route(archive.as_deref(), &provider, block_id).await?;
+
+    let cache = cache.filter(|cache| cache.is_enabled());
+    let cache_key = (address_parsed, block_id.to_string());
+    let cached = match &cache {
+        Some(cache) => cache.get(&cache_key).await,
+        None => None,
+    };
+    let cache_status = cache.as_ref().map(|_| {
+        metrics.record_cache_lookup(cached.is_some());
+        if cached.is_some() {
+            "HIT"
+        } else {
+            "MISS"
+        }
+    });
+
+    let balance = match cached {
+        Some(balance) => balance,
+        None => {
+            // Query the balance via the alloy provider.
+            info!(
+                "Querying balance for address: {} at block: {}",
+                address_parsed, block_id
+            );
+            let balance = provider
+                .get_balance(address_parsed)
+                .block_id(block_id)
+                .await
+                .map_err(AppError::from)?;
+            if let Some(cache) = &cache {
+                cache.insert(cache_key, balance).await;
+            }
+            balance
+        }
+    };
 
     info!("Fetched balance: {}", balance);
     span.add_event(
@@ -63,225 +773,7204 @@ async fn get_balance(
     );
     span.end();
 
-    Ok(warp::reply::json(&BalanceResponse {
-        balance: balance.to_string(),
-    }))
-}
-
-#[derive(Debug)]
-struct ServerError;
-impl warp::reject::Reject for ServerError {}
-
-#[tokio::main]
-async fn main() {
-    env_logger::init();
-    info!("Starting the Warp server...");
-
-    let provider = setup_provider().await;
-
-    // Set up CORS and routes
-    let cors = setup_cors();
-    let routes = setup_routes(provider).with(cors);
-
-    println!("Server starting on http://localhost:3030");
-    warp::serve(routes).run(([0, 0, 0, 0], 3030)).await;
+    let signature = match signer {
+        Some((signer, block_number)) => Some(signing::sign_balance(
+            &signer,
+            address_parsed,
+            balance,
+            block_number,
+        )?),
+        None => None,
+    };
+    let body = BalanceResponse {
+        balance: query.amount.unit.format(balance, query.amount.format)?,
+        unit: query.amount.unit,
+        signature,
+    };
+    let mut response = warp::reply::with_header(
+        negotiate_and_serialize(&wrap_response(body, &envelope), format)?,
+        SERVED_BY_HEADER,
+        served_by.as_str(),
+    )
+    .into_response();
+    if let Some(status) = cache_status {
+        response
+            .headers_mut()
+            .insert(CACHE_HEADER, warp::http::HeaderValue::from_static(status));
+    }
+    Ok(response)
 }
 
-/// Sets up the Ethereum provider.
+/// Balance of the address owning an uncompressed secp256k1 public key.
 ///
 /// # Examples
 ///
 /// ```rust
-/// # async fn test_setup_provider() {
-/// let provider = setup_provider().await;
-/// assert!(provider.is_some());
+/// # async fn test_get_balance_by_pubkey() {
+/// let query = BalanceQuery { amount: AmountQuery::default(), sign: false };
+/// let response = get_balance_by_pubkey(pubkey, BlockId::latest(), query, provider.clone(), None, metrics, Envelope::Bare, Format::Json).await.unwrap();
 /// # }
 /// ```
-async fn setup_provider() -> Arc<dyn Provider> {
-    let ethereum_rpc_url = get_ethereum_rpc_url();
-    let url = Url::parse(&ethereum_rpc_url).expect("Invalid URL");
+// One argument per warp filter of the route.
+#[allow(clippy::too_many_arguments)]
+async fn get_balance_by_pubkey(
+    pubkey: String,
+    block_id: BlockId,
+    query: BalanceQuery,
+    provider: Arc<dyn Provider>,
+    archive: Option<Arc<ArchiveRouter>>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+    format: Format,
+) -> Result<impl Reply, Rejection> {
+    let address = parse_public_key(&pubkey)?;
+    metrics.record_address(address);
+    let (provider, served_by) = archive::route(archive.as_deref(), &provider, block_id).await?;
 
-    let builder = ProviderBuilder::new();
-    let provider = builder.on_http(url);
-    Arc::new(provider)
+    info!(
+        "Querying balance for public key address: {} at block: {}",
+        address, block_id
+    );
+    let balance = provider
+        .get_balance(address)
+        .block_id(block_id)
+        .await
+        .map_err(AppError::from)?;
+
+    let body = PubkeyBalanceResponse {
+        address,
+        balance: query.amount.unit.format(balance, query.amount.format)?,
+        unit: query.amount.unit,
+    };
+    Ok(warp::reply::with_header(
+        negotiate_and_serialize(&wrap_response(body, &envelope), format)?,
+        SERVED_BY_HEADER,
+        served_by.as_str(),
+    ))
 }
 
-/// Retrieves the Ethereum RPC URL from the environment variables.
+/// Server-Sent Events stream of an address's balance, re-sent on every new block.
+///
+/// The first event carries the balance at the current head. A failed lookup
+/// is sent as an `error` event and the stream carries on with the next block.
+/// A head at or below the last one sent is preceded by a `reorg` event with
+/// the old and new heads.
 ///
 /// # Examples
 ///
 /// ```rust
-/// # fn test_get_ethereum_rpc_url() {
-/// let url = get_ethereum_rpc_url();
-/// assert!(!url.is_empty());
+/// # async fn test_get_balance_stream() {
+/// let address = "0x0000000000000000000000000000000000000000".to_string();
+/// let response = get_balance_stream(address, provider, streams, metrics).await.unwrap();
 /// # }
 /// ```
-fn get_ethereum_rpc_url() -> String {
-    env::var("ETHEREUM_RPC_URL").unwrap_or_else(|_| {
-        error!("ETHEREUM_RPC_URL not set, using default");
-        "http://localhost:8545".to_string()
+async fn get_balance_stream(
+    address: String,
+    provider: Arc<dyn Provider>,
+    streams: Arc<BalanceStreams>,
+    metrics: Arc<Metrics>,
+) -> Result<impl Reply, Rejection> {
+    let address = parse_address(&address)?;
+    let heads = streams.open()?;
+    metrics.record_address(address);
+    let head = provider.get_block_number().await.map_err(AppError::from)?;
+    info!(
+        "Streaming balance of {} from block {} ({} streams open)",
+        address,
+        head,
+        streams.active()
+    );
+
+    // Dropping the stream when the client disconnects closes `heads`.
+    let state = (Some(head), HeadTracker::default(), heads);
+    let events = futures::stream::unfold(state, move |(next, mut tracker, mut heads)| {
+        let provider = provider.clone();
+        async move {
+            let block_number = match next {
+                Some(block_number) => block_number,
+                None => heads.next().await?,
+            };
+            let mut events = Vec::new();
+            if let Some(reorg) = tracker.observe(block_number) {
+                warn!(
+                    "Reorg on the balance stream of {}: head {} after {}",
+                    address, reorg.new_head, reorg.old_head
+                );
+                events.push(warp::sse::Event::default().event("reorg").json_data(reorg));
+            }
+            let event = match provider
+                .get_balance(address)
+                .block_id(block_number.into())
+                .await
+            {
+                Ok(balance) => {
+                    warp::sse::Event::default()
+                        .event("balance")
+                        .json_data(BalanceEvent {
+                            block_number,
+                            balance: balance.to_string(),
+                        })
+                }
+                Err(error) => Ok(warp::sse::Event::default()
+                    .event("error")
+                    .data(AppError::from(error).to_string())),
+            };
+            events.push(event);
+            Some((futures::stream::iter(events), (None, tracker, heads)))
+        }
     })
+    .flatten();
+    Ok(warp::sse::reply(warp::sse::keep_alive().stream(events)))
 }
 
-/// Configures CORS for the server.
+/// Number of transactions sent from an address, at the requested block.
 ///
 /// # Examples
 ///
 /// ```rust
-/// # fn test_setup_cors() {
-/// let cors = setup_cors();
-/// assert!(cors.is_some());
+/// # async fn test_get_nonce() {
+/// let address = "0x0000000000000000000000000000000000000000".to_string();
+/// let query = QuantityQuery { format: NumberFormat::Dec };
+/// let response = get_nonce(address, BlockId::latest(), query, provider, None, metrics, Envelope::Bare, Format::Json).await.unwrap();
 /// # }
 /// ```
-fn setup_cors() -> warp::cors::Builder {
-    warp::cors()
-        .allow_any_origin()
-        .allow_methods(vec!["GET", "POST", "PUT", "DELETE"])
-        .allow_headers(vec!["Content-Type", "Authorization"])
+// One argument per warp filter of the route.
+#[allow(clippy::too_many_arguments)]
+async fn get_nonce(
+    address: String,
+    block_id: BlockId,
+    query: QuantityQuery,
+    provider: Arc<dyn Provider>,
+    archive: Option<Arc<ArchiveRouter>>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+    format: Format,
+) -> Result<impl Reply, Rejection> {
+    let address = parse_address(&address)?;
+    metrics.record_address(address);
+    let (provider, served_by) = archive::route(archive.as_deref(), &provider, block_id).await?;
+
+    info!(
+        "Querying nonce for address: {} at block: {}",
+        address, block_id
+    );
+    let nonce = provider
+        .get_transaction_count(address)
+        .block_id(block_id)
+        .await
+        .map_err(AppError::from)?;
+
+    let body = NonceResponse {
+        nonce: query.format.format(U256::from(nonce)),
+    };
+    Ok(warp::reply::with_header(
+        negotiate_and_serialize(&wrap_response(body, &envelope), format)?,
+        SERVED_BY_HEADER,
+        served_by.as_str(),
+    ))
 }
 
-/// Health check route.
+/// Gap between the `latest` and `pending` transaction counts of an address,
+/// the transactions sent to the node but not mined yet.
 ///
 /// # Examples
 ///
 /// ```rust
-/// # fn test_health_check() {
-/// let response = health_check().await;
-/// assert_eq!(response, "OK");
+/// # async fn test_get_nonce_gap() {
+/// let address = "0x0000000000000000000000000000000000000000".to_string();
+/// let response = get_nonce_gap(address, provider, metrics, Envelope::Bare).await.unwrap();
 /// # }
 /// ```
-async fn health_check() -> Result<impl Reply, Rejection> {
-    Ok("OK")
+async fn get_nonce_gap(
+    address: String,
+    provider: Arc<dyn Provider>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let address = parse_address(&address)?;
+    metrics.record_address(address);
+
+    info!("Querying nonce gap for address: {}", address);
+    let (latest, pending) = futures::join!(
+        provider
+            .get_transaction_count(address)
+            .latest()
+            .into_future(),
+        provider
+            .get_transaction_count(address)
+            .pending()
+            .into_future(),
+    );
+    let (latest, pending) = (
+        latest.map_err(AppError::from)?,
+        pending.map_err(AppError::from)?,
+    );
+
+    let body = NonceGapResponse {
+        latest,
+        pending,
+        // Negative only when the node answered from states that disagree.
+        gap: pending as i64 - latest as i64,
+    };
+    Ok(warp::reply::json(&wrap_response(body, &envelope)))
 }
 
-/// Sets up the routes for the server.
+/// Current gas price of the node, in wei unless `?unit=` asks otherwise.
+///
+/// The default node's price is read through the node cache; requests with
+/// their own upstream query it directly.
 ///
 /// # Examples
 ///
 /// ```rust
-/// # fn test_setup_routes() {
-/// let provider = Arc::new(...); // Mock or create a provider
-/// let routes = setup_routes(provider);
-/// assert!(routes.is_some());
+/// # async fn test_get_gas_price() {
+/// let response = get_gas_price(AmountQuery::default(), provider, Some(node_cache), Envelope::Bare).await.unwrap();
 /// # }
 /// ```
-fn setup_routes(
+async fn get_gas_price(
+    query: AmountQuery,
     provider: Arc<dyn Provider>,
-) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
-    let health_route = warp::path!("health")
-        .and(warp::get())
-        .and_then(health_check);
-
-    let balance_route = warp::path!("balance" / String)
-        .and(warp::get())
-        .and(with_provider(provider.clone()))
-        .and_then(get_balance);
-
-    balance_route
-        .with(warp::log::custom(log_request))
-        .or(health_route)
+    node_cache: Option<Arc<NodeCache>>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let gas_price = match node_cache {
+        Some(cache) => cache.gas_price(provider.as_ref()).await,
+        None => provider.get_gas_price().await,
+    }
+    .map_err(AppError::from)?;
+    let body = GasPriceResponse {
+        gas_price: query.unit.format(U256::from(gas_price), query.format)?,
+        unit: query.unit,
+    };
+    Ok(warp::reply::json(&wrap_response(body, &envelope)))
 }
 
-/// Logs the details of the request.
+/// Chain id of the node, queried once and then served from the node cache.
 ///
 /// # Examples
 ///
 /// ```rust
-/// # fn test_log_request() {
-/// let info = ...; // Mock or create a request info
-/// log_request(info);
+/// # async fn test_get_chain_id() {
+/// let response = get_chain_id(provider, Some(node_cache), Envelope::Bare).await.unwrap();
 /// # }
 /// ```
-fn log_request(info: warp::log::Info) {
-    let method = info.method();
-    let path = info.path();
-    let status = info.status();
-    let elapsed = info.elapsed();
-    let ip = info
-        .remote_addr()
-        .map(|addr| addr.to_string())
-        .unwrap_or_else(|| "Unknown".into());
-
-    if status.is_success() {
-        info!(
-            "Request: {} {} from {} took {:?}",
-            method, path, ip, elapsed
-        );
-    } else {
-        error!(
-            "Request: {} {} from {} failed with status {:?} and took {:?}",
-            method, path, ip, status, elapsed
-        );
+async fn get_chain_id(
+    provider: Arc<dyn Provider>,
+    node_cache: Option<Arc<NodeCache>>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let chain_id = match node_cache {
+        Some(cache) => cache.chain_id(provider.as_ref()).await,
+        None => provider.get_chain_id().await,
     }
+    .map_err(AppError::from)?;
+    let body = ChainIdResponse { chain_id };
+    Ok(warp::reply::json(&wrap_response(body, &envelope)))
 }
 
-/// Provides the provider to the warp filters.
+/// Sync progress and peer count of the node.
 ///
 /// # Examples
 ///
 /// ```rust
-/// # fn test_with_provider() {
-/// let provider = Arc::new(...); // Mock or create a provider
-/// let filter = with_provider(provider);
-/// assert!(filter.is_some());
+/// # async fn test_get_node_status() {
+/// let response = get_node_status(provider, Envelope::Bare).await.unwrap();
 /// # }
 /// ```
-fn with_provider(
+async fn get_node_status(
+    provider: Arc<dyn Provider>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let body = node_status::node_status(provider.as_ref()).await?;
+    Ok(warp::reply::json(&wrap_response(body, &envelope)))
+}
+
+/// Base fees, gas used ratios and reward percentiles of the last `?blocks=` blocks.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_fee_history() {
+/// let query = FeeHistoryQuery { blocks: 4, percentiles: Some("10,50,90".to_string()) };
+/// let response = get_fee_history(query, provider, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_fee_history(
+    query: FeeHistoryQuery,
+    provider: Arc<dyn Provider>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let percentiles = query.validate()?;
+    info!("Querying fee history of the last {} blocks", query.blocks);
+    let history = provider
+        .get_fee_history(
+            query.blocks,
+            alloy::eips::BlockNumberOrTag::Latest,
+            &percentiles,
+        )
+        .await
+        .map_err(AppError::from)?;
+    Ok(warp::reply::json(&wrap_response(
+        FeeHistoryResponse::from(history),
+        &envelope,
+    )))
+}
+
+/// Balance of an address in ether, valued in `?currency=` (default `usd`) at the price feed's rate.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_balance_fiat() {
+/// let address = "0x0000000000000000000000000000000000000000".to_string();
+/// let query = FiatQuery { currency: "usd".to_string() };
+/// let response = get_balance_fiat(address, query, provider, feed, metrics, Envelope::Bare, Format::Json).await.unwrap();
+/// # }
+/// ```
+async fn get_balance_fiat(
+    address: String,
+    query: FiatQuery,
+    provider: Arc<dyn Provider>,
+    feed: Arc<PriceFeed>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+    format: Format,
+) -> Result<impl Reply, Rejection> {
+    let address_parsed = parse_address(&address)?;
+    let currency = query.currency.to_ascii_lowercase();
+    if currency.is_empty() || currency.len() > 10 || !currency.chars().all(char::is_alphanumeric) {
+        return Err(
+            AppError::InvalidRequest(format!("invalid currency {}", query.currency)).into(),
+        );
+    }
+    metrics.record_address(address_parsed);
+
+    info!("Querying fiat balance for address: {}", address_parsed);
+    let (balance, price) = futures::join!(
+        provider.get_balance(address_parsed).into_future(),
+        feed.try_price(&currency),
+    );
+    let balance = balance.map_err(AppError::from)?;
+    let balance_eth = Unit::Ether.format(balance, NumberFormat::Dec)?;
+    let fiat_value = price.and_then(|price| fiat_value(balance, price));
+
+    let body = FiatBalanceResponse {
+        balance_eth,
+        price,
+        fiat_value,
+        currency,
+    };
+    Ok(negotiate_and_serialize(
+        &wrap_response(body, &envelope),
+        format,
+    )?)
+}
+
+/// Value of `balance` wei at `price` per ether, rounded once from the exact
+/// product of the balance and the price, the price taken to 8 decimal places.
+fn fiat_value(balance: U256, price: f64) -> Option<f64> {
+    if !price.is_finite() || price < 0.0 {
+        return None;
+    }
+    let price = U256::from((price * 1e8).round() as u128);
+    // In units of 1e-18 ether times 1e-8 of the currency.
+    let value = balance.checked_mul(price)?;
+    format_units(value, 26).ok()?.parse().ok()
+}
+
+/// Balance of an address at the last block mined at or before `?timestamp=`,
+/// with the block it resolved to; `404` for timestamps before the genesis block.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_balance_at() {
+/// let address = "0x0000000000000000000000000000000000000000".to_string();
+/// let query = TimestampQuery { timestamp: 1_700_000_000 };
+/// let response = get_balance_at(address, query, provider, None, timestamps, metrics, Envelope::Bare, Format::Json).await.unwrap();
+/// # }
+/// ```
+// One argument per warp filter of the route.
+#[allow(clippy::too_many_arguments)]
+async fn get_balance_at(
+    address: String,
+    query: TimestampQuery,
+    provider: Arc<dyn Provider>,
+    archive: Option<Arc<ArchiveRouter>>,
+    timestamps: Arc<BlockTimestamps>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+    format: Format,
+) -> Result<impl Reply, Rejection> {
+    let address_parsed = parse_address(&address)?;
+    metrics.record_address(address_parsed);
+
+    let block = timestamps
+        .block_at(provider.as_ref(), query.timestamp)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("block at or before timestamp {}", query.timestamp))
+        })?;
+    let block_id = BlockId::number(block.number);
+    let (provider, served_by) = archive::route(archive.as_deref(), &provider, block_id).await?;
+
+    info!(
+        "Querying balance for address: {} at block: {} (timestamp {})",
+        address_parsed, block.number, query.timestamp
+    );
+    let balance = provider
+        .get_balance(address_parsed)
+        .block_id(block_id)
+        .await
+        .map_err(AppError::from)?;
+    let body = TimestampBalanceResponse {
+        block: block.number,
+        block_timestamp: block.timestamp,
+        balance: balance.to_string(),
+    };
+    Ok(warp::reply::with_header(
+        negotiate_and_serialize(&wrap_response(body, &envelope), format)?,
+        SERVED_BY_HEADER,
+        served_by.as_str(),
+    ))
+}
+
+/// Balances of an address at the `latest`, `safe` and `finalized` tags, fetched concurrently.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_balance_tags() {
+/// let address = "0x0000000000000000000000000000000000000000".to_string();
+/// let metrics = Arc::new(Metrics::new());
+/// let response = get_balance_tags(address, provider.clone(), metrics, Envelope::Bare, Format::Json).await.unwrap();
+/// # }
+/// ```
+async fn get_balance_tags(
+    address: String,
+    provider: Arc<dyn Provider>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+    format: Format,
+) -> Result<impl Reply, Rejection> {
+    let address_parsed = parse_address(&address)?;
+    metrics.record_address(address_parsed);
+
+    info!("Querying tagged balances for address: {}", address_parsed);
+    let balance_at = |block_id: BlockId| {
+        provider
+            .get_balance(address_parsed)
+            .block_id(block_id)
+            .into_future()
+    };
+    let (latest, safe, finalized) = futures::join!(
+        balance_at(BlockId::latest()),
+        balance_at(BlockId::safe()),
+        balance_at(BlockId::finalized()),
+    );
+
+    let body = BalanceTagsResponse {
+        latest: latest.map_err(AppError::from)?.to_string(),
+        safe: optional_tag(safe)?,
+        finalized: optional_tag(finalized)?,
+    };
+    Ok(negotiate_and_serialize(
+        &wrap_response(body, &envelope),
+        format,
+    )?)
+}
+
+/// Treats a node rejecting a block tag as the tag being unsupported; any
+/// other node error, such as a rate limit, is still an error.
+fn optional_tag(result: Result<U256, TransportError>) -> Result<Option<String>, AppError> {
+    match result.map_err(AppError::from) {
+        Ok(balance) => Ok(Some(balance.to_string())),
+        Err(AppError::Rpc { code, message }) if is_unsupported_tag(code, &message) => {
+            info!("Block tag unsupported ({}): {}", code, message);
+            Ok(None)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Whether a node error means it does not know a block tag, as answered by
+/// nodes predating it, e.g. `safe block not found` before the merge.
+fn is_unsupported_tag(code: i64, message: &str) -> bool {
+    let message = message.to_ascii_lowercase();
+    // Invalid params, and EIP-1474's unknown block.
+    code == -32602
+        || code == -39001
+        || (message.contains("block")
+            && ["not found", "unknown", "unsupported", "invalid"]
+                .iter()
+                .any(|reason| message.contains(reason)))
+}
+
+/// Balances of several addresses at the same block.
+///
+/// Entries are returned in request order; repeated addresses are queried once and
+/// repeated in the response at each position they were requested.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_balance_batch() {
+/// let body = BatchBalanceRequest { addresses: vec![address.clone()] };
+/// let response = get_balance_batch(BlockId::latest(), body, provider.clone(), None, metrics, Envelope::Bare, Format::Json).await.unwrap();
+/// # }
+/// ```
+async fn get_balance_batch(
+    block_id: BlockId,
+    body: BatchBalanceRequest,
+    provider: Arc<dyn Provider>,
+    archive: Option<Arc<ArchiveRouter>>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+    format: Format,
+) -> Result<impl Reply, Rejection> {
+    if body.addresses.len() > MAX_BATCH_ADDRESSES {
+        return Err(AppError::InvalidRequest(format!(
+            "at most {} addresses per batch",
+            MAX_BATCH_ADDRESSES
+        ))
+        .into());
+    }
+    let addresses = body
+        .addresses
+        .iter()
+        .map(|address| parse_address(address))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (provider, served_by) = archive::route(archive.as_deref(), &provider, block_id).await?;
+    let balances = fetch_balances(provider.as_ref(), &addresses, block_id, &metrics).await?;
+
+    let body: Vec<BatchBalanceEntry> = addresses
+        .into_iter()
+        .map(|address| BatchBalanceEntry {
+            address,
+            balance: balances[&address].to_string(),
+        })
+        .collect();
+    Ok(warp::reply::with_header(
+        negotiate_and_serialize(&wrap_response(body, &envelope), format)?,
+        SERVED_BY_HEADER,
+        served_by.as_str(),
+    ))
+}
+
+/// Balances of the first `?count=` receiving addresses of an extended public key, by derivation index.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_xpub_balances() {
+/// let query = XpubQuery { count: Some(5) };
+/// let response = get_xpub_balances(xpub, BlockId::latest(), query, provider.clone(), None, metrics, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_xpub_balances(
+    xpub: String,
+    block_id: BlockId,
+    query: XpubQuery,
+    provider: Arc<dyn Provider>,
+    archive: Option<Arc<ArchiveRouter>>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let count = query.count.unwrap_or(xpub::DEFAULT_XPUB_ADDRESSES);
+    if count > xpub::MAX_XPUB_ADDRESSES {
+        return Err(AppError::InvalidRequest(format!(
+            "at most {} addresses per xpub",
+            xpub::MAX_XPUB_ADDRESSES
+        ))
+        .into());
+    }
+    let addresses = xpub::derive_addresses(&xpub::parse_xpub(&xpub)?, count)?;
+    let (provider, served_by) = archive::route(archive.as_deref(), &provider, block_id).await?;
+    let balances = fetch_balances(provider.as_ref(), &addresses, block_id, &metrics).await?;
+
+    let body: Vec<XpubBalanceEntry> = (0..)
+        .zip(addresses)
+        .map(|(index, address)| XpubBalanceEntry {
+            index,
+            address,
+            balance: balances[&address].to_string(),
+        })
+        .collect();
+    Ok(warp::reply::with_header(
+        warp::reply::json(&wrap_response(body, &envelope)),
+        SERVED_BY_HEADER,
+        served_by.as_str(),
+    ))
+}
+
+/// Balances of newline-separated addresses, one per line in the same order.
+///
+/// Blank lines are skipped and malformed addresses are answered with an
+/// `ERROR` line instead of failing the whole batch.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_balance_batch_text() {
+/// let body = Bytes::from("0x0000000000000000000000000000000000000000\nnot-an-address\n");
+/// let response = get_balance_batch_text(BlockId::latest(), body, provider.clone(), None, metrics).await.unwrap();
+/// # }
+/// ```
+async fn get_balance_batch_text(
+    block_id: BlockId,
+    body: warp::hyper::body::Bytes,
+    provider: Arc<dyn Provider>,
+    archive: Option<Arc<ArchiveRouter>>,
+    metrics: Arc<Metrics>,
+) -> Result<impl Reply, Rejection> {
+    let body = std::str::from_utf8(&body)
+        .map_err(|_| AppError::InvalidRequest("body is not UTF-8 text".to_string()))?;
+    let lines: Vec<Option<Address>> = body
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.parse::<Address>().ok())
+        .collect();
+    if lines.len() > MAX_BATCH_ADDRESSES {
+        return Err(AppError::InvalidRequest(format!(
+            "at most {} addresses per batch",
+            MAX_BATCH_ADDRESSES
+        ))
+        .into());
+    }
+    let addresses: Vec<Address> = lines.iter().flatten().copied().collect();
+    let (provider, served_by) = archive::route(archive.as_deref(), &provider, block_id).await?;
+    let balances = fetch_balances(provider.as_ref(), &addresses, block_id, &metrics).await?;
+
+    let body: String = lines
+        .iter()
+        .map(|address| match address {
+            Some(address) => format!("{}\n", balances[address]),
+            None => "ERROR\n".to_string(),
+        })
+        .collect();
+    Ok(warp::reply::with_header(
+        warp::reply::with_header(body, "content-type", "text/plain; charset=utf-8"),
+        SERVED_BY_HEADER,
+        served_by.as_str(),
+    ))
+}
+
+/// Queries the balance of every distinct address concurrently.
+async fn fetch_balances(
+    provider: &dyn Provider,
+    addresses: &[Address],
+    block_id: BlockId,
+    metrics: &Metrics,
+) -> Result<HashMap<Address, U256>, AppError> {
+    let unique: HashSet<Address> = addresses.iter().copied().collect();
+    info!(
+        "Querying balances for {} addresses at block: {}",
+        unique.len(),
+        block_id
+    );
+    let balances = futures::future::try_join_all(unique.into_iter().map(|address| {
+        metrics.record_address(address);
+        let balance = provider.get_balance(address).block_id(block_id);
+        async move { balance.await.map(|balance| (address, balance)) }
+    }))
+    .await?;
+    Ok(balances.into_iter().collect())
+}
+
+/// A block by number, hash, or tag (`latest`, `safe`, `finalized`, ...).
+///
+/// With `?encoding=rlp` the block is returned RLP-encoded instead.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_block() {
+/// let query = EncodingQuery::default();
+/// let response = get_block("latest".to_string(), query, provider.clone(), Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_block(
+    block: String,
+    query: EncodingQuery,
+    provider: Arc<dyn Provider>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let block_id = parse_block_id(&block)?;
+    // Re-encoding needs the full transactions, not just their hashes.
+    let kind = match query.encoding {
+        Encoding::Json => BlockTransactionsKind::Hashes,
+        Encoding::Rlp => BlockTransactionsKind::Full,
+    };
+
+    info!("Querying block: {}", block_id);
+    let found = provider
+        .get_block(block_id, kind)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound(format!("block {}", block)))?;
+
+    Ok(match query.encoding {
+        Encoding::Json => warp::reply::json(&wrap_response(BlockResponse::from(&found), &envelope)),
+        Encoding::Rlp => {
+            let body = RlpResponse {
+                rlp: encode_block(found),
+            };
+            warp::reply::json(&wrap_response(body, &envelope))
+        }
+    })
+}
+
+/// A transaction by hash.
+///
+/// With `?encoding=rlp` the raw signed transaction is returned instead.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_transaction() {
+/// let query = EncodingQuery::default();
+/// let response = get_transaction(hash, query, provider.clone(), None, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_transaction(
+    hash: String,
+    query: EncodingQuery,
+    provider: Arc<dyn Provider>,
+    not_found: Option<Arc<NotFoundCache>>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let hash_parsed = parse_tx_hash(&hash)?;
+    let not_found_error = || AppError::NotFound(format!("transaction {}", hash_parsed));
+    let not_found = not_found.filter(|cache| cache.is_enabled());
+    if let Some(cache) = &not_found {
+        if cache.get(&("tx", hash_parsed)).is_some() {
+            return Err(not_found_error().into());
+        }
+    }
+
+    info!("Querying transaction: {}", hash_parsed);
+    let found = provider
+        .get_transaction_by_hash(hash_parsed)
+        .await
+        .map_err(AppError::from)?;
+    if let Some(cache) = &not_found {
+        match &found {
+            None => cache.insert(("tx", hash_parsed), ()),
+            // A mined transaction has a receipt too.
+            Some(tx) if tx.block_number.is_some() => cache.remove(&("receipt", hash_parsed)),
+            Some(_) => {}
+        }
+    }
+    let found = found.ok_or_else(not_found_error)?;
+
+    Ok(match query.encoding {
+        Encoding::Json => {
+            warp::reply::json(&wrap_response(TransactionResponse::from(&found), &envelope))
+        }
+        Encoding::Rlp => {
+            let body = RlpResponse {
+                rlp: encode_transaction(&found),
+            };
+            warp::reply::json(&wrap_response(body, &envelope))
+        }
+    })
+}
+
+/// Input calldata of a transaction, decoded against the function of the given
+/// ABI matching its selector.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_decode_transaction_input() {
+/// let body = DecodeInputRequest { abi };
+/// let response = decode_transaction_input(hash, body, provider.clone(), Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn decode_transaction_input(
+    hash: String,
+    body: DecodeInputRequest,
+    provider: Arc<dyn Provider>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let hash_parsed = parse_tx_hash(&hash)?;
+
+    info!("Decoding input of transaction: {}", hash_parsed);
+    let found = provider
+        .get_transaction_by_hash(hash_parsed)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound(format!("transaction {}", hash_parsed)))?;
+    let input = found.inner.input().clone();
+
+    let decoded = abi_call::decode_input(&body.abi, &input)?;
+    let body = DecodedInputResponse {
+        hash: hash_parsed,
+        function: decoded.as_ref().map(|(function, _)| function.name.clone()),
+        signature: decoded.as_ref().map(|(function, _)| function.signature()),
+        args: decoded.map(|(_, args)| args),
+        input,
+    };
+    Ok(warp::reply::json(&wrap_response(body, &envelope)))
+}
+
+/// Whether a transaction is `pending`, `mined` or `unknown` to the node.
+///
+/// The receipt is looked up first, so mined transactions take a single call.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_tx_status() {
+/// let response = get_tx_status(hash, provider.clone(), Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_tx_status(
+    hash: String,
+    provider: Arc<dyn Provider>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let hash_parsed = parse_tx_hash(&hash)?;
+
+    info!("Querying status of transaction: {}", hash_parsed);
+    let receipt = provider
+        .get_transaction_receipt(hash_parsed)
+        .await
+        .map_err(AppError::from)?;
+    let tx = match receipt {
+        Some(_) => None,
+        None => provider
+            .get_transaction_by_hash(hash_parsed)
+            .await
+            .map_err(AppError::from)?,
+    };
+
+    let body = TxStatusResponse::new(hash_parsed, receipt.as_ref(), tx.as_ref());
+    Ok(warp::reply::json(&wrap_response(body, &envelope)))
+}
+
+/// The receipt of a mined transaction.
+///
+/// Hashes without a receipt yet are remembered for a couple of seconds, so
+/// clients polling for a pending transaction are answered locally.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_receipt() {
+/// let response = get_receipt(hash, provider.clone(), None, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_receipt(
+    hash: String,
+    provider: Arc<dyn Provider>,
+    not_found: Option<Arc<NotFoundCache>>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let hash_parsed = parse_tx_hash(&hash)?;
+    let key = ("receipt", hash_parsed);
+    let not_found_error = || AppError::NotFound(format!("receipt {}", hash_parsed));
+    let not_found = not_found.filter(|cache| cache.is_enabled());
+    if let Some(cache) = &not_found {
+        if cache.get(&key).is_some() {
+            return Err(not_found_error().into());
+        }
+    }
+
+    info!("Querying receipt: {}", hash_parsed);
+    let found = provider
+        .get_transaction_receipt(hash_parsed)
+        .await
+        .map_err(AppError::from)?;
+    if let Some(cache) = &not_found {
+        match &found {
+            None => cache.insert(key, ()),
+            Some(_) => cache.remove(&key),
+        }
+    }
+    let found = found.ok_or_else(not_found_error)?;
+
+    Ok(warp::reply::json(&wrap_response(
+        ReceiptResponse::from(&found),
+        &envelope,
+    )))
+}
+
+/// Receipts of several transactions, in request order, with `null` for
+/// hashes that are pending or unknown to the node.
+///
+/// At most [`RECEIPT_CONCURRENCY`] receipts are requested from the node at once.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_receipts() {
+/// let body = BatchReceiptsRequest { hashes: vec![hash.clone()] };
+/// let response = get_receipts(body, provider.clone(), Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_receipts(
+    body: BatchReceiptsRequest,
+    provider: Arc<dyn Provider>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    if body.hashes.len() > MAX_BATCH_RECEIPTS {
+        return Err(AppError::InvalidRequest(format!(
+            "at most {} hashes per batch",
+            MAX_BATCH_RECEIPTS
+        ))
+        .into());
+    }
+    let hashes = body
+        .hashes
+        .iter()
+        .map(|hash| parse_tx_hash(hash))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    info!("Querying {} receipts", hashes.len());
+    let body: Vec<Option<ReceiptResponse>> = futures::stream::iter(hashes)
+        .map(|hash| provider.get_transaction_receipt(hash).into_future())
+        .buffered(RECEIPT_CONCURRENCY)
+        .map_ok(|found| found.as_ref().map(ReceiptResponse::from))
+        .try_collect()
+        .await
+        .map_err(AppError::from)?;
+    Ok(warp::reply::json(&wrap_response(body, &envelope)))
+}
+
+/// Logs emitted between two blocks, optionally filtered by address and first topic;
+/// logs of known events are decoded alongside the raw fields.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_logs() {
+/// let query = LogsQuery { from_block: 0, to_block: 10, address: None, topic0: None };
+/// let events = Arc::new(EventRegistry::new(&[]).unwrap());
+/// let response = get_logs(query, provider, LogsConfig::default(), events, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_logs(
+    query: LogsQuery,
+    provider: Arc<dyn Provider>,
+    config: LogsConfig,
+    events: Arc<EventRegistry>,
+    envelope: Envelope,
+    accept: Option<String>,
+) -> Result<impl Reply, Rejection> {
+    let mut filter = alloy::rpc::types::Filter::new();
+    if let Some(address) = &query.address {
+        filter = filter.address(parse_address(address)?);
+    }
+    if let Some(topic0) = &query.topic0 {
+        let topic0 = topic0
+            .parse::<B256>()
+            .map_err(|_| AppError::InvalidRequest(format!("invalid topic {}", topic0)))?;
+        filter = filter.event_signature(topic0);
+    }
+
+    info!(
+        "Querying logs from block {} to {}",
+        query.from_block, query.to_block
+    );
+    if format::accepts_ndjson(accept.as_deref()) {
+        let ranges = logs::plan_ranges(query.from_block, query.to_block, &config)?;
+        let lines = logs::stream_logs(provider, filter, ranges).map(move |chunk| {
+            let chunk = chunk.map_err(|error| {
+                warn!("Streaming logs failed: {}", error);
+                std::io::Error::other(error.to_string())
+            })?;
+            let mut lines = Vec::new();
+            for log in &chunk {
+                serde_json::to_writer(&mut lines, &LogEntry::new(log, &events))?;
+                lines.push(b'\n');
+            }
+            Ok::<_, std::io::Error>(lines)
+        });
+        let mut response = warp::reply::Response::new(warp::hyper::Body::wrap_stream(lines));
+        response.headers_mut().insert(
+            warp::http::header::CONTENT_TYPE,
+            warp::http::HeaderValue::from_static(format::NDJSON_CONTENT_TYPE),
+        );
+        return Ok(response);
+    }
+    let logs = logs::fetch_logs(
+        provider.as_ref(),
+        filter,
+        query.from_block,
+        query.to_block,
+        &config,
+    )
+    .await?;
+    let body: Vec<LogEntry> = logs.iter().map(|log| LogEntry::new(log, &events)).collect();
+    Ok(warp::reply::json(&wrap_response(body, &envelope)).into_response())
+}
+
+/// Traces matching a block range and addresses, from the node's `trace_filter`.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_trace_filter() {
+/// let body = TraceFilterRequest { from_block: 1, to_block: 2, from_address: None, to_address: None };
+/// let response = get_trace_filter(body, provider, config, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_trace_filter(
+    body: TraceFilterRequest,
+    provider: Arc<dyn Provider>,
+    config: TraceConfig,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let traces = trace::trace_filter(provider.as_ref(), &body, &config).await?;
+    Ok(warp::reply::json(&wrap_response(traces, &envelope)))
+}
+
+/// Calls a function by name, encoding the arguments and decoding the outputs with the given ABI.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_call_fn() {
+/// let body = CallFnRequest { to, abi, function: "totalSupply".to_string(), args: vec![] };
+/// let response = call_fn(BlockId::latest(), body, provider, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn call_fn(
+    block_id: BlockId,
+    body: CallFnRequest,
     provider: Arc<dyn Provider>,
-) -> impl Filter<Extract = (Arc<dyn Provider>,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(move || provider.clone())
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let to = parse_address(&body.to)?;
+    let (function, input) = abi_call::encode_call(&body.abi, &body.function, &body.args)?;
+
+    info!(
+        "Calling {} on {} at block: {}",
+        function.signature(),
+        to,
+        block_id
+    );
+    let request = TransactionRequest::default()
+        .to(to)
+        .input(Bytes::from(input).into());
+    let output = provider
+        .call(&request)
+        .block(block_id)
+        .await
+        .map_err(AppError::from)?;
+
+    let body = CallFnResponse {
+        function: function.signature(),
+        outputs: abi_call::decode_output(function, &output)?,
+    };
+    Ok(warp::reply::json(&wrap_response(body, &envelope)))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Broadcasts a signed transaction.
+///
+/// Requests carrying an `Idempotency-Key` are broadcast once: replays of the
+/// same key and transaction within the TTL get the original tx hash back.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_send_raw() {
+/// let body = SendRawRequest { tx: encoded };
+/// let response = send_raw(Some("order-42".to_string()), body, provider, None, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn send_raw(
+    key: Option<String>,
+    body: SendRawRequest,
+    provider: Arc<dyn Provider>,
+    idempotency: Option<Arc<IdempotencyCache>>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    if body.tx.is_empty() {
+        return Err(AppError::InvalidRequest("empty transaction".to_string()).into());
+    }
+    if key
+        .as_ref()
+        .is_some_and(|key| key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LEN)
+    {
+        return Err(AppError::InvalidRequest(format!(
+            "Idempotency-Key must be 1 to {} characters",
+            MAX_IDEMPOTENCY_KEY_LEN
+        ))
+        .into());
+    }
+    let fingerprint = keccak256(&body.tx);
+    let idempotent = key.zip(idempotency.filter(|cache| cache.is_enabled()));
+
+    // Held until the broadcast is remembered, so a concurrent request with
+    // the same key replays it rather than broadcasting again.
+    let _reservation = match &idempotent {
+        Some((key, cache)) => Some(cache.reserve(key).await),
+        None => None,
+    };
+    if let Some((key, cache)) = &idempotent {
+        if let Some((sent, tx_hash)) = cache.get(key) {
+            if sent != fingerprint {
+                return Err(AppError::InvalidRequest(
+                    "Idempotency-Key was already used for another transaction".to_string(),
+                )
+                .into());
+            }
+            info!("Replaying broadcast of {} for idempotency key", tx_hash);
+            let response = SendRawResponse { tx_hash };
+            return Ok(warp::reply::json(&wrap_response(response, &envelope)));
+        }
+    }
+
+    let pending = provider
+        .send_raw_transaction(&body.tx)
+        .await
+        .map_err(AppError::from)?;
+    let tx_hash = *pending.tx_hash();
+    info!("Broadcast transaction {}", tx_hash);
+    if let Some((key, cache)) = idempotent {
+        cache.insert(key, (fingerprint, tx_hash));
+    }
+
+    let response = SendRawResponse { tx_hash };
+    Ok(warp::reply::json(&wrap_response(response, &envelope)))
+}
+
+/// Signs an EIP-712 typed data document with the configured signer.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_sign_typed() {
+/// let response = sign_typed(signer, typed_data, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn sign_typed(
+    signer: Arc<PrivateKeySigner>,
+    typed_data: TypedData,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let signed = signing::sign_typed_data(&signer, &typed_data)?;
+    info!("Signed typed data {}", signed.hash);
+    Ok(warp::reply::json(&wrap_response(signed, &envelope)))
+}
+
+/// Classify an address as an EOA, a contract, or a known proxy.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_address_type() {
+/// let address = "0x0000000000000000000000000000000000000000".to_string();
+/// let response = get_address_type(address, BlockId::latest(), provider.clone(), None, metrics, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_address_type(
+    address: String,
+    block_id: BlockId,
+    provider: Arc<dyn Provider>,
+    archive: Option<Arc<ArchiveRouter>>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let address_parsed = parse_address(&address)?;
+    metrics.record_address(address_parsed);
+    let (provider, served_by) = archive::route(archive.as_deref(), &provider, block_id).await?;
+
+    info!("Querying code for address: {}", address_parsed);
+    let code = provider
+        .get_code_at(address_parsed)
+        .block_id(block_id)
+        .await
+        .map_err(AppError::from)?;
+
+    // Only plain contracts need the extra EIP-1967 storage lookup.
+    let implementation_slot = if code.is_empty() || eip1167_implementation(&code).is_some() {
+        U256::ZERO
+    } else {
+        provider
+            .get_storage_at(address_parsed, EIP1967_IMPLEMENTATION_SLOT.into())
+            .block_id(block_id)
+            .await
+            .map_err(AppError::from)?
+    };
+
+    let body = classify_address(&code, implementation_slot);
+    info!("Classified address {} as {:?}", address_parsed, body.kind);
+    Ok(warp::reply::with_header(
+        warp::reply::json(&wrap_response(body, &envelope)),
+        SERVED_BY_HEADER,
+        served_by.as_str(),
+    ))
+}
+
+/// Implementation and admin addresses of an EIP-1967 proxy, read from its storage slots.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_proxy() {
+/// let address = "0x0000000000000000000000000000000000000000".to_string();
+/// let response = get_proxy(address, BlockId::latest(), provider.clone(), None, metrics, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_proxy(
+    address: String,
+    block_id: BlockId,
+    provider: Arc<dyn Provider>,
+    archive: Option<Arc<ArchiveRouter>>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let address_parsed = parse_address(&address)?;
+    metrics.record_address(address_parsed);
+    let (provider, served_by) = archive::route(archive.as_deref(), &provider, block_id).await?;
+
+    info!("Querying proxy slots of address: {}", address_parsed);
+    let (implementation_slot, admin_slot) = futures::try_join!(
+        provider
+            .get_storage_at(address_parsed, EIP1967_IMPLEMENTATION_SLOT.into())
+            .block_id(block_id)
+            .into_future(),
+        provider
+            .get_storage_at(address_parsed, EIP1967_ADMIN_SLOT.into())
+            .block_id(block_id)
+            .into_future()
+    )
+    .map_err(AppError::from)?;
+
+    let body = proxy_slots(implementation_slot, admin_slot);
+    Ok(warp::reply::with_header(
+        warp::reply::json(&wrap_response(body, &envelope)),
+        SERVED_BY_HEADER,
+        served_by.as_str(),
+    ))
+}
+
+/// First and last blocks an address was active in, estimated from its past
+/// nonces and balances on the archive node, if any.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_activity() {
+/// let address = "0x0000000000000000000000000000000000000000".to_string();
+/// let response = get_activity(address, provider.clone(), None, metrics, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_activity(
+    address: String,
+    provider: Arc<dyn Provider>,
+    archive: Option<Arc<ArchiveRouter>>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let address_parsed = parse_address(&address)?;
+    metrics.record_address(address_parsed);
+    // The search reads state at any block, so it goes to the archive node.
+    let (provider, served_by) =
+        archive::route(archive.as_deref(), &provider, BlockId::earliest()).await?;
+
+    info!("Searching the activity of address: {}", address_parsed);
+    let body = activity::activity(provider.as_ref(), address_parsed).await?;
+    Ok(warp::reply::with_header(
+        warp::reply::json(&wrap_response(body, &envelope)),
+        SERVED_BY_HEADER,
+        served_by.as_str(),
+    ))
+}
+
+/// Native balance, code size and ERC-20 balances of an address, fetched concurrently.
+///
+/// Token decimals of the default node are read through the node cache.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_contract() {
+/// let query = ContractQuery { tokens: Some(token) };
+/// let response = get_contract(address, BlockId::latest(), query, provider.clone(), None, Some(node_cache), metrics, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+// One argument per warp filter of the route.
+#[allow(clippy::too_many_arguments)]
+async fn get_contract(
+    address: String,
+    block_id: BlockId,
+    query: ContractQuery,
+    provider: Arc<dyn Provider>,
+    archive: Option<Arc<ArchiveRouter>>,
+    node_cache: Option<Arc<NodeCache>>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let address_parsed = parse_address(&address)?;
+    let tokens = query
+        .tokens
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .map(parse_address)
+        .collect::<Result<Vec<_>, _>>()?;
+    metrics.record_address(address_parsed);
+    let (provider, served_by) = archive::route(archive.as_deref(), &provider, block_id).await?;
+
+    info!(
+        "Querying contract {} with {} tokens at block: {}",
+        address_parsed,
+        tokens.len(),
+        block_id
+    );
+    let (balance, code, token_balances, token_decimals) = futures::join!(
+        provider
+            .get_balance(address_parsed)
+            .block_id(block_id)
+            .into_future(),
+        provider
+            .get_code_at(address_parsed)
+            .block_id(block_id)
+            .into_future(),
+        futures::future::try_join_all(tokens.iter().map(|&token| {
+            token::token_balance(provider.as_ref(), token, address_parsed, block_id)
+        })),
+        futures::future::try_join_all(tokens.iter().map(|&token| {
+            let (provider, node_cache) = (provider.clone(), node_cache.clone());
+            async move {
+                match node_cache {
+                    Some(cache) => cache.token_decimals(provider.as_ref(), token).await,
+                    None => token::token_decimals(provider.as_ref(), token).await,
+                }
+            }
+        })),
+    );
+    let code = code.map_err(AppError::from)?;
+    let tokens = tokens
+        .into_iter()
+        .zip(token_balances?)
+        .zip(token_decimals?)
+        .map(|((token, balance), decimals)| {
+            Ok(TokenBalance {
+                token,
+                balance: balance.to_string(),
+                decimals: decimals.decimals,
+                formatted: format_units(balance, decimals.decimals)
+                    .map_err(|error| AppError::Internal(error.to_string()))?,
+                decimals_defaulted: decimals.defaulted,
+            })
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    let body = ContractResponse {
+        is_contract: !code.is_empty(),
+        code_size: code.len(),
+        balance: balance.map_err(AppError::from)?.to_string(),
+        tokens,
+    };
+    Ok(warp::reply::with_header(
+        warp::reply::json(&wrap_response(body, &envelope)),
+        SERVED_BY_HEADER,
+        served_by.as_str(),
+    ))
+}
+
+/// Number of the block `block_id` refers to, looking tags and hashes up on the node.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_resolve_block_number() {
+/// let number = resolve_block_number(provider.as_ref(), BlockId::number(12)).await.unwrap();
+/// assert_eq!(number, 12);
+/// # }
+/// ```
+async fn resolve_block_number(provider: &dyn Provider, block_id: BlockId) -> Result<u64, AppError> {
+    if let BlockId::Number(alloy::eips::BlockNumberOrTag::Number(number)) = block_id {
+        return Ok(number);
+    }
+    let block = provider
+        .get_block(block_id, BlockTransactionsKind::Hashes)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| AppError::NotFound(format!("block {}", block_id)))?;
+    Ok(block.header.number)
+}
+
+/// Parses an Ethereum address from a path segment.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_parse_address() {
+/// let address = parse_address("0x0000000000000000000000000000000000000000").unwrap();
+/// # }
+/// ```
+fn parse_address(address: &str) -> Result<Address, Rejection> {
+    info!("Parsing address: {}", address);
+    address.parse::<Address>().map_err(|error| {
+        error!("Failed to parse address: {}", error);
+        AppError::InvalidAddress(address.to_string()).into()
+    })
+}
+
+/// Derives the address of an uncompressed public key: the last 20 bytes of the keccak256
+/// of its 64-byte `x || y` coordinates, with or without the leading `0x04` tag.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_parse_public_key() {
+/// let address = parse_public_key("0x0479be...b8").unwrap();
+/// # }
+/// ```
+fn parse_public_key(pubkey: &str) -> Result<Address, Rejection> {
+    let invalid = || AppError::InvalidRequest(format!("invalid public key {}", pubkey)).into();
+    let bytes = alloy_primitives::hex::decode(pubkey).map_err(|_| invalid())?;
+    let coordinates = match bytes.as_slice() {
+        [0x04, coordinates @ ..] if coordinates.len() == 64 => coordinates,
+        coordinates if coordinates.len() == 64 => coordinates,
+        _ => return Err(invalid()),
+    };
+    Ok(Address::from_raw_public_key(coordinates))
+}
+
+/// Parses a transaction hash from a path segment.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_parse_tx_hash() {
+/// let hash = parse_tx_hash("0x88e96d4537bea4d9c05d12549907b32561d3bf31f45aae734cdc119f13406cb6").unwrap();
+/// # }
+/// ```
+fn parse_tx_hash(hash: &str) -> Result<B256, Rejection> {
+    hash.parse::<B256>().map_err(|error| {
+        error!("Failed to parse transaction hash: {}", error);
+        AppError::InvalidRequest(format!("invalid transaction hash {}", hash)).into()
+    })
+}
+
+fn main() {
+    env_logger::init();
+
+    let worker_threads = runtime::get_worker_threads();
+    info!(
+        "Starting the runtime with {} worker threads",
+        worker_threads
+    );
+    let runtime = runtime::build_runtime(worker_threads).expect("Failed to build the runtime");
+    runtime.block_on(serve());
+}
+
+/// Sets up the providers and background tasks, then serves the API until shutdown.
+async fn serve() {
+    info!("Starting the Warp server...");
+    let _tracer_provider = telemetry::init_tracing();
+    let meter_provider = telemetry::init_metrics();
+
+    let breaker_config = circuit_breaker::get_circuit_breaker_config();
+    let breaker = Arc::new(CircuitBreaker::new(breaker_config));
+    let timings = Arc::new(RpcTimings::new());
+    let metrics = Arc::new(Metrics::new());
+    let supervisor = Arc::new(Supervisor::new(
+        supervisor::INITIAL_RESTART_BACKOFF,
+        supervisor::MAX_RESTART_BACKOFF,
+    ));
+    let concurrency = concurrency::get_concurrency_config().unwrap_or_else(|error| {
+        error!("{:#}", error);
+        std::process::exit(1);
+    });
+    let http_client = setup_http_client();
+    let provider = setup_provider(
+        http_client.clone(),
+        breaker.clone(),
+        timings.clone(),
+        metrics.clone(),
+        concurrency.limit_for(DEFAULT_CHAIN),
+    )
+    .await;
+
+    // Verify the node is reachable before accepting traffic.
+    let allow_degraded = config::env_flag("ALLOW_DEGRADED_START");
+    let probe_timeout = startup::get_startup_probe_timeout();
+    let readiness = match startup::warm_up(provider.as_ref(), probe_timeout, allow_degraded).await {
+        Ok(Some(_)) => {
+            // Open upstream connections ahead of the first requests.
+            let connections = startup::get_warmup_connections();
+            if connections > 0 {
+                startup::prime_connections(provider.as_ref(), connections, probe_timeout).await;
+            }
+            Arc::new(Readiness::new(true))
+        }
+        Ok(None) => {
+            // Serve `503` on read routes until the node answers.
+            let readiness = Arc::new(Readiness::new(false));
+            let (provider, retry_interval) = (
+                provider.clone(),
+                startup::get_startup_probe_retry_interval(),
+            );
+            let waiting = readiness.clone();
+            supervisor.spawn("startup-probe", move || {
+                startup::wait_until_ready(
+                    provider.clone(),
+                    probe_timeout,
+                    retry_interval,
+                    waiting.clone(),
+                )
+            });
+            readiness
+        }
+        Err(error) => {
+            error!("{:#}", error);
+            std::process::exit(1);
+        }
+    };
+
+    // Register every configured chain alongside the default provider.
+    let mut chains = vec![Chain::new(DEFAULT_CHAIN, provider.clone())];
+    let chains_config = chains::get_chains_config().unwrap_or_else(|error| {
+        error!("Invalid CHAINS configuration: {:#}", error);
+        std::process::exit(1);
+    });
+    for (name, url) in chains_config {
+        let limit = concurrency.limit_for(&name);
+        let provider = build_provider(
+            &url,
+            http_client.clone(),
+            Arc::new(CircuitBreaker::new(breaker_config)),
+            timings.clone(),
+            metrics.clone(),
+            limit,
+        )
+        .unwrap_or_else(|error| {
+            error!("Chain {}: {:#}", name, error);
+            std::process::exit(1);
+        });
+        chains.push(Chain::new(name, provider));
+    }
+    let chains = Arc::new(ChainRegistry::new(chains));
+    let ready_policy = chains::get_ready_policy(chains.chains().len());
+    let health_check_interval = chains::get_health_check_interval().unwrap_or_else(|error| {
+        error!("{:#}", error);
+        std::process::exit(1);
+    });
+    let checked = chains.clone();
+    supervisor.spawn("chain-health-checks", move || {
+        checked.clone().run_health_checks(health_check_interval)
+    });
+
+    // Rate limit clients per IP, sweeping idle clients in the background.
+    let rate_limit_config = rate_limit::get_rate_limit_config().unwrap_or_else(|error| {
+        error!("{:#}", error);
+        std::process::exit(1);
+    });
+    let rate_limiter = rate_limit_config.map(|config| {
+        let limiter = Arc::new(RateLimiter::new(config.per_minute, config.burst));
+        let swept = limiter.clone();
+        supervisor.spawn("rate-limit-sweeper", move || {
+            swept
+                .clone()
+                .run_sweeper(config.sweep_interval, config.idle_ttl)
+        });
+        limiter
+    });
+
+    // Settings from the optional config file, re-applied by `/admin/reload`.
+    let config_file = reload::get_config_file();
+    let file_config = match config_file.as_deref().map(reload::load_config_file) {
+        Some(Ok(file_config)) => file_config,
+        Some(Err(error)) => {
+            error!("{:#}", error);
+            std::process::exit(1);
+        }
+        None => reload::FileConfig::default(),
+    };
+    let bind_addr = file_config.bind_addr.unwrap_or(reload::DEFAULT_BIND_ADDR);
+    let balance_cache = cache::get_balance_cache().await.unwrap_or_else(|error| {
+        error!("{:#}", error);
+        std::process::exit(1);
+    });
+    let node_cache = Arc::new(NodeCache::new(node_cache::get_gas_price_ttl()));
+    if !node_cache.gas_price_ttl().is_zero() {
+        let (refreshed, provider) = (node_cache.clone(), provider.clone());
+        supervisor.spawn("gas-price-refresh", move || {
+            refreshed.clone().run_gas_price_refresh(provider.clone())
+        });
+    }
+    let reloader = config_file.map(|path| {
+        let reloader = Reloader::new(path, bind_addr, rate_limiter.clone(), balance_cache.clone());
+        if let Err(error) = reloader.apply(&file_config) {
+            error!("{}", error);
+            std::process::exit(1);
+        }
+        Arc::new(reloader)
+    });
+
+    // Send historical queries to the archive node, if there is one.
+    let archive = archive::get_archive_config().map(|config| {
+        info!(
+            "Using archive node for blocks older than {} blocks",
+            config.threshold
+        );
+        let provider = build_provider(
+            &config.url,
+            http_client.clone(),
+            Arc::new(CircuitBreaker::new(breaker_config)),
+            timings.clone(),
+            metrics.clone(),
+            concurrency.default,
+        )
+        .unwrap_or_else(|error| {
+            error!("ARCHIVE_RPC_URL: {:#}", error);
+            std::process::exit(1);
+        });
+        Arc::new(ArchiveRouter::new(provider, config.threshold))
+    });
+
+    // Buffer the node's pending transactions for `/pending/poll`.
+    let pending_config = pending::get_pending_config().unwrap_or_else(|error| {
+        error!("{:#}", error);
+        std::process::exit(1);
+    });
+    let pending = pending_config.map(|config| {
+        let buffer = Arc::new(PendingBuffer::new(config.capacity));
+        let (filled, provider) = (buffer.clone(), provider.clone());
+        supervisor.spawn("pending-poller", move || {
+            PendingPoller::new(filled.clone()).run(provider.clone(), config.poll_interval)
+        });
+        buffer
+    });
+
+    // Stream balances on every new head seen over the websocket endpoint.
+    let streams = match stream::get_stream_config() {
+        Some(config) => match ProviderBuilder::new()
+            .on_ws(WsConnect::new(config.ws_url))
+            .await
+        {
+            Ok(ws) => {
+                let streams = Arc::new(BalanceStreams::new(config.max_streams));
+                let (subscribed, ws): (_, Arc<dyn Provider>) = (streams.clone(), Arc::new(ws));
+                supervisor.spawn("balance-streams", move || {
+                    subscribed.clone().run_subscription(ws.clone())
+                });
+                Some(streams)
+            }
+            Err(error) => {
+                error!(
+                    "Failed to connect to WS_RPC_URL, balance streams are disabled: {}",
+                    error
+                );
+                None
+            }
+        },
+        None => None,
+    };
+
+    let state = AppState {
+        envelope: envelope::get_response_envelope(),
+        chains,
+        ready_policy,
+        rate_limiter,
+        daily_quotas: quota::get_daily_quotas()
+            .unwrap_or_else(|error| {
+                error!("{:#}", error);
+                std::process::exit(1);
+            })
+            .map(Arc::new),
+        inflight_limit: inflight_limit::get_inflight_limit().map(Arc::new),
+        request_deadline: deadline::get_request_deadline(),
+        query_limits: query_limits::get_query_limits(),
+        route_flags: route_flags::get_route_flags().unwrap_or_else(|error| {
+            error!("{:#}", error);
+            std::process::exit(1);
+        }),
+        route_prefix: route_prefix::get_route_prefix().unwrap_or_else(|error| {
+            error!("{:#}", error);
+            std::process::exit(1);
+        }),
+        ui: ui::get_ui_config(),
+        breaker: Some(breaker),
+        upstreams: Some(Arc::new(UpstreamAllowlist::new(
+            upstream::get_rpc_url_allowlist(),
+            Box::new({
+                let (timings, metrics) = (timings.clone(), metrics.clone());
+                let (client, limit) = (http_client.clone(), concurrency.default);
+                move |url| {
+                    build_provider(
+                        url,
+                        client.clone(),
+                        Arc::new(CircuitBreaker::new(breaker_config)),
+                        timings.clone(),
+                        metrics.clone(),
+                        limit,
+                    )
+                }
+            }),
+        ))),
+        archive,
+        pending,
+        readiness,
+        balance_cache,
+        node_cache,
+        not_found_cache: Arc::new(NotFoundCache::new(cache::get_not_found_ttl())),
+        idempotency_cache: Arc::new(IdempotencyCache::new(cache::get_idempotency_ttl())),
+        admin_token: env::var("ADMIN_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty()),
+        reloader,
+        maintenance: Arc::new(maintenance::get_maintenance()),
+        error_reporter: error_report::get_error_report_url()
+            .map(|url| Arc::new(ErrorReporter::new(url))),
+        streams,
+        logs: logs::get_logs_config(),
+        events: Arc::new(events::get_event_registry().unwrap_or_else(|error| {
+            error!("{:#}", error);
+            std::process::exit(1);
+        })),
+        trace: trace::get_trace_config(),
+        metrics,
+        timings,
+        signer: signing::get_signer()
+            .unwrap_or_else(|error| {
+                error!("{:#}", error);
+                std::process::exit(1);
+            })
+            .map(Arc::new),
+        price_feed: price_feed::get_price_feed().map(Arc::new),
+        request_metrics: meter_provider
+            .as_ref()
+            .map(|_| Arc::new(RequestMetrics::new(&global::meter("rust-alloy")))),
+        supervisor,
+        log_sampler: Arc::new(log_sampling::get_log_sampler().unwrap_or_else(|error| {
+            error!("{:#}", error);
+            std::process::exit(1);
+        })),
+        sensitive_headers: Arc::new(header_scrub::get_sensitive_headers().unwrap_or_else(
+            |error| {
+                error!("{:#}", error);
+                std::process::exit(1);
+            },
+        )),
+        ..AppState::new(provider)
+    };
+
+    // Set up CORS and routes
+    let cors = setup_cors();
+    let routes = setup_routes(state).with(cors);
+
+    let server_config = server::get_server_config().unwrap_or_else(|error| {
+        error!("{:#}", error);
+        std::process::exit(1);
+    });
+    let listeners = server::bind(bind_addr, &server_config)
+        .await
+        .unwrap_or_else(|error| {
+            error!("{:#}", error);
+            std::process::exit(1);
+        });
+    for (listener, config) in &listeners {
+        let scheme = if config.tls.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        let addr = listener.local_addr().map_or(bind_addr, |addr| addr);
+        println!("Server starting on {}://{}", scheme, addr);
+    }
+    if let Err(error) = server::serve_all(routes, listeners).await {
+        error!("{:#}", error);
+        std::process::exit(1);
+    }
+}
+
+/// Builds the reqwest client shared by every upstream provider, from the
+/// environment, so its settings and certificate are read once at startup.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_setup_http_client() {
+/// let client = setup_http_client();
+/// # }
+/// ```
+fn setup_http_client() -> reqwest::Client {
+    let client = http_client::get_http_client_config().and_then(|config| {
+        // Present a client certificate when the upstream requires mutual TLS.
+        if let Some(tls) = &config.tls {
+            info!("Using RPC client certificate: {}", tls.cert_path);
+        }
+        if let Some(client_id) = &config.client_id {
+            info!("Identifying to the RPC provider as: {}", client_id);
+        }
+        if !config.headers.0.is_empty() {
+            // Header values often carry credentials, so only their names are logged.
+            info!(
+                "Sending custom RPC headers: {}",
+                config.headers.names().join(", ")
+            );
+        }
+        http_client::build_http_client(&config)
+    });
+    client.unwrap_or_else(|error| {
+        error!("{:#}", error);
+        std::process::exit(1);
+    })
+}
+
+/// Sets up the Ethereum provider.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_setup_provider() {
+/// let breaker = Arc::new(CircuitBreaker::new(get_circuit_breaker_config()));
+/// let provider = setup_provider(setup_http_client(), breaker, Arc::new(RpcTimings::new()), Arc::new(Metrics::new()), None).await;
+/// # }
+/// ```
+async fn setup_provider(
+    client: reqwest::Client,
+    breaker: Arc<CircuitBreaker>,
+    timings: Arc<RpcTimings>,
+    metrics: Arc<Metrics>,
+    concurrency: Option<usize>,
+) -> Arc<dyn Provider> {
+    get_ethereum_rpc_url()
+        .and_then(|ethereum_rpc_url| {
+            build_provider(
+                &ethereum_rpc_url,
+                client,
+                breaker,
+                timings,
+                metrics,
+                concurrency,
+            )
+        })
+        .unwrap_or_else(|error| {
+            error!("{:#}", error);
+            std::process::exit(1);
+        })
+}
+
+/// Builds an HTTP provider for the given RPC URL on `client`, guarded by the given circuit breaker,
+/// whose calls are timed into `timings` and whose retries are counted into `metrics`.
+/// At most `concurrency` of its node requests run at once, if set.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_build_provider() {
+/// let breaker = Arc::new(CircuitBreaker::new(get_circuit_breaker_config()));
+/// let client = build_http_client(&HttpClientConfig::default()).unwrap();
+/// let provider = build_provider("http://localhost:8545", client, breaker, Arc::new(RpcTimings::new()), Arc::new(Metrics::new()), Some(16)).unwrap();
+/// # }
+/// ```
+fn build_provider(
+    rpc_url: &str,
+    client: reqwest::Client,
+    breaker: Arc<CircuitBreaker>,
+    timings: Arc<RpcTimings>,
+    metrics: Arc<Metrics>,
+    concurrency: Option<usize>,
+) -> anyhow::Result<Arc<dyn Provider>> {
+    // Only the parse error is reported, since RPC URLs often carry an API key.
+    let url = Url::parse(rpc_url).map_err(|error| anyhow::anyhow!("Invalid RPC URL: {}", error))?;
+
+    let transport = Http::with_client(client, url);
+    let is_local = transport.guess_local();
+    let builder = ProviderBuilder::new();
+    // Cache hits are answered before reaching the circuit breaker.
+    let cache = provider_cache::get_provider_cache_config()
+        .map(|config| Arc::new(ProviderCache::new(config)));
+    // Cache hits take no node time, so they are not timed; every retry is.
+    let client = ClientBuilder::default()
+        .layer(ProviderCacheLayer::new(cache))
+        // Cache hits take no slot; a slot is held across all retries of a request.
+        .layer(ConcurrencyLayer::new(concurrency))
+        .layer(RetryLayer::new(retry::get_retry_config(), metrics))
+        .layer(ServerTimingLayer::new(timings))
+        .layer(CircuitBreakerLayer::new(breaker))
+        .transport(transport, is_local);
+    let provider = builder.on_client(client);
+    Ok(Arc::new(provider))
+}
+
+/// Node used when `ETHEREUM_RPC_URL` is unset, unless `REQUIRE_RPC_URL` is set.
+const DEFAULT_RPC_URL: &str = "http://localhost:8545";
+
+/// Retrieves the Ethereum RPC URL from the environment variables.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_ethereum_rpc_url() {
+/// let url = get_ethereum_rpc_url().unwrap();
+/// assert!(!url.is_empty());
+/// # }
+/// ```
+fn get_ethereum_rpc_url() -> anyhow::Result<String> {
+    resolve_rpc_url(
+        env::var("ETHEREUM_RPC_URL").ok(),
+        config::env_flag("REQUIRE_RPC_URL"),
+    )
+}
+
+/// Picks the RPC URL, falling back to the local node only when `required` is off.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_resolve_rpc_url() {
+/// assert!(resolve_rpc_url(None, true).is_err());
+/// # }
+/// ```
+fn resolve_rpc_url(url: Option<String>, required: bool) -> anyhow::Result<String> {
+    match url.filter(|url| !url.trim().is_empty()) {
+        Some(url) => Ok(url),
+        None if required => {
+            anyhow::bail!("ETHEREUM_RPC_URL must be set when REQUIRE_RPC_URL is enabled")
+        }
+        None => {
+            error!("ETHEREUM_RPC_URL not set, using default");
+            Ok(DEFAULT_RPC_URL.to_string())
+        }
+    }
+}
+
+/// Configures CORS for the server.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_setup_cors() {
+/// let cors = setup_cors();
+/// assert!(cors.is_some());
+/// # }
+/// ```
+fn setup_cors() -> warp::cors::Builder {
+    warp::cors()
+        .allow_any_origin()
+        .allow_methods(vec!["GET", "POST", "PUT", "DELETE"])
+        .allow_headers(vec![
+            "Content-Type",
+            "Content-Encoding",
+            "Authorization",
+            "X-API-Key",
+            "X-RPC-URL",
+            "X-Request-Id",
+            "Idempotency-Key",
+        ])
+}
+
+/// Health check route.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_health_check() {
+/// let response = health_check().await;
+/// assert_eq!(response, "OK");
+/// # }
+/// ```
+async fn health_check() -> Result<impl Reply, Rejection> {
+    Ok("OK")
+}
+
+/// Runtime statistics route.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_stats() {
+/// let metrics = Arc::new(Metrics::new());
+/// let response = get_stats(metrics, None, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_stats(
+    metrics: Arc<Metrics>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    supervisor: Arc<Supervisor>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let mut snapshot = metrics.snapshot();
+    snapshot.circuit_breaker = breaker.map(|breaker| breaker.state());
+    snapshot.background_tasks = supervisor.tasks();
+    Ok(warp::reply::json(&wrap_response(snapshot, &envelope)))
+}
+
+/// Re-reads the config file and returns the settings now in effect.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_reload_config() {
+/// let response = reload_config(reloader, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn reload_config(
+    reloader: Arc<Reloader>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let effective = reloader.reload()?;
+    Ok(warp::reply::json(&wrap_response(effective, &envelope)))
+}
+
+/// Turns maintenance mode on or off, or flips it, returning the new state.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_set_maintenance() {
+/// let query = MaintenanceQuery { enabled: Some(true) };
+/// let response = set_maintenance(query, maintenance, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn set_maintenance(
+    query: MaintenanceQuery,
+    maintenance: Arc<Maintenance>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let enabled = maintenance.set(query.enabled);
+    info!("Maintenance mode {}", if enabled { "on" } else { "off" });
+    Ok(warp::reply::json(&wrap_response(
+        MaintenanceResponse {
+            maintenance: enabled,
+        },
+        &envelope,
+    )))
+}
+
+/// Validates an address and its EIP-55 checksum, without querying the node.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_validate() {
+/// let address = "0xd8da6bf26964af9d7eed9e03e53415d37aa96045".to_string();
+/// let response = validate(address, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn validate(address: String, envelope: Envelope) -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::json(&wrap_response(
+        validate::validate_address(&address),
+        &envelope,
+    )))
+}
+
+/// Most queried addresses route.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_top_addresses() {
+/// let metrics = Arc::new(Metrics::new());
+/// let query = TopAddressesQuery { limit: None };
+/// let response = get_top_addresses(query, metrics, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_top_addresses(
+    query: TopAddressesQuery,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let limit = query.limit.unwrap_or(DEFAULT_TOP_ADDRESSES);
+    Ok(warp::reply::json(&wrap_response(
+        metrics.top_addresses(limit),
+        &envelope,
+    )))
+}
+
+/// Pending transaction hashes seen since `?cursor=`, waiting up to `?wait=` seconds for new ones.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_pending() {
+/// let query = PendingQuery { cursor: None, wait: None };
+/// let response = get_pending(query, buffer, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_pending(
+    query: PendingQuery,
+    pending: Arc<PendingBuffer>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let wait = Duration::from_secs(query.wait.unwrap_or(0).min(MAX_PENDING_WAIT_SECS));
+    let page = pending.wait_since(query.cursor, wait).await;
+    Ok(warp::reply::json(&wrap_response(page, &envelope)))
+}
+
+/// Prometheus metrics route.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_metrics() {
+/// let metrics = Arc::new(Metrics::new());
+/// let response = get_metrics(metrics).await.unwrap();
+/// # }
+/// ```
+async fn get_metrics(metrics: Arc<Metrics>) -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::with_header(
+        metrics.render_prometheus(),
+        "Content-Type",
+        "text/plain; version=0.0.4",
+    ))
+}
+
+/// Lists the configured chains with their cached health.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_chains() {
+/// let response = get_chains(chains, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_chains(
+    chains: Arc<ChainRegistry>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let body: Vec<_> = chains.chains().iter().map(Chain::info).collect();
+    Ok(warp::reply::json(&wrap_response(body, &envelope)))
+}
+
+/// Describes the optional features, the chains and the endpoints this server serves.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_capabilities() {
+/// let response = get_capabilities(capabilities, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_capabilities(
+    capabilities: Arc<Capabilities>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::json(&wrap_response(
+        capabilities.as_ref(),
+        &envelope,
+    )))
+}
+
+/// Latest balance of a CAIP-10 account, read from the configured chain with its chain id.
+///
+/// Chains are matched by the id from their last health check, so a chain is
+/// only served once it has answered one.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_caip_balance() {
+/// let account = "eip155:1:0x0000000000000000000000000000000000000000".to_string();
+/// let response = get_caip_balance(account, chains, metrics, Envelope::Bare, Format::Json).await.unwrap();
+/// # }
+/// ```
+async fn get_caip_balance(
+    account: String,
+    chains: Arc<ChainRegistry>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+    format: Format,
+) -> Result<impl Reply, Rejection> {
+    let account: AccountId = account.parse()?;
+    let chain = chains.by_chain_id(account.chain_id).ok_or_else(|| {
+        AppError::InvalidRequest(format!("chain {} is not configured", account.chain_id))
+    })?;
+    metrics.record_address(account.address);
+
+    info!(
+        "Querying balance for {} on chain {}",
+        account.address, chain.name
+    );
+    let balance = chain
+        .provider
+        .get_balance(account.address)
+        .await
+        .map_err(AppError::from)?;
+    let body = CaipBalanceResponse {
+        account: account.to_string(),
+        chain: chain.name.clone(),
+        balance: balance.to_string(),
+    };
+    Ok(negotiate_and_serialize(
+        &wrap_response(body, &envelope),
+        format,
+    )?)
+}
+
+/// Queries the latest balance of an address on every configured chain at
+/// once, by chain name; a failing chain gets an error field instead of
+/// failing the whole response.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_multichain_balance() {
+/// let address = "0x0000000000000000000000000000000000000000".to_string();
+/// let response = get_multichain_balance(address, chains, metrics, Envelope::Bare).await.unwrap();
+/// # }
+/// ```
+async fn get_multichain_balance(
+    address: String,
+    chains: Arc<ChainRegistry>,
+    metrics: Arc<Metrics>,
+    envelope: Envelope,
+) -> Result<impl Reply, Rejection> {
+    let address = parse_address(&address)?;
+    metrics.record_address(address);
+
+    info!(
+        "Querying balance for {} on {} chains",
+        address,
+        chains.chains().len()
+    );
+    let balances = futures::future::join_all(chains.chains().iter().map(|chain| async move {
+        let balance = match chain.provider.get_balance(address).await {
+            Ok(balance) => ChainBalance::Balance(balance.to_string()),
+            Err(error) => {
+                let error = AppError::from(error);
+                warn!("Balance on chain {} failed: {}", chain.name, error);
+                ChainBalance::Error {
+                    error: error.to_string(),
+                }
+            }
+        };
+        (chain.name.clone(), balance)
+    }))
+    .await;
+    let body: BTreeMap<String, ChainBalance> = balances.into_iter().collect();
+    Ok(warp::reply::json(&wrap_response(body, &envelope)))
+}
+
+/// Reports whether enough chains are reachable, with a per-chain breakdown;
+/// answers 503 when the ready policy is not met.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_ready() {
+/// let response = get_ready(chains, ReadyPolicy::All).await.unwrap();
+/// # }
+/// ```
+async fn get_ready(
+    chains: Arc<ChainRegistry>,
+    policy: ReadyPolicy,
+) -> Result<impl Reply, Rejection> {
+    let report = chains.ready_report(policy);
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&report), status))
+}
+
+/// Lists the endpoints served, or sends browsers to the web UI when it is enabled.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_get_index() {
+/// let index = Arc::new(Index::new(&RouteFlags::default(), false, &RoutePrefix::default()));
+/// let response = get_index(index, None).await.unwrap();
+/// # }
+/// ```
+async fn get_index(
+    index: Arc<Index>,
+    ui: Option<String>,
+) -> Result<warp::reply::Response, Rejection> {
+    if let Some(ui) = ui {
+        let uri = warp::http::Uri::try_from(ui)
+            .map_err(|error| AppError::Internal(format!("Invalid UI path: {}", error)))?;
+        return Ok(warp::redirect::temporary(uri).into_response());
+    }
+    Ok(warp::reply::json(index.as_ref()).into_response())
+}
+
+/// Sets up the routes for the server.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_setup_routes() {
+/// let provider = Arc::new(...); // Mock or create a provider
+/// let routes = setup_routes(AppState::new(provider));
+/// assert!(routes.is_some());
+/// # }
+/// ```
+fn setup_routes(state: AppState) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+    let AppState {
+        provider,
+        metrics,
+        envelope,
+        chains,
+        ready_policy,
+        rate_limiter,
+        daily_quotas,
+        inflight_limit,
+        request_deadline,
+        query_limits,
+        route_flags,
+        route_prefix,
+        ui,
+        breaker,
+        upstreams,
+        archive,
+        pending,
+        readiness,
+        balance_cache,
+        node_cache,
+        not_found_cache,
+        idempotency_cache,
+        admin_token,
+        reloader,
+        maintenance,
+        error_reporter,
+        streams,
+        logs,
+        events,
+        trace,
+        timings,
+        signer,
+        price_feed,
+        block_timestamps,
+        tracer,
+        request_metrics,
+        supervisor,
+        log_sampler,
+        sensitive_headers,
+    } = state;
+
+    let index = Arc::new(Index::new(&route_flags, ui.is_some(), &route_prefix));
+    let endpoints = index.clone();
+    let features = Features {
+        balance_streams: streams.is_some(),
+        pending_transactions: pending.is_some(),
+        trace_routes: trace.is_some(),
+        signing: signer.is_some(),
+        fiat_prices: price_feed.is_some(),
+        archive: archive.is_some(),
+        admin: admin_token.is_some(),
+        config_reload: reloader.is_some(),
+        ui: ui.is_some(),
+    };
+    let chain_names = chains
+        .chains()
+        .iter()
+        .map(|chain| chain.name.clone())
+        .collect();
+    let capabilities = Arc::new(Capabilities::new(
+        features,
+        chain_names,
+        &index,
+        &route_prefix,
+    ));
+    let ui_path = ui.as_ref().map(|_| route_prefix.mounted("/ui"));
+    let index_route = warp::path::end()
+        .and(warp::get())
+        .and(warp::any().map(move || index.clone()))
+        .and(warp::any().map(move || ui_path.clone()))
+        .and_then(get_index);
+
+    let health_route = warp::path!("health")
+        .and(warp::get())
+        .and_then(health_check);
+
+    let ready_route = warp::path!("ready")
+        .and(warp::get())
+        .and(with_chains(chains.clone()))
+        .and(warp::any().map(move || ready_policy))
+        .and_then(get_ready);
+
+    let admin_reload_route = warp::path!("admin" / "reload")
+        .and(warp::post())
+        .and(with_admin(admin_token.clone(), reloader))
+        .and(with_envelope(envelope))
+        .and_then(reload_config);
+
+    let admin_maintenance_route = warp::path!("admin" / "maintenance")
+        .and(warp::post())
+        .and(warp::query::<MaintenanceQuery>())
+        .and(with_admin_token(admin_token))
+        .and(with_maintenance_toggle(maintenance.clone()))
+        .and(with_envelope(envelope))
+        .and_then(set_maintenance);
+
+    let stats_route = warp::path!("stats")
+        .and(warp::get())
+        .and(with_metrics(metrics.clone()))
+        .and(with_breaker(breaker))
+        .and(with_supervisor(supervisor))
+        .and(with_envelope(envelope))
+        .and_then(get_stats);
+
+    let top_addresses_route = warp::path!("stats" / "top-addresses")
+        .and(warp::get())
+        .and(warp::query::<TopAddressesQuery>())
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_top_addresses);
+
+    let pending_route = warp::path!("pending" / "poll")
+        .and(warp::get())
+        .and(warp::query::<PendingQuery>())
+        .and(with_pending(pending))
+        .and(with_envelope(envelope))
+        .and_then(get_pending);
+
+    let validate_route = warp::path!("validate" / String)
+        .and(warp::get())
+        .and(with_envelope(envelope))
+        .and_then(validate);
+
+    let metrics_route = warp::path!("metrics")
+        .and(warp::get())
+        .and(with_metrics(metrics.clone()))
+        .and_then(get_metrics);
+
+    let caip_balance_route = warp::path!("caip" / "balance" / String)
+        .and(warp::get())
+        .and(with_chains(chains.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and(with_format())
+        .and_then(get_caip_balance);
+
+    let multichain_balance_route = warp::path!("multichain" / "balance" / String)
+        .and(warp::get())
+        .and(with_chains(chains.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_multichain_balance);
+
+    let chains_route = warp::path!("chains")
+        .and(warp::get())
+        .and(with_chains(chains))
+        .and(with_envelope(envelope))
+        .and_then(get_chains);
+
+    let capabilities_route = warp::path!("capabilities")
+        .and(warp::get())
+        .and(warp::any().map(move || capabilities.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_capabilities);
+
+    let balance_route = warp::path!("balance" / String)
+        .and(warp::get())
+        .and(block_id_query())
+        .and(warp::query::<BalanceQuery>())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_archive(archive.clone()))
+        .and(with_cache(balance_cache))
+        .and({
+            let signer = signer.clone();
+            warp::any().map(move || signer.clone())
+        })
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and(with_format())
+        .and_then(get_balance);
+
+    let balance_by_pubkey_route = warp::path!("balance-by-pubkey" / String)
+        .and(warp::get())
+        .and(block_id_query())
+        .and(warp::query::<BalanceQuery>())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_archive(archive.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and(with_format())
+        .and_then(get_balance_by_pubkey);
+
+    // Heads come from the default node, so `X-RPC-URL` is not honored here.
+    let stream_provider = provider.clone();
+    let balance_stream_route = warp::path!("balance" / String / "stream")
+        .and(warp::get())
+        .and(warp::any().map(move || stream_provider.clone()))
+        .and(with_streams(streams))
+        .and(with_metrics(metrics.clone()))
+        .and_then(get_balance_stream);
+
+    let balance_tags_route = warp::path!("balance" / String / "tags")
+        .and(warp::get())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and(with_format())
+        .and_then(get_balance_tags);
+
+    let balance_fiat_route = warp::path!("balance" / String / "fiat")
+        .and(warp::get())
+        .and(warp::query::<FiatQuery>())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_price_feed(price_feed))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and(with_format())
+        .and_then(get_balance_fiat);
+
+    let balance_at_route = warp::path!("balance" / String / "at")
+        .and(warp::get())
+        .and(warp::query::<TimestampQuery>())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_archive(archive.clone()))
+        .and(warp::any().map(move || block_timestamps.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and(with_format())
+        .and_then(get_balance_at);
+
+    let balance_batch_text_route = warp::path!("balance" / "batch")
+        .and(warp::post())
+        .and(with_content_type("text/plain"))
+        .and(block_id_query())
+        .and(request_body::bytes(MAX_BODY_BYTES))
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_archive(archive.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and_then(get_balance_batch_text);
+
+    let balance_batch_route = warp::path!("balance" / "batch")
+        .and(warp::post())
+        .and(block_id_query())
+        .and(validated_json(MAX_BODY_BYTES))
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_archive(archive.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and(with_format())
+        .and_then(get_balance_batch);
+
+    let contract_route = warp::path!("contract" / String)
+        .and(warp::get())
+        .and(block_id_query())
+        .and(warp::query::<ContractQuery>())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_archive(archive.clone()))
+        .and(with_node_cache(node_cache.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_contract);
+
+    let block_route = warp::path!("block" / String)
+        .and(warp::get())
+        .and(warp::query::<EncodingQuery>())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_block);
+
+    let transaction_route = warp::path!("tx" / String)
+        .and(warp::get())
+        .and(warp::query::<EncodingQuery>())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_cache(not_found_cache.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_transaction);
+
+    let tx_status_route = warp::path!("tx" / String / "status")
+        .and(warp::get())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_tx_status);
+
+    let tx_decode_route = warp::path!("tx" / String / "decode")
+        .and(warp::post())
+        .and(validated_json(MAX_CALL_FN_BODY_BYTES))
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_envelope(envelope))
+        .and_then(decode_transaction_input);
+
+    let receipt_route = warp::path!("receipt" / String)
+        .and(warp::get())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_cache(not_found_cache))
+        .and(with_envelope(envelope))
+        .and_then(get_receipt);
+
+    let receipts_route = warp::path!("receipts")
+        .and(warp::post())
+        .and(validated_json(MAX_BODY_BYTES))
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_receipts);
+
+    let logs_route = warp::path!("logs")
+        .and(warp::get())
+        .and(warp::query::<LogsQuery>())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(warp::any().map(move || logs))
+        .and(warp::any().map(move || events.clone()))
+        .and(with_envelope(envelope))
+        .and(warp::header::optional::<String>("accept"))
+        .and_then(get_logs);
+
+    let trace_filter_route = warp::path!("trace-filter")
+        .and(warp::post())
+        .and(request_body::json(MAX_BODY_BYTES))
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_trace(trace))
+        .and(with_envelope(envelope))
+        .and_then(get_trace_filter);
+
+    let call_fn_route = warp::path!("call-fn")
+        .and(warp::post())
+        .and(block_id_query())
+        .and(validated_json(MAX_CALL_FN_BODY_BYTES))
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_envelope(envelope))
+        .and_then(call_fn);
+
+    let send_raw_route = warp::path!("send-raw")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("idempotency-key"))
+        .and(request_body::json(MAX_RAW_TX_BODY_BYTES))
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_cache(idempotency_cache))
+        .and(with_envelope(envelope))
+        .and_then(send_raw);
+
+    let sign_typed_route = warp::path!("sign-typed")
+        .and(warp::post())
+        .and(with_signer(signer))
+        .and(request_body::json(MAX_BODY_BYTES))
+        .and(with_envelope(envelope))
+        .and_then(sign_typed);
+
+    let xpub_balances_route = warp::path!("xpub" / String / "balances")
+        .and(warp::get())
+        .and(block_id_query())
+        .and(warp::query::<XpubQuery>())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_archive(archive.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_xpub_balances);
+
+    let nonce_route = warp::path!("nonce" / String)
+        .and(warp::get())
+        .and(block_id_query())
+        .and(warp::query::<QuantityQuery>())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_archive(archive.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and(with_format())
+        .and_then(get_nonce);
+
+    let nonce_gap_route = warp::path!("nonce" / String / "gap")
+        .and(warp::get())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_nonce_gap);
+
+    let gas_price_route = warp::path!("gas-price")
+        .and(warp::get())
+        .and(warp::query::<AmountQuery>())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_node_cache(node_cache.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_gas_price);
+
+    let chain_id_route = warp::path!("chain-id")
+        .and(warp::get())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_node_cache(node_cache))
+        .and(with_envelope(envelope))
+        .and_then(get_chain_id);
+
+    let node_status_route = warp::path!("node-status")
+        .and(warp::get())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_node_status);
+
+    let fee_history_route = warp::path!("fee-history")
+        .and(warp::get())
+        .and(warp::query::<FeeHistoryQuery>())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_fee_history);
+
+    let address_type_route = warp::path!("type" / String)
+        .and(warp::get())
+        .and(block_id_query())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_archive(archive.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_address_type);
+
+    let proxy_route = warp::path!("proxy" / String)
+        .and(warp::get())
+        .and(block_id_query())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_archive(archive.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_proxy);
+
+    let activity_route = warp::path!("activity" / String)
+        .and(warp::get())
+        .and(with_provider(provider.clone(), upstreams.clone()))
+        .and(with_archive(archive.clone()))
+        .and(with_metrics(metrics.clone()))
+        .and(with_envelope(envelope))
+        .and_then(get_activity);
+
+    // Boxed to keep the type of the combined filter within the compiler's
+    // recursion limit.
+    let balance_routes = balance_route
+        .or(balance_tags_route)
+        .or(balance_fiat_route)
+        .or(balance_at_route)
+        .or(balance_stream_route)
+        .or(balance_batch_text_route)
+        .or(balance_batch_route)
+        .or(balance_by_pubkey_route)
+        .map(Reply::into_response)
+        .boxed();
+
+    // Routes that read from the node wait for the startup probe; boxed like
+    // the balance routes.
+    let read_routes = with_readiness(readiness).and(
+        balance_routes
+            .or(nonce_route)
+            .or(nonce_gap_route)
+            .or(gas_price_route)
+            .or(chain_id_route)
+            .or(node_status_route)
+            .or(fee_history_route)
+            .or(block_route)
+            .or(transaction_route)
+            .or(tx_status_route)
+            .or(tx_decode_route)
+            .or(receipt_route)
+            .or(receipts_route)
+            .or(logs_route)
+            .or(trace_filter_route)
+            .or(send_raw_route)
+            .or(address_type_route)
+            .or(proxy_route)
+            .or(activity_route)
+            .or(contract_route)
+            .or(call_fn_route)
+            .or(xpub_balances_route)
+            .or(pending_route)
+            .map(Reply::into_response)
+            .boxed(),
+    );
+
+    // Health checks and the index are never rate limited. In maintenance mode
+    // only `/health` and the admin routes stay up. Everything but `/health`
+    // is shed when too many requests are in flight.
+    let routes = with_inflight_limit(inflight_limit).and(
+        with_maintenance(maintenance.clone())
+            .and(index_route.or(ready_route))
+            .or(with_rate_limit(rate_limiter)
+                .and(with_daily_quota(daily_quotas))
+                .and(
+                    admin_reload_route
+                        .or(admin_maintenance_route)
+                        .or(with_maintenance(maintenance).and(
+                            read_routes
+                                .or(stats_route)
+                                .or(top_addresses_route)
+                                .or(validate_route)
+                                .or(metrics_route)
+                                .or(chains_route)
+                                .or(capabilities_route)
+                                .or(caip_balance_route)
+                                .or(multichain_balance_route)
+                                .or(sign_typed_route)
+                                .or(ui::ui_routes(ui)),
+                        )),
+                )),
+    );
+    let routes = routes.map(|_permit: Option<OwnedSemaphorePermit>, reply| reply);
+    // Probes that do not know the prefix can keep reaching `/health` at the root.
+    let prefix = route_prefix.filter();
+    let api = if route_prefix.includes_health() {
+        prefix
+            .and(health_route.or(routes))
+            .map(Reply::into_response)
+            .boxed()
+    } else {
+        health_route
+            .or(prefix.and(routes))
+            .map(Reply::into_response)
+            .boxed()
+    };
+    // Rejections are answered inside the deadline, which itself rejects with
+    // a `504` answered below like any other error.
+    let api = deadline::with_deadline(
+        request_deadline,
+        api.recover(handle_rejection)
+            .map(Reply::into_response)
+            .boxed(),
+    );
+
+    // The guard keeps the request counted as in flight until the inner
+    // filters resolve, reject, or are dropped.
+    with_inflight(metrics.clone())
+        .and(with_timer(timings))
+        .and(with_route(metrics.clone(), route_prefix.clone()))
+        .and(with_request_span(tracer, route_prefix.clone()))
+        .and(with_envelope(envelope))
+        .and(debug_info::with_debug())
+        .and(warp::path::full())
+        .and(error_report::request_id())
+        .and(
+            with_query_limits(query_limits)
+                .and(with_route_flags(route_flags, route_prefix.clone()))
+                .and(api)
+                .recover(handle_rejection),
+        )
+        .map(
+            move |_inflight: InflightGuard,
+                  timer: RequestTimer,
+                  _route: RouteGuard,
+                  span: RequestSpan,
+                  envelope: Envelope,
+                  debug: Option<&'static str>,
+                  path: warp::path::FullPath,
+                  request_id: String,
+                  reply| {
+                let mut response = warp::Reply::into_response(reply);
+                if response.status() == StatusCode::METHOD_NOT_ALLOWED {
+                    let allowed = endpoints.allowed_methods(path.as_str());
+                    response = with_allowed_methods(response, allowed);
+                }
+                if envelope == Envelope::JsonRpc && !response.status().is_success() {
+                    response = into_jsonrpc_error(response);
+                }
+                if let Some(provider) = debug {
+                    debug_info::attach(&mut response, timer.rpc(), provider);
+                }
+                if let Ok(value) = timer.header_value().parse() {
+                    response.headers_mut().insert(SERVER_TIMING_HEADER, value);
+                }
+                if let Some(reporter) = &error_reporter {
+                    report_server_error(reporter, &response, path.as_str(), &request_id);
+                }
+                if let Ok(value) = request_id.parse() {
+                    response.headers_mut().insert(REQUEST_ID_HEADER, value);
+                }
+                span.finish(response.status());
+                response
+            },
+        )
+        .then(debug_info::append_debug)
+        // Only reached when the request id header cannot be read.
+        .recover(handle_rejection)
+        .with(warp::log::custom(move |info: warp::log::Info| {
+            let path = route_prefix.strip(info.path());
+            metrics.record_request(path, info.status(), info.elapsed());
+            if let Some(request_metrics) = &request_metrics {
+                request_metrics.record(info.method(), path, info.status(), info.elapsed());
+            }
+            log_request(info, &log_sampler, &sensitive_headers);
+        }))
+}
+
+/// Converts rejections into JSON error responses with a matching status code.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_handle_rejection() {
+/// let rejection = warp::reject::custom(InvalidBlock("tomorrow".to_string()));
+/// let response = handle_rejection(rejection).await.unwrap();
+/// # }
+/// ```
+async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+    let mut code = None;
+    let mut retry_after = None;
+    let mut errors = Vec::new();
+    let mut error_type = ErrorType::InvalidInput;
+    let (status, message) = if err.is_not_found() {
+        error_type = ErrorType::NotFound;
+        (StatusCode::NOT_FOUND, "Not found".to_string())
+    } else if let Some(error) = err.find::<AppError>() {
+        error!("Request failed: {}", error);
+        code = error.code();
+        retry_after = error.retry_after();
+        errors = error.field_errors().to_vec();
+        error_type = error.error_type();
+        (error.status(), error.to_string())
+    } else if let Some(error) = err.find::<InvalidBlock>() {
+        (StatusCode::BAD_REQUEST, error.to_string())
+    } else if err.find::<warp::reject::InvalidQuery>().is_some() {
+        (StatusCode::BAD_REQUEST, "Invalid query string".to_string())
+    } else if let Some(error) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        (StatusCode::BAD_REQUEST, error.to_string())
+    } else if err.find::<warp::reject::PayloadTooLarge>().is_some() {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Request body too large".to_string(),
+        )
+    } else if err.find::<warp::reject::UnsupportedMediaType>().is_some() {
+        (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Unsupported media type".to_string(),
+        )
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Method not allowed".to_string(),
+        )
+    } else {
+        error_type = ErrorType::Internal;
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Internal server error".to_string(),
+        )
+    };
+
+    let body = warp::reply::json(&ErrorResponse {
+        error: message.clone(),
+        error_type,
+        code,
+        allowed: Vec::new(),
+        errors,
+    });
+    let mut response = warp::reply::with_status(body, status).into_response();
+    response.extensions_mut().insert(ErrorMessage(message));
+    if let Some(code) = code {
+        response.extensions_mut().insert(ErrorCode(code));
+    }
+    if let Some(secs) = retry_after {
+        response
+            .headers_mut()
+            .insert(warp::http::header::RETRY_AFTER, secs.into());
+    }
+    Ok(response)
+}
+
+/// Lists the methods served at the path of a `405` response in its `Allow`
+/// header and `allowed` field; left as is when no endpoint has that path.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_allowed_methods() {
+/// let response = with_allowed_methods(response, vec!["GET"]);
+/// assert_eq!(response.headers()[ALLOW], "GET");
+/// # }
+/// ```
+fn with_allowed_methods(
+    response: warp::reply::Response,
+    allowed: Vec<&'static str>,
+) -> warp::reply::Response {
+    if allowed.is_empty() {
+        return response;
+    }
+    let error = response.extensions().get::<ErrorMessage>().map_or_else(
+        || "Method not allowed".to_string(),
+        |message| message.0.clone(),
+    );
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(warp::http::header::CONTENT_LENGTH);
+    if let Ok(value) = allowed.join(", ").parse() {
+        parts.headers.insert(warp::http::header::ALLOW, value);
+    }
+    let body = ErrorResponse {
+        error,
+        error_type: ErrorType::InvalidInput,
+        code: None,
+        allowed,
+        errors: Vec::new(),
+    };
+    let body = serde_json::to_vec(&body).unwrap_or_default();
+    warp::reply::Response::from_parts(parts, body.into())
+}
+
+/// Reports `5xx` responses to the error sink, in the background.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_report_server_error() {
+/// let reporter = ErrorReporter::new("https://errors.example/events");
+/// report_server_error(&reporter, &response, "/balance/0x00", "abc-123");
+/// # }
+/// ```
+fn report_server_error(
+    reporter: &ErrorReporter,
+    response: &warp::reply::Response,
+    route: &str,
+    request_id: &str,
+) {
+    let status = response.status();
+    if !status.is_server_error() {
+        return;
+    }
+    let message = response
+        .extensions()
+        .get::<ErrorMessage>()
+        .map(|message| message.0.clone())
+        .unwrap_or_else(|| status.to_string());
+    reporter.report(ErrorEvent {
+        message,
+        route: route.to_string(),
+        status: status.as_u16(),
+        request_id: request_id.to_string(),
+        timestamp: error_report::unix_millis(),
+    });
+}
+
+/// Logs the details of the request, unless it succeeded and was not sampled.
+///
+/// Request headers are logged at debug level, with sensitive values redacted.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_log_request() {
+/// let info = ...; // Mock or create a request info
+/// log_request(info, &LogSampler::default(), &SensitiveHeaders::default());
+/// # }
+/// ```
+fn log_request(info: warp::log::Info, sampler: &LogSampler, sensitive: &SensitiveHeaders) {
+    let status = info.status();
+    if !sampler.should_log(status) {
+        return;
+    }
+    let method = info.method();
+    let path = info.path();
+    let elapsed = info.elapsed();
+    let ip = info
+        .remote_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_else(|| "Unknown".into());
+
+    if status.is_success() {
+        info!(
+            "Request: {} {} from {} answered {} and took {:?}",
+            method,
+            path,
+            ip,
+            status.as_u16(),
+            elapsed
+        );
+    } else {
+        error!(
+            "Request: {} {} from {} failed with status {:?} and took {:?}",
+            method, path, ip, status, elapsed
+        );
+    }
+    debug!("{}", request_headers_line(&info, sensitive));
+}
+
+/// Log line of the headers of a request, with sensitive values redacted.
+fn request_headers_line(info: &warp::log::Info, sensitive: &SensitiveHeaders) -> String {
+    format!(
+        "Request headers: {} {}: {}",
+        info.method(),
+        info.path(),
+        sensitive.scrub(info.request_headers())
+    )
+}
+
+/// Provides the provider to the warp filters.
+///
+/// Requests carrying an `X-RPC-URL` header get a provider for that URL instead,
+/// as long as it is allowlisted; otherwise they are rejected with `403 Forbidden`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_provider() {
+/// let provider = Arc::new(...); // Mock or create a provider
+/// let filter = with_provider(provider, None);
+/// assert!(filter.is_some());
+/// # }
+/// ```
+fn with_provider(
+    provider: Arc<dyn Provider>,
+    upstreams: Option<Arc<UpstreamAllowlist>>,
+) -> impl Filter<Extract = (Arc<dyn Provider>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-rpc-url").and_then(move |url: Option<String>| {
+        let provider = provider.clone();
+        let upstreams = upstreams.clone();
+        async move {
+            let Some(url) = url else {
+                return Ok(provider);
+            };
+            match &upstreams {
+                Some(upstreams) => upstreams.provider_for(&url),
+                None => Err(AppError::UpstreamNotAllowed(upstream::origin(&url))),
+            }
+            .map_err(warp::reject::custom)
+        }
+    })
+}
+
+/// Provides the archive router to the warp filters.
+///
+/// Requests selecting their own upstream with `X-RPC-URL` are never sent to the archive node.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_archive() {
+/// let filter = with_archive(None);
+/// # }
+/// ```
+fn with_archive(
+    archive: Option<Arc<ArchiveRouter>>,
+) -> impl Filter<Extract = (Option<Arc<ArchiveRouter>>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-rpc-url")
+        .map(move |url: Option<String>| archive.clone().filter(|_| url.is_none()))
+}
+
+/// Provides a response cache to the warp filters.
+///
+/// Requests selecting their own upstream with `X-RPC-URL` bypass the cache.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_cache() {
+/// let filter = with_cache(Arc::new(IdempotencyCache::new(Duration::from_secs(2))));
+/// # }
+/// ```
+fn with_cache<C>(
+    cache: Arc<C>,
+) -> impl Filter<Extract = (Option<Arc<C>>,), Error = Rejection> + Clone
+where
+    C: ?Sized + Send + Sync + 'static,
+{
+    warp::header::optional::<String>("x-rpc-url")
+        .map(move |url: Option<String>| url.is_none().then(|| cache.clone()))
+}
+
+/// Provides the node cache to requests served by the default provider.
+///
+/// Like [`with_cache`], requests with an `X-RPC-URL` header get `None`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_node_cache() {
+/// let filter = with_node_cache(Arc::new(NodeCache::default()));
+/// # }
+/// ```
+fn with_node_cache(
+    cache: Arc<NodeCache>,
+) -> impl Filter<Extract = (Option<Arc<NodeCache>>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-rpc-url")
+        .map(move |url: Option<String>| url.is_none().then(|| cache.clone()))
+}
+
+/// Provides the config reloader to requests bearing the admin token.
+///
+/// Rejects as not found unless both `ADMIN_TOKEN` and `CONFIG_FILE` are set,
+/// and with `401 Unauthorized` when the `Authorization: Bearer` token is wrong.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_admin() {
+/// let filter = with_admin(Some("secret".to_string()), Some(reloader));
+/// # }
+/// ```
+fn with_admin(
+    token: Option<String>,
+    reloader: Option<Arc<Reloader>>,
+) -> impl Filter<Extract = (Arc<Reloader>,), Error = Rejection> + Clone {
+    warp::any()
+        .and_then(move || {
+            let reloader = reloader.clone();
+            async move { reloader.ok_or_else(warp::reject::not_found) }
+        })
+        .and(with_admin_token(token))
+}
+
+/// Lets through requests bearing the admin token.
+///
+/// Rejects as not found unless `ADMIN_TOKEN` is set, and with `401 Unauthorized`
+/// when the `Authorization: Bearer` token is wrong.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_admin_token() {
+/// let filter = with_admin_token(Some("secret".to_string()));
+/// # }
+/// ```
+fn with_admin_token(token: Option<String>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and_then(move |authorization: Option<String>| {
+            let token = token.clone();
+            async move {
+                let Some(token) = token else {
+                    return Err(warp::reject::not_found());
+                };
+                let bearer = authorization
+                    .as_deref()
+                    .and_then(|value| value.strip_prefix("Bearer "));
+                // Digests are compared in constant time, so neither the token's
+                // contents nor its length can be guessed from response times.
+                let matches = bearer.is_some_and(|bearer| {
+                    keccak256(bearer)
+                        .as_slice()
+                        .ct_eq(keccak256(&token).as_slice())
+                        .into()
+                });
+                if !matches {
+                    return Err(warp::reject::custom(AppError::Unauthorized));
+                }
+                Ok(())
+            }
+        })
+        .untuple_one()
+}
+
+/// Provides the signer, rejecting as not found when none is configured.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_signer() {
+/// let filter = with_signer(Some(Arc::new(PrivateKeySigner::random())));
+/// # }
+/// ```
+fn with_signer(
+    signer: Option<Arc<PrivateKeySigner>>,
+) -> impl Filter<Extract = (Arc<PrivateKeySigner>,), Error = Rejection> + Clone {
+    warp::any().and_then(move || {
+        let signer = signer.clone();
+        async move { signer.ok_or_else(warp::reject::not_found) }
+    })
+}
+
+/// Provides the maintenance toggle to the warp filters.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_maintenance_toggle() {
+/// let filter = with_maintenance_toggle(Arc::new(Maintenance::default()));
+/// # }
+/// ```
+fn with_maintenance_toggle(
+    maintenance: Arc<Maintenance>,
+) -> impl Filter<Extract = (Arc<Maintenance>,), Error = Infallible> + Clone {
+    warp::any().map(move || maintenance.clone())
+}
+
+/// Rejects the request with `503 Service Unavailable` while maintenance mode is on.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_maintenance() {
+/// let filter = with_maintenance(Arc::new(Maintenance::default()));
+/// # }
+/// ```
+fn with_maintenance(
+    maintenance: Arc<Maintenance>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::any()
+        .and_then(move || {
+            let rejection = maintenance
+                .is_enabled()
+                .then(|| AppError::Maintenance(maintenance.message().to_string()));
+            async move {
+                match rejection {
+                    Some(error) => Err(warp::reject::custom(error)),
+                    None => Ok(()),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Provides the balance streams, or rejects as not found when streaming is disabled.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_streams() {
+/// let filter = with_streams(Some(streams));
+/// # }
+/// ```
+fn with_streams(
+    streams: Option<Arc<BalanceStreams>>,
+) -> impl Filter<Extract = (Arc<BalanceStreams>,), Error = Rejection> + Clone {
+    warp::any().and_then(move || {
+        let streams = streams.clone();
+        async move { streams.ok_or_else(warp::reject::not_found) }
+    })
+}
+
+/// Provides the pending transaction buffer, or rejects as not found when polling is disabled.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_pending() {
+/// let filter = with_pending(Some(buffer));
+/// # }
+/// ```
+fn with_pending(
+    pending: Option<Arc<PendingBuffer>>,
+) -> impl Filter<Extract = (Arc<PendingBuffer>,), Error = Rejection> + Clone {
+    warp::any().and_then(move || {
+        let pending = pending.clone();
+        async move { pending.ok_or_else(warp::reject::not_found) }
+    })
+}
+
+/// Starts timing the request, for its `Server-Timing` header.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_timer() {
+/// let filter = with_timer(Arc::new(RpcTimings::new()));
+/// # }
+/// ```
+fn with_timer(
+    timings: Arc<RpcTimings>,
+) -> impl Filter<Extract = (RequestTimer,), Error = Infallible> + Clone {
+    warp::any().map(move || timings.start())
+}
+
+/// Provides the trace route settings, rejecting with `404` while the routes are disabled.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_trace() {
+/// let filter = with_trace(trace::get_trace_config());
+/// # }
+/// ```
+fn with_trace(
+    trace: Option<TraceConfig>,
+) -> impl Filter<Extract = (TraceConfig,), Error = Rejection> + Clone {
+    warp::any().and_then(move || async move { trace.ok_or_else(warp::reject::not_found) })
+}
+
+/// Provides the price feed, rejecting with `404` unless `PRICE_FEED_URL` is set.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_price_feed() {
+/// let filter = with_price_feed(price_feed::get_price_feed().map(Arc::new));
+/// # }
+/// ```
+fn with_price_feed(
+    feed: Option<Arc<PriceFeed>>,
+) -> impl Filter<Extract = (Arc<PriceFeed>,), Error = Rejection> + Clone {
+    warp::any().and_then(move || {
+        let feed = feed.clone();
+        async move { feed.ok_or_else(warp::reject::not_found) }
+    })
+}
+
+/// Provides the shared metrics to the warp filters.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_metrics() {
+/// let metrics = Arc::new(Metrics::new());
+/// let filter = with_metrics(metrics);
+/// # }
+/// ```
+fn with_metrics(
+    metrics: Arc<Metrics>,
+) -> impl Filter<Extract = (Arc<Metrics>,), Error = Infallible> + Clone {
+    warp::any().map(move || metrics.clone())
+}
+
+/// Tracks the request as in flight for as long as the extracted guard lives.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_inflight() {
+/// let metrics = Arc::new(Metrics::new());
+/// let filter = with_inflight(metrics);
+/// # }
+/// ```
+fn with_inflight(
+    metrics: Arc<Metrics>,
+) -> impl Filter<Extract = (InflightGuard,), Error = Infallible> + Clone {
+    warp::any().map(move || metrics.track_inflight())
+}
+
+/// Attributes the request's node retries to its route for as long as the extracted guard lives.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_route() {
+/// let metrics = Arc::new(Metrics::new());
+/// let filter = with_route(metrics, RoutePrefix::default());
+/// # }
+/// ```
+fn with_route(
+    metrics: Arc<Metrics>,
+    prefix: RoutePrefix,
+) -> impl Filter<Extract = (RouteGuard,), Error = Infallible> + Clone {
+    route_path(prefix).map(move |path: String| metrics.track_route(&path))
+}
+
+/// Provides the request path without the route prefix, which names the route.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_route_path() {
+/// let filter = route_path(RoutePrefix::new("api/v1", true).unwrap());
+/// # }
+/// ```
+fn route_path(prefix: RoutePrefix) -> impl Filter<Extract = (String,), Error = Infallible> + Clone {
+    warp::path::full()
+        .map(move |path: warp::path::FullPath| prefix.strip(path.as_str()).to_string())
+}
+
+/// Starts the request's span, ended with the response status once the handler completes.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_request_span() {
+/// let filter = with_request_span(Arc::new(global::tracer("rust-alloy")), RoutePrefix::default());
+/// # }
+/// ```
+fn with_request_span(
+    tracer: Arc<BoxedTracer>,
+    prefix: RoutePrefix,
+) -> impl Filter<Extract = (RequestSpan,), Error = Infallible> + Clone {
+    warp::method()
+        .and(route_path(prefix))
+        .map(move |method: warp::http::Method, path: String| {
+            RequestSpan::start(&tracer, &method, &path)
+        })
+}
+
+/// Provides the background task supervisor to the warp filters.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_supervisor() {
+/// let filter = with_supervisor(supervisor.clone());
+/// # }
+/// ```
+fn with_supervisor(
+    supervisor: Arc<Supervisor>,
+) -> impl Filter<Extract = (Arc<Supervisor>,), Error = Infallible> + Clone {
+    warp::any().map(move || supervisor.clone())
+}
+
+/// Provides the chain registry to the warp filters.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_chains() {
+/// let filter = with_chains(chains);
+/// # }
+/// ```
+fn with_chains(
+    chains: Arc<ChainRegistry>,
+) -> impl Filter<Extract = (Arc<ChainRegistry>,), Error = Infallible> + Clone {
+    warp::any().map(move || chains.clone())
+}
+
+/// Provides the default provider's circuit breaker to the warp filters.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_breaker() {
+/// let filter = with_breaker(Some(breaker));
+/// # }
+/// ```
+fn with_breaker(
+    breaker: Option<Arc<CircuitBreaker>>,
+) -> impl Filter<Extract = (Option<Arc<CircuitBreaker>>,), Error = Infallible> + Clone {
+    warp::any().map(move || breaker.clone())
+}
+
+/// Rejects the request when the client's IP has exhausted its rate limit.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_rate_limit() {
+/// let limiter = Arc::new(RateLimiter::new(60, 10));
+/// let filter = with_rate_limit(Some(limiter));
+/// # }
+/// ```
+fn with_rate_limit(
+    limiter: Option<Arc<RateLimiter>>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    server::client_addr()
+        .and_then(move |addr: Option<std::net::SocketAddr>| {
+            let limiter = limiter.clone();
+            async move {
+                match (limiter, addr) {
+                    (Some(limiter), Some(addr)) if !limiter.check(addr.ip()) => {
+                        Err(warp::reject::custom(AppError::RateLimited))
+                    }
+                    _ => Ok(()),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Sheds the request with `503` when `limit` requests are already in flight;
+/// the extracted permit admits it until dropped.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_inflight_limit() {
+/// let filter = with_inflight_limit(Some(Arc::new(InflightLimit::new(512))));
+/// # }
+/// ```
+fn with_inflight_limit(
+    limit: Option<Arc<InflightLimit>>,
+) -> impl Filter<Extract = (Option<OwnedSemaphorePermit>,), Error = Rejection> + Clone {
+    warp::any().and_then(move || {
+        let limit = limit.clone();
+        async move {
+            limit
+                .map(|limit| limit.try_acquire())
+                .transpose()
+                .map_err(warp::reject::custom)
+        }
+    })
+}
+
+/// Rejects the request when its `X-API-Key` has used up its daily quota.
+///
+/// Requests without a key, or with a key that has no quota, pass through.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_daily_quota() {
+/// let quotas = Arc::new(DailyQuotas::new(quota::parse_quotas("client-a=1000").unwrap()));
+/// let filter = with_daily_quota(Some(quotas));
+/// # }
+/// ```
+fn with_daily_quota(
+    quotas: Option<Arc<DailyQuotas>>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-api-key")
+        .and_then(move |key: Option<String>| {
+            let quotas = quotas.clone();
+            async move {
+                match (quotas, key) {
+                    (Some(quotas), Some(key)) => quotas.check(&key).map_err(warp::reject::custom),
+                    _ => Ok(()),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Only matches requests whose `Content-Type` is `mime`, ignoring parameters such as `charset`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_content_type() {
+/// let filter = with_content_type("text/plain");
+/// # }
+/// ```
+fn with_content_type(mime: &'static str) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::<String>("content-type")
+        .and_then(move |content_type: String| async move {
+            let essence = content_type.split(';').next().unwrap_or("").trim();
+            if essence.eq_ignore_ascii_case(mime) {
+                Ok(())
+            } else {
+                Err(warp::reject::not_found())
+            }
+        })
+        .untuple_one()
+}
+
+/// Rejects the request with `503 Service Unavailable` until the startup probe succeeds.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_readiness() {
+/// let filter = with_readiness(Arc::new(Readiness::new(false)));
+/// # }
+/// ```
+fn with_readiness(
+    readiness: Arc<Readiness>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::any()
+        .and_then(move || {
+            let ready = readiness.is_ready();
+            async move {
+                if ready {
+                    Ok(())
+                } else {
+                    Err(warp::reject::custom(AppError::NotReady))
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Rejects the request with `400 Bad Request` when its query string breaks `limits`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_query_limits() {
+/// let filter = with_query_limits(QueryLimits::default());
+/// # }
+/// ```
+fn with_query_limits(limits: QueryLimits) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::query::raw()
+        .or(warp::any().map(String::new))
+        .unify()
+        .and_then(
+            move |query: String| async move { limits.check(&query).map_err(warp::reject::custom) },
+        )
+        .untuple_one()
+}
+
+/// Rejects requests to routes left out of `ENABLE_ROUTES` with `404 Not Found`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_route_flags() {
+/// let filter = with_route_flags(RouteFlags::default(), RoutePrefix::default());
+/// # }
+/// ```
+fn with_route_flags(
+    flags: RouteFlags,
+    prefix: RoutePrefix,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    route_path(prefix)
+        .and_then(move |path: String| {
+            let enabled = flags.is_enabled(metrics::route_label(&path));
+            async move {
+                if enabled {
+                    Ok(())
+                } else {
+                    Err(warp::reject::not_found())
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Provides the response envelope to the warp filters: the configured one,
+/// unless the query string asks for `jsonrpc=2.0`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_with_envelope() {
+/// let filter = with_envelope(Envelope::Data);
+/// # }
+/// ```
+fn with_envelope(
+    envelope: Envelope,
+) -> impl Filter<Extract = (Envelope,), Error = Infallible> + Clone {
+    warp::query::raw()
+        .or(warp::any().map(String::new))
+        .unify()
+        .map(move |query: String| Envelope::from_query(&query, envelope))
+}
+
+/// Rewrites the body of a failed request in the JSON-RPC error envelope,
+/// keeping its status and headers.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_into_jsonrpc_error() {
+/// let response = handle_rejection(rejection).await.unwrap().into_response();
+/// let response = into_jsonrpc_error(response);
+/// # }
+/// ```
+fn into_jsonrpc_error(response: warp::reply::Response) -> warp::reply::Response {
+    let Some(ErrorMessage(message)) = response.extensions().get::<ErrorMessage>().cloned() else {
+        return response;
+    };
+    let upstream = response.extensions().get::<ErrorCode>().map(|code| code.0);
+    let body = wrap_error(response.status(), upstream, message);
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(warp::http::header::CONTENT_LENGTH);
+    let body = serde_json::to_vec(&body).unwrap_or_default();
+    warp::reply::Response::from_parts(parts, body.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use warp::http::StatusCode;
+    use warp::test::request;
+
+    use alloy::network::Ethereum;
+    use alloy::providers::{
+        Caller, EthCall, EthCallManyParams, EthCallParams, Provider, ProviderCall, RootProvider,
+        RpcWithBlock,
+    };
+    use alloy::rpc::types::TransactionRequest;
+    use alloy::sol_types::SolValue;
+
+    use alloy::consensus;
+    use alloy::consensus::TxEnvelope;
+    use alloy::eips::eip2718::Decodable2718;
+    use alloy::eips::BlockNumberOrTag;
+    use alloy::rlp::Decodable;
+    use alloy::rpc::client::NoParams;
+    use alloy::rpc::json_rpc::ErrorPayload;
+    use alloy::rpc::types::{Block, BlockTransactions, Header, Transaction, TransactionReceipt};
+    use alloy::transports::{RpcError, TransportError, TransportResult};
+    use alloy_primitives::{address, Bytes, U128, U64};
+    use opentelemetry::trace::TraceError;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use transaction::tests::{dummy_call, dummy_receipt, dummy_transaction};
+
+    const ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+    const CONTRACT: &str = "0x0000000000000000000000000000000000000001";
+    const BLOCK_HASH: &str = "0x88e96d4537bea4d9c05d12549907b32561d3bf31f45aae734cdc119f13406cb6";
+
+    struct DummyProvider;
+
+    #[async_trait::async_trait]
+    impl Provider for DummyProvider {
+        fn get_balance(
+            &self,
+            _address: alloy_primitives::Address,
+        ) -> RpcWithBlock<alloy_primitives::Address, alloy_primitives::Uint<256, 4>> {
+            RpcWithBlock::new_provider(|_block_id| {
+                // Parse "1000" into the expected type.
+                let dummy_balance = alloy_primitives::Uint::<256, 4>::from_str("1000")
+                    .expect("failed to parse dummy balance");
+                ProviderCall::ready(Ok(dummy_balance))
+            })
+        }
+
+        fn get_code_at(&self, address: Address) -> RpcWithBlock<Address, Bytes> {
+            // The zero address is an EOA, every other address is a plain contract.
+            let code = if address.is_zero() {
+                Bytes::new()
+            } else {
+                Bytes::from_static(&[0x60, 0x80, 0x60, 0x40, 0x52])
+            };
+            RpcWithBlock::new_provider(move |_block_id| ProviderCall::ready(Ok(code.clone())))
+        }
+
+        fn get_storage_at(
+            &self,
+            _address: Address,
+            _key: U256,
+        ) -> RpcWithBlock<(Address, U256), U256> {
+            RpcWithBlock::new_provider(|_block_id| ProviderCall::ready(Ok(U256::ZERO)))
+        }
+
+        fn get_block_number(&self) -> ProviderCall<NoParams, U64, u64> {
+            ProviderCall::ready(Ok(1000))
+        }
+
+        fn get_transaction_count(&self, _address: Address) -> RpcWithBlock<Address, U64, u64> {
+            RpcWithBlock::new_provider(|_block_id| ProviderCall::ready(Ok(7)))
+        }
+
+        fn get_gas_price(&self) -> ProviderCall<NoParams, U128, u128> {
+            ProviderCall::ready(Ok(1_000_000_000))
+        }
+
+        async fn get_fee_history(
+            &self,
+            block_count: u64,
+            _last_block: BlockNumberOrTag,
+            reward_percentiles: &[f64],
+        ) -> TransportResult<alloy::rpc::types::FeeHistory> {
+            // History ending at the head block, number 1000.
+            let blocks = block_count as usize;
+            Ok(alloy::rpc::types::FeeHistory {
+                oldest_block: 1001 - block_count,
+                base_fee_per_gas: vec![7; blocks + 1],
+                gas_used_ratio: vec![0.5; blocks],
+                reward: Some(vec![
+                    reward_percentiles.iter().map(|p| *p as u128).collect();
+                    blocks
+                ]),
+                ..Default::default()
+            })
+        }
+
+        async fn get_block(
+            &self,
+            block: BlockId,
+            kind: BlockTransactionsKind,
+        ) -> TransportResult<Option<Block>> {
+            // Only the head block, number 1000, exists.
+            let number = match block {
+                BlockId::Number(BlockNumberOrTag::Number(number)) => number,
+                BlockId::Number(BlockNumberOrTag::Latest) => 1000,
+                _ => return Ok(None),
+            };
+            if number != 1000 {
+                return Ok(None);
+            }
+            let tx = dummy_transaction();
+            let transactions = match kind {
+                BlockTransactionsKind::Full => BlockTransactions::Full(vec![tx]),
+                BlockTransactionsKind::Hashes => {
+                    BlockTransactions::Hashes(vec![*tx.inner.tx_hash()])
+                }
+            };
+            Ok(Some(Block {
+                header: Header::new(consensus::Header {
+                    number,
+                    timestamp: 1_700_000_000,
+                    ..Default::default()
+                }),
+                transactions,
+                ..Default::default()
+            }))
+        }
+
+        fn call<'req>(&self, tx: &'req TransactionRequest) -> EthCall<'req, Ethereum, Bytes> {
+            EthCall::call(TokenCaller, tx)
+        }
+
+        fn get_transaction_by_hash(
+            &self,
+            hash: B256,
+        ) -> ProviderCall<(B256,), Option<Transaction>> {
+            let tx = dummy_transaction();
+            let found = (*tx.inner.tx_hash() == hash).then_some(tx);
+            ProviderCall::ready(Ok(found))
+        }
+
+        fn get_transaction_receipt(
+            &self,
+            hash: B256,
+        ) -> ProviderCall<(B256,), Option<TransactionReceipt>> {
+            let receipt = dummy_receipt();
+            let found = (receipt.transaction_hash == hash).then_some(receipt);
+            ProviderCall::ready(Ok(found))
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("DummyProvider does not support `root`")
+        }
+    }
+
+    /// Provider counting broadcasts, answering each with the keccak hash of the raw bytes.
+    struct BroadcastProvider {
+        broadcasts: AtomicUsize,
+        /// How long each broadcast takes.
+        delay: Duration,
+        root: RootProvider,
+    }
+
+    impl BroadcastProvider {
+        fn new() -> Self {
+            BroadcastProvider::slow(Duration::ZERO)
+        }
+
+        fn slow(delay: Duration) -> Self {
+            BroadcastProvider {
+                broadcasts: AtomicUsize::new(0),
+                delay,
+                root: RootProvider::new_http("http://localhost:1".parse().unwrap()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for BroadcastProvider {
+        async fn send_raw_transaction(
+            &self,
+            encoded_tx: &[u8],
+        ) -> TransportResult<alloy::providers::PendingTransactionBuilder<Ethereum>> {
+            self.broadcasts.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            Ok(alloy::providers::PendingTransactionBuilder::new(
+                self.root.clone(),
+                keccak256(encoded_tx),
+            ))
+        }
+
+        fn root(&self) -> &RootProvider {
+            &self.root
+        }
+    }
+
+    /// Provider that never has a receipt, counting how often it was asked.
+    #[derive(Default)]
+    struct ReceiptPollProvider(AtomicUsize);
+
+    impl Provider for ReceiptPollProvider {
+        fn get_transaction_receipt(
+            &self,
+            _hash: B256,
+        ) -> ProviderCall<(B256,), Option<TransactionReceipt>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            ProviderCall::ready(Ok(None))
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("ReceiptPollProvider does not support `root`")
+        }
+    }
+
+    /// Answers every `eth_call` as an ERC-20 `balanceOf` returning 5000.
+    struct TokenCaller;
+
+    /// Token with 6 decimals, like USDC; [`TokenCaller`] answers 5000 to
+    /// every other call, which is no `uint8` for `decimals()`.
+    const USDC: &str = "0x6060606060606060606060606060606060606060";
+
+    impl Caller<Ethereum, Bytes> for TokenCaller {
+        fn call(
+            &self,
+            params: EthCallParams<'_, Ethereum>,
+        ) -> TransportResult<ProviderCall<EthCallParams<'static, Ethereum>, Bytes>> {
+            let usdc_decimals = token::decimals_request(USDC.parse().unwrap());
+            let output = if params.data() == &usdc_decimals {
+                U256::from(6)
+            } else {
+                U256::from(5000)
+            };
+            Ok(ProviderCall::ready(Ok(output.abi_encode().into())))
+        }
+
+        fn estimate_gas(
+            &self,
+            _params: EthCallParams<'_, Ethereum>,
+        ) -> TransportResult<ProviderCall<EthCallParams<'static, Ethereum>, Bytes>> {
+            unimplemented!("TokenCaller does not support `estimate_gas`")
+        }
+
+        fn call_many(
+            &self,
+            _params: EthCallManyParams<'_>,
+        ) -> TransportResult<ProviderCall<EthCallManyParams<'static>, Bytes>> {
+            unimplemented!("TokenCaller does not support `call_many`")
+        }
+    }
+
+    /// Provider whose calls fail with the given JSON-RPC error code.
+    struct FailingProvider(i64);
+
+    impl FailingProvider {
+        fn error(&self) -> TransportError {
+            RpcError::ErrorResp(ErrorPayload {
+                code: self.0,
+                message: "upstream failure".into(),
+                data: None,
+            })
+        }
+    }
+
+    impl Provider for FailingProvider {
+        fn get_balance(&self, _address: Address) -> RpcWithBlock<Address, U256> {
+            let code = self.0;
+            RpcWithBlock::new_provider(move |_block_id| {
+                ProviderCall::ready(Err(FailingProvider(code).error()))
+            })
+        }
+
+        fn get_chain_id(&self) -> ProviderCall<NoParams, U64, u64> {
+            ProviderCall::ready(Err(self.error()))
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("FailingProvider does not support `root`")
+        }
+    }
+
+    /// Provider returning a fixed balance for every address.
+    struct BalanceProvider(u64);
+
+    impl Provider for BalanceProvider {
+        fn get_balance(&self, _address: Address) -> RpcWithBlock<Address, U256> {
+            let balance = U256::from(self.0);
+            RpcWithBlock::new_provider(move |_block_id| ProviderCall::ready(Ok(balance)))
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("BalanceProvider does not support `root`")
+        }
+    }
+
+    /// Provider that only knows its chain id.
+    struct ChainIdProvider(u64);
+
+    impl Provider for ChainIdProvider {
+        fn get_chain_id(&self) -> ProviderCall<NoParams, U64, u64> {
+            ProviderCall::ready(Ok(self.0))
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("ChainIdProvider does not support `root`")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_balance() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = warp::path!("balance" / String)
+            .and(warp::get())
+            .and(block_id_query())
+            .and(warp::query::<BalanceQuery>())
+            .and(super::with_provider(provider.clone(), None))
+            .and(super::with_archive(None))
+            .and(super::with_cache(
+                Arc::new(TtlCache::new(Duration::ZERO)) as Arc<BalanceCache>
+            ))
+            .and(warp::any().map(|| None))
+            .and(super::with_metrics(Arc::new(Metrics::new())))
+            .and(super::with_envelope(Envelope::Bare))
+            .and(with_format())
+            .and_then(get_balance);
+
+        // Use a valid dummy Ethereum address.
+        let address = "0x0000000000000000000000000000000000000000";
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/balance/{}", address))
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        // Further assertions can be made by parsing the JSON response.
+    }
+
+    #[tokio::test]
+    async fn test_quantities_follow_format() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+        let cases = [
+            (format!("/balance/{}", ADDRESS), "balance", "1000", "0x3e8"),
+            (format!("/nonce/{}", ADDRESS), "nonce", "7", "0x7"),
+            (
+                "/gas-price".to_string(),
+                "gas_price",
+                "1000000000",
+                "0x3b9aca00",
+            ),
+        ];
+
+        for (path, field, dec, hex) in cases {
+            let resp = request().path(&path).reply(&api).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", path);
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            assert_eq!(body[field], dec, "{}", path);
+
+            let resp = request()
+                .path(&format!("{}?format=hex", path))
+                .reply(&api)
+                .await;
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            assert_eq!(body[field], hex, "{}", path);
+        }
+
+        let resp = request()
+            .path(&format!("/balance/{}?block=latest&format=hex", ADDRESS))
+            .reply(&api)
+            .await;
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["balance"], "0x3e8");
+
+        let resp = request()
+            .path(&format!("/balance/{}?format=oct", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_amounts_follow_unit() {
+        let provider: Arc<dyn Provider> = Arc::new(BalanceProvider(1_500_000_000_000_000_000));
+        let api = setup_routes(AppState::new(provider));
+        let cases = [
+            ("", "1500000000000000000", "wei"),
+            ("?unit=wei", "1500000000000000000", "wei"),
+            ("?unit=gwei", "1500000000.000000000", "gwei"),
+            ("?unit=ether", "1.500000000000000000", "ether"),
+        ];
+        for (query, balance, unit) in cases {
+            let resp = request()
+                .path(&format!("/balance/{}{}", ADDRESS, query))
+                .reply(&api)
+                .await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", query);
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            assert_eq!(body["balance"], balance, "{}", query);
+            assert_eq!(body["unit"], unit, "{}", query);
+        }
+
+        // Gas price of 1 gwei.
+        let api = setup_routes(AppState::new(Arc::new(DummyProvider)));
+        let cases = [
+            ("?unit=wei", "1000000000", "wei"),
+            ("?unit=gwei", "1.000000000", "gwei"),
+            ("?unit=ether", "0.000000001000000000", "ether"),
+        ];
+        for (query, gas_price, unit) in cases {
+            let resp = request()
+                .path(&format!("/gas-price{}", query))
+                .reply(&api)
+                .await;
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            assert_eq!(body["gas_price"], gas_price, "{}", query);
+            assert_eq!(body["unit"], unit, "{}", query);
+        }
+
+        for path in ["/gas-price?unit=gwei&format=hex", "/gas-price?unit=finney"] {
+            let resp = request().path(path).reply(&api).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signed_balance_recovers_to_signer() {
+        let signer = PrivateKeySigner::random();
+        let api = setup_routes(AppState {
+            signer: Some(Arc::new(signer.clone())),
+            ..AppState::new(Arc::new(DummyProvider))
+        });
+
+        let resp = request()
+            .path(&format!("/balance/{}?sign=true&unit=ether", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        // `latest` is pinned to the head block.
+        assert_eq!(body["block_number"], 1000);
+        let signer_address: Address = serde_json::from_value(body["signer"].clone()).unwrap();
+        assert_eq!(signer_address, signer.address());
+
+        let signature: Bytes = serde_json::from_value(body["signature"].clone()).unwrap();
+        let signature = alloy_primitives::PrimitiveSignature::try_from(signature.as_ref()).unwrap();
+        let digest = signing::balance_digest(ADDRESS.parse().unwrap(), U256::from(1000), 1000);
+        assert_eq!(
+            signature.recover_address_from_prehash(&digest).unwrap(),
+            signer.address()
+        );
+
+        // Unsigned responses are unchanged.
+        let resp = request()
+            .path(&format!("/balance/{}", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.body(), r#"{"balance":"1000","unit":"wei"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_signing_requires_a_signer() {
+        let api = setup_routes(AppState::new(Arc::new(DummyProvider)));
+        let resp = request()
+            .path(&format!("/balance/{}?sign=true", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_sign_typed_data() {
+        let api = setup_routes(AppState {
+            signer: Some(Arc::new(signing::tests::cow())),
+            ..AppState::new(Arc::new(DummyProvider))
+        });
+        let resp = request()
+            .method("POST")
+            .path("/sign-typed")
+            .json(&signing::tests::mail())
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "hash": "0xbe609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2",
+                "signer": "0xcd2a3d9f938e13cd947ec05abc7fe734df8dd826",
+                "signature": signing::tests::MAIL_SIGNATURE,
+            })
+        );
+
+        // A primary type missing from `types` is rejected.
+        let mut untyped = signing::tests::mail();
+        untyped["primaryType"] = "Letter".into();
+        let resp = request()
+            .method("POST")
+            .path("/sign-typed")
+            .json(&untyped)
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let unsigned = setup_routes(AppState::new(Arc::new(DummyProvider)));
+        let resp = request()
+            .method("POST")
+            .path("/sign-typed")
+            .json(&signing::tests::mail())
+            .reply(&unsigned)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_get_balance_at_block_tag() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        for block in ["pending", "safe", "finalized", "12", BLOCK_HASH] {
+            let resp = request()
+                .method("GET")
+                .path(&format!("/balance/{}?block={}", ADDRESS, block))
+                .reply(&api)
+                .await;
+
+            assert_eq!(resp.status(), StatusCode::OK, "block={}", block);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_balance_rejects_unknown_block_tag() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/balance/{}?block=tomorrow", ADDRESS))
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert!(body["error"].as_str().unwrap().contains("tomorrow"));
+    }
+
+    #[tokio::test]
+    async fn test_stats_reflect_requests() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let metrics = Arc::new(Metrics::new());
+        let api = setup_routes(AppState {
+            metrics: metrics.clone(),
+            ..AppState::new(provider)
+        });
+
+        request().method("GET").path("/health").reply(&api).await;
+        request()
+            .method("GET")
+            .path(&format!("/balance/{}?block=tomorrow", ADDRESS))
+            .reply(&api)
+            .await;
+
+        let resp = request().method("GET").path("/stats").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["total_requests"], 2);
+        assert_eq!(body["errors"], 1);
+        assert_eq!(body["requests_by_route"]["health"], 1);
+        assert_eq!(body["requests_by_route"]["balance"], 1);
+        assert!(body["uptime_seconds"].is_u64());
+        assert_eq!(metrics.snapshot().total_requests, 3);
+    }
+
+    #[tokio::test]
+    async fn test_get_balance_with_data_envelope() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState {
+            envelope: Envelope::Data,
+            ..AppState::new(provider)
+        });
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/balance/{}", ADDRESS))
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({ "data": { "balance": "1000", "unit": "wei" } })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jsonrpc_envelope_from_the_query() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/balance/{}?jsonrpc=2.0", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": { "balance": "1000", "unit": "wei" }
+            })
+        );
+
+        // Strict queries accept the option too.
+        let resp = request()
+            .method("GET")
+            .path(&format!(
+                "/balance/{}/fiat?currency=usd&jsonrpc=2.0",
+                ADDRESS
+            ))
+            .reply(&api)
+            .await;
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["jsonrpc"], "2.0");
+    }
+
+    #[tokio::test]
+    async fn test_debug_block_only_when_requested() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request()
+            .path(&format!("/balance/{}?debug=true", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["balance"], "1000");
+        assert_eq!(body["_debug"]["provider"], "default");
+        assert!(body["_debug"]["rpc_latency_ms"].is_u64());
+
+        for path in [
+            format!("/balance/{}", ADDRESS),
+            format!("/balance/{}?debug=false", ADDRESS),
+        ] {
+            let resp = request().path(&path).reply(&api).await;
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            assert!(body.get("_debug").is_none(), "{}", path);
+        }
+
+        // Strict queries accept the option too, and errors are left alone.
+        let resp = request()
+            .path(&format!(
+                "/balance/{}/fiat?currency=usd&debug=true",
+                ADDRESS
+            ))
+            .reply(&api)
+            .await;
+        assert_ne!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = request()
+            .path("/balance/0x1234?debug=true")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert!(body.get("_debug").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_jsonrpc_envelope_wraps_errors() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider.clone()));
+
+        let resp = request()
+            .method("GET")
+            .path("/balance/0x1234?jsonrpc=2.0")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["jsonrpc"], "2.0");
+        assert_eq!(body["id"], 1);
+        assert_eq!(body["error"]["code"], -32602);
+        assert!(body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid address"));
+
+        // The configured envelope applies to errors as well.
+        let api = setup_routes(AppState {
+            envelope: Envelope::JsonRpc,
+            ..AppState::new(provider)
+        });
+        let resp = request().method("GET").path("/missing").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "error": { "code": -32001, "message": "Not found" }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_address_type() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        for (address, expected) in [(ADDRESS, "eoa"), (CONTRACT, "contract")] {
+            let resp = request()
+                .method("GET")
+                .path(&format!("/type/{}", address))
+                .reply(&api)
+                .await;
+
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            assert_eq!(body, serde_json::json!({ "type": expected }));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_balance_surfaces_upstream_errors() {
+        let provider: Arc<dyn Provider> = Arc::new(FailingProvider(-32601));
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/balance/{}", ADDRESS))
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::NOT_IMPLEMENTED);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["code"], -32601);
+        assert!(body["error"].as_str().unwrap().contains("upstream failure"));
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_are_reported() {
+        let (sender, mut events) = tokio::sync::mpsc::unbounded_channel();
+        let sink = warp::post()
+            .and(warp::body::json())
+            .map(move |event: serde_json::Value| {
+                sender.send(event).unwrap();
+                "ok"
+            });
+        let (sink_addr, sink_server) = warp::serve(sink).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(sink_server);
+
+        let provider: Arc<dyn Provider> = Arc::new(FailingProvider(-32000));
+        let reporter = ErrorReporter::new(format!("http://{}/events", sink_addr));
+        let api = setup_routes(AppState {
+            error_reporter: Some(Arc::new(reporter)),
+            ..AppState::new(provider)
+        });
+
+        // Client errors are not reported.
+        let resp = request().path("/balance/0x123").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let path = format!("/balance/{}", ADDRESS);
+        let resp = request()
+            .path(&path)
+            .header(REQUEST_ID_HEADER, "req-1")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(resp.headers()[REQUEST_ID_HEADER], "req-1");
+
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event["route"], path);
+        assert_eq!(event["status"], 502);
+        assert_eq!(event["request_id"], "req-1");
+        assert!(event["message"]
+            .as_str()
+            .unwrap()
+            .contains("upstream failure"));
+        assert!(event["timestamp"].as_u64().unwrap() > 0);
+    }
+
+    /// Reads the next complete Server-Sent Event from a streaming response.
+    async fn next_event(resp: &mut reqwest::Response, buffer: &mut String) -> String {
+        loop {
+            if let Some(end) = buffer.find("\n\n") {
+                let event = buffer[..end].to_string();
+                buffer.drain(..end + 2);
+                return event;
+            }
+            let chunk = tokio::time::timeout(Duration::from_secs(5), resp.chunk())
+                .await
+                .expect("timed out waiting for an event")
+                .unwrap()
+                .expect("stream ended");
+            buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_balance_stream_sends_balance_per_block() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let streams = Arc::new(BalanceStreams::new(1));
+        let api = setup_routes(AppState {
+            streams: Some(streams.clone()),
+            ..AppState::new(provider)
+        });
+        let (addr, server) = warp::serve(api).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let url = format!("http://{}/balance/{}/stream", addr, ADDRESS);
+
+        let mut resp = reqwest::get(&url).await.unwrap();
+        assert_eq!(resp.status().as_u16(), 200);
+        assert_eq!(resp.headers()["content-type"], "text/event-stream");
+        let mut buffer = String::new();
+        let event = next_event(&mut resp, &mut buffer).await;
+        assert!(event.contains("event:balance"), "{}", event);
+        assert!(event.contains(r#""block_number":1000"#), "{}", event);
+        assert!(event.contains(r#""balance":"1000""#), "{}", event);
+
+        streams.publish(1001);
+        let event = next_event(&mut resp, &mut buffer).await;
+        assert!(event.contains(r#""block_number":1001"#), "{}", event);
+
+        // A lower head is announced as a reorg before its balance.
+        streams.publish(999);
+        let event = next_event(&mut resp, &mut buffer).await;
+        assert!(event.contains("event:reorg"), "{}", event);
+        assert!(
+            event.contains(r#"{"old_head":1001,"new_head":999}"#),
+            "{}",
+            event
+        );
+        let event = next_event(&mut resp, &mut buffer).await;
+        assert!(event.contains("event:balance"), "{}", event);
+        assert!(event.contains(r#""block_number":999"#), "{}", event);
+
+        // Only one stream may be open at a time.
+        let rejected = reqwest::get(&url).await.unwrap();
+        assert_eq!(rejected.status().as_u16(), 503);
+
+        // Disconnecting frees the slot once the server notices.
+        drop(resp);
+        for block_number in 1002.. {
+            if streams.active() == 0 {
+                break;
+            }
+            assert!(block_number < 1100, "stream was never closed");
+            streams.publish(block_number);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_balance_stream_is_disabled_by_default() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+        let resp = request()
+            .path(&format!("/balance/{}/stream", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_get_balance_rejects_invalid_address() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request()
+            .method("GET")
+            .path("/balance/0x1234")
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_inflight_gauge_returns_to_zero() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let metrics = Arc::new(Metrics::new());
+        let api = setup_routes(AppState {
+            metrics: metrics.clone(),
+            ..AppState::new(provider)
+        });
+
+        request()
+            .method("GET")
+            .path(&format!("/balance/{}", ADDRESS))
+            .reply(&api)
+            .await;
+        request()
+            .method("GET")
+            .path(&format!("/balance/{}?block=tomorrow", ADDRESS))
+            .reply(&api)
+            .await;
+        request().method("GET").path("/missing").reply(&api).await;
+
+        // The scrape itself is the only request in flight.
+        let resp = request().method("GET").path("/metrics").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = String::from_utf8(resp.body().to_vec()).unwrap();
+        assert!(body.contains("http_requests_inflight 1"));
+
+        assert_eq!(metrics.snapshot().inflight, 0);
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_follow_the_configuration() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let resp = request()
+            .path("/capabilities")
+            .reply(&setup_routes(AppState::new(provider.clone())))
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["chains"], serde_json::json!([DEFAULT_CHAIN]));
+        for flag in [
+            "multichain",
+            "signing",
+            "trace_routes",
+            "balance_streams",
+            "admin",
+        ] {
+            assert_eq!(body[flag], false, "{}", flag);
+        }
+
+        let api = setup_routes(AppState {
+            chains: Arc::new(ChainRegistry::new(vec![
+                Chain::new("mainnet", provider.clone()),
+                Chain::new("sepolia", provider.clone()),
+            ])),
+            signer: Some(Arc::new(signing::tests::cow())),
+            trace: Some(TraceConfig { max_range: 100 }),
+            ..AppState::new(provider)
+        });
+        let resp = request().path("/capabilities").reply(&api).await;
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["chains"], serde_json::json!(["mainnet", "sepolia"]));
+        assert_eq!(body["multichain"], true);
+        assert_eq!(body["signing"], true);
+        assert_eq!(body["trace_routes"], true);
+        assert_eq!(body["balance_streams"], false);
+        let paths: Vec<&str> = body["endpoints"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|endpoint| endpoint["path"].as_str().unwrap())
+            .collect();
+        assert!(paths.contains(&"/sign-typed"));
+        assert!(paths.contains(&"/trace-filter"));
+        assert!(!paths.contains(&"/balance/{address}/stream"));
+    }
+
+    #[tokio::test]
+    async fn test_get_chains_lists_configured_chains() {
+        let chains = Arc::new(ChainRegistry::new(vec![
+            Chain::new("mainnet", Arc::new(ChainIdProvider(1))),
+            Chain::new("sepolia", Arc::new(ChainIdProvider(11155111))),
+            Chain::new("broken", Arc::new(FailingProvider(-32000))),
+        ]));
+        chains.check_health().await;
+
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState {
+            chains,
+            ..AppState::new(provider)
+        });
+
+        let resp = request().method("GET").path("/chains").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!([
+                { "name": "mainnet", "chain_id": 1, "status": "reachable" },
+                { "name": "sepolia", "chain_id": 11155111, "status": "reachable" },
+                { "name": "broken", "chain_id": null, "status": "unreachable" },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ready_follows_quorum_policy() {
+        let chains = Arc::new(ChainRegistry::new(vec![
+            Chain::new("mainnet", Arc::new(ChainIdProvider(1))),
+            Chain::new("sepolia", Arc::new(ChainIdProvider(11155111))),
+            Chain::new("broken", Arc::new(FailingProvider(-32000))),
+        ]));
+        chains.check_health().await;
+
+        let ready = |ready_policy| {
+            let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+            setup_routes(AppState {
+                chains: chains.clone(),
+                ready_policy,
+                ..AppState::new(provider)
+            })
+        };
+
+        let resp = request()
+            .path("/ready")
+            .reply(&ready(ReadyPolicy::All))
+            .await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["ready"], false);
+        assert_eq!(body["reachable"], 2);
+        assert_eq!(body["required"], 3);
+        assert_eq!(body["chains"][2]["status"], "unreachable");
+
+        let resp = request()
+            .path("/ready")
+            .reply(&ready(ReadyPolicy::Quorum(2)))
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["ready"], true);
+        assert_eq!(body["required"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_rejects_excess_requests() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState {
+            rate_limiter: Some(Arc::new(RateLimiter::new(1, 1))),
+            ..AppState::new(provider)
+        });
+        let client = "10.0.0.1:5000".parse().unwrap();
+
+        let path = format!("/balance/{}", ADDRESS);
+        let first = request().path(&path).remote_addr(client).reply(&api).await;
+        let second = request().path(&path).remote_addr(client).reply(&api).await;
+        let health = request()
+            .path("/health")
+            .remote_addr(client)
+            .reply(&api)
+            .await;
+
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body: serde_json::Value = serde_json::from_slice(second.body()).unwrap();
+        assert_eq!(body["error_type"], "rate_limited");
+        assert_eq!(health.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_daily_quota_rejects_excess_requests() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let quotas = quota::parse_quotas("client-a=2").unwrap();
+        let api = setup_routes(AppState {
+            daily_quotas: Some(Arc::new(DailyQuotas::new(quotas))),
+            ..AppState::new(provider)
+        });
+        let path = format!("/balance/{}", ADDRESS);
+        let with_key = |key: &'static str| request().path(&path).header("x-api-key", key);
+
+        assert_eq!(
+            with_key("client-a").reply(&api).await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            with_key("client-a").reply(&api).await.status(),
+            StatusCode::OK
+        );
+        let exhausted = with_key("client-a").reply(&api).await;
+        assert_eq!(exhausted.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = exhausted.headers()["retry-after"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=86_400).contains(&retry_after));
+        let body: serde_json::Value = serde_json::from_slice(exhausted.body()).unwrap();
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Daily quota exceeded; it resets at Unix time"));
+
+        // Other keys, requests without a key and health checks are unaffected.
+        assert_eq!(
+            with_key("client-b").reply(&api).await.status(),
+            StatusCode::OK
+        );
+        let anonymous = request().path(&path).reply(&api).await;
+        assert_eq!(anonymous.status(), StatusCode::OK);
+        let health = request()
+            .path("/health")
+            .header("x-api-key", "client-a")
+            .reply(&api)
+            .await;
+        assert_eq!(health.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_read_routes_wait_for_readiness() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let readiness = Arc::new(Readiness::new(false));
+        let api = setup_routes(AppState {
+            readiness: readiness.clone(),
+            ..AppState::new(provider)
+        });
+        let path = format!("/balance/{}", ADDRESS);
+
+        let starting = request().path(&path).reply(&api).await;
+        assert_eq!(starting.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(starting.headers()["retry-after"], "1");
+        let health = request().path("/health").reply(&api).await;
+        assert_eq!(health.status(), StatusCode::OK);
+
+        readiness.mark_ready();
+        let ready = request().path(&path).reply(&api).await;
+        assert_eq!(ready.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_balance_cache_serves_repeats() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let metrics = Arc::new(Metrics::new());
+        let api = setup_routes(AppState {
+            metrics: metrics.clone(),
+            balance_cache: Arc::new(TtlCache::new(Duration::from_secs(60))),
+            ..AppState::new(provider)
+        });
+        let path = format!("/balance/{}", ADDRESS);
+
+        for _ in 0..2 {
+            let resp = request().path(&path).reply(&api).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            assert_eq!(body["balance"], "1000");
+        }
+        assert_eq!(metrics.snapshot().cache_hit_rate, Some(0.5));
+    }
+
+    #[tokio::test]
+    async fn test_node_cache_serves_chain_id_and_gas_price() {
+        let mock = Arc::new(
+            testing::MockProviderBuilder::new()
+                .chain_id(1)
+                .gas_price(20)
+                .build(),
+        );
+        let provider: Arc<dyn Provider> = mock.clone();
+        let api = setup_routes(AppState {
+            node_cache: Arc::new(NodeCache::new(Duration::from_secs(60))),
+            ..AppState::new(provider)
+        });
+
+        for _ in 0..5 {
+            let resp = request().path("/chain-id").reply(&api).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            assert_eq!(body["chain_id"], 1);
+
+            let resp = request().path("/gas-price").reply(&api).await;
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            assert_eq!(body["gas_price"], "20");
+        }
+        mock.assert_calls("eth_chainId", 1);
+        mock.assert_calls("eth_gasPrice", 1);
+    }
+
+    #[tokio::test]
+    async fn test_node_status() {
+        let provider: Arc<dyn Provider> =
+            Arc::new(node_status::tests::SyncingProvider { peers: Some(25) });
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request().path("/node-status").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["syncing"]["currentBlock"], "0x2a");
+        assert_eq!(body["syncing"]["highestBlock"], "0x64");
+        assert_eq!(body["peers"], 25);
+    }
+
+    #[tokio::test]
+    async fn test_activity() {
+        let provider: Arc<dyn Provider> = Arc::new(activity::tests::wallet());
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request()
+            .path(&format!("/activity/{}", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[SERVED_BY_HEADER], "default");
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({ "first_seen": 300, "last_seen": 700, "head": 1000 })
+        );
+
+        let resp = request().path("/activity/0x1234").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_method_not_allowed_lists_the_allowed_methods() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request()
+            .method("POST")
+            .path(&format!("/balance/{}", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()[warp::http::header::ALLOW], "GET");
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "error": "Method not allowed",
+                "error_type": "invalid_input",
+                "allowed": ["GET"],
+            })
+        );
+
+        let resp = request().method("GET").path("/call-fn").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()[warp::http::header::ALLOW], "POST");
+    }
+
+    #[tokio::test]
+    async fn test_logged_request_headers_are_redacted() {
+        let lines = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let logged = lines.clone();
+        let sensitive = SensitiveHeaders::default();
+        let api =
+            warp::any()
+                .map(warp::reply)
+                .with(warp::log::custom(move |info: warp::log::Info| {
+                    logged
+                        .lock()
+                        .unwrap()
+                        .push(request_headers_line(&info, &sensitive));
+                }));
+
+        request()
+            .path("/health")
+            .header("authorization", "Bearer secret-token")
+            .header("x-request-id", "req-1")
+            .reply(&api)
+            .await;
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(
+            lines[0],
+            "Request headers: GET /health: authorization: [REDACTED], x-request-id: req-1"
+        );
+        assert!(!lines[0].contains("secret-token"));
+    }
+
+    #[tokio::test]
+    async fn test_balance_cache_header() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState {
+            balance_cache: Arc::new(TtlCache::new(Duration::from_secs(60))),
+            ..AppState::new(provider.clone())
+        });
+        let path = format!("/balance/{}", ADDRESS);
+
+        let first = request().path(&path).reply(&api).await;
+        assert_eq!(first.headers()[CACHE_HEADER], "MISS");
+        let second = request().path(&path).reply(&api).await;
+        assert_eq!(second.headers()[CACHE_HEADER], "HIT");
+        // Another block is another entry.
+        let other = request()
+            .path(&format!("{}?block=safe", path))
+            .reply(&api)
+            .await;
+        assert_eq!(other.headers()[CACHE_HEADER], "MISS");
+
+        // Without a cache there is nothing to report.
+        let api = setup_routes(AppState::new(provider));
+        let resp = request().path(&path).reply(&api).await;
+        assert!(resp.headers().get(CACHE_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_admin_reload_applies_config_file() {
+        let path = std::env::temp_dir().join(format!("rust-alloy-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{ "cache_ttl_ms": 5000, "log_level": "info" }"#).unwrap();
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let balance_cache = Arc::new(TtlCache::new(Duration::ZERO));
+        let reloader = Reloader::new(
+            path.clone(),
+            reload::DEFAULT_BIND_ADDR,
+            None,
+            balance_cache.clone(),
+        );
+        let api = setup_routes(AppState {
+            balance_cache: balance_cache.clone(),
+            admin_token: Some("secret".to_string()),
+            reloader: Some(Arc::new(reloader)),
+            ..AppState::new(provider.clone())
+        });
+
+        for authorization in ["Bearer wrong", "Bearer secre", "Bearer secrets", "secret"] {
+            let unauthorized = request()
+                .method("POST")
+                .path("/admin/reload")
+                .header("authorization", authorization)
+                .reply(&api)
+                .await;
+            assert_eq!(unauthorized.status(), StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(balance_cache.ttl(), Duration::ZERO);
+
+        let resp = request()
+            .method("POST")
+            .path("/admin/reload")
+            .header("authorization", "Bearer secret")
+            .reply(&api)
+            .await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["cache_ttl_ms"], 5000);
+        assert_eq!(body["requires_restart"], serde_json::json!([]));
+        assert_eq!(balance_cache.ttl(), Duration::from_secs(5));
+
+        let disabled = setup_routes(AppState::new(provider));
+        let resp = request()
+            .method("POST")
+            .path("/admin/reload")
+            .header("authorization", "Bearer secret")
+            .reply(&disabled)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_mode_toggles_routes() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState {
+            admin_token: Some("secret".to_string()),
+            ..AppState::new(provider)
+        });
+        let toggle = |query: &'static str| {
+            request()
+                .method("POST")
+                .path(query)
+                .header("authorization", "Bearer secret")
+        };
+        let balance = format!("/balance/{}", ADDRESS);
+
+        let resp = toggle("/admin/maintenance").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.body().as_ref(), br#"{"maintenance":true}"#);
+
+        let resp = request().path(&balance).reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers()["retry-after"], "30");
+        assert_eq!(
+            resp.body().as_ref(),
+            br#"{"error":"Down for maintenance","error_type":"upstream_unavailable"}"#
+        );
+        let resp = request().path("/health").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = request().path("/").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers()["retry-after"], "30");
+        let resp = request().path("/ready").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            resp.body().as_ref(),
+            br#"{"error":"Down for maintenance","error_type":"upstream_unavailable"}"#
+        );
+
+        let resp = toggle("/admin/maintenance?enabled=false").reply(&api).await;
+        assert_eq!(resp.body().as_ref(), br#"{"maintenance":false}"#);
+        let resp = request().path(&balance).reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = request().path("/").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_query_limits_apply_to_every_route() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState {
+            query_limits: QueryLimits {
+                max_params: 2,
+                max_value_len: 8,
+            },
+            ..AppState::new(provider)
+        });
+        let balance = format!("/balance/{}", ADDRESS);
+
+        let resp = request()
+            .path(&format!("{}?block=latest", balance))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = request()
+            .path(&format!("{}?block=latest&unit=wei&format=hex", balance))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = request().path("/health?a=1&b=2&c=3").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = request()
+            .path(&format!("{}?block={}", balance, "9".repeat(9)))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert!(body["error"].as_str().unwrap().contains("block"));
+    }
+
+    #[tokio::test]
+    async fn test_fiat_balance_uses_price_feed() {
+        let mock = warp::path!("price" / String).map(|currency: String| match currency.as_str() {
+            "usd" => warp::reply::json(&serde_json::json!({ "ethereum": { "usd": 2000.0 } })),
+            _ => warp::reply::json(&serde_json::json!({})),
+        });
+        let (addr, server) = warp::serve(mock).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let feed = PriceFeed::new(
+            format!("http://{}/price/{{currency}}", addr),
+            Duration::from_secs(60),
+        );
+
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState {
+            price_feed: Some(Arc::new(feed)),
+            ..AppState::new(provider)
+        });
+
+        let resp = request()
+            .path(&format!("/balance/{}/fiat?currency=USD", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["balance_eth"], "0.000000000000001000");
+        assert_eq!(body["price"], 2000.0);
+        assert_eq!(body["currency"], "usd");
+        let fiat = body["fiat_value"].as_f64().unwrap();
+        assert!((fiat - 2e-12).abs() < 1e-20);
+
+        // Without a price the balance is still returned.
+        let resp = request()
+            .path(&format!("/balance/{}/fiat?currency=eur", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["balance_eth"], "0.000000000000001000");
+        assert_eq!(body["price"], serde_json::Value::Null);
+        assert_eq!(body["fiat_value"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_fiat_value_is_rounded_once() {
+        let balance = U256::from(78_122_573_303_404_879_460_515_376_u128);
+        // Parsing the ether string and then multiplying gives 7812257.330340489.
+        assert_eq!(
+            fiat_value(balance, 0.1),
+            Some("7812257.3303404879460515376".parse().unwrap())
+        );
+        assert_eq!(
+            fiat_value(U256::from(10).pow(U256::from(18)), 2000.0),
+            Some(2000.0)
+        );
+        assert_eq!(fiat_value(balance, f64::NAN), None);
+    }
+
+    #[tokio::test]
+    async fn test_fee_history() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request()
+            .path("/fee-history?blocks=2&percentiles=10,50,90")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "oldest_block": 999,
+                "base_fee_per_gas": ["7", "7", "7"],
+                "gas_used_ratio": [0.5, 0.5],
+                "reward": [["10", "50", "90"], ["10", "50", "90"]],
+            })
+        );
+
+        for path in [
+            "/fee-history?blocks=2&percentiles=10,150",
+            "/fee-history?blocks=2&percentiles=-5",
+            "/fee-history?blocks=2000",
+        ] {
+            let resp = request().path(path).reply(&api).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_routes_with_their_own_query_accept_global_parameters() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        // An unreachable feed only leaves the fiat value out.
+        let feed = PriceFeed::new("http://127.0.0.1:9/{currency}", Duration::from_secs(60));
+        let api = setup_routes(AppState {
+            price_feed: Some(Arc::new(feed)),
+            ..AppState::new(provider)
+        });
+
+        for path in [
+            "/fee-history?blocks=2&jsonrpc=2.0&debug=true&block=latest".to_string(),
+            format!(
+                "/balance/{}/fiat?currency=usd&block=latest&unit=wei",
+                ADDRESS
+            ),
+        ] {
+            let resp = request().path(&path).reply(&api).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_disabled_routes_are_not_found() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState {
+            route_flags: RouteFlags::only(["balance"]).unwrap(),
+            ..AppState::new(provider)
+        });
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/balance/{}", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/nonce/{}", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = request().method("GET").path("/health").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_balance_queries_the_provider_once() {
+        let mock = Arc::new(
+            testing::MockProviderBuilder::new()
+                .balance(
+                    address!("1111111111111111111111111111111111111111"),
+                    U256::from(42),
+                )
+                .build(),
+        );
+        let api = setup_routes(AppState::new(mock.clone()));
+
+        let resp = request()
+            .method("GET")
+            .path("/balance/0x1111111111111111111111111111111111111111")
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(String::from_utf8_lossy(resp.body()).contains("42"));
+        mock.assert_calls("eth_getBalance", 1);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_reads_eip1967_slots() {
+        let proxy = address!("1111111111111111111111111111111111111111");
+        let implementation = address!("bebebebebebebebebebebebebebebebebebebebe");
+        let admin = address!("adadadadadadadadadadadadadadadadadadadad");
+        let word = |address: Address| U256::from_be_slice(address.as_slice());
+        let mock = Arc::new(
+            testing::MockProviderBuilder::new()
+                .storage(
+                    proxy,
+                    EIP1967_IMPLEMENTATION_SLOT.into(),
+                    word(implementation),
+                )
+                .storage(proxy, EIP1967_ADMIN_SLOT.into(), word(admin))
+                .build(),
+        );
+        let api = setup_routes(AppState::new(mock.clone()));
+
+        let resp = request()
+            .path(&format!("/proxy/{}", proxy))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "proxy": true,
+                "implementation": "0xbebebebebebebebebebebebebebebebebebebebe",
+                "admin": "0xadadadadadadadadadadadadadadadadadadadad",
+            })
+        );
+        mock.assert_calls("eth_getStorageAt", 2);
+
+        let resp = request()
+            .path(&format!("/proxy/{}", ADDRESS))
+            .reply(&api)
+            .await;
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({ "proxy": false, "implementation": null, "admin": null })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_maximum_balance_is_written_exactly() {
+        let holder = address!("1111111111111111111111111111111111111111");
+        let mock = testing::MockProviderBuilder::new()
+            .balance(holder, U256::MAX)
+            .build();
+        let api = setup_routes(AppState::new(Arc::new(mock)));
+
+        for (unit, expected) in [
+            (
+                "wei",
+                "115792089237316195423570985008687907853269984665640564039457584007913129639935",
+            ),
+            (
+                "ether",
+                "115792089237316195423570985008687907853269984665640564039457.584007913129639935",
+            ),
+        ] {
+            let resp = request()
+                .method("GET")
+                .path(&format!("/balance/{}?unit={}", holder, unit))
+                .reply(&api)
+                .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            assert_eq!(body["balance"], expected, "{}", unit);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_otel_metrics_record_request_latency() {
+        let reader = telemetry::tests::SharedReader::default();
+        let (_meter_provider, request_metrics) = telemetry::tests::request_metrics(&reader);
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let metrics = Arc::new(Metrics::new());
+        let api = setup_routes(AppState {
+            metrics: metrics.clone(),
+            request_metrics: Some(Arc::new(request_metrics)),
+            ..AppState::new(provider)
+        });
+
+        let resp = request()
+            .path(&format!("/balance/{}", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let (count, seconds) = reader.histogram("http.server.request.duration").unwrap();
+        assert_eq!(count, 1);
+        assert!(seconds > 0.0);
+        // The Prometheus counters are recorded as before.
+        assert_eq!(metrics.snapshot().total_requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_tracer_init_still_serves_balances() {
+        let exporter = Err(TraceError::from("collector unreachable"));
+        assert!(telemetry::install_tracer(exporter).is_none());
+
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+        let resp = request()
+            .method("GET")
+            .path(&format!("/balance/{}", ADDRESS))
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(String::from_utf8_lossy(resp.body()).contains("1000"));
+    }
+
+    #[tokio::test]
+    async fn test_multichain_balance_reports_failing_chains() {
+        let chains = Arc::new(ChainRegistry::new(vec![
+            Chain::new(DEFAULT_CHAIN, Arc::new(BalanceProvider(1000))),
+            Chain::new("sepolia", Arc::new(FailingProvider(-32000))),
+        ]));
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState {
+            chains,
+            ..AppState::new(provider)
+        });
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/multichain/balance/{}", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["default"], "1000");
+        assert_eq!(
+            body["sepolia"]["error"],
+            "Upstream error -32000: upstream failure"
+        );
+
+        let resp = request()
+            .method("GET")
+            .path("/multichain/balance/0x1234")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_caip_balance_routes_by_chain_id() {
+        let mainnet = testing::MockProviderBuilder::new()
+            .chain_id(1)
+            .balance(
+                address!("1111111111111111111111111111111111111111"),
+                U256::from(7),
+            )
+            .build();
+        let chains = Arc::new(ChainRegistry::new(vec![
+            Chain::new(DEFAULT_CHAIN, Arc::new(ChainIdProvider(31337))),
+            Chain::new("mainnet", Arc::new(mainnet)),
+        ]));
+        chains.check_health().await;
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState {
+            chains,
+            ..AppState::new(provider)
+        });
+
+        let resp = request()
+            .method("GET")
+            .path("/caip/balance/eip155:1:0x1111111111111111111111111111111111111111")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["chain"], "mainnet");
+        assert_eq!(body["balance"], "7");
+        assert_eq!(
+            body["account"],
+            "eip155:1:0x1111111111111111111111111111111111111111"
+        );
+
+        for account in [
+            "eip155:5:0x1111111111111111111111111111111111111111",
+            "solana:mainnet:0x1111111111111111111111111111111111111111",
+        ] {
+            let resp = request()
+                .method("GET")
+                .path(&format!("/caip/balance/{}", account))
+                .reply(&api)
+                .await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", account);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_are_counted_by_route() {
+        let metrics = Arc::new(Metrics::new());
+        let retry = retry::RetryConfig {
+            max_retries: 3,
+            backoff: Duration::from_millis(1),
+        };
+        let client = ClientBuilder::default()
+            .layer(RetryLayer::new(Some(retry), metrics.clone()))
+            .transport(retry::tests::FlakyTransport::new(1), true);
+        let provider: Arc<dyn Provider> = Arc::new(ProviderBuilder::new().on_client(client));
+        let api = setup_routes(AppState {
+            metrics: metrics.clone(),
+            ..AppState::new(provider)
+        });
+
+        // Test bodies run in `block_on`, which has no task id to attribute retries by.
+        let resp = tokio::spawn({
+            let api = api.clone();
+            async move {
+                request()
+                    .method("GET")
+                    .path(&format!("/balance/{}", ADDRESS))
+                    .reply(&api)
+                    .await
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = request().method("GET").path("/metrics").reply(&api).await;
+        let body = String::from_utf8_lossy(resp.body());
+        assert!(body.contains("rpc_retry_attempts_total{route=\"balance\"} 1"));
+        assert!(body.contains("rpc_retry_exhausted_total{route=\"balance\"} 0"));
+    }
+
+    #[tokio::test]
+    async fn test_root_lists_endpoints() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request().method("GET").path("/").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        let endpoints = body["endpoints"].as_array().unwrap();
+        assert!(endpoints
+            .contains(&serde_json::json!({ "method": "GET", "path": "/balance/{address}" })));
+        assert!(!endpoints.iter().any(|endpoint| endpoint["path"] == "/ui"));
+
+        let api = setup_routes(AppState {
+            ui: Some(ui::UiConfig::default()),
+            ..AppState::new(Arc::new(DummyProvider))
+        });
+        let resp = request().method("GET").path("/").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(resp.headers()["location"], "/ui");
+    }
+
+    #[tokio::test]
+    async fn test_balance_at_timestamp() {
+        let provider: Arc<dyn Provider> = Arc::new(block_time::tests::TimestampProvider::default());
+        let api = setup_routes(AppState::new(provider));
+
+        // Block 250 was mined 3000 seconds after genesis, block 251 12 seconds later.
+        let timestamp = block_time::tests::GENESIS_TIME + 3005;
+        let resp = request()
+            .method("GET")
+            .path(&format!("/balance/{}/at?timestamp={}", ADDRESS, timestamp))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "block": 250,
+                "block_timestamp": block_time::tests::GENESIS_TIME + 3000,
+                "balance": "250",
+            })
+        );
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/balance/{}/at?timestamp=1", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    /// Keeps the spans it is given.
+    #[derive(Debug, Clone, Default)]
+    struct CollectingExporter(Arc<std::sync::Mutex<Vec<opentelemetry_sdk::trace::SpanData>>>);
+
+    impl opentelemetry_sdk::trace::SpanExporter for CollectingExporter {
+        fn export(
+            &mut self,
+            batch: Vec<opentelemetry_sdk::trace::SpanData>,
+        ) -> futures::future::BoxFuture<'static, opentelemetry_sdk::error::OTelSdkResult> {
+            self.0.lock().unwrap().extend(batch);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_spans_record_the_status() {
+        use opentelemetry::trace::TracerProvider;
+
+        let exporter = CollectingExporter::default();
+        let tracer_provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState {
+            tracer: Arc::new(BoxedTracer::new(Box::new(tracer_provider.tracer("test")))),
+            ..AppState::new(provider)
+        });
+
+        let resp = request()
+            .method("GET")
+            .path("/balance/not-an-address")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let spans = exporter.0.lock().unwrap();
+        let span = spans
+            .iter()
+            .find(|span| span.name == "GET balance")
+            .expect("request span exported");
+        let status = span
+            .attributes
+            .iter()
+            .find(|attribute| attribute.key.as_str() == "http.status_code")
+            .map(|attribute| attribute.value.clone());
+        assert_eq!(status, Some(opentelemetry::Value::I64(400)));
+    }
+
+    #[tokio::test]
+    async fn test_balance_in_binary_formats() {
+        let provider: Arc<dyn Provider> = Arc::new(
+            testing::MockProviderBuilder::new()
+                .balance(Address::ZERO, U256::from(1000))
+                .build(),
+        );
+        let api = setup_routes(AppState::new(provider));
+        let balance = |accept: &'static str| {
+            request()
+                .method("GET")
+                .path("/balance/0x0000000000000000000000000000000000000000")
+                .header("accept", accept)
+                .reply(&api)
+        };
+
+        let json = balance("application/json").await;
+        assert_eq!(json.headers()["content-type"], "application/json");
+        let expected: serde_json::Value = serde_json::from_slice(json.body()).unwrap();
+        assert_eq!(expected["balance"], "1000");
+
+        let msgpack = balance("application/msgpack").await;
+        assert_eq!(msgpack.status(), StatusCode::OK);
+        assert_eq!(msgpack.headers()["content-type"], "application/msgpack");
+        let decoded: serde_json::Value = rmp_serde::from_slice(msgpack.body()).unwrap();
+        assert_eq!(decoded, expected);
+
+        let cbor = balance("application/cbor").await;
+        assert_eq!(cbor.headers()["content-type"], "application/cbor");
+        let decoded: serde_json::Value = ciborium::from_reader(cbor.body().as_ref()).unwrap();
+        assert_eq!(decoded, expected);
+    }
+
+    #[tokio::test]
+    async fn test_tx_decode_reads_the_called_function() {
+        let abi = serde_json::json!([{
+            "type": "function",
+            "name": "transfer",
+            "stateMutability": "nonpayable",
+            "inputs": [
+                { "name": "to", "type": "address" },
+                { "name": "amount", "type": "uint256" }
+            ],
+            "outputs": [{ "name": "", "type": "bool" }]
+        }]);
+        let recipient = "0x2222222222222222222222222222222222222222";
+        let parsed: JsonAbi = serde_json::from_value(abi.clone()).unwrap();
+        let (_, input) = abi_call::encode_call(
+            &parsed,
+            "transfer",
+            &[serde_json::json!(recipient), serde_json::json!("1000")],
+        )
+        .unwrap();
+        let tx = dummy_call(input.clone().into());
+        let hash = tx.inner.tx_hash().to_string();
+        let mock = Arc::new(testing::MockProviderBuilder::new().transaction(tx).build());
+        let api = setup_routes(AppState::new(mock.clone()));
+        let decode = |hash: &str, abi: &serde_json::Value| {
+            request()
+                .method("POST")
+                .path(&format!("/tx/{}/decode", hash))
+                .json(&serde_json::json!({ "abi": abi }))
+                .reply(&api)
+        };
+
+        let resp = decode(&hash, &abi).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "hash": hash,
+                "function": "transfer",
+                "signature": "transfer(address,uint256)",
+                "args": [
+                    { "name": "to", "type": "address", "value": recipient },
+                    { "name": "amount", "type": "uint256", "value": "1000" }
+                ],
+                "input": Bytes::from(input.clone()),
+            })
+        );
+        mock.assert_calls("eth_getTransactionByHash", 1);
+
+        // Unknown selectors come back as the raw input.
+        let resp = decode(&hash, &serde_json::json!([])).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert!(body["function"].is_null());
+        assert!(body["args"].is_null());
+        assert_eq!(body["input"], serde_json::json!(Bytes::from(input)));
+
+        let resp = decode(BLOCK_HASH, &abi).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_call_fn_decodes_a_getter() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+        let abi = serde_json::json!([{
+            "type": "function",
+            "name": "totalSupply",
+            "stateMutability": "view",
+            "inputs": [],
+            "outputs": [{ "name": "supply", "type": "uint256" }]
+        }]);
+        let call = |function: &str| {
+            request()
+                .method("POST")
+                .path("/call-fn")
+                .json(&serde_json::json!({
+                    "to": "0x7070707070707070707070707070707070707070",
+                    "abi": abi,
+                    "function": function,
+                }))
+                .reply(&api)
+        };
+
+        let resp = call("totalSupply").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "function": "totalSupply()",
+                "outputs": [{ "name": "supply", "type": "uint256", "value": "5000" }]
+            })
+        );
+
+        let resp = call("decimals").await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_xpub_balances_by_derivation_index() {
+        use xpub::tests::{ADDRESSES, XPUB};
+
+        let provider: Arc<dyn Provider> = Arc::new(
+            testing::MockProviderBuilder::new()
+                .balance(ADDRESSES[0], U256::from(1000))
+                .balance(ADDRESSES[2], U256::from(3000))
+                .build(),
+        );
+        let api = setup_routes(AppState::new(provider));
+        let balances = |query: String| request().method("GET").path(&query).reply(&api);
+
+        let resp = balances(format!("/xpub/{}/balances?count=3", XPUB)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!([
+                { "index": 0, "address": ADDRESSES[0], "balance": "1000" },
+                { "index": 1, "address": ADDRESSES[1], "balance": "0" },
+                { "index": 2, "address": ADDRESSES[2], "balance": "3000" },
+            ])
+        );
+
+        let resp = balances(format!("/xpub/{}/balances", XPUB)).await;
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 20);
+
+        let resp = balances(format!("/xpub/{}/balances?count=101", XPUB)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = balances("/xpub/xpub123/balances".to_string()).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// Never answers balance queries; every call holds a clone of `calls`
+    /// until it is dropped.
+    struct HangingProvider {
+        calls: Arc<()>,
+    }
+
+    impl Provider for HangingProvider {
+        fn get_balance(&self, _address: Address) -> RpcWithBlock<Address, U256> {
+            let calls = self.calls.clone();
+            RpcWithBlock::new_provider(move |_block_id| {
+                let call = calls.clone();
+                ProviderCall::BoxedFuture(Box::pin(async move {
+                    let _call = call;
+                    std::future::pending().await
+                }))
+            })
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("HangingProvider does not support `root`")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requests_beyond_the_inflight_limit_are_shed() {
+        let calls = Arc::new(());
+        let provider: Arc<dyn Provider> = Arc::new(HangingProvider {
+            calls: calls.clone(),
+        });
+        let api = setup_routes(AppState {
+            inflight_limit: Some(Arc::new(InflightLimit::new(2))),
+            ..AppState::new(provider)
+        });
+
+        let hanging: Vec<_> = (0..2)
+            .map(|_| {
+                let api = api.clone();
+                tokio::spawn(async move {
+                    request()
+                        .path(&format!("/balance/{}", ADDRESS))
+                        .reply(&api)
+                        .await
+                })
+            })
+            .collect();
+        // One reference is ours and one the provider's; the rest are node calls.
+        for _ in 0..200 {
+            if Arc::strong_count(&calls) - 2 == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(Arc::strong_count(&calls) - 2, 2);
+
+        // Shed at once, even on a route that never reaches the node.
+        let path = format!("/validate/{}", ADDRESS);
+        let resp = request().path(&path).reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers()["retry-after"], "1");
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["error"], "Server is overloaded");
+        assert_eq!(body["error_type"], "upstream_unavailable");
+        let resp = request().path("/health").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        for task in hanging {
+            task.abort();
+            let _ = task.await;
+        }
+        let resp = request().path(&path).reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requests_past_the_deadline_are_answered_504() {
+        let calls = Arc::new(());
+        let provider: Arc<dyn Provider> = Arc::new(HangingProvider {
+            calls: calls.clone(),
+        });
+        let metrics = Arc::new(Metrics::new());
+        let api = setup_routes(AppState {
+            request_deadline: Some(Duration::from_millis(500)),
+            metrics: metrics.clone(),
+            ..AppState::new(provider)
+        });
+
+        // Every address is its own node call, all bounded by the one deadline.
+        let resp = request()
+            .method("POST")
+            .path("/balance/batch")
+            .json(&serde_json::json!({ "addresses": [ADDRESS, CONTRACT] }))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert!(resp.headers().contains_key(REQUEST_ID_HEADER));
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["error"], "Request deadline of 500ms exceeded");
+        assert_eq!(body["error_type"], "timeout");
+        // One reference is ours and one the provider's: the calls were dropped
+        // once the aborted request task got to run.
+        tokio::task::yield_now().await;
+        assert_eq!(Arc::strong_count(&calls), 2);
+        let snapshot = metrics.snapshot();
+        assert_eq!((snapshot.total_requests, snapshot.errors), (1, 1));
+
+        let path = format!("/balance/{}?jsonrpc=2.0", ADDRESS);
+        let resp = request().path(&path).reply(&api).await;
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body["error"]["message"],
+            "Request deadline of 500ms exceeded"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_requests_under_a_deadline_keep_their_timings_and_retries() {
+        let metrics = Arc::new(Metrics::new());
+        let timings = Arc::new(RpcTimings::new());
+        let retry = retry::RetryConfig {
+            max_retries: 3,
+            backoff: Duration::from_millis(5),
+        };
+        // Timed around the retries, so the backoff makes the node time non-zero.
+        let client = ClientBuilder::default()
+            .layer(ServerTimingLayer::new(timings.clone()))
+            .layer(RetryLayer::new(Some(retry), metrics.clone()))
+            .transport(retry::tests::FlakyTransport::new(1), true);
+        let provider: Arc<dyn Provider> = Arc::new(ProviderBuilder::new().on_client(client));
+        let api = setup_routes(AppState {
+            request_deadline: Some(Duration::from_secs(10)),
+            metrics: metrics.clone(),
+            timings,
+            ..AppState::new(provider)
+        });
+
+        // Test bodies run in `block_on`, which has no task id to attribute calls by.
+        let resp = tokio::spawn({
+            let api = api.clone();
+            async move {
+                request()
+                    .path(&format!("/balance/{}?debug=true", ADDRESS))
+                    .reply(&api)
+                    .await
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let header = resp.headers()[SERVER_TIMING_HEADER].to_str().unwrap();
+        let rpc: f64 = header
+            .strip_prefix("rpc;dur=")
+            .and_then(|rest| rest.split(',').next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(rpc >= 5.0, "{}", header);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert!(body["_debug"]["rpc_latency_ms"].as_u64().unwrap() >= 5);
+
+        let retries = metrics.retries();
+        assert_eq!(retries["balance"].attempts, 1);
+        assert!(!retries.contains_key("other"));
+    }
+
+    #[tokio::test]
+    async fn test_disconnected_client_cancels_the_node_call() {
+        use tokio::io::AsyncWriteExt;
+
+        let calls = Arc::new(());
+        let provider: Arc<dyn Provider> = Arc::new(HangingProvider {
+            calls: calls.clone(),
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let routes = setup_routes(AppState::new(provider)).with(setup_cors());
+        tokio::spawn(server::serve(
+            routes,
+            listener,
+            server::ServerConfig::default(),
+        ));
+
+        // One reference is ours and one the provider's; the rest are node calls.
+        let in_flight = || Arc::strong_count(&calls) - 2;
+        let wait_for = |expected: usize| async move {
+            for _ in 0..200 {
+                if in_flight() == expected {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            panic!(
+                "{} node calls in flight, expected {}",
+                in_flight(),
+                expected
+            );
+        };
+
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET /balance/{} HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            ADDRESS
+        );
+        client.write_all(request.as_bytes()).await.unwrap();
+        wait_for(1).await;
+
+        drop(client);
+        wait_for(0).await;
+    }
+
+    /// Has three transactions of every address queued behind its 7 mined ones.
+    struct QueuedNonceProvider;
+
+    impl Provider for QueuedNonceProvider {
+        fn get_transaction_count(&self, _address: Address) -> RpcWithBlock<Address, U64, u64> {
+            RpcWithBlock::new_provider(|block_id| {
+                let pending = block_id == BlockId::pending();
+                ProviderCall::ready(Ok(if pending { 10 } else { 7 }))
+            })
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("QueuedNonceProvider does not support `root`")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nonce_gap() {
+        let gap = |provider: Arc<dyn Provider>| async move {
+            let api = setup_routes(AppState::new(provider));
+            let resp = request()
+                .method("GET")
+                .path(&format!("/nonce/{}/gap", ADDRESS))
+                .reply(&api)
+                .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            serde_json::from_slice::<serde_json::Value>(resp.body()).unwrap()
+        };
+
+        assert_eq!(
+            gap(Arc::new(QueuedNonceProvider)).await,
+            serde_json::json!({ "latest": 7, "pending": 10, "gap": 3 })
+        );
+        assert_eq!(gap(Arc::new(DummyProvider)).await["gap"], 0);
+
+        let api = setup_routes(AppState::new(Arc::new(QueuedNonceProvider)));
+        let resp = request()
+            .method("GET")
+            .path("/nonce/0x1234/gap")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// Knows `dummy_transaction` only from its mempool.
+    struct MempoolProvider;
+
+    impl Provider for MempoolProvider {
+        fn get_transaction_by_hash(
+            &self,
+            _hash: B256,
+        ) -> ProviderCall<(B256,), Option<Transaction>> {
+            let mut tx = dummy_transaction();
+            tx.block_number = None;
+            tx.transaction_index = None;
+            ProviderCall::ready(Ok(Some(tx)))
+        }
+
+        fn get_transaction_receipt(
+            &self,
+            _hash: B256,
+        ) -> ProviderCall<(B256,), Option<TransactionReceipt>> {
+            ProviderCall::ready(Ok(None))
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("MempoolProvider does not support `root`")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tx_status_states() {
+        let hash = *dummy_transaction().inner.tx_hash();
+        let status = |provider: Arc<dyn Provider>, hash: String| async move {
+            let api = setup_routes(AppState::new(provider));
+            let resp = request()
+                .method("GET")
+                .path(&format!("/tx/{}/status", hash))
+                .reply(&api)
+                .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            serde_json::from_slice::<serde_json::Value>(resp.body()).unwrap()
+        };
+
+        let mined = status(Arc::new(DummyProvider), hash.to_string()).await;
+        assert_eq!(mined["status"], "mined");
+        assert_eq!(mined["block_number"], 1000);
+
+        let pending = status(Arc::new(MempoolProvider), hash.to_string()).await;
+        assert_eq!(
+            pending,
+            serde_json::json!({ "hash": hash, "status": "pending" })
+        );
+
+        let unknown = status(Arc::new(DummyProvider), B256::repeat_byte(0x99).to_string()).await;
+        assert_eq!(unknown["status"], "unknown");
+    }
+
+    #[tokio::test]
+    async fn test_route_prefix() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let metrics = Arc::new(Metrics::new());
+        let api = setup_routes(AppState {
+            route_prefix: RoutePrefix::new("/api/v1", true).unwrap(),
+            metrics: metrics.clone(),
+            ..AppState::new(provider.clone())
+        });
+        let status = |path: String| {
+            let api = api.clone();
+            async move {
+                request()
+                    .method("GET")
+                    .path(&path)
+                    .reply(&api)
+                    .await
+                    .status()
+            }
+        };
+
+        assert_eq!(status("/api/v1/health".to_string()).await, StatusCode::OK);
+        assert_eq!(status("/health".to_string()).await, StatusCode::NOT_FOUND);
+        assert_eq!(
+            status(format!("/api/v1/balance/{}", ADDRESS)).await,
+            StatusCode::OK
+        );
+        // Routes are still labelled by their own first segment.
+        assert_eq!(metrics.snapshot().requests_by_route["balance"], 1);
+        assert_eq!(
+            status(format!("/balance/{}", ADDRESS)).await,
+            StatusCode::NOT_FOUND
+        );
+
+        let resp = request().method("GET").path("/api/v1").reply(&api).await;
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["endpoints"][2]["path"], "/api/v1/balance/{address}");
+
+        // `/health` can stay at the root for probes.
+        let api = setup_routes(AppState {
+            route_prefix: RoutePrefix::new("/api/v1", false).unwrap(),
+            ..AppState::new(provider)
+        });
+        let resp = request().method("GET").path("/health").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = request()
+            .method("GET")
+            .path("/api/v1/health")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_get_receipt() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+        let hash = dummy_transaction().inner.tx_hash().to_string();
+
+        let resp = request()
+            .path(&format!("/receipt/{}", hash))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["transaction_hash"], hash);
+        assert_eq!(body["status"], true);
+
+        let resp = request()
+            .path(&format!("/receipt/{}", BLOCK_HASH))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_receipts_batch_keeps_request_order() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+        let mined = dummy_transaction().inner.tx_hash().to_string();
+
+        let resp = request()
+            .method("POST")
+            .path("/receipts")
+            .json(&serde_json::json!({ "hashes": [BLOCK_HASH, mined, mined] }))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        let receipts = body.as_array().unwrap();
+        assert_eq!(receipts.len(), 3);
+        assert!(receipts[0].is_null());
+        assert_eq!(receipts[1]["transaction_hash"], mined);
+        assert_eq!(receipts[1]["status"], true);
+        assert_eq!(receipts[2], receipts[1]);
+
+        let too_many = vec![BLOCK_HASH; MAX_BATCH_RECEIPTS + 1];
+        for body in [
+            serde_json::json!({ "hashes": too_many }),
+            serde_json::json!({ "hashes": ["0x1234"] }),
+        ] {
+            let resp = request()
+                .method("POST")
+                .path("/receipts")
+                .json(&body)
+                .reply(&api)
+                .await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_not_found_receipts_are_cached() {
+        let counting = Arc::new(ReceiptPollProvider::default());
+        let provider: Arc<dyn Provider> = counting.clone();
+        let api = setup_routes(AppState {
+            not_found_cache: Arc::new(NotFoundCache::new(Duration::from_secs(2))),
+            ..AppState::new(provider)
+        });
+        let path = format!("/receipt/{}", BLOCK_HASH);
+
+        for _ in 0..2 {
+            let resp = request().path(&path).reply(&api).await;
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        }
+        assert_eq!(counting.0.load(Ordering::SeqCst), 1);
+
+        // With the cache disabled every poll reaches the node.
+        let uncached = setup_routes(AppState::new(counting.clone()));
+        request().path(&path).reply(&uncached).await;
+        assert_eq!(counting.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_receipt_found_clears_not_found_entry() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let cache = Arc::new(NotFoundCache::new(Duration::from_secs(2)));
+        let hash = *dummy_transaction().inner.tx_hash();
+        cache.insert(("receipt", hash), ());
+        let api = setup_routes(AppState {
+            not_found_cache: cache.clone(),
+            ..AppState::new(provider)
+        });
+
+        // The transaction turned out to be mined, so its receipt is no longer cached as missing.
+        let resp = request().path(&format!("/tx/{}", hash)).reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(cache.get(&("receipt", hash)).is_none());
+        let resp = request()
+            .path(&format!("/receipt/{}", hash))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_send_raw_replays_idempotent_requests() {
+        let broadcaster = Arc::new(BroadcastProvider::new());
+        let provider: Arc<dyn Provider> = broadcaster.clone();
+        let api = setup_routes(AppState {
+            idempotency_cache: Arc::new(IdempotencyCache::new(Duration::from_secs(60))),
+            ..AppState::new(provider)
+        });
+        let send = |key: &'static str, tx: &'static str| {
+            request()
+                .method("POST")
+                .path("/send-raw")
+                .header("idempotency-key", key)
+                .json(&serde_json::json!({ "tx": tx }))
+                .reply(&api)
+        };
+
+        let first = send("order-42", "0x01020304").await;
+        let replay = send("order-42", "0x01020304").await;
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(replay.status(), StatusCode::OK);
+        assert_eq!(first.body(), replay.body());
+        assert_eq!(broadcaster.broadcasts.load(Ordering::SeqCst), 1);
+        let body: serde_json::Value = serde_json::from_slice(first.body()).unwrap();
+        assert_eq!(
+            body["tx_hash"],
+            keccak256([1u8, 2, 3, 4]).to_string().as_str()
+        );
+
+        // The same key cannot be reused for another transaction.
+        let conflict = send("order-42", "0x05").await;
+        assert_eq!(conflict.status(), StatusCode::BAD_REQUEST);
+
+        let other = send("order-43", "0x01020304").await;
+        assert_eq!(other.status(), StatusCode::OK);
+        assert_eq!(broadcaster.broadcasts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_send_raw_broadcasts_concurrent_same_key_requests_once() {
+        let broadcaster = Arc::new(BroadcastProvider::slow(Duration::from_millis(50)));
+        let provider: Arc<dyn Provider> = broadcaster.clone();
+        let api = setup_routes(AppState {
+            idempotency_cache: Arc::new(IdempotencyCache::new(Duration::from_secs(60))),
+            ..AppState::new(provider)
+        });
+        let send = || {
+            request()
+                .method("POST")
+                .path("/send-raw")
+                .header("idempotency-key", "order-42")
+                .json(&serde_json::json!({ "tx": "0x01020304" }))
+                .reply(&api)
+        };
+
+        let (first, second) = futures::join!(send(), send());
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(second.status(), StatusCode::OK);
+        assert_eq!(first.body(), second.body());
+        assert_eq!(broadcaster.broadcasts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_raw_without_key_always_broadcasts() {
+        let broadcaster = Arc::new(BroadcastProvider::new());
+        let provider: Arc<dyn Provider> = broadcaster.clone();
+        let api = setup_routes(AppState {
+            idempotency_cache: Arc::new(IdempotencyCache::new(Duration::from_secs(60))),
+            ..AppState::new(provider)
+        });
+
+        for _ in 0..2 {
+            let resp = request()
+                .method("POST")
+                .path("/send-raw")
+                .json(&serde_json::json!({ "tx": "0x01" }))
+                .reply(&api)
+                .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+        assert_eq!(broadcaster.broadcasts.load(Ordering::SeqCst), 2);
+
+        let resp = request()
+            .method("POST")
+            .path("/send-raw")
+            .json(&serde_json::json!({ "tx": "0x01", "gas": 1 }))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_logs_stream_as_ndjson() {
+        let api = setup_routes(AppState {
+            logs: LogsConfig {
+                max_range: 4,
+                auto_chunk: true,
+                chunk_size: 3,
+                max_chunks: 5,
+            },
+            ..AppState::new(Arc::new(logs::tests::BlockLogsProvider::default()))
+        });
+
+        let resp = request()
+            .method("GET")
+            .path("/logs?from_block=0&to_block=9")
+            .header("accept", "application/x-ndjson")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["content-type"], "application/x-ndjson");
+        let body = std::str::from_utf8(resp.body()).unwrap();
+        assert!(body.ends_with('\n'));
+        let blocks: Vec<u64> = body
+            .lines()
+            .map(|line| {
+                let log: serde_json::Value = serde_json::from_str(line).unwrap();
+                assert!(log.is_object(), "{}", line);
+                log["block_number"].as_u64().unwrap()
+            })
+            .collect();
+        assert_eq!(blocks, (0..=9).collect::<Vec<_>>());
+
+        // Invalid ranges are still rejected before anything is streamed.
+        let resp = request()
+            .method("GET")
+            .path("/logs?from_block=9&to_block=0")
+            .header("accept", "application/x-ndjson")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_logs_rejects_wide_ranges_without_auto_chunk() {
+        let api = setup_routes(AppState {
+            logs: LogsConfig {
+                max_range: 10,
+                ..LogsConfig::default()
+            },
+            ..AppState::new(Arc::new(DummyProvider))
+        });
+
+        let resp = request()
+            .method("GET")
+            .path("/logs?from_block=0&to_block=10")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert!(body["error"].as_str().unwrap().contains("maximum of 10"));
+
+        let resp = request()
+            .method("GET")
+            .path("/logs?from_block=0&to_block=5&topic0=0x12")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_validate_does_not_need_the_node() {
+        let api = setup_routes(AppState {
+            readiness: Arc::new(Readiness::new(false)),
+            ..AppState::new(Arc::new(FailingProvider(-32000)))
+        });
+
+        let resp = request()
+            .path("/validate/0xd8da6bf26964af9d7eed9e03e53415d37aa96045")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "valid": true,
+                "checksummed": "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045",
+            })
+        );
+
+        let resp = request()
+            .path("/validate/0xD8da6BF26964aF9D7eEd9e03E53415D37aA96045")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["valid"], false);
+        assert!(body["reason"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_trace_filter_is_disabled_by_default() {
+        let api = setup_routes(AppState::new(Arc::new(DummyProvider)));
+        let resp = request()
+            .method("POST")
+            .path("/trace-filter")
+            .json(&serde_json::json!({ "from_block": 1, "to_block": 2 }))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let api = setup_routes(AppState {
+            trace: Some(TraceConfig { max_range: 10 }),
+            ..AppState::new(Arc::new(DummyProvider))
+        });
+        let resp = request()
+            .method("POST")
+            .path("/trace-filter")
+            .json(&serde_json::json!({ "from_block": 1, "to_block": 20 }))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_responses_carry_server_timing() {
+        let api = setup_routes(AppState::new(Arc::new(DummyProvider)));
+
+        for path in [format!("/balance/{}", ADDRESS), "/nope".to_string()] {
+            let resp = request().path(&path).reply(&api).await;
+            let header = resp.headers()[SERVER_TIMING_HEADER].to_str().unwrap();
+
+            let metrics: Vec<(&str, f64)> = header
+                .split(", ")
+                .map(|metric| {
+                    let (name, dur) = metric.split_once(";dur=").unwrap();
+                    (name, dur.parse().unwrap())
+                })
+                .collect();
+            assert_eq!(metrics.len(), 2, "{}", header);
+            assert_eq!(metrics[0].0, "rpc");
+            assert_eq!(metrics[1].0, "total");
+            assert!(metrics[0].1 <= metrics[1].1, "{}", header);
+        }
+    }
+
+    #[test]
+    fn test_resolve_rpc_url_lenient() {
+        let url = "https://eth.example".to_string();
+        assert_eq!(resolve_rpc_url(Some(url.clone()), false).unwrap(), url);
+        assert_eq!(resolve_rpc_url(None, false).unwrap(), DEFAULT_RPC_URL);
+        assert_eq!(
+            resolve_rpc_url(Some(" ".to_string()), false).unwrap(),
+            DEFAULT_RPC_URL
+        );
+    }
+
+    #[test]
+    fn test_resolve_rpc_url_strict() {
+        let url = "https://eth.example".to_string();
+        assert_eq!(resolve_rpc_url(Some(url.clone()), true).unwrap(), url);
+        let error = resolve_rpc_url(None, true).unwrap_err();
+        assert!(error.to_string().contains("REQUIRE_RPC_URL"));
+        assert!(resolve_rpc_url(Some(String::new()), true).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ui_route_follows_configuration() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let disabled = setup_routes(AppState::new(provider.clone()));
+        let enabled = setup_routes(AppState {
+            ui: Some(ui::UiConfig::default()),
+            ..AppState::new(provider)
+        });
+
+        let resp = request().method("GET").path("/ui").reply(&disabled).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = request().method("GET").path("/ui").reply(&enabled).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+    }
+
+    #[tokio::test]
+    async fn test_stats_reports_circuit_breaker_state() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let breaker = Arc::new(CircuitBreaker::new(circuit_breaker::CircuitBreakerConfig {
+            failure_threshold: 1,
+            cooldown: Duration::from_secs(30),
+        }));
+        let api = setup_routes(AppState {
+            breaker: Some(breaker.clone()),
+            ..AppState::new(provider)
+        });
+
+        let resp = request().method("GET").path("/stats").reply(&api).await;
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["circuit_breaker"], "closed");
+
+        breaker.record_failure();
+        let resp = request().method("GET").path("/stats").reply(&api).await;
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["circuit_breaker"], "open");
+    }
+
+    #[tokio::test]
+    async fn test_stats_reports_background_tasks() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let supervisor = Arc::new(Supervisor::new(Duration::ZERO, Duration::ZERO));
+        let api = setup_routes(AppState {
+            supervisor: supervisor.clone(),
+            ..AppState::new(provider)
+        });
+
+        let resp = request().method("GET").path("/stats").reply(&api).await;
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert!(body.get("background_tasks").is_none());
 
-    use std::sync::Arc;
-    use warp::http::StatusCode;
-    use warp::test::request;
+        supervisor.spawn("idle", std::future::pending::<()>);
+        let resp = request().method("GET").path("/stats").reply(&api).await;
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body["background_tasks"]["idle"],
+            serde_json::json!({ "state": "running", "restarts": 0 })
+        );
+    }
 
-    use alloy::providers::{Provider, ProviderCall, RootProvider, RpcWithBlock};
+    #[tokio::test]
+    async fn test_top_addresses_surfaces_repeated_queries() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
 
-    use std::str::FromStr;
+        for _ in 0..3 {
+            let path = format!("/balance/{}", CONTRACT);
+            request().method("GET").path(&path).reply(&api).await;
+        }
+        let path = format!("/type/{}", ADDRESS);
+        request().method("GET").path(&path).reply(&api).await;
 
-    struct DummyProvider;
+        let resp = request()
+            .method("GET")
+            .path("/stats/top-addresses?limit=1")
+            .reply(&api)
+            .await;
 
-    impl Provider for DummyProvider {
-        fn get_balance<'a>(
-            &'a self,
-            _address: alloy_primitives::Address,
-        ) -> RpcWithBlock<alloy_primitives::Address, alloy_primitives::Uint<256, 4>> {
-            RpcWithBlock::new_provider(|_block_id| {
-                // Parse "1000" into the expected type.
-                let dummy_balance = alloy_primitives::Uint::<256, 4>::from_str("1000")
-                    .expect("failed to parse dummy balance");
-                ProviderCall::ready(Ok(dummy_balance))
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!([{ "address": CONTRACT.to_lowercase(), "count": 3 }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cors_allows_the_api_request_headers() {
+        let api = setup_routes(AppState::new(Arc::new(DummyProvider))).with(setup_cors());
+        let resp = request()
+            .method("OPTIONS")
+            .path(&format!("/balance/{}", ADDRESS))
+            .header("origin", "https://wallet.example")
+            .header("access-control-request-method", "POST")
+            .header(
+                "access-control-request-headers",
+                "content-encoding, x-api-key, x-rpc-url, x-request-id, idempotency-key",
+            )
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_rpc_url_header_selects_allowlisted_upstream() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let upstreams = UpstreamAllowlist::new(
+            ["http://tenant-node:8545"],
+            Box::new(|_url| Ok(Arc::new(FailingProvider(-32601)) as Arc<dyn Provider>)),
+        );
+        let api = setup_routes(AppState {
+            upstreams: Some(Arc::new(upstreams)),
+            ..AppState::new(provider)
+        });
+        let path = format!("/balance/{}", ADDRESS);
+
+        // Without the header the default provider answers.
+        let resp = request().method("GET").path(&path).reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // The allowlisted upstream is used instead of the default one.
+        let resp = request()
+            .method("GET")
+            .path(&path)
+            .header("x-rpc-url", "http://tenant-node:8545")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_IMPLEMENTED);
+
+        let resp = request()
+            .method("GET")
+            .path(&path)
+            .header("x-rpc-url", "http://elsewhere:8545")
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["error"], "Upstream not allowed: http://elsewhere:8545");
+    }
+
+    #[tokio::test]
+    async fn test_balance_tags() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/balance/{}/tags", ADDRESS))
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({ "latest": "1000", "safe": "1000", "finalized": "1000" })
+        );
+    }
+
+    /// Provider failing balance queries at the `safe` tag with the given error.
+    struct SafeTagErrorProvider(i64, &'static str);
+
+    impl Provider for SafeTagErrorProvider {
+        fn get_balance(&self, _address: Address) -> RpcWithBlock<Address, U256> {
+            let (code, message) = (self.0, self.1);
+            RpcWithBlock::new_provider(move |block_id| {
+                if block_id != BlockId::safe() {
+                    return ProviderCall::ready(Ok(U256::from(1000)));
+                }
+                ProviderCall::ready(Err(RpcError::ErrorResp(ErrorPayload {
+                    code,
+                    message: message.into(),
+                    data: None,
+                })))
             })
         }
 
         fn root(&self) -> &RootProvider {
-            unimplemented!("DummyProvider does not support `root`")
+            unimplemented!("SafeTagErrorProvider does not support `root`")
         }
     }
 
     #[tokio::test]
-    async fn test_get_balance() {
+    async fn test_balance_tags_only_hide_unsupported_tags() {
+        let path = format!("/balance/{}/tags", ADDRESS);
+        for (code, message) in [(-32000, "safe block not found"), (-39001, "Unknown block")] {
+            let provider: Arc<dyn Provider> = Arc::new(SafeTagErrorProvider(code, message));
+            let api = setup_routes(AppState::new(provider));
+            let resp = request().path(&path).reply(&api).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", message);
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            assert_eq!(body["safe"], serde_json::Value::Null);
+            assert_eq!(body["finalized"], "1000");
+        }
+
+        let provider: Arc<dyn Provider> =
+            Arc::new(SafeTagErrorProvider(-32005, "rate limit exceeded"));
+        let api = setup_routes(AppState::new(provider));
+        let resp = request().path(&path).reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_balance_batch_accepts_plain_text() {
         let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
-        let api = warp::path!("balance" / String)
-            .and(warp::get())
-            .and(super::with_provider(provider.clone()))
-            .and_then(get_balance);
+        let api = setup_routes(AppState::new(provider));
+        let body = format!("{}\n\nnot-an-address\n  {}  \n0x1234\n", ADDRESS, CONTRACT);
 
-        // Use a valid dummy Ethereum address.
-        let address = "0x0000000000000000000000000000000000000000";
+        let resp = request()
+            .method("POST")
+            .path("/balance/batch")
+            .header("content-type", "text/plain; charset=utf-8")
+            .body(body)
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+        assert_eq!(resp.headers()[SERVED_BY_HEADER], "default");
+        assert_eq!(resp.body().as_ref(), b"1000\nERROR\n1000\nERROR\n");
+    }
+
+    #[tokio::test]
+    async fn test_balance_batch_preserves_request_order() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+        let third = "0x00000000000000000000000000000000000000ff";
+
+        let resp = request()
+            .method("POST")
+            .path("/balance/batch")
+            .json(&serde_json::json!({ "addresses": [third, ADDRESS, CONTRACT, ADDRESS] }))
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        let addresses: Vec<&str> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["address"].as_str().unwrap())
+            .collect();
+        assert_eq!(addresses, vec![third, ADDRESS, CONTRACT, ADDRESS]);
+        assert!(body
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|entry| entry["balance"] == "1000"));
+    }
 
+    #[tokio::test]
+    async fn test_balance_batch_accepts_gzip_bodies() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+        let body = serde_json::json!({ "addresses": [ADDRESS, CONTRACT] }).to_string();
+        let post = |body: Vec<u8>| {
+            request()
+                .method("POST")
+                .path("/balance/batch")
+                .header("content-type", "application/json")
+                .header("content-encoding", "gzip")
+                .body(body)
+                .reply(&api)
+        };
+
+        let resp = post(request_body::tests::gzip(body.as_bytes())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!([
+                { "address": ADDRESS, "balance": "1000" },
+                { "address": CONTRACT, "balance": "1000" },
+            ])
+        );
+
+        let resp = post(b"{\"addresses\": []}".to_vec()).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("malformed gzip body"));
+    }
+
+    #[tokio::test]
+    async fn test_balance_batch_rejects_invalid_bodies() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let too_many = vec![ADDRESS; MAX_BATCH_ADDRESSES + 1];
+        for body in [
+            serde_json::json!({ "addresses": ["0x123"] }),
+            serde_json::json!({ "addresses": too_many }),
+            serde_json::json!({ "address": ADDRESS }),
+        ] {
+            let resp = request()
+                .method("POST")
+                .path("/balance/batch")
+                .json(&body)
+                .reply(&api)
+                .await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", body);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_bodies_list_every_problem() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request()
+            .method("POST")
+            .path("/balance/batch")
+            .json(&serde_json::json!({ "addresses": [ADDRESS, "0x123"], "blokc": "latest" }))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body["errors"],
+            serde_json::json!([
+                { "field": "blokc", "message": "unknown field `blokc`" },
+                { "field": "addresses[1]", "message": "invalid address: \"0x123\"" },
+            ])
+        );
+
+        let resp = request()
+            .method("POST")
+            .path("/call-fn")
+            .json(&serde_json::json!({ "to": "0x123", "abi": {}, "args": 1 }))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        let fields: Vec<&str> = body["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| error["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, vec!["to", "abi", "function", "args"]);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("function: missing field"));
+    }
+
+    #[tokio::test]
+    async fn test_historical_blocks_use_the_archive_provider() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let archive = ArchiveRouter::new(Arc::new(BalanceProvider(7)), 128);
+        let api = setup_routes(AppState {
+            archive: Some(Arc::new(archive)),
+            ..AppState::new(provider)
+        });
+
+        // The dummy head is block 1000, so block 1 is well past the threshold.
         let resp = request()
             .method("GET")
-            .path(&format!("/balance/{}", address))
+            .path(&format!("/balance/{}?block=1", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[SERVED_BY_HEADER], "archive");
+        assert_eq!(resp.body(), r#"{"balance":"7","unit":"wei"}"#);
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/balance/{}", ADDRESS))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[SERVED_BY_HEADER], "default");
+        assert_eq!(resp.body(), r#"{"balance":"1000","unit":"wei"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_body_fields_are_rejected() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request()
+            .method("POST")
+            .path("/balance/batch")
+            .json(&serde_json::json!({ "addresses": [ADDRESS], "blokc": "latest" }))
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        let message = body["error"].as_str().unwrap();
+        assert!(message.contains("unknown field `blokc`"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_block_latest_returns_the_head() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let latest = request()
+            .method("GET")
+            .path("/block/latest")
+            .reply(&api)
+            .await;
+        assert_eq!(latest.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(latest.body()).unwrap();
+        assert_eq!(body["number"], 1000);
+        assert_eq!(body["timestamp"], 1_700_000_000);
+
+        // The tag and the numeric route share the same serialization.
+        let numbered = request()
+            .method("GET")
+            .path("/block/1000")
+            .reply(&api)
+            .await;
+        assert_eq!(numbered.body(), latest.body());
+    }
+
+    #[tokio::test]
+    async fn test_block_errors() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request().method("GET").path("/block/7").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            resp.body(),
+            r#"{"error":"Not found: block 7","error_type":"not_found"}"#
+        );
+
+        let resp = request()
+            .method("GET")
+            .path("/block/head")
             .reply(&api)
             .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_rlp_encoding_decodes_back() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+        let tx = dummy_transaction();
+        let rlp = |body: &[u8]| {
+            let body: serde_json::Value = serde_json::from_slice(body).unwrap();
+            serde_json::from_value::<Bytes>(body["rlp"].clone()).unwrap()
+        };
 
+        let resp = request()
+            .method("GET")
+            .path("/block/latest?encoding=rlp")
+            .reply(&api)
+            .await;
         assert_eq!(resp.status(), StatusCode::OK);
-        // Further assertions can be made by parsing the JSON response.
+        let block = consensus::Block::<TxEnvelope>::decode(&mut rlp(resp.body()).as_ref()).unwrap();
+        assert_eq!(block.header.number, 1000);
+        assert_eq!(block.body.transactions, vec![tx.inner.clone()]);
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/tx/{}?encoding=rlp", tx.inner.tx_hash()))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let decoded = TxEnvelope::decode_2718(&mut rlp(resp.body()).as_ref()).unwrap();
+        assert_eq!(decoded, tx.inner);
+    }
+
+    #[tokio::test]
+    async fn test_get_transaction() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+        let tx = dummy_transaction();
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/tx/{}", tx.inner.tx_hash()))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["nonce"], 7);
+        assert_eq!(body["block_number"], 1000);
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/tx/{}", BLOCK_HASH))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = request().method("GET").path("/tx/0x1234").reply(&api).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/tx/{}?encoding=xml", tx.inner.tx_hash()))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_contract_with_code_and_one_token() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+        let token = "0x7070707070707070707070707070707070707070";
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/contract/{}?tokens={}", CONTRACT, token))
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "is_contract": true,
+                "code_size": 5,
+                "balance": "1000",
+                "tokens": [{
+                    "token": token,
+                    "balance": "5000",
+                    "decimals": 18,
+                    "formatted": "0.000000000000005000",
+                    "decimals_defaulted": true,
+                }],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_contract_formats_a_six_decimal_token() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/contract/{}?tokens={}", CONTRACT, USDC))
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            body["tokens"],
+            serde_json::json!([{
+                "token": USDC,
+                "balance": "5000",
+                "decimals": 6,
+                "formatted": "0.005000",
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pending_poll_returns_new_hashes() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let buffer = Arc::new(PendingBuffer::new(100));
+        let api = setup_routes(AppState {
+            pending: Some(buffer.clone()),
+            ..AppState::new(provider.clone())
+        });
+        let poll = |cursor: u64| {
+            let api = api.clone();
+            async move {
+                let resp = request()
+                    .method("GET")
+                    .path(&format!("/pending/poll?cursor={}", cursor))
+                    .reply(&api)
+                    .await;
+                assert_eq!(resp.status(), StatusCode::OK);
+                serde_json::from_slice::<serde_json::Value>(resp.body()).unwrap()
+            }
+        };
+
+        buffer.push([B256::repeat_byte(1)]);
+        let first = poll(0).await;
+        assert_eq!(first["hashes"], serde_json::json!([B256::repeat_byte(1)]));
+
+        buffer.push([B256::repeat_byte(2), B256::repeat_byte(3)]);
+        let second = poll(first["cursor"].as_u64().unwrap()).await;
+        assert_eq!(
+            second["hashes"],
+            serde_json::json!([B256::repeat_byte(2), B256::repeat_byte(3)])
+        );
+        assert_eq!(second["cursor"], 3);
+
+        // Disabled unless a buffer is configured.
+        let disabled = setup_routes(AppState::new(provider));
+        let resp = request()
+            .method("GET")
+            .path("/pending/poll")
+            .reply(&disabled)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_balance_by_pubkey() {
+        let provider: Arc<dyn Provider> = Arc::new(DummyProvider);
+        let api = setup_routes(AppState::new(provider));
+        // Public key of the secp256k1 private key 1.
+        let pubkey = "0x0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\
+                      483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8";
+
+        let resp = request()
+            .method("GET")
+            .path(&format!("/balance-by-pubkey/{}", pubkey))
+            .reply(&api)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.body(),
+            r#"{"address":"0x7e5f4552091a69125d5dfcb7b8c2659029395bdf","balance":"1000","unit":"wei"}"#
+        );
+
+        // Without the 0x04 tag the same address is derived; truncated keys are rejected.
+        assert_eq!(
+            parse_public_key(&pubkey.replacen("0x04", "0x", 1)).unwrap(),
+            address!("7e5f4552091a69125d5dfcb7b8c2659029395bdf")
+        );
+        let resp = request()
+            .method("GET")
+            .path(&format!("/balance-by-pubkey/{}", &pubkey[..100]))
+            .reply(&api)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
     }
 }
@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Message returned while maintenance mode is on, unless overridden by `MAINTENANCE_MESSAGE`.
+const DEFAULT_MAINTENANCE_MESSAGE: &str = "Down for maintenance";
+
+/// Whether the server is in maintenance mode, toggled through `/admin/maintenance`.
+///
+/// Every route but `/health` and the admin routes answers `503 Service Unavailable`
+/// while it is on.
+#[derive(Debug)]
+pub struct Maintenance {
+    enabled: AtomicBool,
+    message: String,
+}
+
+impl Maintenance {
+    pub fn new(message: impl Into<String>) -> Self {
+        Maintenance {
+            enabled: AtomicBool::new(false),
+            message: message.into(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Acquire)
+    }
+
+    /// Turns maintenance mode on or off, or flips it when `enabled` is `None`,
+    /// returning the new state.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_set() {
+    /// let maintenance = Maintenance::new("Down for maintenance");
+    /// assert!(maintenance.set(None));
+    /// # }
+    /// ```
+    pub fn set(&self, enabled: Option<bool>) -> bool {
+        match enabled {
+            Some(enabled) => {
+                self.enabled.store(enabled, Ordering::Release);
+                enabled
+            }
+            None => !self.enabled.fetch_xor(true, Ordering::AcqRel),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Maintenance::new(DEFAULT_MAINTENANCE_MESSAGE)
+    }
+}
+
+/// Builds the maintenance toggle, off, with the message from `MAINTENANCE_MESSAGE`.
+pub fn get_maintenance() -> Maintenance {
+    match std::env::var("MAINTENANCE_MESSAGE") {
+        Ok(message) if !message.is_empty() => Maintenance::new(message),
+        _ => Maintenance::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_toggles_or_forces_the_flag() {
+        let maintenance = Maintenance::default();
+        assert!(!maintenance.is_enabled());
+        assert!(maintenance.set(None));
+        assert!(maintenance.is_enabled());
+        assert!(!maintenance.set(None));
+        assert!(maintenance.set(Some(true)));
+        assert!(maintenance.set(Some(true)));
+        assert!(!maintenance.set(Some(false)));
+        assert!(!maintenance.is_enabled());
+    }
+}
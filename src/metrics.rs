@@ -0,0 +1,546 @@
+use crate::circuit_breaker::BreakerState;
+use crate::deadline::request_task_id;
+use crate::supervisor::TaskStatus;
+use alloy_primitives::Address;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Write};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task;
+use warp::http::StatusCode;
+
+/// Maximum number of distinct addresses whose query counts are kept.
+const MAX_TRACKED_ADDRESSES: usize = 1000;
+
+/// Routes tracked individually; anything else is counted as `other`.
+pub const ROUTES: &[&str] = &[
+    "activity",
+    "admin",
+    "balance",
+    "balance-by-pubkey",
+    "block",
+    "caip",
+    "call-fn",
+    "capabilities",
+    "chain-id",
+    "chains",
+    "contract",
+    "fee-history",
+    "gas-price",
+    "health",
+    "index",
+    "logs",
+    "metrics",
+    "multichain",
+    "node-status",
+    "nonce",
+    "pending",
+    "proxy",
+    "ready",
+    "receipt",
+    "receipts",
+    "send-raw",
+    "sign-typed",
+    "stats",
+    "trace-filter",
+    "tx",
+    "type",
+    "ui",
+    "validate",
+    "xpub",
+];
+
+/// Runtime counters shared by every request.
+pub struct Metrics {
+    started_at: Instant,
+    requests: AtomicU64,
+    errors: AtomicU64,
+    latency_micros: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    inflight: AtomicI64,
+    routes: Mutex<BTreeMap<&'static str, u64>>,
+    addresses: Mutex<HashMap<Address, u64>>,
+    /// Route of the request each task is serving, for counters recorded below
+    /// the handlers, like [`crate::server_timing::RpcTimings`].
+    task_routes: Mutex<HashMap<task::Id, &'static str>>,
+    retries: Mutex<BTreeMap<&'static str, RetryCounts>>,
+}
+
+/// Node request retries made while serving a route.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetryCounts {
+    pub attempts: u64,
+    pub exhausted: u64,
+}
+
+/// Attributes node requests made by the current task to a route while alive.
+pub struct RouteGuard {
+    metrics: Arc<Metrics>,
+    task: Option<task::Id>,
+}
+
+impl Drop for RouteGuard {
+    fn drop(&mut self) {
+        if let Some(task) = self.task {
+            self.metrics
+                .task_routes
+                .lock()
+                .expect("task routes poisoned")
+                .remove(&task);
+        }
+    }
+}
+
+/// Counts a request as in flight for as long as it is alive.
+///
+/// Dropping the guard decrements the gauge, so rejections, cancellations and
+/// panics are all accounted for.
+pub struct InflightGuard(Arc<Metrics>);
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        self.0.inflight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Point-in-time view of the counters, as returned by `/stats`.
+#[derive(Debug, Serialize)]
+pub struct StatsSnapshot {
+    pub total_requests: u64,
+    pub requests_by_route: BTreeMap<&'static str, u64>,
+    pub errors: u64,
+    pub average_latency_ms: f64,
+    pub inflight: i64,
+    pub cache_hit_rate: Option<f64>,
+    /// State of the default provider's circuit breaker, filled in by `/stats`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker: Option<BreakerState>,
+    /// Liveness of the supervised background tasks, filled in by `/stats`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub background_tasks: BTreeMap<&'static str, TaskStatus>,
+    pub uptime_seconds: u64,
+}
+
+/// Query count of a single address, as returned by `/stats/top-addresses`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AddressCount {
+    pub address: Address,
+    pub count: u64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics {
+            started_at: Instant::now(),
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            latency_micros: AtomicU64::new(0),
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+            inflight: AtomicI64::new(0),
+            routes: Mutex::new(BTreeMap::new()),
+            addresses: Mutex::new(HashMap::new()),
+            task_routes: Mutex::new(HashMap::new()),
+            retries: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Records a completed request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_record_request() {
+    /// let metrics = Metrics::new();
+    /// metrics.record_request("/health", StatusCode::OK, Duration::from_millis(3));
+    /// assert_eq!(metrics.snapshot().total_requests, 1);
+    /// # }
+    /// ```
+    pub fn record_request(&self, path: &str, status: StatusCode, elapsed: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.latency_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        if status.is_client_error() || status.is_server_error() {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut routes = self.routes.lock().expect("route counters poisoned");
+        *routes.entry(route_label(path)).or_insert(0) += 1;
+    }
+
+    /// Counts a query for `address`.
+    ///
+    /// Once `MAX_TRACKED_ADDRESSES` are tracked, a new address replaces the least queried one
+    /// and inherits its count, so frequently queried addresses are never evicted by a long
+    /// tail of one-off lookups.
+    pub fn record_address(&self, address: Address) {
+        let mut addresses = self.addresses.lock().expect("address counters poisoned");
+        if let Some(count) = addresses.get_mut(&address) {
+            *count += 1;
+            return;
+        }
+        let mut count = 1;
+        if addresses.len() >= MAX_TRACKED_ADDRESSES {
+            if let Some((&evicted, &min)) = addresses.iter().min_by_key(|(_, count)| **count) {
+                addresses.remove(&evicted);
+                count += min;
+            }
+        }
+        addresses.insert(address, count);
+    }
+
+    /// The `limit` most queried addresses, most queried first.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_top_addresses() {
+    /// let metrics = Metrics::new();
+    /// metrics.record_address(Address::ZERO);
+    /// assert_eq!(metrics.top_addresses(10)[0].address, Address::ZERO);
+    /// # }
+    /// ```
+    pub fn top_addresses(&self, limit: usize) -> Vec<AddressCount> {
+        let addresses = self.addresses.lock().expect("address counters poisoned");
+        let mut top: Vec<AddressCount> = addresses
+            .iter()
+            .map(|(&address, &count)| AddressCount { address, count })
+            .collect();
+        top.sort_by(|a, b| b.count.cmp(&a.count).then(a.address.cmp(&b.address)));
+        top.truncate(limit);
+        top
+    }
+
+    /// Records a cache lookup outcome.
+    pub fn record_cache_lookup(&self, hit: bool) {
+        let counter = if hit {
+            &self.cache_hits
+        } else {
+            &self.cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Marks a request as in flight until the returned guard is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_track_inflight() {
+    /// let metrics = Arc::new(Metrics::new());
+    /// let guard = metrics.track_inflight();
+    /// drop(guard);
+    /// assert_eq!(metrics.snapshot().inflight, 0);
+    /// # }
+    /// ```
+    pub fn track_inflight(self: &Arc<Self>) -> InflightGuard {
+        self.inflight.fetch_add(1, Ordering::Relaxed);
+        InflightGuard(self.clone())
+    }
+
+    /// Attributes retries made by the current task to the route of `path`.
+    ///
+    /// Outside a spawned task there is no task id, and retries count as `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_track_route() {
+    /// let metrics = Arc::new(Metrics::new());
+    /// let guard = metrics.track_route("/balance/0x00");
+    /// # }
+    /// ```
+    pub fn track_route(self: &Arc<Self>, path: &str) -> RouteGuard {
+        let task = request_task_id();
+        if let Some(task) = task {
+            self.task_routes
+                .lock()
+                .expect("task routes poisoned")
+                .insert(task, route_label(path));
+        }
+        RouteGuard {
+            metrics: self.clone(),
+            task,
+        }
+    }
+
+    /// Counts a retried node request for the route served by the current task.
+    pub fn record_retry_attempt(&self) {
+        self.record_retry(|counts| counts.attempts += 1);
+    }
+
+    /// Counts a node request that still failed after its last retry.
+    pub fn record_retry_exhausted(&self) {
+        self.record_retry(|counts| counts.exhausted += 1);
+    }
+
+    fn record_retry(&self, update: impl FnOnce(&mut RetryCounts)) {
+        let route = request_task_id()
+            .and_then(|task| {
+                let routes = self.task_routes.lock().expect("task routes poisoned");
+                routes.get(&task).copied()
+            })
+            .unwrap_or("other");
+        let mut retries = self.retries.lock().expect("retry counters poisoned");
+        update(retries.entry(route).or_default());
+    }
+
+    /// Retry counters by route.
+    pub fn retries(&self) -> BTreeMap<&'static str, RetryCounts> {
+        self.retries
+            .lock()
+            .expect("retry counters poisoned")
+            .clone()
+    }
+
+    /// Renders the counters in the Prometheus text exposition format.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_render_prometheus() {
+    /// let metrics = Metrics::new();
+    /// assert!(metrics.render_prometheus().contains("http_requests_inflight 0"));
+    /// # }
+    /// ```
+    pub fn render_prometheus(&self) -> String {
+        let snapshot = self.snapshot();
+        let mut out = String::new();
+
+        write_family(
+            &mut out,
+            "http_requests_total",
+            "counter",
+            "Total HTTP requests served.",
+        );
+        for (route, count) in &snapshot.requests_by_route {
+            write_sample(&mut out, "http_requests_total", &[("route", route)], count);
+        }
+        write_family(
+            &mut out,
+            "http_request_errors_total",
+            "counter",
+            "HTTP requests answered with a 4xx or 5xx status.",
+        );
+        write_sample(&mut out, "http_request_errors_total", &[], snapshot.errors);
+        write_family(
+            &mut out,
+            "http_request_duration_seconds_sum",
+            "counter",
+            "Total time spent serving requests.",
+        );
+        write_sample(
+            &mut out,
+            "http_request_duration_seconds_sum",
+            &[],
+            self.latency_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+        );
+        write_family(
+            &mut out,
+            "http_requests_inflight",
+            "gauge",
+            "Requests currently being served.",
+        );
+        write_sample(&mut out, "http_requests_inflight", &[], snapshot.inflight);
+        let retries = self.retries();
+        write_family(
+            &mut out,
+            "rpc_retry_attempts_total",
+            "counter",
+            "Node requests retried after a retryable failure.",
+        );
+        for (route, counts) in &retries {
+            write_sample(
+                &mut out,
+                "rpc_retry_attempts_total",
+                &[("route", route)],
+                counts.attempts,
+            );
+        }
+        write_family(
+            &mut out,
+            "rpc_retry_exhausted_total",
+            "counter",
+            "Node requests that still failed after their last retry.",
+        );
+        for (route, counts) in &retries {
+            write_sample(
+                &mut out,
+                "rpc_retry_exhausted_total",
+                &[("route", route)],
+                counts.exhausted,
+            );
+        }
+        write_family(
+            &mut out,
+            "process_uptime_seconds",
+            "gauge",
+            "Seconds since the server started.",
+        );
+        write_sample(
+            &mut out,
+            "process_uptime_seconds",
+            &[],
+            snapshot.uptime_seconds,
+        );
+        out
+    }
+
+    /// Takes a consistent-enough snapshot of all counters.
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let latency_micros = self.latency_micros.load(Ordering::Relaxed);
+        let hits = self.cache_hits.load(Ordering::Relaxed);
+        let misses = self.cache_misses.load(Ordering::Relaxed);
+
+        let average_latency_ms = if requests == 0 {
+            0.0
+        } else {
+            latency_micros as f64 / requests as f64 / 1000.0
+        };
+        let cache_hit_rate = match hits + misses {
+            0 => None,
+            lookups => Some(hits as f64 / lookups as f64),
+        };
+
+        StatsSnapshot {
+            total_requests: requests,
+            requests_by_route: self.routes.lock().expect("route counters poisoned").clone(),
+            errors: self.errors.load(Ordering::Relaxed),
+            average_latency_ms,
+            inflight: self.inflight.load(Ordering::Relaxed),
+            cache_hit_rate,
+            circuit_breaker: None,
+            background_tasks: BTreeMap::new(),
+            uptime_seconds: self.started_at.elapsed().as_secs(),
+        }
+    }
+}
+
+/// Writes the `HELP` and `TYPE` header of a metric family.
+fn write_family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Writes one sample of a metric family with optional labels.
+fn write_sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl Display) {
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| format!("{}=\"{}\"", key, value))
+        .collect();
+    if labels.is_empty() {
+        let _ = writeln!(out, "{} {}", name, value);
+    } else {
+        let _ = writeln!(out, "{}{{{}}} {}", name, labels.join(","), value);
+    }
+}
+
+/// Maps a request path to the route label it is counted under.
+pub fn route_label(path: &str) -> &'static str {
+    let segment = match path.trim_start_matches('/').split('/').next() {
+        Some("") | None => "index",
+        Some(segment) => segment,
+    };
+    ROUTES
+        .iter()
+        .find(|route| **route == segment)
+        .copied()
+        .unwrap_or("other")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_aggregates_requests() {
+        let metrics = Metrics::new();
+        metrics.record_request("/health", StatusCode::OK, Duration::from_millis(2));
+        metrics.record_request("/balance/0x00", StatusCode::OK, Duration::from_millis(4));
+        metrics.record_request("/nope", StatusCode::NOT_FOUND, Duration::from_millis(6));
+        metrics.record_cache_lookup(true);
+        metrics.record_cache_lookup(false);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.total_requests, 3);
+        assert_eq!(snapshot.errors, 1);
+        assert_eq!(snapshot.requests_by_route["health"], 1);
+        assert_eq!(snapshot.requests_by_route["balance"], 1);
+        assert_eq!(snapshot.requests_by_route["other"], 1);
+        assert!((snapshot.average_latency_ms - 4.0).abs() < f64::EPSILON);
+        assert_eq!(snapshot.cache_hit_rate, Some(0.5));
+    }
+
+    #[test]
+    fn test_inflight_guard_decrements_on_drop() {
+        let metrics = Arc::new(Metrics::new());
+        let first = metrics.track_inflight();
+        let second = metrics.track_inflight();
+        assert_eq!(metrics.snapshot().inflight, 2);
+
+        drop(first);
+        assert_eq!(metrics.snapshot().inflight, 1);
+
+        // A panicking request still releases its guard while unwinding.
+        let tracked = metrics.clone();
+        let result = std::panic::catch_unwind(move || {
+            let _guard = tracked.track_inflight();
+            panic!("handler panicked");
+        });
+        assert!(result.is_err());
+        drop(second);
+        assert_eq!(metrics.snapshot().inflight, 0);
+    }
+
+    #[test]
+    fn test_render_prometheus() {
+        let metrics = Metrics::new();
+        metrics.record_request("/health", StatusCode::OK, Duration::from_millis(2));
+
+        let rendered = metrics.render_prometheus();
+        assert!(rendered.contains("http_requests_total{route=\"health\"} 1"));
+        assert!(rendered.contains("http_request_errors_total 0"));
+        assert!(rendered.contains("# TYPE http_requests_inflight gauge"));
+        assert!(rendered.contains("http_requests_inflight 0"));
+    }
+
+    #[test]
+    fn test_top_addresses_orders_by_count() {
+        let metrics = Metrics::new();
+        let popular = Address::repeat_byte(0xaa);
+        for _ in 0..3 {
+            metrics.record_address(popular);
+        }
+        metrics.record_address(Address::ZERO);
+
+        let top = metrics.top_addresses(1);
+        assert_eq!(
+            top,
+            vec![AddressCount {
+                address: popular,
+                count: 3
+            }]
+        );
+        assert_eq!(metrics.top_addresses(10).len(), 2);
+    }
+
+    #[test]
+    fn test_address_counters_are_bounded() {
+        let metrics = Metrics::new();
+        let popular = Address::repeat_byte(0xaa);
+        for _ in 0..10 {
+            metrics.record_address(popular);
+        }
+        for i in 0..MAX_TRACKED_ADDRESSES as u64 * 2 {
+            metrics.record_address(Address::left_padding_from(&i.to_be_bytes()));
+        }
+
+        let top = metrics.top_addresses(usize::MAX);
+        assert_eq!(top.len(), MAX_TRACKED_ADDRESSES);
+        assert!(top.iter().any(|entry| entry.address == popular));
+    }
+}
@@ -0,0 +1,234 @@
+use crate::config;
+use crate::error::AppError;
+use crate::token::{self, TokenDecimals};
+use alloy::providers::Provider;
+use alloy::transports::TransportResult;
+use alloy_primitives::Address;
+use log::warn;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+use tokio::time::interval;
+
+/// Values of the default node that never or seldom change, shared by every
+/// request: the chain id and token decimals, kept forever, and the gas
+/// price, kept for a TTL.
+pub struct NodeCache {
+    chain_id: OnceCell<u64>,
+    decimals: Mutex<HashMap<Address, TokenDecimals>>,
+    gas_price_ttl: Duration,
+    /// Last gas price and when the node returned it.
+    gas_price: Mutex<Option<(u128, Instant)>>,
+}
+
+impl NodeCache {
+    /// Reuses the gas price for `gas_price_ttl`; zero queries it on every request.
+    pub fn new(gas_price_ttl: Duration) -> Self {
+        NodeCache {
+            chain_id: OnceCell::new(),
+            decimals: Mutex::new(HashMap::new()),
+            gas_price_ttl,
+            gas_price: Mutex::new(None),
+        }
+    }
+
+    pub fn gas_price_ttl(&self) -> Duration {
+        self.gas_price_ttl
+    }
+
+    /// Chain id of `provider`, only queried until it first succeeds.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_chain_id() {
+    /// let cache = NodeCache::default();
+    /// assert_eq!(cache.chain_id(provider.as_ref()).await.unwrap(), 1);
+    /// # }
+    /// ```
+    pub async fn chain_id(&self, provider: &dyn Provider) -> TransportResult<u64> {
+        self.chain_id
+            .get_or_try_init(|| async { provider.get_chain_id().await })
+            .await
+            .copied()
+    }
+
+    /// Decimals of `token`, only queried until it first succeeds.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_token_decimals() {
+    /// let cache = NodeCache::default();
+    /// assert_eq!(cache.token_decimals(provider.as_ref(), usdc).await.unwrap().decimals, 6);
+    /// # }
+    /// ```
+    pub async fn token_decimals(
+        &self,
+        provider: &dyn Provider,
+        token: Address,
+    ) -> Result<TokenDecimals, AppError> {
+        let cached = self
+            .decimals
+            .lock()
+            .expect("decimals cache poisoned")
+            .get(&token)
+            .copied();
+        if let Some(decimals) = cached {
+            return Ok(decimals);
+        }
+        let decimals = token::token_decimals(provider, token).await?;
+        self.decimals
+            .lock()
+            .expect("decimals cache poisoned")
+            .insert(token, decimals);
+        Ok(decimals)
+    }
+
+    /// Gas price of `provider`, queried again once the cached one is older than the TTL.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_gas_price() {
+    /// let cache = NodeCache::new(Duration::from_secs(2));
+    /// let gas_price = cache.gas_price(provider.as_ref()).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn gas_price(&self, provider: &dyn Provider) -> TransportResult<u128> {
+        let cached = *self.gas_price.lock().expect("gas price cache poisoned");
+        match cached {
+            Some((gas_price, at)) if at.elapsed() < self.gas_price_ttl => Ok(gas_price),
+            _ => self.refresh_gas_price(provider).await,
+        }
+    }
+
+    /// Queries the gas price and caches it.
+    pub async fn refresh_gas_price(&self, provider: &dyn Provider) -> TransportResult<u128> {
+        let gas_price = provider.get_gas_price().await?;
+        *self.gas_price.lock().expect("gas price cache poisoned") =
+            Some((gas_price, Instant::now()));
+        Ok(gas_price)
+    }
+
+    /// Refreshes the gas price once per TTL, so requests seldom wait for the node.
+    ///
+    /// The TTL must not be zero.
+    pub async fn run_gas_price_refresh(self: Arc<Self>, provider: Arc<dyn Provider>) {
+        let mut ticker = interval(self.gas_price_ttl);
+        loop {
+            ticker.tick().await;
+            if let Err(error) = self.refresh_gas_price(provider.as_ref()).await {
+                warn!("Failed to refresh the gas price: {}", error);
+            }
+        }
+    }
+}
+
+impl Default for NodeCache {
+    fn default() -> Self {
+        NodeCache::new(Duration::ZERO)
+    }
+}
+
+/// Retrieves how long the gas price is reused from `GAS_PRICE_CACHE_TTL_MS`; zero disables it.
+pub fn get_gas_price_ttl() -> Duration {
+    Duration::from_millis(config::env_or("GAS_PRICE_CACHE_TTL_MS", 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockProviderBuilder;
+    use alloy::sol_types::SolValue;
+    use alloy_primitives::U256;
+
+    #[tokio::test]
+    async fn test_chain_id_is_queried_once() {
+        let provider = MockProviderBuilder::new().chain_id(1).build();
+        let cache = NodeCache::default();
+        for _ in 0..5 {
+            assert_eq!(cache.chain_id(&provider).await.unwrap(), 1);
+        }
+        provider.assert_calls("eth_chainId", 1);
+
+        // Failures are not cached.
+        let unreachable = MockProviderBuilder::new().build();
+        let cache = NodeCache::default();
+        assert!(cache.chain_id(&unreachable).await.is_err());
+        assert!(cache.chain_id(&unreachable).await.is_err());
+        unreachable.assert_calls("eth_chainId", 2);
+    }
+
+    #[tokio::test]
+    async fn test_token_decimals_are_queried_once_per_token() {
+        let usdc = Address::repeat_byte(0x60);
+        let input = token::decimals_request(usdc).input.into_input().unwrap();
+        let provider = MockProviderBuilder::new()
+            .call_output(usdc, input, U256::from(6).abi_encode().into())
+            .build();
+        let cache = NodeCache::default();
+        for _ in 0..3 {
+            assert_eq!(
+                cache
+                    .token_decimals(&provider, usdc)
+                    .await
+                    .unwrap()
+                    .decimals,
+                6
+            );
+        }
+        provider.assert_calls("eth_call", 1);
+
+        let other = Address::repeat_byte(0x70);
+        assert!(
+            cache
+                .token_decimals(&provider, other)
+                .await
+                .unwrap()
+                .defaulted
+        );
+        assert!(
+            cache
+                .token_decimals(&provider, other)
+                .await
+                .unwrap()
+                .defaulted
+        );
+        provider.assert_calls("eth_call", 2);
+    }
+
+    #[tokio::test]
+    async fn test_gas_price_refreshes_after_its_ttl() {
+        let provider = MockProviderBuilder::new().gas_price(20).build();
+        let cache = NodeCache::new(Duration::from_millis(50));
+        for _ in 0..3 {
+            assert_eq!(cache.gas_price(&provider).await.unwrap(), 20);
+        }
+        provider.assert_calls("eth_gasPrice", 1);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(cache.gas_price(&provider).await.unwrap(), 20);
+        provider.assert_calls("eth_gasPrice", 2);
+
+        let uncached = NodeCache::default();
+        uncached.gas_price(&provider).await.unwrap();
+        uncached.gas_price(&provider).await.unwrap();
+        provider.assert_calls("eth_gasPrice", 4);
+    }
+
+    #[tokio::test]
+    async fn test_background_refresh_keeps_the_gas_price_fresh() {
+        let provider = Arc::new(MockProviderBuilder::new().gas_price(20).build());
+        let cache = Arc::new(NodeCache::new(Duration::from_millis(20)));
+        let refresh = tokio::spawn(cache.clone().run_gas_price_refresh(provider.clone()));
+
+        tokio::time::sleep(Duration::from_millis(70)).await;
+        assert!(provider.calls("eth_gasPrice") >= 3);
+        let refreshed = provider.calls("eth_gasPrice");
+        assert_eq!(cache.gas_price(provider.as_ref()).await.unwrap(), 20);
+        assert!(provider.calls("eth_gasPrice") <= refreshed + 1);
+        refresh.abort();
+    }
+}
@@ -0,0 +1,136 @@
+use crate::error::AppError;
+use alloy::providers::Provider;
+use alloy::rpc::json_rpc::RpcError;
+use alloy::rpc::types::SyncStatus;
+use alloy_primitives::U64;
+use log::debug;
+use serde::Serialize;
+use serde_json::value::RawValue;
+
+/// Body of `/node-status`.
+#[derive(Debug, Serialize)]
+pub struct NodeStatus {
+    /// `false`, or the sync progress reported by the node.
+    pub syncing: SyncStatus,
+    /// Connected peers; left out when the node does not serve `net_peerCount`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peers: Option<u64>,
+}
+
+/// Queries `eth_syncing` and `net_peerCount` at once.
+///
+/// A node answering `net_peerCount` with a JSON-RPC error, as nodes without
+/// the `net` namespace do, still reports its sync status.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_node_status() {
+/// let status = node_status(provider.as_ref()).await.unwrap();
+/// assert!(matches!(status.syncing, SyncStatus::None));
+/// # }
+/// ```
+pub async fn node_status(provider: &dyn Provider) -> Result<NodeStatus, AppError> {
+    let no_params = RawValue::from_string("[]".to_string())
+        .map_err(|error| AppError::Internal(error.to_string()))?;
+    let (syncing, peers) = futures::join!(
+        provider.syncing(),
+        provider.raw_request_dyn("net_peerCount".into(), &no_params)
+    );
+    let peers = match peers {
+        Ok(peers) => {
+            let peers: U64 = serde_json::from_str(peers.get())
+                .map_err(|error| AppError::Decode(format!("net_peerCount: {}", error)))?;
+            Some(peers.to::<u64>())
+        }
+        Err(RpcError::ErrorResp(payload)) => {
+            debug!("net_peerCount is not served: {}", payload.message);
+            None
+        }
+        Err(error) => return Err(error.into()),
+    };
+    Ok(NodeStatus {
+        syncing: syncing.map_err(AppError::from)?,
+        peers,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloy::providers::{ProviderCall, RootProvider};
+    use alloy::rpc::client::NoParams;
+    use alloy::rpc::json_rpc::ErrorPayload;
+    use alloy::rpc::types::SyncInfo;
+    use alloy::transports::TransportResult;
+    use alloy_primitives::U256;
+    use std::borrow::Cow;
+
+    /// Provider syncing from block 10 to 100, with `peers` peers or, when
+    /// `None`, without the `net` namespace.
+    pub(crate) struct SyncingProvider {
+        pub(crate) peers: Option<u64>,
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for SyncingProvider {
+        fn syncing(&self) -> ProviderCall<NoParams, SyncStatus> {
+            ProviderCall::ready(Ok(SyncStatus::Info(Box::new(SyncInfo {
+                starting_block: U256::from(10),
+                current_block: U256::from(42),
+                highest_block: U256::from(100),
+                ..Default::default()
+            }))))
+        }
+
+        async fn raw_request_dyn(
+            &self,
+            method: Cow<'static, str>,
+            _params: &RawValue,
+        ) -> TransportResult<Box<RawValue>> {
+            assert_eq!(method, "net_peerCount");
+            match self.peers {
+                Some(peers) => Ok(serde_json::value::to_raw_value(&U64::from(peers)).unwrap()),
+                None => Err(RpcError::ErrorResp(ErrorPayload {
+                    code: -32601,
+                    message: "the method net_peerCount does not exist".into(),
+                    data: None,
+                })),
+            }
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("SyncingProvider does not support `root`")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reports_sync_progress_and_peers() {
+        let status = node_status(&SyncingProvider { peers: Some(25) })
+            .await
+            .unwrap();
+        assert_eq!(status.peers, Some(25));
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            serde_json::json!({
+                "syncing": {
+                    "startingBlock": "0xa",
+                    "currentBlock": "0x2a",
+                    "highestBlock": "0x64",
+                    "warpChunksAmount": null,
+                    "warpChunksProcessed": null,
+                },
+                "peers": 25,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_omits_peers_the_node_does_not_serve() {
+        let status = node_status(&SyncingProvider { peers: None }).await.unwrap();
+        assert_eq!(status.peers, None);
+        let body = serde_json::to_value(&status).unwrap();
+        assert!(body.get("peers").is_none());
+        assert_eq!(body["syncing"]["currentBlock"], "0x2a");
+    }
+}
@@ -0,0 +1,180 @@
+use crate::error::AppError;
+use alloy_primitives::utils::format_units;
+use alloy_primitives::U256;
+use serde::{Deserialize, Serialize};
+
+/// How quantities are written by the routes accepting `?format=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberFormat {
+    /// A decimal string, e.g. `"1000"`.
+    #[default]
+    Dec,
+    /// A `0x`-prefixed hex string as in JSON-RPC, e.g. `"0x3e8"`.
+    Hex,
+}
+
+impl NumberFormat {
+    /// Writes `value` in this format.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_format() {
+    /// assert_eq!(NumberFormat::Hex.format(U256::from(1000)), "0x3e8");
+    /// # }
+    /// ```
+    pub fn format(self, value: U256) -> String {
+        match self {
+            NumberFormat::Dec => value.to_string(),
+            NumberFormat::Hex => format!("{:#x}", value),
+        }
+    }
+}
+
+/// Denomination of the amounts of ether written by the routes accepting `?unit=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    #[default]
+    Wei,
+    Gwei,
+    Ether,
+}
+
+impl Unit {
+    /// Number of decimals of this unit relative to wei.
+    pub fn decimals(self) -> u8 {
+        match self {
+            Unit::Wei => 0,
+            Unit::Gwei => 9,
+            Unit::Ether => 18,
+        }
+    }
+
+    /// Writes an amount of wei in this unit and `format`.
+    ///
+    /// Wei stay an integer in either format; gwei and ether are decimal
+    /// strings with every fractional digit, and cannot be written in hex.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_unit_format() {
+    /// let value = U256::from(1_500_000_000u64);
+    /// assert_eq!(Unit::Gwei.format(value, NumberFormat::Dec).unwrap(), "1.500000000");
+    /// # }
+    /// ```
+    pub fn format(self, value: U256, format: NumberFormat) -> Result<String, AppError> {
+        match (self, format) {
+            (Unit::Wei, format) => Ok(format.format(value)),
+            (unit, NumberFormat::Dec) => format_units(value, unit.decimals())
+                .map_err(|error| AppError::Internal(error.to_string())),
+            (_, NumberFormat::Hex) => Err(AppError::InvalidRequest(
+                "format=hex is only supported with unit=wei".to_string(),
+            )),
+        }
+    }
+}
+
+/// Query parameters accepted by the routes returning a single amount of ether.
+#[derive(Debug, Default, Deserialize)]
+pub struct AmountQuery {
+    #[serde(default)]
+    pub format: NumberFormat,
+    #[serde(default)]
+    pub unit: Unit,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(NumberFormat::Dec.format(U256::from(1000)), "1000");
+        assert_eq!(NumberFormat::Hex.format(U256::from(1000)), "0x3e8");
+        assert_eq!(NumberFormat::Hex.format(U256::ZERO), "0x0");
+    }
+
+    #[test]
+    fn test_unit_format() {
+        let value = U256::from(1_234_567_890_123_456_789u64);
+        assert_eq!(
+            Unit::Wei.format(value, NumberFormat::Dec).unwrap(),
+            "1234567890123456789"
+        );
+        assert_eq!(
+            Unit::Gwei.format(value, NumberFormat::Dec).unwrap(),
+            "1234567890.123456789"
+        );
+        assert_eq!(
+            Unit::Ether.format(value, NumberFormat::Dec).unwrap(),
+            "1.234567890123456789"
+        );
+        assert_eq!(
+            Unit::Wei
+                .format(U256::from(1000), NumberFormat::Hex)
+                .unwrap(),
+            "0x3e8"
+        );
+    }
+
+    #[test]
+    fn test_unit_format_pads_small_amounts() {
+        assert_eq!(
+            Unit::Gwei.format(U256::from(1), NumberFormat::Dec).unwrap(),
+            "0.000000001"
+        );
+        assert_eq!(
+            Unit::Ether.format(U256::ZERO, NumberFormat::Dec).unwrap(),
+            "0.000000000000000000"
+        );
+        assert_eq!(
+            Unit::Ether
+                .format(U256::from(10u64.pow(18)), NumberFormat::Dec)
+                .unwrap(),
+            "1.000000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_unit_format_is_exact_at_the_maximum() {
+        let max = "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+        assert_eq!(Unit::Wei.format(U256::MAX, NumberFormat::Dec).unwrap(), max);
+        assert_eq!(
+            Unit::Wei.format(U256::MAX, NumberFormat::Hex).unwrap(),
+            format!("0x{}", "f".repeat(64))
+        );
+        assert_eq!(
+            Unit::Gwei.format(U256::MAX, NumberFormat::Dec).unwrap(),
+            format!("{}.{}", &max[..max.len() - 9], &max[max.len() - 9..])
+        );
+        assert_eq!(
+            Unit::Ether.format(U256::MAX, NumberFormat::Dec).unwrap(),
+            "115792089237316195423570985008687907853269984665640564039457.584007913129639935"
+        );
+    }
+
+    #[test]
+    fn test_units_other_than_wei_reject_hex() {
+        for unit in [Unit::Gwei, Unit::Ether] {
+            let error = unit.format(U256::from(1), NumberFormat::Hex).unwrap_err();
+            assert!(matches!(error, AppError::InvalidRequest(_)));
+        }
+    }
+
+    #[test]
+    fn test_amount_query() {
+        let query: AmountQuery = serde_json::from_str("{}").unwrap();
+        assert_eq!(query.format, NumberFormat::Dec);
+        let query: AmountQuery = serde_json::from_str(r#"{"format":"hex"}"#).unwrap();
+        assert_eq!(query.format, NumberFormat::Hex);
+        assert!(serde_json::from_str::<AmountQuery>(r#"{"format":"oct"}"#).is_err());
+
+        assert_eq!(query.unit, Unit::Wei);
+        let query: AmountQuery = serde_json::from_str(r#"{"unit":"ether"}"#).unwrap();
+        assert_eq!(query.unit, Unit::Ether);
+        assert!(serde_json::from_str::<AmountQuery>(r#"{"unit":"finney"}"#).is_err());
+    }
+}
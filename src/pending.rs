@@ -0,0 +1,284 @@
+use crate::config;
+use alloy::providers::Provider;
+use alloy::rpc::types::FilterChanges;
+use alloy::transports::TransportError;
+use alloy_primitives::{B256, U256};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::{interval, timeout};
+
+/// Pending transaction poller settings read from the environment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PendingConfig {
+    pub poll_interval: Duration,
+    pub capacity: usize,
+}
+
+/// Reads the poller settings; returns `None` unless `ENABLE_PENDING_POLL=true`.
+///
+/// Fails when `PENDING_POLL_INTERVAL_MS` is zero.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_pending_config() {
+/// let config = get_pending_config().unwrap();
+/// assert!(config.is_none());
+/// # }
+/// ```
+pub fn get_pending_config() -> anyhow::Result<Option<PendingConfig>> {
+    if !config::env_flag("ENABLE_PENDING_POLL") {
+        return Ok(None);
+    }
+    Ok(Some(PendingConfig {
+        poll_interval: config::nonzero_interval(
+            "PENDING_POLL_INTERVAL_MS",
+            Duration::from_millis(config::env_or("PENDING_POLL_INTERVAL_MS", 1000)),
+        )?,
+        capacity: config::env_or("PENDING_BUFFER_SIZE", 10_000),
+    }))
+}
+
+/// Hashes returned by one `/pending/poll` call.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PendingPage {
+    /// Cursor to send with the next poll.
+    pub cursor: u64,
+    pub hashes: Vec<B256>,
+    /// Whether hashes after the given cursor were already evicted from the buffer.
+    pub truncated: bool,
+}
+
+struct BufferInner {
+    /// Sequence number of the oldest buffered hash.
+    first: u64,
+    hashes: VecDeque<B256>,
+}
+
+impl BufferInner {
+    fn end(&self) -> u64 {
+        self.first + self.hashes.len() as u64
+    }
+}
+
+/// Bounded ring buffer of pending transaction hashes, each numbered in arrival order.
+pub struct PendingBuffer {
+    capacity: usize,
+    inner: Mutex<BufferInner>,
+    arrived: Notify,
+}
+
+impl PendingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        PendingBuffer {
+            capacity: capacity.max(1),
+            inner: Mutex::new(BufferInner {
+                first: 0,
+                hashes: VecDeque::new(),
+            }),
+            arrived: Notify::new(),
+        }
+    }
+
+    /// Appends hashes, evicting the oldest ones beyond the capacity.
+    pub fn push(&self, hashes: impl IntoIterator<Item = B256>) {
+        let mut inner = self.inner.lock().expect("pending buffer poisoned");
+        for hash in hashes {
+            if inner.hashes.len() == self.capacity {
+                inner.hashes.pop_front();
+                inner.first += 1;
+            }
+            inner.hashes.push_back(hash);
+        }
+        drop(inner);
+        self.arrived.notify_waiters();
+    }
+
+    /// Hashes buffered at or after `cursor`; without a cursor, everything buffered.
+    pub fn since(&self, cursor: Option<u64>) -> PendingPage {
+        let inner = self.inner.lock().expect("pending buffer poisoned");
+        let cursor = cursor.unwrap_or(inner.first);
+        let start = cursor.clamp(inner.first, inner.end());
+        PendingPage {
+            cursor: inner.end(),
+            hashes: inner
+                .hashes
+                .iter()
+                .skip((start - inner.first) as usize)
+                .copied()
+                .collect(),
+            truncated: cursor < inner.first,
+        }
+    }
+
+    /// Like [`PendingBuffer::since`], but waits up to `wait` for new hashes when there are none.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_wait_since() {
+    /// let buffer = PendingBuffer::new(100);
+    /// let page = buffer.wait_since(Some(0), Duration::from_millis(10)).await;
+    /// assert!(page.hashes.is_empty());
+    /// # }
+    /// ```
+    pub async fn wait_since(&self, cursor: Option<u64>, wait: Duration) -> PendingPage {
+        let arrived = self.arrived.notified();
+        let page = self.since(cursor);
+        if !page.hashes.is_empty() || wait.is_zero() {
+            return page;
+        }
+        // Hashes pushed after `since` released the lock still wake `arrived`.
+        let _ = timeout(wait, arrived).await;
+        self.since(Some(page.cursor))
+    }
+}
+
+/// Feeds a [`PendingBuffer`] from an `eth_newPendingTransactionFilter` filter.
+pub struct PendingPoller {
+    buffer: Arc<PendingBuffer>,
+    filter: Option<U256>,
+}
+
+impl PendingPoller {
+    pub fn new(buffer: Arc<PendingBuffer>) -> Self {
+        PendingPoller {
+            buffer,
+            filter: None,
+        }
+    }
+
+    /// Fetches the hashes seen since the last poll, installing the filter first if needed.
+    pub async fn poll_once(&mut self, provider: &dyn Provider) -> Result<usize, TransportError> {
+        let filter = match self.filter {
+            Some(filter) => filter,
+            None => {
+                let filter = provider.new_pending_transactions_filter(false).await?;
+                info!("Installed pending transaction filter {}", filter);
+                *self.filter.insert(filter)
+            }
+        };
+
+        let hashes = match provider.get_filter_changes_dyn(filter).await {
+            Ok(FilterChanges::Hashes(hashes)) => hashes,
+            Ok(_) => Vec::new(),
+            Err(error) => {
+                // The node may have dropped the filter; install a new one next time.
+                self.filter = None;
+                return Err(error);
+            }
+        };
+        let count = hashes.len();
+        self.buffer.push(hashes);
+        Ok(count)
+    }
+
+    /// Polls forever, at the given interval.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_run() {
+    /// let buffer = Arc::new(PendingBuffer::new(10_000));
+    /// tokio::spawn(PendingPoller::new(buffer.clone()).run(provider.clone(), Duration::from_secs(1)));
+    /// # }
+    /// ```
+    pub async fn run(mut self, provider: Arc<dyn Provider>, every: Duration) {
+        let mut ticker = interval(every);
+        loop {
+            ticker.tick().await;
+            if let Err(error) = self.poll_once(provider.as_ref()).await {
+                warn!("Polling pending transactions failed: {}", error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::providers::RootProvider;
+    use alloy::transports::TransportResult;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    fn hash(n: u8) -> B256 {
+        B256::repeat_byte(n)
+    }
+
+    #[test]
+    fn test_since_returns_hashes_after_cursor() {
+        let buffer = PendingBuffer::new(10);
+        buffer.push([hash(1), hash(2)]);
+
+        let page = buffer.since(None);
+        assert_eq!(page.hashes, vec![hash(1), hash(2)]);
+        assert_eq!(page.cursor, 2);
+
+        buffer.push([hash(3)]);
+        let page = buffer.since(Some(page.cursor));
+        assert_eq!(page.hashes, vec![hash(3)]);
+        assert_eq!(page.cursor, 3);
+        assert!(!page.truncated);
+
+        assert!(buffer.since(Some(3)).hashes.is_empty());
+    }
+
+    #[test]
+    fn test_buffer_is_bounded() {
+        let buffer = PendingBuffer::new(2);
+        buffer.push([hash(1), hash(2), hash(3)]);
+
+        let page = buffer.since(Some(0));
+        assert_eq!(page.hashes, vec![hash(2), hash(3)]);
+        assert!(page.truncated);
+        assert_eq!(page.cursor, 3);
+    }
+
+    #[tokio::test]
+    async fn test_wait_since_wakes_on_new_hashes() {
+        let buffer = Arc::new(PendingBuffer::new(10));
+        let pusher = buffer.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            pusher.push([hash(9)]);
+        });
+
+        let page = buffer.wait_since(Some(0), Duration::from_secs(5)).await;
+        assert_eq!(page.hashes, vec![hash(9)]);
+    }
+
+    /// Node reporting one new pending hash per poll.
+    #[derive(Default)]
+    struct MempoolProvider(AtomicU64);
+
+    #[async_trait::async_trait]
+    impl Provider for MempoolProvider {
+        async fn new_pending_transactions_filter(&self, _full: bool) -> TransportResult<U256> {
+            Ok(U256::from(1))
+        }
+
+        async fn get_filter_changes_dyn(&self, _id: U256) -> TransportResult<FilterChanges> {
+            let n = self.0.fetch_add(1, Ordering::Relaxed) as u8;
+            Ok(FilterChanges::Hashes(vec![hash(n)]))
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("MempoolProvider does not support `root`")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_poll_once_feeds_the_buffer() {
+        let buffer = Arc::new(PendingBuffer::new(10));
+        let mut poller = PendingPoller::new(buffer.clone());
+        let provider = MempoolProvider::default();
+
+        assert_eq!(poller.poll_once(&provider).await.unwrap(), 1);
+        assert_eq!(poller.poll_once(&provider).await.unwrap(), 1);
+        assert_eq!(buffer.since(None).hashes, vec![hash(0), hash(1)]);
+    }
+}
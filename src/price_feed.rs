@@ -0,0 +1,130 @@
+use crate::cache::TtlCache;
+use crate::config;
+use anyhow::{anyhow, Context};
+use log::warn;
+use std::env;
+use std::time::Duration;
+
+/// Placeholder replaced by the requested currency in `PRICE_FEED_URL`.
+const CURRENCY_PLACEHOLDER: &str = "{currency}";
+
+/// ETH prices fetched from an HTTP price feed and cached briefly.
+///
+/// The feed is expected to answer like CoinGecko's `simple/price`, e.g.
+/// `{ "ethereum": { "usd": 3012.5 } }`.
+pub struct PriceFeed {
+    url: String,
+    client: reqwest::Client,
+    prices: TtlCache<String, f64>,
+}
+
+impl PriceFeed {
+    pub fn new(url: impl Into<String>, ttl: Duration) -> Self {
+        PriceFeed {
+            url: url.into(),
+            client: reqwest::Client::new(),
+            prices: TtlCache::new(ttl),
+        }
+    }
+
+    /// Price of one ether in `currency`, from the cache when fresh.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_price() {
+    /// let feed = PriceFeed::new("https://prices.example/eth?vs={currency}", Duration::from_secs(60));
+    /// let price = feed.price("usd").await.unwrap();
+    /// # }
+    /// ```
+    pub async fn price(&self, currency: &str) -> anyhow::Result<f64> {
+        if let Some(price) = self.prices.get(&currency.to_string()) {
+            return Ok(price);
+        }
+        let url = self.url.replace(CURRENCY_PLACEHOLDER, currency);
+        let body: serde_json::Value = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context("Price feed request failed")?
+            .json()
+            .await
+            .context("Price feed returned invalid JSON")?;
+        let price = body["ethereum"][currency]
+            .as_f64()
+            .ok_or_else(|| anyhow!("Price feed has no ethereum price in {}", currency))?;
+        self.prices.insert(currency.to_string(), price);
+        Ok(price)
+    }
+
+    /// Like [`PriceFeed::price`], logging failures and returning `None` instead.
+    pub async fn try_price(&self, currency: &str) -> Option<f64> {
+        self.price(currency)
+            .await
+            .map_err(|error| warn!("{:#}", error))
+            .ok()
+    }
+}
+
+/// Builds the price feed from `PRICE_FEED_URL`, caching prices for
+/// `PRICE_FEED_TTL_MS` (default 60000); `None` when the URL is unset.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_price_feed() {
+/// let feed = get_price_feed();
+/// assert!(feed.is_none());
+/// # }
+/// ```
+pub fn get_price_feed() -> Option<PriceFeed> {
+    let url = env::var("PRICE_FEED_URL")
+        .ok()
+        .filter(|url| !url.is_empty())?;
+    let ttl = Duration::from_millis(config::env_or("PRICE_FEED_TTL_MS", 60_000));
+    Some(PriceFeed::new(url, ttl))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use warp::Filter;
+
+    #[tokio::test]
+    async fn test_prices_are_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mock = warp::path!("price" / String).map(move |currency: String| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            warp::reply::json(&serde_json::json!({ "ethereum": { currency: 2000.5 } }))
+        });
+        let (addr, server) = warp::serve(mock).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let feed = PriceFeed::new(
+            format!("http://{}/price/{{currency}}", addr),
+            Duration::from_secs(60),
+        );
+        assert_eq!(feed.price("usd").await.unwrap(), 2000.5);
+        assert_eq!(feed.price("usd").await.unwrap(), 2000.5);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        assert_eq!(feed.price("eur").await.unwrap(), 2000.5);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_missing_prices_are_errors() {
+        let mock = warp::any().map(|| warp::reply::json(&serde_json::json!({})));
+        let (addr, server) = warp::serve(mock).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let feed = PriceFeed::new(format!("http://{}/", addr), Duration::from_secs(60));
+        assert!(feed.price("usd").await.is_err());
+        assert_eq!(feed.try_price("usd").await, None);
+    }
+}
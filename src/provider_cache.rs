@@ -0,0 +1,426 @@
+use crate::config;
+use alloy::rpc::json_rpc::{
+    RequestPacket, Response, ResponsePacket, ResponsePayload, SerializedRequest,
+};
+use alloy::transports::{TransportError, TransportFut};
+use log::debug;
+use serde_json::value::RawValue;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower::{Layer, Service};
+
+/// Provider cache settings read from the environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProviderCacheConfig {
+    /// How long results at a moving tag such as `latest` are reused.
+    pub latest_ttl: Duration,
+    /// Entries kept before expired ones are dropped.
+    pub max_entries: usize,
+}
+
+/// Reads the provider cache settings; returns `None` unless `ENABLE_PROVIDER_CACHE=true`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_provider_cache_config() {
+/// let config = get_provider_cache_config();
+/// assert!(config.is_none());
+/// # }
+/// ```
+pub fn get_provider_cache_config() -> Option<ProviderCacheConfig> {
+    if !config::env_flag("ENABLE_PROVIDER_CACHE") {
+        return None;
+    }
+    Some(ProviderCacheConfig {
+        latest_ttl: Duration::from_millis(config::env_or("PROVIDER_CACHE_LATEST_TTL_MS", 1000)),
+        max_entries: config::env_or("PROVIDER_CACHE_MAX_ENTRIES", 10_000),
+    })
+}
+
+/// How long the result of a request may be reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// Queried at a specific block number or hash, so it never changes.
+    Pinned,
+    /// Queried at a tag that moves with the chain head.
+    Latest,
+}
+
+/// Position of the block parameter of the methods whose results are cached.
+fn block_param_index(method: &str) -> Option<usize> {
+    match method {
+        "eth_getBlockByNumber" => Some(0),
+        "eth_getBalance" | "eth_getTransactionCount" | "eth_getCode" | "eth_call" => Some(1),
+        "eth_getStorageAt" => Some(2),
+        _ => None,
+    }
+}
+
+/// Decides whether a request may be cached, and for how long.
+///
+/// Requests at `pending`, and methods without a block parameter, are never cached.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_classify() {
+/// let params = serde_json::json!(["0x0000000000000000000000000000000000000000", "0x10"]);
+/// assert_eq!(classify("eth_getBalance", Some(&params)), Some(Freshness::Pinned));
+/// # }
+/// ```
+pub fn classify(method: &str, params: Option<&Value>) -> Option<Freshness> {
+    let index = block_param_index(method)?;
+    let block = params
+        .and_then(Value::as_array)
+        .and_then(|params| params.get(index));
+    match block {
+        // Omitted blocks default to `latest`.
+        None | Some(Value::Null) => Some(Freshness::Latest),
+        Some(Value::String(tag)) => match tag.as_str() {
+            "pending" => None,
+            "latest" | "safe" | "finalized" => Some(Freshness::Latest),
+            // `earliest`, a number, or a hash.
+            _ => Some(Freshness::Pinned),
+        },
+        // EIP-1898 `{ "blockHash" }` or `{ "blockNumber" }`.
+        Some(Value::Object(_)) => Some(Freshness::Pinned),
+        Some(_) => None,
+    }
+}
+
+struct Entry {
+    inserted: Instant,
+    freshness: Freshness,
+    result: Box<RawValue>,
+}
+
+/// Results of node requests keyed by method and params, including the block.
+///
+/// Results pinned to a block number or hash are kept until evicted, while
+/// those at `latest`, `safe` or `finalized` expire after a short TTL. Blocks
+/// close to the head can still be reorged, so requests pinned to them may be
+/// answered from the replaced chain until the entry is evicted.
+pub struct ProviderCache {
+    config: ProviderCacheConfig,
+    entries: Mutex<HashMap<(String, String), Entry>>,
+}
+
+impl ProviderCache {
+    pub fn new(config: ProviderCacheConfig) -> Self {
+        ProviderCache {
+            config,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn is_fresh(&self, entry: &Entry, now: Instant) -> bool {
+        match entry.freshness {
+            Freshness::Pinned => true,
+            Freshness::Latest => {
+                now.saturating_duration_since(entry.inserted) < self.config.latest_ttl
+            }
+        }
+    }
+
+    fn get_at(&self, key: &(String, String), now: Instant) -> Option<Box<RawValue>> {
+        let entries = self.entries.lock().expect("provider cache poisoned");
+        entries
+            .get(key)
+            .filter(|entry| self.is_fresh(entry, now))
+            .map(|entry| entry.result.clone())
+    }
+
+    fn insert_at(
+        &self,
+        key: (String, String),
+        freshness: Freshness,
+        result: Box<RawValue>,
+        now: Instant,
+    ) {
+        let mut entries = self.entries.lock().expect("provider cache poisoned");
+        if entries.len() >= self.config.max_entries {
+            entries.retain(|_, entry| self.is_fresh(entry, now));
+            if entries.len() >= self.config.max_entries {
+                entries.clear();
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                inserted: now,
+                freshness,
+                result,
+            },
+        );
+    }
+}
+
+/// Cache key and freshness of a request, when its result may be cached.
+fn cacheable(request: &SerializedRequest) -> Option<((String, String), Freshness)> {
+    let params = request.params().map(RawValue::get).unwrap_or("null");
+    let value: Value = serde_json::from_str(params).ok()?;
+    let freshness = classify(request.method(), Some(&value))?;
+    Some((
+        (request.method().to_string(), params.to_string()),
+        freshness,
+    ))
+}
+
+/// Transport layer answering repeated requests from a [`ProviderCache`], if
+/// there is one, and passing every request through otherwise.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_provider_cache_layer() {
+/// let cache = get_provider_cache_config().map(|config| Arc::new(ProviderCache::new(config)));
+/// let client = ClientBuilder::default()
+///     .layer(ProviderCacheLayer::new(cache))
+///     .transport(transport, is_local);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ProviderCacheLayer {
+    cache: Option<Arc<ProviderCache>>,
+}
+
+impl ProviderCacheLayer {
+    pub fn new(cache: Option<Arc<ProviderCache>>) -> Self {
+        ProviderCacheLayer { cache }
+    }
+}
+
+impl<S> Layer<S> for ProviderCacheLayer {
+    type Service = ProviderCacheService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ProviderCacheService {
+            inner,
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ProviderCacheService<S> {
+    inner: S,
+    cache: Option<Arc<ProviderCache>>,
+}
+
+impl<S> Service<RequestPacket> for ProviderCacheService<S>
+where
+    S: Service<
+            RequestPacket,
+            Response = ResponsePacket,
+            Error = TransportError,
+            Future = TransportFut<'static>,
+        > + Send
+        + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        // Batches are passed through as-is.
+        let cacheable = match (&self.cache, &request) {
+            (Some(cache), RequestPacket::Single(single)) => {
+                cacheable(single).map(|(key, freshness)| (cache.clone(), key, freshness))
+            }
+            _ => None,
+        };
+        let Some((cache, key, freshness)) = cacheable else {
+            return self.inner.call(request);
+        };
+
+        if let Some(result) = cache.get_at(&key, Instant::now()) {
+            debug!("Provider cache hit for {}", key.0);
+            let RequestPacket::Single(single) = &request else {
+                unreachable!("only single requests are cached")
+            };
+            let response = ResponsePacket::Single(Response {
+                id: single.id().clone(),
+                payload: ResponsePayload::Success(result),
+            });
+            return Box::pin(async move { Ok(response) });
+        }
+
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            // Errors and `null` results, e.g. a block not mined yet, are not kept.
+            if let ResponsePacket::Single(Response {
+                payload: ResponsePayload::Success(result),
+                ..
+            }) = &response
+            {
+                if result.get() != "null" {
+                    cache.insert_at(key, freshness, result.clone(), Instant::now());
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::rpc::json_rpc::Request;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Transport answering every request with its request count so far.
+    #[derive(Clone, Default)]
+    struct CountingTransport(Arc<AtomicUsize>);
+
+    impl Service<RequestPacket> for CountingTransport {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = TransportFut<'static>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: RequestPacket) -> Self::Future {
+            let count = self.0.fetch_add(1, Ordering::Relaxed) + 1;
+            let RequestPacket::Single(single) = request else {
+                unimplemented!("CountingTransport does not support batches")
+            };
+            let result = RawValue::from_string(format!("\"{:#x}\"", count)).unwrap();
+            let response = ResponsePacket::Single(Response {
+                id: single.id().clone(),
+                payload: ResponsePayload::Success(result),
+            });
+            Box::pin(async move { Ok(response) })
+        }
+    }
+
+    const ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+
+    fn balance_at(block: &str) -> RequestPacket {
+        Request::new("eth_getBalance", 1.into(), (ADDRESS, block))
+            .serialize()
+            .unwrap()
+            .into()
+    }
+
+    fn result(response: ResponsePacket) -> String {
+        match response {
+            ResponsePacket::Single(Response {
+                payload: ResponsePayload::Success(result),
+                ..
+            }) => result.get().to_string(),
+            other => panic!("unexpected response {:?}", other),
+        }
+    }
+
+    fn service(
+        latest_ttl: Duration,
+    ) -> (
+        ProviderCacheService<CountingTransport>,
+        Arc<ProviderCache>,
+        Arc<AtomicUsize>,
+    ) {
+        let transport = CountingTransport::default();
+        let calls = transport.0.clone();
+        let cache = Arc::new(ProviderCache::new(ProviderCacheConfig {
+            latest_ttl,
+            max_entries: 100,
+        }));
+        let service = ProviderCacheLayer::new(Some(cache.clone())).layer(transport);
+        (service, cache, calls)
+    }
+
+    #[test]
+    fn test_classify() {
+        let at = |block: Value| json!([ADDRESS, block]);
+        assert_eq!(
+            classify("eth_getBalance", Some(&at(json!("0x10")))),
+            Some(Freshness::Pinned)
+        );
+        assert_eq!(
+            classify("eth_getCode", Some(&at(json!({ "blockHash": "0x01" })))),
+            Some(Freshness::Pinned)
+        );
+        assert_eq!(
+            classify("eth_getBalance", Some(&at(json!("latest")))),
+            Some(Freshness::Latest)
+        );
+        assert_eq!(
+            classify("eth_call", Some(&json!([{}]))),
+            Some(Freshness::Latest)
+        );
+        assert_eq!(
+            classify("eth_getBlockByNumber", Some(&json!(["finalized", false]))),
+            Some(Freshness::Latest)
+        );
+        assert_eq!(
+            classify("eth_getBalance", Some(&at(json!("pending")))),
+            None
+        );
+        assert_eq!(classify("eth_blockNumber", None), None);
+        assert_eq!(
+            classify("eth_sendRawTransaction", Some(&json!(["0x01"]))),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pinned_queries_are_served_from_cache() {
+        let (mut service, cache, calls) = service(Duration::ZERO);
+
+        let first = result(service.call(balance_at("0x10")).await.unwrap());
+        let second = result(service.call(balance_at("0x10")).await.unwrap());
+        assert_eq!(first, second);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // Another block is another entry.
+        service.call(balance_at("0x11")).await.unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(cache.entries.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_latest_queries_expire() {
+        let (mut service, cache, calls) = service(Duration::from_secs(60));
+
+        let first = result(service.call(balance_at("latest")).await.unwrap());
+        let second = result(service.call(balance_at("latest")).await.unwrap());
+        assert_eq!(first, second);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        let key = cacheable(&match balance_at("latest") {
+            RequestPacket::Single(single) => single,
+            RequestPacket::Batch(_) => unreachable!(),
+        })
+        .unwrap()
+        .0;
+        let later = Instant::now() + Duration::from_secs(61);
+        assert!(cache.get_at(&key, later).is_none());
+
+        // A zero TTL sends every `latest` query to the node.
+        let (mut service, _, calls) = self::service(Duration::ZERO);
+        service.call(balance_at("latest")).await.unwrap();
+        service.call(balance_at("latest")).await.unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pending_queries_are_not_cached() {
+        let (mut service, cache, calls) = service(Duration::from_secs(60));
+        service.call(balance_at("pending")).await.unwrap();
+        service.call(balance_at("pending")).await.unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(cache.entries.lock().unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::config;
+use crate::error::AppError;
+
+/// Caps on the query string accepted by every route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryLimits {
+    /// Most `key=value` pairs in one query string.
+    pub max_params: usize,
+    /// Longest value, in bytes as sent (still percent-encoded).
+    pub max_value_len: usize,
+}
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        QueryLimits {
+            max_params: 32,
+            max_value_len: 1024,
+        }
+    }
+}
+
+/// Reads the query string caps from `MAX_QUERY_PARAMS` and `MAX_QUERY_VALUE_LEN`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_query_limits() {
+/// let limits = get_query_limits();
+/// assert_eq!(limits.max_params, 32);
+/// # }
+/// ```
+pub fn get_query_limits() -> QueryLimits {
+    let defaults = QueryLimits::default();
+    QueryLimits {
+        max_params: config::env_or("MAX_QUERY_PARAMS", defaults.max_params),
+        max_value_len: config::env_or("MAX_QUERY_VALUE_LEN", defaults.max_value_len),
+    }
+}
+
+impl QueryLimits {
+    /// Rejects a raw query string with too many parameters or an over-long value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_check() {
+    /// assert!(QueryLimits::default().check("block=latest").is_ok());
+    /// # }
+    /// ```
+    pub fn check(&self, query: &str) -> Result<(), AppError> {
+        let params: Vec<&str> = query.split('&').filter(|pair| !pair.is_empty()).collect();
+        if params.len() > self.max_params {
+            return Err(AppError::InvalidRequest(format!(
+                "too many query parameters ({}, at most {})",
+                params.len(),
+                self.max_params
+            )));
+        }
+        for pair in params {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            if value.len() > self.max_value_len {
+                return Err(AppError::InvalidRequest(format!(
+                    "query parameter {} is longer than {} bytes",
+                    key, self.max_value_len
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: QueryLimits = QueryLimits {
+        max_params: 2,
+        max_value_len: 4,
+    };
+
+    #[test]
+    fn test_too_many_params_are_rejected() {
+        assert!(LIMITS.check("").is_ok());
+        assert!(LIMITS.check("a=1&b=2").is_ok());
+        // Empty pairs from stray separators do not count.
+        assert!(LIMITS.check("a=1&&b=2&").is_ok());
+        assert!(matches!(
+            LIMITS.check("a=1&b=2&c=3"),
+            Err(AppError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_long_values_are_rejected() {
+        assert!(LIMITS.check("block=safe").is_ok());
+        let error = LIMITS.check("block=latest").unwrap_err();
+        assert!(error.to_string().contains("block"));
+    }
+}
@@ -0,0 +1,168 @@
+use crate::error::AppError;
+use anyhow::{bail, Context as _};
+use log::info;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// Daily request quotas by API key, counted per UTC day.
+///
+/// Requests are counted per `X-API-Key`; keys without a quota, and requests
+/// without a key, are not limited here.
+pub struct DailyQuotas {
+    limits: HashMap<String, u64>,
+    /// Requests made with each key, and the UTC day they were counted on.
+    used: Mutex<HashMap<String, (u64, u64)>>,
+}
+
+impl DailyQuotas {
+    pub fn new(limits: HashMap<String, u64>) -> Self {
+        DailyQuotas {
+            limits,
+            used: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts a request made with `key`, rejecting it once the key has used
+    /// up its quota for the day.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_check() {
+    /// let quotas = DailyQuotas::new(parse_quotas("client-a=1").unwrap());
+    /// assert!(quotas.check("client-a").is_ok());
+    /// assert!(matches!(quotas.check("client-a"), Err(AppError::QuotaExceeded { .. })));
+    /// # }
+    /// ```
+    pub fn check(&self, key: &str) -> Result<(), AppError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        self.check_at(key, now)
+    }
+
+    /// [`check`](Self::check) at `now`, in Unix seconds.
+    fn check_at(&self, key: &str, now: u64) -> Result<(), AppError> {
+        let Some(&limit) = self.limits.get(key) else {
+            return Ok(());
+        };
+        let today = now / SECS_PER_DAY;
+        let mut used = self.used.lock().expect("quotas poisoned");
+        let (day, count) = used.entry(key.to_string()).or_insert((today, 0));
+        if *day != today {
+            (*day, *count) = (today, 0);
+        }
+        if *count >= limit {
+            let resets_at = (today + 1) * SECS_PER_DAY;
+            return Err(AppError::QuotaExceeded {
+                resets_at,
+                retry_after: resets_at - now,
+            });
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+/// Parses `API_KEY_DAILY_QUOTAS` entries of the form `key=requests,key=requests`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_parse_quotas() {
+/// let quotas = parse_quotas("client-a=10000, client-b=500").unwrap();
+/// assert_eq!(quotas["client-b"], 500);
+/// # }
+/// ```
+pub fn parse_quotas(value: &str) -> anyhow::Result<HashMap<String, u64>> {
+    let mut quotas = HashMap::new();
+    for entry in value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        // Entries are not echoed, since they hold API keys.
+        let Some((key, quota)) = entry.rsplit_once('=') else {
+            bail!("Quota entries must be of the form key=requests");
+        };
+        let quota = quota
+            .trim()
+            .parse()
+            .context("Quotas must be numbers of requests")?;
+        if quotas.insert(key.trim().to_string(), quota).is_some() {
+            bail!("An API key has more than one quota");
+        }
+    }
+    Ok(quotas)
+}
+
+/// Reads the daily quota of each API key from `API_KEY_DAILY_QUOTAS`; `None` when unset.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_daily_quotas() {
+/// let quotas = get_daily_quotas().unwrap();
+/// assert!(quotas.is_none());
+/// # }
+/// ```
+pub fn get_daily_quotas() -> anyhow::Result<Option<DailyQuotas>> {
+    let Ok(value) = env::var("API_KEY_DAILY_QUOTAS") else {
+        return Ok(None);
+    };
+    let limits = parse_quotas(&value).context("Invalid API_KEY_DAILY_QUOTAS")?;
+    info!("Enforcing daily quotas of {} API keys", limits.len());
+    Ok(Some(DailyQuotas::new(limits)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-01-01T23:59:00Z.
+    const BEFORE_MIDNIGHT: u64 = 1_704_153_540;
+
+    #[test]
+    fn test_quota_resets_at_utc_midnight() {
+        let quotas = DailyQuotas::new(parse_quotas("client-a=2").unwrap());
+        assert!(quotas.check_at("client-a", BEFORE_MIDNIGHT).is_ok());
+        assert!(quotas.check_at("client-a", BEFORE_MIDNIGHT + 1).is_ok());
+        match quotas.check_at("client-a", BEFORE_MIDNIGHT + 2) {
+            Err(AppError::QuotaExceeded {
+                resets_at,
+                retry_after,
+            }) => {
+                assert_eq!(resets_at, BEFORE_MIDNIGHT + 60);
+                assert_eq!(retry_after, 58);
+            }
+            other => panic!("expected the quota to be exceeded, got {:?}", other),
+        }
+
+        // The next UTC day starts from zero.
+        assert!(quotas.check_at("client-a", BEFORE_MIDNIGHT + 60).is_ok());
+        assert!(quotas.check_at("client-a", BEFORE_MIDNIGHT + 61).is_ok());
+        assert!(quotas.check_at("client-a", BEFORE_MIDNIGHT + 62).is_err());
+
+        // Keys without a quota are not counted.
+        for _ in 0..5 {
+            assert!(quotas.check_at("other", BEFORE_MIDNIGHT).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_parse_quotas() {
+        let quotas = parse_quotas("client-a=10000, client-b = 500").unwrap();
+        assert_eq!(quotas.len(), 2);
+        assert_eq!(quotas["client-a"], 10_000);
+        assert_eq!(quotas["client-b"], 500);
+        assert!(parse_quotas("").unwrap().is_empty());
+        assert!(parse_quotas("client-a").is_err());
+        assert!(parse_quotas("client-a=many").is_err());
+        assert!(parse_quotas("client-a=1,client-a=2").is_err());
+    }
+}
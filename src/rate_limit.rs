@@ -0,0 +1,223 @@
+use crate::config;
+use log::{debug, info};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::time::interval;
+
+/// Token bucket of a single client.
+struct Bucket {
+    tokens: f64,
+    last_seen: Instant,
+}
+
+/// Bucket size and refill rate shared by every client.
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    per_minute: u32,
+    burst: u32,
+}
+
+impl Limits {
+    fn capacity(&self) -> f64 {
+        f64::from(self.burst.max(1))
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+}
+
+/// Per-IP token bucket rate limiter.
+pub struct RateLimiter {
+    limits: RwLock<Limits>,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+/// Rate limiter settings read from the environment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitConfig {
+    pub per_minute: u32,
+    pub burst: u32,
+    pub sweep_interval: Duration,
+    pub idle_ttl: Duration,
+}
+
+/// Reads the rate limiter settings; returns `None` when `RATE_LIMIT_PER_MINUTE` is unset or zero.
+///
+/// Fails when `RATE_LIMIT_SWEEP_INTERVAL_SECS` is zero.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_rate_limit_config() {
+/// let config = get_rate_limit_config().unwrap();
+/// assert!(config.is_none());
+/// # }
+/// ```
+pub fn get_rate_limit_config() -> anyhow::Result<Option<RateLimitConfig>> {
+    let per_minute: u32 = config::env_or("RATE_LIMIT_PER_MINUTE", 0);
+    if per_minute == 0 {
+        return Ok(None);
+    }
+    Ok(Some(RateLimitConfig {
+        per_minute,
+        burst: config::env_or("RATE_LIMIT_BURST", per_minute),
+        sweep_interval: config::nonzero_interval(
+            "RATE_LIMIT_SWEEP_INTERVAL_SECS",
+            Duration::from_secs(config::env_or("RATE_LIMIT_SWEEP_INTERVAL_SECS", 60)),
+        )?,
+        idle_ttl: Duration::from_secs(config::env_or("RATE_LIMIT_IDLE_TTL_SECS", 300)),
+    }))
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        RateLimiter {
+            limits: RwLock::new(Limits { per_minute, burst }),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Requests per minute and burst size currently applied.
+    pub fn limits(&self) -> (u32, u32) {
+        let limits = *self.limits.read().expect("rate limiter poisoned");
+        (limits.per_minute, limits.burst)
+    }
+
+    /// Applies new limits; existing buckets keep their tokens, capped at the new burst.
+    pub fn reconfigure(&self, per_minute: u32, burst: u32) {
+        *self.limits.write().expect("rate limiter poisoned") = Limits { per_minute, burst };
+    }
+
+    /// Takes one token for `ip`, returning `false` when its bucket is empty.
+    pub fn check(&self, ip: IpAddr) -> bool {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: Instant) -> bool {
+        let limits = *self.limits.read().expect("rate limiter poisoned");
+        let capacity = limits.capacity();
+        let mut buckets = self.buckets.lock().expect("rate limiter poisoned");
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: capacity,
+            last_seen: now,
+        });
+
+        let elapsed = now
+            .saturating_duration_since(bucket.last_seen)
+            .as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limits.refill_per_sec()).min(capacity);
+        bucket.last_seen = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Number of clients currently tracked.
+    pub fn tracked_clients(&self) -> usize {
+        self.buckets.lock().expect("rate limiter poisoned").len()
+    }
+
+    /// Drops the buckets of clients idle for longer than `ttl`, returning how many were removed.
+    pub fn sweep(&self, ttl: Duration) -> usize {
+        self.sweep_at(Instant::now(), ttl)
+    }
+
+    fn sweep_at(&self, now: Instant, ttl: Duration) -> usize {
+        let mut buckets = self.buckets.lock().expect("rate limiter poisoned");
+        let before = buckets.len();
+        buckets.retain(|_, bucket| now.saturating_duration_since(bucket.last_seen) <= ttl);
+        before - buckets.len()
+    }
+
+    /// Sweeps idle clients forever, at the given interval.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_run_sweeper() {
+    /// let limiter = Arc::new(RateLimiter::new(60, 60));
+    /// tokio::spawn(limiter.clone().run_sweeper(Duration::from_secs(60), Duration::from_secs(300)));
+    /// # }
+    /// ```
+    pub async fn run_sweeper(self: Arc<Self>, every: Duration, ttl: Duration) {
+        info!(
+            "Sweeping idle rate limiter entries every {:?} (ttl {:?})",
+            every, ttl
+        );
+        let mut ticker = interval(every);
+        loop {
+            ticker.tick().await;
+            let removed = self.sweep(ttl);
+            debug!(
+                "Rate limiter sweep removed {} entries, {} remain",
+                removed,
+                self.tracked_clients()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const IDLE: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const ACTIVE: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    #[test]
+    fn test_bucket_empties_and_refills() {
+        let limiter = RateLimiter::new(60, 2);
+        let start = Instant::now();
+
+        assert!(limiter.check_at(IDLE, start));
+        assert!(limiter.check_at(IDLE, start));
+        assert!(!limiter.check_at(IDLE, start));
+        // One token per second at 60 per minute.
+        assert!(limiter.check_at(IDLE, start + Duration::from_secs(1)));
+        // Other clients have their own bucket.
+        assert!(limiter.check_at(ACTIVE, start));
+    }
+
+    #[test]
+    fn test_reconfigure_applies_new_burst() {
+        let limiter = RateLimiter::new(60, 1);
+        let start = Instant::now();
+
+        assert!(limiter.check_at(IDLE, start));
+        assert!(!limiter.check_at(IDLE, start));
+
+        limiter.reconfigure(120, 3);
+        assert_eq!(limiter.limits(), (120, 3));
+        assert!(limiter.check_at(ACTIVE, start));
+        assert!(limiter.check_at(ACTIVE, start));
+        assert!(limiter.check_at(ACTIVE, start));
+        assert!(!limiter.check_at(ACTIVE, start));
+    }
+
+    #[test]
+    fn test_sweep_evicts_idle_clients_only() {
+        let limiter = RateLimiter::new(60, 10);
+        let ttl = Duration::from_secs(300);
+        let start = Instant::now();
+
+        limiter.check_at(IDLE, start);
+        limiter.check_at(ACTIVE, start);
+        limiter.check_at(ACTIVE, start + Duration::from_secs(200));
+
+        let removed = limiter.sweep_at(start + Duration::from_secs(400), ttl);
+        assert_eq!(removed, 1);
+        assert_eq!(limiter.tracked_clients(), 1);
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert!(buckets.contains_key(&ACTIVE));
+        assert!(!buckets.contains_key(&IDLE));
+    }
+}
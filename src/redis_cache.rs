@@ -0,0 +1,150 @@
+use crate::cache::Cache;
+use crate::error_report::unix_millis;
+use alloy_primitives::{Address, U256};
+use anyhow::Context as _;
+use futures::future::BoxFuture;
+use log::warn;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Balance cache stored in Redis, shared by every instance using the same server.
+///
+/// Entries are written with their insertion time and a Redis expiry of the
+/// TTL they were inserted with, so a lowered TTL expires them as in memory;
+/// a raised TTL only applies to entries inserted afterwards, since Redis has
+/// already dropped older ones by then. Redis failures are logged and read as
+/// misses, so a Redis outage only costs node calls.
+pub struct RedisCache {
+    connection: ConnectionManager,
+    ttl_millis: AtomicU64,
+}
+
+impl RedisCache {
+    /// Connects to the Redis server at `url`, e.g. `redis://127.0.0.1:6379`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_redis_cache_connect() {
+    /// let cache = RedisCache::connect("redis://127.0.0.1:6379", Duration::from_secs(2)).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn connect(url: &str, ttl: Duration) -> anyhow::Result<Self> {
+        let client = redis::Client::open(url).context("Invalid REDIS_URL")?;
+        let connection = ConnectionManager::new(client)
+            .await
+            .context("Failed to connect to Redis")?;
+        Ok(RedisCache {
+            connection,
+            ttl_millis: AtomicU64::new(ttl.as_millis() as u64),
+        })
+    }
+}
+
+/// Redis key of the balance of `address` at `block`.
+fn key(address: &Address, block: &str) -> String {
+    format!("balance:{:#x}:{}", address, block)
+}
+
+/// `inserted_at:balance`, with the insertion time in Unix milliseconds.
+fn encode(inserted_at: u64, balance: U256) -> String {
+    format!("{}:{}", inserted_at, balance)
+}
+
+/// The balance of an entry written by [`encode`], unless it is `ttl_millis` old.
+fn decode(entry: &str, now: u64, ttl_millis: u64) -> Option<U256> {
+    let (inserted_at, balance) = entry.split_once(':')?;
+    let inserted_at: u64 = inserted_at.parse().ok()?;
+    if now.saturating_sub(inserted_at) >= ttl_millis {
+        return None;
+    }
+    balance.parse().ok()
+}
+
+impl Cache<(Address, String), U256> for RedisCache {
+    fn ttl(&self) -> Duration {
+        Duration::from_millis(self.ttl_millis.load(Ordering::Relaxed))
+    }
+
+    fn set_ttl(&self, ttl: Duration) {
+        self.ttl_millis
+            .store(ttl.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn get<'a>(&'a self, (address, block): &'a (Address, String)) -> BoxFuture<'a, Option<U256>> {
+        Box::pin(async move {
+            let ttl_millis = self.ttl_millis.load(Ordering::Relaxed);
+            if ttl_millis == 0 {
+                return None;
+            }
+            let mut connection = self.connection.clone();
+            match connection
+                .get::<_, Option<String>>(key(address, block))
+                .await
+            {
+                Ok(entry) => decode(&entry?, unix_millis(), ttl_millis),
+                Err(error) => {
+                    warn!("Failed to read the balance cache from Redis: {}", error);
+                    None
+                }
+            }
+        })
+    }
+
+    fn insert(&self, (address, block): (Address, String), balance: U256) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            let ttl_millis = self.ttl_millis.load(Ordering::Relaxed);
+            if ttl_millis == 0 {
+                return;
+            }
+            let mut connection = self.connection.clone();
+            let entry = encode(unix_millis(), balance);
+            let written: redis::RedisResult<()> = connection
+                .pset_ex(key(&address, &block), entry, ttl_millis)
+                .await;
+            if let Err(error) = written {
+                warn!("Failed to write the balance cache to Redis: {}", error);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_entries_expire_against_the_current_ttl() {
+        let entry = encode(1_000, U256::from(1000));
+        assert_eq!(entry, "1000:1000");
+        assert_eq!(decode(&entry, 1_500, 2_000), Some(U256::from(1000)));
+        assert_eq!(decode(&entry, 3_000, 2_000), None);
+        // A TTL lowered after the insert expires the entry early.
+        assert_eq!(decode(&entry, 1_500, 500), None);
+        assert_eq!(decode("garbage", 1_500, 2_000), None);
+    }
+
+    /// Runs against the server at `REDIS_URL`, and passes trivially without one.
+    #[tokio::test]
+    async fn test_redis_cache_round_trip() {
+        let Ok(url) = env::var("REDIS_URL") else {
+            return;
+        };
+        let cache = RedisCache::connect(&url, Duration::from_millis(200))
+            .await
+            .unwrap();
+        let entry = (Address::repeat_byte(0x42), "latest".to_string());
+        cache.insert(entry.clone(), U256::from(1000)).await;
+        assert_eq!(cache.get(&entry).await, Some(U256::from(1000)));
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(cache.get(&entry).await, None);
+
+        cache.set_ttl(Duration::ZERO);
+        cache.insert(entry.clone(), U256::from(1)).await;
+        assert_eq!(cache.get(&entry).await, None);
+    }
+}
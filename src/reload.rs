@@ -0,0 +1,227 @@
+use crate::cache::BalanceCache;
+use crate::error::AppError;
+use crate::rate_limit::RateLimiter;
+use anyhow::Context;
+use log::{info, LevelFilter};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Address the server listens on unless the config file sets `bind_addr`.
+pub const DEFAULT_BIND_ADDR: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED), 3030);
+
+/// Settings read from the file named by `CONFIG_FILE`; every key is optional.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    pub bind_addr: Option<SocketAddr>,
+    pub rate_limit_per_minute: Option<u32>,
+    pub rate_limit_burst: Option<u32>,
+    pub cache_ttl_ms: Option<u64>,
+    pub log_level: Option<String>,
+}
+
+/// Retrieves the path of the config file from the `CONFIG_FILE` environment variable.
+pub fn get_config_file() -> Option<PathBuf> {
+    env::var("CONFIG_FILE").ok().map(PathBuf::from)
+}
+
+/// Reads and parses a JSON config file.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_load_config_file() {
+/// let config = load_config_file(Path::new("config.json")).unwrap();
+/// println!("{:?}", config.cache_ttl_ms);
+/// # }
+/// ```
+pub fn load_config_file(path: &Path) -> anyhow::Result<FileConfig> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&contents)
+        .with_context(|| format!("Invalid config file {}", path.display()))
+}
+
+/// Settings in effect after a reload.
+#[derive(Debug, Serialize)]
+pub struct EffectiveConfig {
+    pub bind_addr: SocketAddr,
+    /// `null` when rate limiting is disabled.
+    pub rate_limit_per_minute: Option<u32>,
+    pub rate_limit_burst: Option<u32>,
+    pub cache_ttl_ms: u64,
+    pub log_level: String,
+    /// Keys whose new value only takes effect after a restart.
+    pub requires_restart: Vec<&'static str>,
+}
+
+/// Applies the hot-reloadable settings of the config file to the running server.
+pub struct Reloader {
+    config_file: PathBuf,
+    bind_addr: SocketAddr,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    balance_cache: Arc<BalanceCache>,
+}
+
+impl Reloader {
+    pub fn new(
+        config_file: PathBuf,
+        bind_addr: SocketAddr,
+        rate_limiter: Option<Arc<RateLimiter>>,
+        balance_cache: Arc<BalanceCache>,
+    ) -> Self {
+        Reloader {
+            config_file,
+            bind_addr,
+            rate_limiter,
+            balance_cache,
+        }
+    }
+
+    /// Re-reads the config file and applies it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_reload() {
+    /// let effective = reloader.reload().unwrap();
+    /// println!("{}", effective.cache_ttl_ms);
+    /// # }
+    /// ```
+    pub fn reload(&self) -> Result<EffectiveConfig, AppError> {
+        let config = load_config_file(&self.config_file)
+            .map_err(|error| AppError::Internal(format!("{:#}", error)))?;
+        self.apply(&config)
+    }
+
+    /// Applies the rate limit, cache TTL and log level of `config`.
+    ///
+    /// Nothing is applied when any of them is invalid. The bind address, and
+    /// the rate limit when it was disabled at startup, are reported in
+    /// `requires_restart` instead.
+    pub fn apply(&self, config: &FileConfig) -> Result<EffectiveConfig, AppError> {
+        let log_level = config
+            .log_level
+            .as_deref()
+            .map(|level| {
+                level
+                    .parse::<LevelFilter>()
+                    .map_err(|_| AppError::Internal(format!("unknown log level {:?}", level)))
+            })
+            .transpose()?;
+
+        let mut requires_restart = Vec::new();
+        if config.bind_addr.is_some_and(|addr| addr != self.bind_addr) {
+            requires_restart.push("bind_addr");
+        }
+
+        let wants_rate_limit =
+            config.rate_limit_per_minute.is_some() || config.rate_limit_burst.is_some();
+        match &self.rate_limiter {
+            Some(limiter) if wants_rate_limit => {
+                let (per_minute, burst) = limiter.limits();
+                let per_minute = config.rate_limit_per_minute.unwrap_or(per_minute);
+                limiter.reconfigure(per_minute, config.rate_limit_burst.unwrap_or(burst));
+            }
+            None if wants_rate_limit => requires_restart.push("rate_limit"),
+            _ => {}
+        }
+
+        if let Some(ttl_ms) = config.cache_ttl_ms {
+            self.balance_cache.set_ttl(Duration::from_millis(ttl_ms));
+        }
+        if let Some(level) = log_level {
+            log::set_max_level(level);
+        }
+
+        let effective = self.effective(requires_restart);
+        info!("Applied configuration: {:?}", effective);
+        Ok(effective)
+    }
+
+    fn effective(&self, requires_restart: Vec<&'static str>) -> EffectiveConfig {
+        let limits = self.rate_limiter.as_ref().map(|limiter| limiter.limits());
+        EffectiveConfig {
+            bind_addr: self.bind_addr,
+            rate_limit_per_minute: limits.map(|(per_minute, _)| per_minute),
+            rate_limit_burst: limits.map(|(_, burst)| burst),
+            cache_ttl_ms: self.balance_cache.ttl().as_millis() as u64,
+            log_level: log::max_level().to_string().to_lowercase(),
+            requires_restart,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::TtlCache;
+
+    fn reloader(rate_limiter: Option<Arc<RateLimiter>>) -> Reloader {
+        Reloader::new(
+            PathBuf::from("config.json"),
+            DEFAULT_BIND_ADDR,
+            rate_limiter,
+            Arc::new(TtlCache::new(Duration::from_secs(1))),
+        )
+    }
+
+    #[test]
+    fn test_apply_changes_cache_ttl_and_rate_limit() {
+        let limiter = Arc::new(RateLimiter::new(60, 10));
+        let reloader = reloader(Some(limiter.clone()));
+
+        let effective = reloader
+            .apply(&FileConfig {
+                rate_limit_per_minute: Some(120),
+                cache_ttl_ms: Some(5000),
+                ..FileConfig::default()
+            })
+            .unwrap();
+
+        assert_eq!(effective.cache_ttl_ms, 5000);
+        assert_eq!(reloader.balance_cache.ttl(), Duration::from_secs(5));
+        assert_eq!(limiter.limits(), (120, 10));
+        assert_eq!(effective.rate_limit_burst, Some(10));
+        assert!(effective.requires_restart.is_empty());
+    }
+
+    #[test]
+    fn test_apply_reports_settings_requiring_restart() {
+        let reloader = reloader(None);
+        let effective = reloader
+            .apply(&FileConfig {
+                bind_addr: Some("127.0.0.1:8080".parse().unwrap()),
+                rate_limit_per_minute: Some(60),
+                ..FileConfig::default()
+            })
+            .unwrap();
+        assert_eq!(effective.requires_restart, vec!["bind_addr", "rate_limit"]);
+        assert_eq!(effective.bind_addr, DEFAULT_BIND_ADDR);
+        assert_eq!(effective.rate_limit_per_minute, None);
+    }
+
+    #[test]
+    fn test_apply_rejects_unknown_log_level() {
+        let reloader = reloader(None);
+        let result = reloader.apply(&FileConfig {
+            log_level: Some("loud".to_string()),
+            cache_ttl_ms: Some(0),
+            ..FileConfig::default()
+        });
+        assert!(matches!(result, Err(AppError::Internal(_))));
+        assert_eq!(reloader.balance_cache.ttl(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_config_file_rejects_unknown_keys() {
+        assert!(serde_json::from_str::<FileConfig>(r#"{"cache_ttl_ms": 10}"#).is_ok());
+        assert!(serde_json::from_str::<FileConfig>(r#"{"cache_ttl": 10}"#).is_err());
+    }
+}
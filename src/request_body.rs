@@ -0,0 +1,164 @@
+use crate::error::AppError;
+use flate2::read::GzDecoder;
+use serde::de::DeserializeOwned;
+use std::io::Read;
+use warp::hyper::body::Bytes;
+use warp::{Filter, Rejection};
+
+/// Body of a request of at most `limit` bytes, decompressed according to
+/// its `Content-Encoding`.
+///
+/// `gzip` bodies are decompressed, and must also fit in `limit` once
+/// decompressed; `identity` bodies are passed through. Other encodings are
+/// rejected with `415`, and malformed gzip with `400`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_bytes() {
+/// let filter = request_body::bytes(16 * 1024);
+/// # }
+/// ```
+pub fn bytes(limit: u64) -> impl Filter<Extract = (Bytes,), Error = Rejection> + Clone {
+    warp::body::content_length_limit(limit)
+        .and(warp::header::optional::<String>("content-encoding"))
+        .and(warp::body::bytes())
+        .and_then(move |encoding: Option<String>, body: Bytes| async move {
+            decode(encoding.as_deref(), body, limit).map_err(warp::reject::custom)
+        })
+}
+
+/// JSON body of a request, decompressed as by [`bytes`].
+///
+/// Like `warp::body::json`, bodies without a `Content-Type` are accepted and
+/// any other type than JSON is rejected with `415`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_json() {
+/// let filter = request_body::json::<SendRawRequest>(MAX_RAW_TX_BODY_BYTES);
+/// # }
+/// ```
+pub fn json<T: DeserializeOwned + Send>(
+    limit: u64,
+) -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("content-type")
+        .and_then(|content_type: Option<String>| async move {
+            match content_type {
+                Some(content_type) if !is_json(&content_type) => Err(warp::reject::custom(
+                    AppError::UnsupportedMediaType(content_type),
+                )),
+                _ => Ok(()),
+            }
+        })
+        .untuple_one()
+        .and(bytes(limit))
+        .and_then(|body: Bytes| async move {
+            serde_json::from_slice(&body).map_err(|error| {
+                warp::reject::custom(AppError::InvalidRequest(format!(
+                    "Request body deserialize error: {}",
+                    error
+                )))
+            })
+        })
+}
+
+/// Whether `content_type` is `application/json` or a `+json` type, ignoring parameters.
+fn is_json(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or("").trim();
+    let Some((kind, subtype)) = essence.split_once('/') else {
+        return false;
+    };
+    kind.eq_ignore_ascii_case("application")
+        && (subtype.eq_ignore_ascii_case("json") || subtype.to_ascii_lowercase().ends_with("+json"))
+}
+
+/// Undoes the `encoding` of `body`, rejecting a result longer than `limit`.
+fn decode(encoding: Option<&str>, body: Bytes, limit: u64) -> Result<Bytes, AppError> {
+    let Some(encoding) = encoding else {
+        return Ok(body);
+    };
+    // Codings are listed in the order they were applied.
+    let mut body = body;
+    for coding in encoding.rsplit(',').map(str::trim) {
+        if coding.eq_ignore_ascii_case("gzip") || coding.eq_ignore_ascii_case("x-gzip") {
+            body = gunzip(&body, limit)?;
+        } else if !coding.eq_ignore_ascii_case("identity") {
+            return Err(AppError::UnsupportedMediaType(format!(
+                "Content-Encoding {}",
+                coding
+            )));
+        }
+    }
+    Ok(body)
+}
+
+fn gunzip(body: &[u8], limit: u64) -> Result<Bytes, AppError> {
+    let mut decoded = Vec::new();
+    // One byte past the limit tells a body that fits from one that does not.
+    GzDecoder::new(body)
+        .take(limit + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|error| AppError::InvalidRequest(format!("malformed gzip body: {}", error)))?;
+    if decoded.len() as u64 > limit {
+        return Err(AppError::PayloadTooLarge);
+    }
+    Ok(decoded.into())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    pub(crate) fn gzip(body: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_decodes_gzip_and_identity() {
+        let body = Bytes::from(gzip(b"{\"addresses\":[]}"));
+        assert_eq!(
+            decode(Some("gzip"), body.clone(), 1024).unwrap(),
+            &b"{\"addresses\":[]}"[..]
+        );
+        assert_eq!(
+            decode(Some("identity, GZIP"), body, 1024).unwrap(),
+            &b"{\"addresses\":[]}"[..]
+        );
+        let plain = Bytes::from_static(b"plain");
+        assert_eq!(decode(None, plain.clone(), 1024).unwrap(), plain);
+        assert!(matches!(
+            decode(Some("br"), plain, 1024),
+            Err(AppError::UnsupportedMediaType(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_malformed_and_oversized_gzip() {
+        let error = decode(Some("gzip"), Bytes::from_static(b"not gzip"), 1024).unwrap_err();
+        assert!(matches!(error, AppError::InvalidRequest(_)), "{:?}", error);
+
+        // A small body that expands past the limit.
+        let bomb = Bytes::from(gzip(&[b' '; 4096]));
+        assert!(bomb.len() < 1024);
+        assert!(matches!(
+            decode(Some("gzip"), bomb, 1024),
+            Err(AppError::PayloadTooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_json_content_types() {
+        assert!(is_json("application/json"));
+        assert!(is_json("Application/JSON; charset=utf-8"));
+        assert!(is_json("application/merge-patch+json"));
+        assert!(!is_json("text/plain"));
+        assert!(!is_json("json"));
+    }
+}
@@ -0,0 +1,243 @@
+use crate::config;
+use crate::metrics::Metrics;
+use alloy::rpc::json_rpc::{RequestPacket, ResponsePacket};
+use alloy::transports::{RpcError, TransportError, TransportFut};
+use log::warn;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::{Layer, Service};
+
+/// Retry settings read from the environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryConfig {
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled before each further one.
+    pub backoff: Duration,
+}
+
+/// Reads the retry settings; returns `None` when `RPC_MAX_RETRIES` is unset or zero.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_retry_config() {
+/// let config = get_retry_config();
+/// assert!(config.is_none());
+/// # }
+/// ```
+pub fn get_retry_config() -> Option<RetryConfig> {
+    let max_retries: u32 = config::env_or("RPC_MAX_RETRIES", 0);
+    (max_retries > 0).then(|| RetryConfig {
+        max_retries,
+        backoff: Duration::from_millis(config::env_or("RPC_RETRY_BACKOFF_MS", 100)),
+    })
+}
+
+/// Transport layer retrying node requests that failed with a retryable error
+/// (rate limited or temporarily unavailable), counting retries into `metrics`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_retry_layer() {
+/// let client = ClientBuilder::default()
+///     .layer(RetryLayer::new(get_retry_config(), metrics))
+///     .transport(transport, is_local);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RetryLayer {
+    config: Option<RetryConfig>,
+    metrics: Arc<Metrics>,
+}
+
+impl RetryLayer {
+    pub fn new(config: Option<RetryConfig>, metrics: Arc<Metrics>) -> Self {
+        RetryLayer { config, metrics }
+    }
+}
+
+impl<S> Layer<S> for RetryLayer {
+    type Service = RetryService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RetryService {
+            inner,
+            config: self.config,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RetryService<S> {
+    inner: S,
+    config: Option<RetryConfig>,
+    metrics: Arc<Metrics>,
+}
+
+impl<S> Service<RequestPacket> for RetryService<S>
+where
+    S: Service<
+            RequestPacket,
+            Response = ResponsePacket,
+            Error = TransportError,
+            Future = TransportFut<'static>,
+        > + Clone
+        + Send
+        + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        let Some(config) = self.config else {
+            return self.inner.call(request);
+        };
+        let first = self.inner.call(request.clone());
+        let mut inner = self.inner.clone();
+        let metrics = self.metrics.clone();
+        Box::pin(async move {
+            let mut result = first.await;
+            let mut backoff = config.backoff;
+            for retry in 1..=config.max_retries {
+                match &result {
+                    Err(error) if is_retryable(error) => {
+                        warn!(
+                            "Node request failed, retry {} of {} in {:?}: {}",
+                            retry, config.max_retries, backoff, error
+                        );
+                    }
+                    _ => return result,
+                }
+                metrics.record_retry_attempt();
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                result = match futures::future::poll_fn(|cx| inner.poll_ready(cx)).await {
+                    Ok(()) => inner.call(request.clone()).await,
+                    Err(error) => Err(error),
+                };
+            }
+            if matches!(&result, Err(error) if is_retryable(error)) {
+                metrics.record_retry_exhausted();
+            }
+            result
+        })
+    }
+}
+
+/// Rate limits and temporary unavailability; JSON-RPC errors arrive as responses
+/// and are never retried here.
+fn is_retryable(error: &TransportError) -> bool {
+    matches!(error, RpcError::Transport(kind) if kind.is_retry_err())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloy::rpc::json_rpc::{Request, Response, ResponsePayload};
+    use alloy::transports::{HttpError, TransportErrorKind};
+    use serde_json::value::RawValue;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Transport answering `503` to its first `failures` requests and `"0x3e8"` afterwards.
+    #[derive(Clone)]
+    pub(crate) struct FlakyTransport {
+        pub(crate) failures: usize,
+        pub(crate) calls: Arc<AtomicUsize>,
+    }
+
+    impl FlakyTransport {
+        pub(crate) fn new(failures: usize) -> Self {
+            FlakyTransport {
+                failures,
+                calls: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    impl Service<RequestPacket> for FlakyTransport {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = TransportFut<'static>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: RequestPacket) -> Self::Future {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            let failing = call < self.failures;
+            Box::pin(async move {
+                if failing {
+                    return Err(TransportErrorKind::http_error(
+                        503,
+                        "unavailable".to_string(),
+                    ));
+                }
+                let RequestPacket::Single(request) = request else {
+                    unimplemented!("FlakyTransport does not support batches")
+                };
+                Ok(ResponsePacket::Single(Response {
+                    id: request.id().clone(),
+                    payload: ResponsePayload::Success(
+                        RawValue::from_string("\"0x3e8\"".to_string()).unwrap(),
+                    ),
+                }))
+            })
+        }
+    }
+
+    fn packet() -> RequestPacket {
+        Request::new("eth_chainId", 1.into(), ())
+            .serialize()
+            .unwrap()
+            .into()
+    }
+
+    fn config(max_retries: u32) -> Option<RetryConfig> {
+        Some(RetryConfig {
+            max_retries,
+            backoff: Duration::from_millis(1),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_the_node_answers() {
+        let metrics = Arc::new(Metrics::new());
+        let transport = FlakyTransport::new(2);
+        let mut service = RetryLayer::new(config(3), metrics.clone()).layer(transport.clone());
+
+        assert!(service.call(packet()).await.is_ok());
+        assert_eq!(transport.calls.load(Ordering::SeqCst), 3);
+        let retries = metrics.retries()["other"];
+        assert_eq!((retries.attempts, retries.exhausted), (2, 0));
+    }
+
+    #[tokio::test]
+    async fn test_counts_exhausted_retries() {
+        let metrics = Arc::new(Metrics::new());
+        let transport = FlakyTransport::new(usize::MAX);
+        let mut service = RetryLayer::new(config(2), metrics.clone()).layer(transport.clone());
+
+        let error = service.call(packet()).await.unwrap_err();
+        assert!(matches!(
+            error,
+            TransportError::Transport(TransportErrorKind::HttpError(HttpError { status: 503, .. }))
+        ));
+        assert_eq!(transport.calls.load(Ordering::SeqCst), 3);
+        let retries = metrics.retries()["other"];
+        assert_eq!((retries.attempts, retries.exhausted), (2, 1));
+
+        // Without a config requests are sent once.
+        let mut service = RetryLayer::new(None, metrics).layer(transport.clone());
+        assert!(service.call(packet()).await.is_err());
+        assert_eq!(transport.calls.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::metrics;
+use anyhow::bail;
+use log::info;
+use std::collections::BTreeSet;
+use std::env;
+
+/// Routes that stay enabled whatever the allowlist says.
+const ALWAYS_ENABLED: &[&str] = &["health", "index"];
+
+/// Which routes are served, by their first path segment (as labelled in `/metrics`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteFlags {
+    /// `None` serves every route.
+    enabled: Option<BTreeSet<String>>,
+}
+
+impl RouteFlags {
+    /// Serves only the named routes, plus `/health` and `/`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_route_flags_only() {
+    /// let flags = RouteFlags::only(["balance"]).unwrap();
+    /// assert!(!flags.is_enabled("block"));
+    /// # }
+    /// ```
+    pub fn only<'a>(routes: impl IntoIterator<Item = &'a str>) -> anyhow::Result<Self> {
+        let mut enabled = BTreeSet::new();
+        for route in routes {
+            if !metrics::ROUTES.contains(&route) {
+                bail!("Unknown route {:?} in ENABLE_ROUTES", route);
+            }
+            enabled.insert(route.to_string());
+        }
+        Ok(RouteFlags {
+            enabled: Some(enabled),
+        })
+    }
+
+    /// Whether requests labelled `route` are served.
+    pub fn is_enabled(&self, route: &str) -> bool {
+        match &self.enabled {
+            Some(enabled) => ALWAYS_ENABLED.contains(&route) || enabled.contains(route),
+            None => true,
+        }
+    }
+}
+
+/// Reads the comma-separated allowlist of `ENABLE_ROUTES`; every route is served when unset.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_route_flags() {
+/// let flags = get_route_flags().unwrap();
+/// assert!(flags.is_enabled("balance"));
+/// # }
+/// ```
+pub fn get_route_flags() -> anyhow::Result<RouteFlags> {
+    let Ok(value) = env::var("ENABLE_ROUTES") else {
+        return Ok(RouteFlags::default());
+    };
+    let flags = RouteFlags::only(
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|route| !route.is_empty()),
+    )?;
+    info!("Serving only routes: {}", value);
+    Ok(flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowlist_keeps_health() {
+        let flags = RouteFlags::only(["balance", "block"]).unwrap();
+        assert!(flags.is_enabled("balance"));
+        assert!(flags.is_enabled("health"));
+        assert!(flags.is_enabled("index"));
+        assert!(!flags.is_enabled("nonce"));
+        assert!(!flags.is_enabled("other"));
+
+        assert!(RouteFlags::default().is_enabled("nonce"));
+        assert!(RouteFlags::only(["block-number"]).is_err());
+    }
+}
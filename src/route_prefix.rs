@@ -0,0 +1,170 @@
+use crate::config;
+use anyhow::bail;
+use log::info;
+use std::env;
+use warp::filters::BoxedFilter;
+use warp::Filter;
+
+/// Path segments every route is mounted under, e.g. `/api/v1`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoutePrefix {
+    segments: Vec<String>,
+    /// The segments joined as a path, e.g. `/api/v1`; empty without a prefix.
+    path: String,
+    /// Whether `/health` is mounted under the prefix too, or stays at the root.
+    health: bool,
+}
+
+impl RoutePrefix {
+    /// Mounts the routes under `prefix`, with or without `/health`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_route_prefix_new() {
+    /// let prefix = RoutePrefix::new("/api/v1/", true).unwrap();
+    /// assert_eq!(prefix.path(), "/api/v1");
+    /// # }
+    /// ```
+    pub fn new(prefix: &str, health: bool) -> anyhow::Result<Self> {
+        let prefix = prefix.trim().trim_matches('/');
+        let segments: Vec<String> = if prefix.is_empty() {
+            Vec::new()
+        } else {
+            prefix.split('/').map(str::to_string).collect()
+        };
+        for segment in &segments {
+            let valid = segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.~".contains(c));
+            if segment.is_empty() || !valid {
+                bail!("Invalid ROUTE_PREFIX segment {:?}", segment);
+            }
+        }
+        let path = segments
+            .iter()
+            .map(|segment| format!("/{}", segment))
+            .collect();
+        Ok(RoutePrefix {
+            segments,
+            path,
+            health,
+        })
+    }
+
+    /// The prefix as a path, empty when there is none.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Where the route at `path` is served.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_mounted() {
+    /// let prefix = RoutePrefix::new("api/v1", false).unwrap();
+    /// assert_eq!(prefix.mounted("/nonce/{address}"), "/api/v1/nonce/{address}");
+    /// assert_eq!(prefix.mounted("/health"), "/health");
+    /// # }
+    /// ```
+    pub fn mounted(&self, path: &str) -> String {
+        if path == "/health" && !self.health {
+            return path.to_string();
+        }
+        match path {
+            "/" if !self.segments.is_empty() => self.path.clone(),
+            _ => format!("{}{}", self.path, path),
+        }
+    }
+
+    /// The route part of a request path, left as is when it lacks the prefix.
+    ///
+    /// Route labels, flags and spans are named after this part.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_strip() {
+    /// let prefix = RoutePrefix::new("api/v1", true).unwrap();
+    /// assert_eq!(prefix.strip("/api/v1/balance/0x00"), "/balance/0x00");
+    /// assert_eq!(prefix.strip("/api/v1"), "/");
+    /// # }
+    /// ```
+    pub fn strip<'a>(&self, path: &'a str) -> &'a str {
+        match path.strip_prefix(self.path.as_str()) {
+            Some("") => "/",
+            Some(rest) if rest.starts_with('/') => rest,
+            _ => path,
+        }
+    }
+
+    /// Matches and consumes the prefix segments.
+    pub fn filter(&self) -> BoxedFilter<()> {
+        self.segments
+            .iter()
+            .fold(warp::any().boxed(), |filter, segment| {
+                filter.and(warp::path(segment.clone())).boxed()
+            })
+    }
+
+    /// Whether `/health` is mounted under the prefix.
+    pub fn includes_health(&self) -> bool {
+        self.health
+    }
+}
+
+/// Reads the prefix of every route from `ROUTE_PREFIX` (unset for none);
+/// `/health` stays at the root when `ROUTE_PREFIX_HEALTH=false`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_route_prefix() {
+/// let prefix = get_route_prefix().unwrap();
+/// assert_eq!(prefix.path(), "");
+/// # }
+/// ```
+pub fn get_route_prefix() -> anyhow::Result<RoutePrefix> {
+    let prefix = env::var("ROUTE_PREFIX").unwrap_or_default();
+    let prefix = RoutePrefix::new(&prefix, config::env_or("ROUTE_PREFIX_HEALTH", true))?;
+    if !prefix.segments.is_empty() {
+        info!("Serving routes under {}", prefix.path());
+    }
+    Ok(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_prefix_new() {
+        assert_eq!(RoutePrefix::new("", true).unwrap().path(), "");
+        assert_eq!(RoutePrefix::new("/", true).unwrap().path(), "");
+        assert_eq!(RoutePrefix::new("api/v1", true).unwrap().path(), "/api/v1");
+        assert_eq!(
+            RoutePrefix::new("/api/v1/", true).unwrap().path(),
+            "/api/v1"
+        );
+        assert!(RoutePrefix::new("api//v1", true).is_err());
+        assert!(RoutePrefix::new("api/v 1", true).is_err());
+    }
+
+    #[test]
+    fn test_strip_and_mount() {
+        let prefix = RoutePrefix::new("api/v1", false).unwrap();
+        assert_eq!(prefix.strip("/api/v1/health"), "/health");
+        assert_eq!(prefix.strip("/api/v1/"), "/");
+        assert_eq!(prefix.strip("/api/v10/health"), "/api/v10/health");
+        assert_eq!(prefix.strip("/health"), "/health");
+        assert_eq!(prefix.mounted("/"), "/api/v1");
+        assert_eq!(prefix.mounted("/ui"), "/api/v1/ui");
+        assert_eq!(prefix.mounted("/health"), "/health");
+
+        let none = RoutePrefix::default();
+        assert_eq!(none.strip("/balance/0x00"), "/balance/0x00");
+        assert_eq!(none.mounted("/"), "/");
+        assert_eq!(none.mounted("/health"), "/health");
+    }
+}
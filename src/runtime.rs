@@ -0,0 +1,49 @@
+use crate::config;
+use std::io;
+use std::num::NonZeroUsize;
+use std::thread;
+use tokio::runtime::{Builder, Runtime};
+
+/// Reads the number of runtime worker threads from `WORKER_THREADS`, defaulting to the
+/// number of CPUs available to the process.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_worker_threads() {
+/// let worker_threads = get_worker_threads();
+/// assert!(worker_threads > 0);
+/// # }
+/// ```
+pub fn get_worker_threads() -> usize {
+    let cpus = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    config::env_or("WORKER_THREADS", cpus).max(1)
+}
+
+/// Builds the multi-threaded runtime serving the API.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_build_runtime() {
+/// let runtime = build_runtime(4).unwrap();
+/// runtime.block_on(async {});
+/// # }
+/// ```
+pub fn build_runtime(worker_threads: usize) -> io::Result<Runtime> {
+    Builder::new_multi_thread()
+        .worker_threads(worker_threads)
+        .enable_all()
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_runtime_uses_worker_threads() {
+        let runtime = build_runtime(3).unwrap();
+        assert_eq!(runtime.metrics().num_workers(), 3);
+    }
+}
@@ -0,0 +1,456 @@
+use crate::config;
+use anyhow::{bail, Context};
+use hyper::server::accept::Accept;
+use hyper::server::conn::{AddrIncoming, Http};
+use hyper::service::{service_fn, Service};
+use log::{debug, info, warn};
+use std::convert::Infallible;
+use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls;
+use tokio_rustls::TlsAcceptor;
+use warp::{Filter, Rejection, Reply};
+
+/// Address of the client, attached to every request as an extension.
+///
+/// Connections are accepted outside of warp, so `warp::addr::remote` is only
+/// set by the test harness; use [`client_addr`] to read either.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientAddr(pub SocketAddr);
+
+/// PEM files holding the server certificate chain and private key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerTlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+/// Listener settings read from the environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerConfig {
+    pub tls: Option<ServerTlsConfig>,
+    /// Offer HTTP/2 through ALPN; only allowed with TLS.
+    pub http2: bool,
+    /// Idle time before TCP keep-alive probes are sent; `None` leaves them off.
+    pub tcp_keepalive: Option<Duration>,
+    /// Whether HTTP/1 connections are kept open between requests.
+    pub http1_keepalive: bool,
+    /// How long an HTTP/1 client may take to send the headers of a request.
+    pub header_read_timeout: Option<Duration>,
+    /// Interval of HTTP/2 keep-alive pings; `None` sends none.
+    pub http2_keepalive_interval: Option<Duration>,
+    /// Address of a plaintext listener served next to the TLS one, e.g.
+    /// while clients migrate to HTTPS; only allowed with TLS.
+    pub plain_addr: Option<SocketAddr>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            tls: None,
+            http2: false,
+            tcp_keepalive: None,
+            http1_keepalive: true,
+            header_read_timeout: None,
+            http2_keepalive_interval: None,
+            plain_addr: None,
+        }
+    }
+}
+
+/// Reads a duration in seconds, where unset or `0` means disabled.
+fn env_secs(name: &str) -> Option<Duration> {
+    let secs: u64 = config::env_or(name, 0);
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// Reads the listener settings from `TLS_CERT_PATH`, `TLS_KEY_PATH`, `ENABLE_HTTP2`,
+/// `TCP_KEEPALIVE_SECS`, `HTTP_KEEPALIVE`, `HTTP_HEADER_READ_TIMEOUT_SECS`,
+/// `HTTP2_KEEPALIVE_INTERVAL_SECS` and `PLAIN_BIND_ADDR`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_server_config() {
+/// let config = get_server_config().unwrap();
+/// assert!(config.tls.is_none());
+/// # }
+/// ```
+pub fn get_server_config() -> anyhow::Result<ServerConfig> {
+    let tls = server_tls_config(
+        env::var("TLS_CERT_PATH").ok(),
+        env::var("TLS_KEY_PATH").ok(),
+    )?;
+    let plain_addr = env::var("PLAIN_BIND_ADDR")
+        .ok()
+        .map(|addr| {
+            addr.parse()
+                .with_context(|| format!("Invalid PLAIN_BIND_ADDR {:?}", addr))
+        })
+        .transpose()?;
+    let config = ServerConfig {
+        tls,
+        http2: config::env_flag("ENABLE_HTTP2"),
+        tcp_keepalive: env_secs("TCP_KEEPALIVE_SECS"),
+        http1_keepalive: config::env_or("HTTP_KEEPALIVE", true),
+        header_read_timeout: env_secs("HTTP_HEADER_READ_TIMEOUT_SECS"),
+        http2_keepalive_interval: env_secs("HTTP2_KEEPALIVE_INTERVAL_SECS"),
+        plain_addr,
+    };
+    check_server_config(&config)?;
+    Ok(config)
+}
+
+/// Combines the certificate and key paths, requiring both or neither.
+fn server_tls_config(
+    cert_path: Option<String>,
+    key_path: Option<String>,
+) -> anyhow::Result<Option<ServerTlsConfig>> {
+    match (cert_path, key_path) {
+        (Some(cert_path), Some(key_path)) => Ok(Some(ServerTlsConfig {
+            cert_path,
+            key_path,
+        })),
+        (None, None) => Ok(None),
+        (Some(_), None) => bail!("TLS_CERT_PATH is set but TLS_KEY_PATH is not"),
+        (None, Some(_)) => bail!("TLS_KEY_PATH is set but TLS_CERT_PATH is not"),
+    }
+}
+
+/// HTTP/2 is only offered over TLS, where clients negotiate it through ALPN,
+/// and a second plaintext listener only makes sense next to a TLS one.
+fn check_server_config(config: &ServerConfig) -> anyhow::Result<()> {
+    if config.http2 && config.tls.is_none() {
+        bail!("ENABLE_HTTP2 requires TLS_CERT_PATH and TLS_KEY_PATH");
+    }
+    if config.plain_addr.is_some() && config.tls.is_none() {
+        bail!("PLAIN_BIND_ADDR requires TLS_CERT_PATH and TLS_KEY_PATH");
+    }
+    Ok(())
+}
+
+/// Binds `addr` with `config`, and the plaintext listener of `config.plain_addr`
+/// when set, which shares every other setting but HTTP/2.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_bind() {
+/// let listeners = bind("0.0.0.0:3030".parse().unwrap(), &ServerConfig::default()).await.unwrap();
+/// assert_eq!(listeners.len(), 1);
+/// # }
+/// ```
+pub async fn bind(
+    addr: SocketAddr,
+    config: &ServerConfig,
+) -> anyhow::Result<Vec<(TcpListener, ServerConfig)>> {
+    check_server_config(config)?;
+    let listen = |addr: SocketAddr| async move {
+        TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind {}", addr))
+    };
+    let mut listeners = vec![(
+        listen(addr).await?,
+        ServerConfig {
+            plain_addr: None,
+            ..config.clone()
+        },
+    )];
+    if let Some(plain_addr) = config.plain_addr {
+        let plain = ServerConfig {
+            tls: None,
+            http2: false,
+            plain_addr: None,
+            ..config.clone()
+        };
+        listeners.push((listen(plain_addr).await?, plain));
+    }
+    Ok(listeners)
+}
+
+/// Serves `filter` on every listener, each in its own task, until one fails.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_serve_all() {
+/// let listeners = bind(bind_addr, &config).await.unwrap();
+/// serve_all(routes, listeners).await.unwrap();
+/// # }
+/// ```
+pub async fn serve_all<F, R>(
+    filter: F,
+    listeners: Vec<(TcpListener, ServerConfig)>,
+) -> anyhow::Result<()>
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply + 'static,
+{
+    let tasks = listeners
+        .into_iter()
+        .map(|(listener, config)| tokio::spawn(serve(filter.clone(), listener, config)));
+    for result in futures::future::try_join_all(tasks)
+        .await
+        .context("A listener task panicked")?
+    {
+        result?;
+    }
+    Ok(())
+}
+
+/// Loads the certificate chain and key, advertising `h2` when HTTP/2 is enabled.
+fn tls_acceptor(tls: &ServerTlsConfig, http2: bool) -> anyhow::Result<TlsAcceptor> {
+    let cert = fs::read(&tls.cert_path)
+        .with_context(|| format!("Failed to read server certificate {}", tls.cert_path))?;
+    let key = fs::read(&tls.key_path)
+        .with_context(|| format!("Failed to read server key {}", tls.key_path))?;
+    let certs = rustls_pemfile::certs(&mut cert.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to parse the server certificate")?;
+    let key = rustls_pemfile::private_key(&mut key.as_slice())
+        .context("Failed to parse the server key")?
+        .context("The server key file holds no private key")?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .context("Failed to configure TLS")?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("The server certificate does not match its key")?;
+    config.alpn_protocols = if http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Serves `filter` on `listener` until the listener fails.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_serve() {
+/// let listener = TcpListener::bind("0.0.0.0:3030").await.unwrap();
+/// serve(routes, listener, ServerConfig::default()).await.unwrap();
+/// # }
+/// ```
+pub async fn serve<F, R>(
+    filter: F,
+    listener: TcpListener,
+    config: ServerConfig,
+) -> anyhow::Result<()>
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    check_server_config(&config)?;
+    let acceptor = config
+        .tls
+        .as_ref()
+        .map(|tls| tls_acceptor(tls, config.http2))
+        .transpose()?;
+
+    let mut incoming =
+        AddrIncoming::from_listener(listener).context("Failed to listen for connections")?;
+    incoming.set_nodelay(true);
+    incoming.set_keepalive(config.tcp_keepalive);
+
+    let mut http = Http::new();
+    // A client closing its side drops its request, and with it the pending
+    // node calls, instead of leaving them running for nobody to read.
+    http.http1_only(!config.http2)
+        .http1_half_close(false)
+        .http1_keep_alive(config.http1_keepalive)
+        .http2_keep_alive_interval(config.http2_keepalive_interval);
+    if let Some(timeout) = config.header_read_timeout {
+        http.http1_header_read_timeout(timeout);
+    }
+    info!(
+        "Serving HTTP/1.1{} on {}",
+        if config.http2 { " and HTTP/2" } else { "" },
+        incoming.local_addr()
+    );
+
+    let service = warp::service(filter);
+    loop {
+        let stream =
+            match futures::future::poll_fn(|cx| Pin::new(&mut incoming).poll_accept(cx)).await {
+                Some(Ok(stream)) => stream,
+                Some(Err(error)) => {
+                    warn!("Failed to accept a connection: {}", error);
+                    continue;
+                }
+                None => return Ok(()),
+            };
+        let remote = stream.remote_addr();
+        let (service, http, acceptor) = (service.clone(), http.clone(), acceptor.clone());
+
+        tokio::spawn(async move {
+            let service = service_fn(move |mut request: hyper::Request<hyper::Body>| {
+                request.extensions_mut().insert(ClientAddr(remote));
+                service.clone().call(request)
+            });
+            let result = match acceptor {
+                Some(acceptor) => match acceptor.accept(stream).await {
+                    Ok(stream) => http.serve_connection(stream, service).await,
+                    Err(error) => {
+                        debug!("TLS handshake with {} failed: {}", remote, error);
+                        return;
+                    }
+                },
+                None => http.serve_connection(stream, service).await,
+            };
+            if let Err(error) = result {
+                debug!("Connection with {} failed: {}", remote, error);
+            }
+        });
+    }
+}
+
+/// Provides the address of the client, when known.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_client_addr() {
+/// let filter = client_addr();
+/// # }
+/// ```
+pub fn client_addr() -> impl Filter<Extract = (Option<SocketAddr>,), Error = Infallible> + Clone {
+    warp::addr::remote()
+        .and(warp::ext::optional::<ClientAddr>())
+        .map(|remote: Option<SocketAddr>, client: Option<ClientAddr>| {
+            remote.or(client.map(|ClientAddr(addr)| addr))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/client.crt");
+    const KEY_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/client.key");
+
+    fn tls() -> ServerTlsConfig {
+        ServerTlsConfig {
+            cert_path: CERT_PATH.into(),
+            key_path: KEY_PATH.into(),
+        }
+    }
+
+    async fn spawn(config: ServerConfig) -> SocketAddr {
+        let routes = warp::path::end()
+            .and(client_addr())
+            .map(|addr: Option<SocketAddr>| {
+                addr.map(|addr| addr.ip().to_string()).unwrap_or_default()
+            });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(routes, listener, config));
+        addr
+    }
+
+    #[test]
+    fn test_http2_requires_tls() {
+        let plain = ServerConfig {
+            http2: true,
+            ..Default::default()
+        };
+        assert!(check_server_config(&plain).is_err());
+        let tls = ServerConfig {
+            tls: Some(tls()),
+            ..plain
+        };
+        assert!(check_server_config(&tls).is_ok());
+
+        assert!(server_tls_config(Some(CERT_PATH.into()), None).is_err());
+        assert_eq!(server_tls_config(None, None).unwrap(), None);
+
+        let plain = ServerConfig {
+            plain_addr: Some("127.0.0.1:0".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(check_server_config(&plain).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_serves_plain_and_tls_side_by_side() {
+        let routes = warp::path::end().map(|| "ok");
+        let config = ServerConfig {
+            tls: Some(tls()),
+            http2: true,
+            plain_addr: Some("127.0.0.1:0".parse().unwrap()),
+            ..Default::default()
+        };
+        let listeners = bind("127.0.0.1:0".parse().unwrap(), &config).await.unwrap();
+        assert_eq!(listeners.len(), 2);
+        assert_eq!(listeners[0].1.tls, Some(tls()));
+        assert_eq!(listeners[1].1.tls, None);
+        assert!(!listeners[1].1.http2);
+        let tls_addr = listeners[0].0.local_addr().unwrap();
+        let plain_addr = listeners[1].0.local_addr().unwrap();
+        assert_ne!(tls_addr, plain_addr);
+        tokio::spawn(serve_all(routes, listeners));
+
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+        for url in [
+            format!("https://{}/", tls_addr),
+            format!("http://{}/", plain_addr),
+        ] {
+            let resp = client.get(&url).send().await.unwrap();
+            assert_eq!(resp.text().await.unwrap(), "ok", "{}", url);
+        }
+
+        let single = bind("127.0.0.1:0".parse().unwrap(), &ServerConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(single.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_plain_requests_carry_the_client_address() {
+        let addr = spawn(ServerConfig {
+            tcp_keepalive: Some(Duration::from_secs(30)),
+            ..Default::default()
+        })
+        .await;
+
+        let resp = reqwest::get(format!("http://{}/", addr)).await.unwrap();
+        assert_eq!(resp.version(), reqwest::Version::HTTP_11);
+        assert_eq!(resp.text().await.unwrap(), "127.0.0.1");
+    }
+
+    #[tokio::test]
+    async fn test_http2_over_tls() {
+        let addr = spawn(ServerConfig {
+            tls: Some(tls()),
+            http2: true,
+            ..Default::default()
+        })
+        .await;
+
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .http2_prior_knowledge()
+            .build()
+            .unwrap();
+        let resp = client
+            .get(format!("https://{}/", addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.version(), reqwest::Version::HTTP_2);
+        assert_eq!(resp.text().await.unwrap(), "127.0.0.1");
+    }
+}
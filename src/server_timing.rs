@@ -0,0 +1,272 @@
+use crate::deadline::request_task_id;
+use alloy::rpc::json_rpc::{RequestPacket, ResponsePacket};
+use alloy::transports::{TransportError, TransportFut};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::task;
+use tower::{Layer, Service};
+
+pub const SERVER_TIMING_HEADER: &str = "server-timing";
+
+/// Time spent in node requests by each request being served.
+///
+/// Requests are told apart by the task serving them: warp polls a request's
+/// handlers, and the HTTP transport its node calls, on the connection's task,
+/// or on a nested request task standing in for it under a request deadline.
+/// Node calls made from other tasks, such as the pollers, are not counted, and
+/// neither is anything outside a spawned task, where there is no task id.
+pub struct RpcTimings {
+    micros: Mutex<HashMap<task::Id, u64>>,
+}
+
+/// Times a request for as long as it is alive, like the metrics' inflight guard.
+pub struct RequestTimer {
+    timings: Arc<RpcTimings>,
+    task: Option<task::Id>,
+    started_at: Instant,
+}
+
+impl RpcTimings {
+    pub fn new() -> Self {
+        RpcTimings {
+            micros: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts timing the request served by the current task.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_start() {
+    /// let timings = Arc::new(RpcTimings::new());
+    /// let timer = timings.start();
+    /// assert!(timer.header_value().starts_with("rpc;dur="));
+    /// # }
+    /// ```
+    pub fn start(self: &Arc<Self>) -> RequestTimer {
+        let task = request_task_id();
+        if let Some(task) = task {
+            self.micros
+                .lock()
+                .expect("rpc timings poisoned")
+                .insert(task, 0);
+        }
+        RequestTimer {
+            timings: self.clone(),
+            task,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Adds a node call to the request served by the current task, if it is being timed.
+    pub fn record(&self, elapsed: Duration) {
+        let Some(task) = request_task_id() else {
+            return;
+        };
+        if let Some(micros) = self
+            .micros
+            .lock()
+            .expect("rpc timings poisoned")
+            .get_mut(&task)
+        {
+            *micros += elapsed.as_micros() as u64;
+        }
+    }
+
+    fn rpc_micros(&self, task: Option<task::Id>) -> u64 {
+        task.and_then(|task| {
+            self.micros
+                .lock()
+                .expect("rpc timings poisoned")
+                .get(&task)
+                .copied()
+        })
+        .unwrap_or(0)
+    }
+}
+
+impl RequestTimer {
+    /// Time spent in node calls so far; concurrent calls are summed.
+    pub fn rpc(&self) -> Duration {
+        Duration::from_micros(self.timings.rpc_micros(self.task))
+    }
+
+    /// `Server-Timing` value with the node and total time so far, in milliseconds.
+    pub fn header_value(&self) -> String {
+        format!(
+            "rpc;dur={:.1}, total;dur={:.1}",
+            millis(self.rpc()),
+            millis(self.started_at.elapsed())
+        )
+    }
+}
+
+impl Drop for RequestTimer {
+    fn drop(&mut self) {
+        if let Some(task) = self.task {
+            self.timings
+                .micros
+                .lock()
+                .expect("rpc timings poisoned")
+                .remove(&task);
+        }
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+/// Transport layer recording how long each node request took.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_server_timing_layer() {
+/// let client = ClientBuilder::default()
+///     .layer(ServerTimingLayer::new(timings))
+///     .transport(transport, is_local);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ServerTimingLayer {
+    timings: Arc<RpcTimings>,
+}
+
+impl ServerTimingLayer {
+    pub fn new(timings: Arc<RpcTimings>) -> Self {
+        ServerTimingLayer { timings }
+    }
+}
+
+impl<S> Layer<S> for ServerTimingLayer {
+    type Service = ServerTimingService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ServerTimingService {
+            inner,
+            timings: self.timings.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ServerTimingService<S> {
+    inner: S,
+    timings: Arc<RpcTimings>,
+}
+
+impl<S> Service<RequestPacket> for ServerTimingService<S>
+where
+    S: Service<
+            RequestPacket,
+            Response = ResponsePacket,
+            Error = TransportError,
+            Future = TransportFut<'static>,
+        > + Send
+        + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        let timings = self.timings.clone();
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let started_at = Instant::now();
+            let result = response.await;
+            timings.record(started_at.elapsed());
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::rpc::json_rpc::{Request, Response, ResponsePayload};
+    use serde_json::value::RawValue;
+
+    /// Transport taking 20ms to answer.
+    #[derive(Clone)]
+    struct SlowTransport;
+
+    impl Service<RequestPacket> for SlowTransport {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = TransportFut<'static>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _request: RequestPacket) -> Self::Future {
+            Box::pin(async {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok(ResponsePacket::Single(Response {
+                    id: 1.into(),
+                    payload: ResponsePayload::Success(
+                        RawValue::from_string("\"0x1\"".to_string()).unwrap(),
+                    ),
+                }))
+            })
+        }
+    }
+
+    fn packet() -> RequestPacket {
+        Request::new("eth_chainId", 1.into(), ())
+            .serialize()
+            .unwrap()
+            .into()
+    }
+
+    #[tokio::test]
+    async fn test_node_calls_are_recorded_for_the_current_request() {
+        // Test bodies run in `block_on`, which has no task id.
+        tokio::spawn(async {
+            let timings = Arc::new(RpcTimings::new());
+            let mut service = ServerTimingLayer::new(timings.clone()).layer(SlowTransport);
+
+            // Calls outside a timed request are not kept.
+            service.call(packet()).await.unwrap();
+            assert!(timings.micros.lock().unwrap().is_empty());
+
+            let timer = timings.start();
+            service.call(packet()).await.unwrap();
+            service.call(packet()).await.unwrap();
+            assert!(timer.rpc() >= Duration::from_millis(40));
+            assert!(timer.header_value().starts_with("rpc;dur="));
+
+            drop(timer);
+            assert!(timings.micros.lock().unwrap().is_empty());
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_requests_on_other_tasks_are_timed_separately() {
+        let timings = Arc::new(RpcTimings::new());
+        let timer = timings.start();
+
+        let service = ServerTimingLayer::new(timings.clone()).layer(SlowTransport);
+        let other = timings.clone();
+        tokio::spawn(async move {
+            let timer = other.start();
+            service.clone().call(packet()).await.unwrap();
+            assert!(timer.rpc() >= Duration::from_millis(20));
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(timer.rpc(), Duration::ZERO);
+    }
+}
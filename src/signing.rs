@@ -0,0 +1,237 @@
+use crate::error::AppError;
+use alloy::dyn_abi::TypedData;
+use alloy::signers::local::PrivateKeySigner;
+use alloy::signers::SignerSync;
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
+use anyhow::Context;
+use log::info;
+use serde::Serialize;
+use std::env;
+
+/// Signature of a balance response, letting clients check it came from this server.
+#[derive(Debug, Serialize)]
+pub struct BalanceSignature {
+    /// Block the balance was read at, part of the signed digest.
+    pub block_number: u64,
+    pub signer: Address,
+    /// 65-byte `r || s || v` signature of [`balance_digest`].
+    pub signature: Bytes,
+}
+
+/// Signature of an EIP-712 typed data document, as returned by `/sign-typed`.
+#[derive(Debug, Serialize)]
+pub struct TypedDataSignature {
+    /// EIP-712 signing hash of the document.
+    pub hash: B256,
+    pub signer: Address,
+    /// 65-byte `r || s || v` signature of `hash`.
+    pub signature: Bytes,
+}
+
+/// Reads the key signing balance responses from `SIGNER_PRIVATE_KEY`, if set.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_signer() {
+/// let signer = get_signer().unwrap();
+/// assert!(signer.is_none());
+/// # }
+/// ```
+pub fn get_signer() -> anyhow::Result<Option<PrivateKeySigner>> {
+    let Ok(key) = env::var("SIGNER_PRIVATE_KEY") else {
+        return Ok(None);
+    };
+    let signer: PrivateKeySigner = key
+        .trim()
+        .parse()
+        .context("SIGNER_PRIVATE_KEY is not a valid private key")?;
+    info!("Signing balance responses as {}", signer.address());
+    Ok(Some(signer))
+}
+
+/// `keccak256(address || balance || block_number)`, with the balance and block
+/// number as 32-byte big-endian words, like Solidity's
+/// `keccak256(abi.encodePacked(address, uint256, uint256))`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_balance_digest() {
+/// let digest = balance_digest(Address::ZERO, U256::from(1000), 12);
+/// # }
+/// ```
+pub fn balance_digest(address: Address, balance: U256, block_number: u64) -> B256 {
+    let mut message = Vec::with_capacity(20 + 32 + 32);
+    message.extend_from_slice(address.as_slice());
+    message.extend_from_slice(&balance.to_be_bytes::<32>());
+    message.extend_from_slice(&U256::from(block_number).to_be_bytes::<32>());
+    keccak256(message)
+}
+
+/// Signs the balance of `address` at `block_number`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_sign_balance() {
+/// let signature = sign_balance(&signer, Address::ZERO, U256::from(1000), 12).unwrap();
+/// assert_eq!(signature.signer, signer.address());
+/// # }
+/// ```
+pub fn sign_balance(
+    signer: &PrivateKeySigner,
+    address: Address,
+    balance: U256,
+    block_number: u64,
+) -> Result<BalanceSignature, AppError> {
+    let digest = balance_digest(address, balance, block_number);
+    let signature = signer
+        .sign_hash_sync(&digest)
+        .map_err(|error| AppError::Internal(error.to_string()))?;
+    Ok(BalanceSignature {
+        block_number,
+        signer: signer.address(),
+        signature: Bytes::from(signature.as_bytes().to_vec()),
+    })
+}
+
+/// Hashes `typed_data` as EIP-712 prescribes and signs the hash.
+///
+/// A message that does not match its declared types is rejected as an
+/// invalid request.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_sign_typed_data() {
+/// let signed = sign_typed_data(&signer, &typed_data).unwrap();
+/// assert_eq!(signed.hash, typed_data.eip712_signing_hash().unwrap());
+/// # }
+/// ```
+pub fn sign_typed_data(
+    signer: &PrivateKeySigner,
+    typed_data: &TypedData,
+) -> Result<TypedDataSignature, AppError> {
+    let hash = typed_data
+        .eip712_signing_hash()
+        .map_err(|error| AppError::InvalidRequest(format!("Invalid typed data: {}", error)))?;
+    let signature = signer
+        .sign_hash_sync(&hash)
+        .map_err(|error| AppError::Internal(error.to_string()))?;
+    Ok(TypedDataSignature {
+        hash,
+        signer: signer.address(),
+        signature: Bytes::from(signature.as_bytes().to_vec()),
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloy_primitives::{address, b256, PrimitiveSignature};
+
+    const ADDRESS: Address = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
+
+    /// The `Mail` example of EIP-712, signed there with the key `keccak256("cow")`.
+    pub(crate) fn mail() -> serde_json::Value {
+        serde_json::json!({
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "version", "type": "string" },
+                    { "name": "chainId", "type": "uint256" },
+                    { "name": "verifyingContract", "type": "address" },
+                ],
+                "Person": [
+                    { "name": "name", "type": "string" },
+                    { "name": "wallet", "type": "address" },
+                ],
+                "Mail": [
+                    { "name": "from", "type": "Person" },
+                    { "name": "to", "type": "Person" },
+                    { "name": "contents", "type": "string" },
+                ],
+            },
+            "primaryType": "Mail",
+            "domain": {
+                "name": "Ether Mail",
+                "version": "1",
+                "chainId": 1,
+                "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC",
+            },
+            "message": {
+                "from": { "name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826" },
+                "to": { "name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" },
+                "contents": "Hello, Bob!",
+            },
+        })
+    }
+
+    pub(crate) fn cow() -> PrivateKeySigner {
+        PrivateKeySigner::from_bytes(&keccak256("cow")).unwrap()
+    }
+
+    /// Signature of [`mail`] by [`cow`] given in EIP-712.
+    pub(crate) const MAIL_SIGNATURE: &str = "0x4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b915621c";
+
+    #[test]
+    fn test_sign_typed_data_matches_eip712() {
+        let typed_data: TypedData = serde_json::from_value(mail()).unwrap();
+        let signed = sign_typed_data(&cow(), &typed_data).unwrap();
+        assert_eq!(
+            signed.hash,
+            b256!("be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2")
+        );
+        assert_eq!(
+            signed.signer,
+            address!("cd2a3d9f938e13cd947ec05abc7fe734df8dd826")
+        );
+        assert_eq!(signed.signature.to_string(), MAIL_SIGNATURE);
+
+        // A message not matching its types cannot be hashed.
+        let mut mismatched = mail();
+        mismatched["message"]["to"]["wallet"] = "bob".into();
+        let typed_data: TypedData = serde_json::from_value(mismatched).unwrap();
+        assert!(matches!(
+            sign_typed_data(&cow(), &typed_data),
+            Err(AppError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_signature_recovers_to_signer() {
+        let signer = PrivateKeySigner::random();
+        let balance = U256::from(1000);
+        let signed = sign_balance(&signer, ADDRESS, balance, 12).unwrap();
+        assert_eq!(signed.signer, signer.address());
+        assert_eq!(signed.signature.len(), 65);
+
+        let signature = PrimitiveSignature::try_from(signed.signature.as_ref()).unwrap();
+        let digest = balance_digest(ADDRESS, balance, 12);
+        assert_eq!(
+            signature.recover_address_from_prehash(&digest).unwrap(),
+            signer.address()
+        );
+
+        // Any other balance or block recovers to someone else.
+        let other = balance_digest(ADDRESS, balance, 13);
+        assert_ne!(
+            signature.recover_address_from_prehash(&other).unwrap(),
+            signer.address()
+        );
+    }
+
+    #[test]
+    fn test_balance_digest_packs_words() {
+        let mut packed = ADDRESS.to_vec();
+        packed.extend_from_slice(&[0u8; 30]);
+        packed.extend_from_slice(&[0x03, 0xe8]);
+        packed.extend_from_slice(&[0u8; 31]);
+        packed.push(12);
+        assert_eq!(
+            balance_digest(ADDRESS, U256::from(1000), 12),
+            keccak256(packed)
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use crate::config;
+use alloy::providers::Provider;
+use anyhow::{anyhow, Context};
+use log::{info, warn};
+use std::future::IntoFuture;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+/// How long the startup probe waits for the node before giving up, unless
+/// overridden by `STARTUP_PROBE_TIMEOUT_MS`.
+const DEFAULT_STARTUP_PROBE_TIMEOUT_MS: u64 = 5_000;
+
+/// Retrieves the startup probe timeout from the environment.
+pub fn get_startup_probe_timeout() -> Duration {
+    Duration::from_millis(config::env_or(
+        "STARTUP_PROBE_TIMEOUT_MS",
+        DEFAULT_STARTUP_PROBE_TIMEOUT_MS,
+    ))
+}
+
+/// How long to wait between startup probes while the server is not ready,
+/// unless overridden by `STARTUP_PROBE_RETRY_MS`.
+const DEFAULT_STARTUP_PROBE_RETRY_MS: u64 = 1_000;
+
+/// Retrieves the delay between startup probes from the environment.
+pub fn get_startup_probe_retry_interval() -> Duration {
+    Duration::from_millis(config::env_or(
+        "STARTUP_PROBE_RETRY_MS",
+        DEFAULT_STARTUP_PROBE_RETRY_MS,
+    ))
+}
+
+/// Retrieves how many upstream connections to open before serving, from
+/// `WARMUP_CONNECTIONS`; `0`, the default, opens none ahead of traffic.
+pub fn get_warmup_connections() -> usize {
+    config::env_or("WARMUP_CONNECTIONS", 0)
+}
+
+/// Whether the node has answered a startup probe yet.
+///
+/// Read routes answer `503 Service Unavailable` until the server is ready.
+#[derive(Debug)]
+pub struct Readiness(AtomicBool);
+
+impl Readiness {
+    pub fn new(ready: bool) -> Self {
+        Readiness(AtomicBool::new(ready))
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    pub fn mark_ready(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+/// Chain details learned from the node at startup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeResult {
+    pub chain_id: u64,
+    pub block_number: u64,
+}
+
+/// Queries the chain id and head block once, bounded by `limit`.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_probe_provider() {
+/// let probe = probe_provider(provider.as_ref(), get_startup_probe_timeout()).await.unwrap();
+/// println!("{}", probe.chain_id);
+/// # }
+/// ```
+pub async fn probe_provider(
+    provider: &dyn Provider,
+    limit: Duration,
+) -> anyhow::Result<ProbeResult> {
+    let probe = async {
+        let chain_id = provider
+            .get_chain_id()
+            .await
+            .context("eth_chainId failed")?;
+        let block_number = provider
+            .get_block_number()
+            .await
+            .context("eth_blockNumber failed")?;
+        Ok(ProbeResult {
+            chain_id,
+            block_number,
+        })
+    };
+
+    timeout(limit, probe)
+        .await
+        .map_err(|_| anyhow!("Timed out after {:?}", limit))?
+}
+
+/// Probes the node and decides whether the server may start.
+///
+/// Returns an error when the probe fails, unless `allow_degraded` is set, in
+/// which case the server starts without a verified connection.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_warm_up() {
+/// let probe = warm_up(provider.as_ref(), get_startup_probe_timeout(), false).await.unwrap();
+/// # }
+/// ```
+pub async fn warm_up(
+    provider: &dyn Provider,
+    limit: Duration,
+    allow_degraded: bool,
+) -> anyhow::Result<Option<ProbeResult>> {
+    match probe_provider(provider, limit).await {
+        Ok(probe) => {
+            info!(
+                "Connected to chain id {} at block {}",
+                probe.chain_id, probe.block_number
+            );
+            Ok(Some(probe))
+        }
+        Err(error) if allow_degraded => {
+            warn!(
+                "Startup probe failed, starting in degraded mode: {:#}",
+                error
+            );
+            Ok(None)
+        }
+        Err(error) => Err(error.context("Startup probe failed")),
+    }
+}
+
+/// Primes the provider's connection pool by sending `connections` concurrent
+/// `eth_blockNumber` requests, each bounded by `limit`, so the first requests
+/// served do not pay for connection setup.
+///
+/// Returns how many requests succeeded; failures are only logged, since the
+/// pool fills on demand anyway.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_prime_connections() {
+/// let primed = prime_connections(provider.as_ref(), 4, get_startup_probe_timeout()).await;
+/// assert_eq!(primed, 4);
+/// # }
+/// ```
+pub async fn prime_connections(
+    provider: &dyn Provider,
+    connections: usize,
+    limit: Duration,
+) -> usize {
+    let requests =
+        (0..connections).map(|_| timeout(limit, provider.get_block_number().into_future()));
+    let mut primed = 0;
+    for result in futures::future::join_all(requests).await {
+        match result {
+            Ok(Ok(_)) => primed += 1,
+            Ok(Err(error)) => warn!("Warm-up request failed: {}", error),
+            Err(_) => warn!("Warm-up request timed out after {:?}", limit),
+        }
+    }
+    info!(
+        "Warmed up {} of {} upstream connections",
+        primed, connections
+    );
+    primed
+}
+
+/// Re-probes the node every `every` until it answers, then marks the server ready.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_wait_until_ready() {
+/// let readiness = Arc::new(Readiness::new(false));
+/// tokio::spawn(wait_until_ready(provider.clone(), get_startup_probe_timeout(), Duration::from_secs(1), readiness.clone()));
+/// # }
+/// ```
+pub async fn wait_until_ready(
+    provider: Arc<dyn Provider>,
+    limit: Duration,
+    every: Duration,
+    readiness: Arc<Readiness>,
+) {
+    loop {
+        match probe_provider(provider.as_ref(), limit).await {
+            Ok(probe) => {
+                info!(
+                    "Connected to chain id {} at block {}, now ready",
+                    probe.chain_id, probe.block_number
+                );
+                readiness.mark_ready();
+                return;
+            }
+            Err(error) => warn!("Startup probe failed, retrying in {:?}: {:#}", every, error),
+        }
+        sleep(every).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::providers::{ProviderCall, RootProvider};
+    use alloy::rpc::client::NoParams;
+    use alloy::transports::TransportErrorKind;
+    use alloy_primitives::U64;
+    use std::sync::atomic::AtomicUsize;
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    struct HealthyProvider;
+
+    impl Provider for HealthyProvider {
+        fn get_chain_id(&self) -> ProviderCall<NoParams, U64, u64> {
+            ProviderCall::ready(Ok(31337))
+        }
+
+        fn get_block_number(&self) -> ProviderCall<NoParams, U64, u64> {
+            ProviderCall::ready(Ok(42))
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("HealthyProvider does not support `root`")
+        }
+    }
+
+    /// Counts `eth_blockNumber` calls, failing every one after the first `healthy`.
+    struct CountingProvider {
+        healthy: usize,
+        calls: AtomicUsize,
+    }
+
+    impl Provider for CountingProvider {
+        fn get_block_number(&self) -> ProviderCall<NoParams, U64, u64> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            if call < self.healthy {
+                ProviderCall::ready(Ok(42))
+            } else {
+                ProviderCall::ready(Err(TransportErrorKind::custom_str("connection refused")))
+            }
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("CountingProvider does not support `root`")
+        }
+    }
+
+    struct UnreachableProvider;
+
+    impl Provider for UnreachableProvider {
+        fn get_chain_id(&self) -> ProviderCall<NoParams, U64, u64> {
+            ProviderCall::ready(Err(TransportErrorKind::custom_str("connection refused")))
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("UnreachableProvider does not support `root`")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_reports_chain_id() {
+        let probe = warm_up(&HealthyProvider, TIMEOUT, false).await.unwrap();
+        assert_eq!(
+            probe,
+            Some(ProbeResult {
+                chain_id: 31337,
+                block_number: 42
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_fails_fast() {
+        let error = warm_up(&UnreachableProvider, TIMEOUT, false)
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("connection refused"));
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_allows_degraded_start() {
+        let probe = warm_up(&UnreachableProvider, TIMEOUT, true).await.unwrap();
+        assert_eq!(probe, None);
+    }
+
+    #[tokio::test]
+    async fn test_prime_connections_issues_one_call_per_connection() {
+        let provider = CountingProvider {
+            healthy: usize::MAX,
+            calls: AtomicUsize::new(0),
+        };
+        assert_eq!(prime_connections(&provider, 4, TIMEOUT).await, 4);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 4);
+
+        assert_eq!(prime_connections(&provider, 0, TIMEOUT).await, 0);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 4);
+
+        let flaky = CountingProvider {
+            healthy: 2,
+            calls: AtomicUsize::new(0),
+        };
+        assert_eq!(prime_connections(&flaky, 3, TIMEOUT).await, 2);
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_ready_marks_ready() {
+        let readiness = Arc::new(Readiness::new(false));
+        assert!(!readiness.is_ready());
+        wait_until_ready(
+            Arc::new(HealthyProvider),
+            TIMEOUT,
+            Duration::from_millis(10),
+            readiness.clone(),
+        )
+        .await;
+        assert!(readiness.is_ready());
+    }
+}
@@ -0,0 +1,245 @@
+use crate::config;
+use crate::error::AppError;
+use alloy::providers::Provider;
+use futures::StreamExt;
+use log::{info, warn};
+use serde::Serialize;
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::sleep;
+
+/// Heads buffered per stream; slower streams skip to the newest head.
+const HEAD_BUFFER: usize = 16;
+
+/// Delay before re-subscribing after the head subscription ends.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// Balance streaming settings read from the environment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamConfig {
+    pub ws_url: String,
+    pub max_streams: usize,
+}
+
+/// Reads the streaming settings; returns `None` unless `WS_RPC_URL` is set.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_stream_config() {
+/// let config = get_stream_config();
+/// assert!(config.is_none());
+/// # }
+/// ```
+pub fn get_stream_config() -> Option<StreamConfig> {
+    let ws_url = env::var("WS_RPC_URL").ok().filter(|url| !url.is_empty())?;
+    Some(StreamConfig {
+        ws_url,
+        max_streams: config::env_or("MAX_BALANCE_STREAMS", 100),
+    })
+}
+
+/// Fans new block numbers out to the open balance streams, at most `max_streams` at a time.
+pub struct BalanceStreams {
+    heads: broadcast::Sender<u64>,
+    active: Arc<AtomicUsize>,
+    max_streams: usize,
+}
+
+impl BalanceStreams {
+    pub fn new(max_streams: usize) -> Self {
+        let (heads, _) = broadcast::channel(HEAD_BUFFER);
+        BalanceStreams {
+            heads,
+            active: Arc::new(AtomicUsize::new(0)),
+            max_streams,
+        }
+    }
+
+    /// Number of streams currently open.
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Announces a new head to every open stream.
+    pub fn publish(&self, block_number: u64) {
+        // Sending only fails when no stream is open.
+        let _ = self.heads.send(block_number);
+    }
+
+    /// Opens a stream of new heads, or fails when `max_streams` are already open.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_open() {
+    /// let streams = BalanceStreams::new(1);
+    /// let mut heads = streams.open().unwrap();
+    /// streams.publish(1001);
+    /// assert_eq!(heads.next().await, Some(1001));
+    /// # }
+    /// ```
+    pub fn open(&self) -> Result<HeadStream, AppError> {
+        self.active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |active| {
+                (active < self.max_streams).then_some(active + 1)
+            })
+            .map_err(|_| AppError::TooManyStreams)?;
+        Ok(HeadStream {
+            receiver: self.heads.subscribe(),
+            active: self.active.clone(),
+        })
+    }
+
+    /// Subscribes to new heads on `provider` forever, re-subscribing when the subscription ends.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # async fn test_run_subscription() {
+    /// tokio::spawn(streams.clone().run_subscription(ws_provider));
+    /// # }
+    /// ```
+    pub async fn run_subscription(self: Arc<Self>, provider: Arc<dyn Provider>) {
+        loop {
+            match provider.subscribe_blocks().await {
+                Ok(subscription) => {
+                    info!("Subscribed to new heads");
+                    let mut headers = subscription.into_stream();
+                    while let Some(header) = headers.next().await {
+                        self.publish(header.number);
+                    }
+                    warn!("New heads subscription ended, re-subscribing");
+                }
+                Err(error) => warn!("Failed to subscribe to new heads: {}", error),
+            }
+            sleep(RESUBSCRIBE_DELAY).await;
+        }
+    }
+}
+
+/// Sent by a stream when a head is not above the last one it sent.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Reorg {
+    pub old_head: u64,
+    pub new_head: u64,
+}
+
+/// Last head sent by one stream, to notice reorgs.
+#[derive(Debug, Default)]
+pub struct HeadTracker {
+    last: Option<u64>,
+}
+
+impl HeadTracker {
+    /// Records `head`, returning a reorg when it does not advance past the last head.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_observe() {
+    /// let mut tracker = HeadTracker::default();
+    /// assert_eq!(tracker.observe(1001), None);
+    /// assert_eq!(tracker.observe(1000), Some(Reorg { old_head: 1001, new_head: 1000 }));
+    /// # }
+    /// ```
+    pub fn observe(&mut self, head: u64) -> Option<Reorg> {
+        let reorg = self
+            .last
+            .filter(|last| head <= *last)
+            .map(|old_head| Reorg {
+                old_head,
+                new_head: head,
+            });
+        self.last = Some(head);
+        reorg
+    }
+}
+
+/// New heads seen by one open stream; closing it frees its slot.
+pub struct HeadStream {
+    receiver: broadcast::Receiver<u64>,
+    active: Arc<AtomicUsize>,
+}
+
+impl HeadStream {
+    /// Waits for the next head, skipping any the stream fell behind on.
+    pub async fn next(&mut self) -> Option<u64> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(block_number) => return Some(block_number),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for HeadStream {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_heads_reach_every_stream() {
+        let streams = BalanceStreams::new(2);
+        let mut first = streams.open().unwrap();
+        let mut second = streams.open().unwrap();
+
+        streams.publish(1001);
+        assert_eq!(first.next().await, Some(1001));
+        assert_eq!(second.next().await, Some(1001));
+    }
+
+    #[test]
+    fn test_tracker_reports_reorgs() {
+        let mut tracker = HeadTracker::default();
+        let reorgs: Vec<_> = [1000, 1001, 1002, 1001, 1002, 1002, 1003]
+            .into_iter()
+            .filter_map(|head| tracker.observe(head))
+            .collect();
+        assert_eq!(
+            reorgs,
+            vec![
+                Reorg {
+                    old_head: 1002,
+                    new_head: 1001
+                },
+                Reorg {
+                    old_head: 1002,
+                    new_head: 1002
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_open_streams_are_capped() {
+        let streams = BalanceStreams::new(1);
+        let heads = streams.open().unwrap();
+        assert!(matches!(streams.open(), Err(AppError::TooManyStreams)));
+        assert_eq!(streams.active(), 1);
+
+        drop(heads);
+        assert_eq!(streams.active(), 0);
+        assert!(streams.open().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_lagging_stream_skips_to_newer_heads() {
+        let streams = BalanceStreams::new(1);
+        let mut heads = streams.open().unwrap();
+        for block_number in 0..(HEAD_BUFFER as u64 + 4) {
+            streams.publish(block_number);
+        }
+        assert_eq!(heads.next().await, Some(4));
+    }
+}
@@ -0,0 +1,173 @@
+use log::{error, info, warn};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Wait before the first restart of a panicked task, doubled on every further panic.
+pub const INITIAL_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Longest wait between restarts.
+pub const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Whether a background task is doing its work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskState {
+    Running,
+    /// The task panicked and waits for its restart.
+    Restarting,
+    /// The task returned; only one-off tasks such as the startup probe do.
+    Finished,
+}
+
+/// Liveness of a background task, as returned by `/stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TaskStatus {
+    pub state: TaskState,
+    pub restarts: u64,
+}
+
+/// Owns the server's background tasks, restarting them with backoff when they panic.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_supervisor() {
+/// let supervisor = Arc::new(Supervisor::new(INITIAL_RESTART_BACKOFF, MAX_RESTART_BACKOFF));
+/// supervisor.spawn("rate-limit-sweeper", move || limiter.clone().run_sweeper(every, ttl));
+/// # }
+/// ```
+pub struct Supervisor {
+    tasks: Mutex<BTreeMap<&'static str, TaskStatus>>,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Supervisor {
+    pub fn new(initial_backoff: Duration, max_backoff: Duration) -> Self {
+        Supervisor {
+            tasks: Mutex::new(BTreeMap::new()),
+            initial_backoff,
+            max_backoff,
+        }
+    }
+
+    /// Runs `task()` in the background under `name`, calling it again whenever
+    /// the future it returned panics.
+    pub fn spawn<F, Fut>(self: &Arc<Self>, name: &'static str, task: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.set_state(name, TaskState::Running);
+        let supervisor = self.clone();
+        tokio::spawn(async move {
+            let mut backoff = supervisor.initial_backoff;
+            loop {
+                match tokio::spawn(task()).await {
+                    Ok(()) => {
+                        info!("Background task {} finished", name);
+                        supervisor.set_state(name, TaskState::Finished);
+                        return;
+                    }
+                    Err(error) if error.is_panic() => {
+                        warn!(
+                            "Background task {} panicked, restarting in {:?}",
+                            name, backoff
+                        );
+                        supervisor.set_state(name, TaskState::Restarting);
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(supervisor.max_backoff);
+                        supervisor.record_restart(name);
+                    }
+                    Err(error) => {
+                        error!("Background task {} was cancelled: {}", name, error);
+                        supervisor.set_state(name, TaskState::Finished);
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Status of every task spawned so far, by name.
+    pub fn tasks(&self) -> BTreeMap<&'static str, TaskStatus> {
+        self.tasks.lock().expect("supervisor poisoned").clone()
+    }
+
+    fn set_state(&self, name: &'static str, state: TaskState) {
+        let mut tasks = self.tasks.lock().expect("supervisor poisoned");
+        tasks
+            .entry(name)
+            .and_modify(|status| status.state = state)
+            .or_insert(TaskStatus { state, restarts: 0 });
+    }
+
+    fn record_restart(&self, name: &'static str) {
+        let mut tasks = self.tasks.lock().expect("supervisor poisoned");
+        if let Some(status) = tasks.get_mut(name) {
+            status.state = TaskState::Running;
+            status.restarts += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn supervisor() -> Arc<Supervisor> {
+        Arc::new(Supervisor::new(
+            Duration::from_millis(1),
+            Duration::from_millis(4),
+        ))
+    }
+
+    async fn wait_for(supervisor: &Supervisor, name: &str, expected: TaskStatus) {
+        for _ in 0..200 {
+            if supervisor.tasks().get(name) == Some(&expected) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("{} is {:?}", name, supervisor.tasks().get(name));
+    }
+
+    #[tokio::test]
+    async fn test_panicking_task_is_restarted() {
+        let supervisor = supervisor();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        supervisor.spawn("flaky", move || {
+            let run = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if run < 2 {
+                    panic!("run {} failed", run);
+                }
+                std::future::pending::<()>().await
+            }
+        });
+
+        let running = TaskStatus {
+            state: TaskState::Running,
+            restarts: 2,
+        };
+        wait_for(&supervisor, "flaky", running).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_returning_task_is_finished() {
+        let supervisor = supervisor();
+        supervisor.spawn("once", || async {});
+
+        let finished = TaskStatus {
+            state: TaskState::Finished,
+            restarts: 0,
+        };
+        wait_for(&supervisor, "once", finished).await;
+    }
+}
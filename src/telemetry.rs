@@ -0,0 +1,277 @@
+use crate::config;
+use crate::metrics;
+use log::{info, warn};
+use opentelemetry::global::{self, BoxedSpan, BoxedTracer};
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+use opentelemetry::trace::noop::NoopTracerProvider;
+use opentelemetry::trace::{Span, Status, TraceError, Tracer};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{MetricExporter, SpanExporter};
+use opentelemetry_sdk::metrics::{MetricResult, SdkMeterProvider};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use std::env;
+use std::time::Duration;
+use warp::http::{Method, StatusCode};
+
+/// Exports spans to the collector at `OTEL_EXPORTER_OTLP_ENDPOINT` over OTLP/HTTP.
+///
+/// Tracing is optional: when the variable is unset, or the exporter cannot be
+/// built, spans go to a no-op tracer and the server starts regardless. Keep
+/// the returned provider alive for as long as spans should be exported.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_init_tracing() {
+/// let tracer_provider = init_tracing();
+/// # }
+/// ```
+pub fn init_tracing() -> Option<SdkTracerProvider> {
+    let endpoint = env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok()?;
+    info!("Exporting traces to {}", endpoint);
+    // The exporter reads the endpoint itself, appending `/v1/traces`.
+    install_tracer(SpanExporter::builder().with_http().build())
+}
+
+/// Installs a tracer exporting to `exporter`, or the no-op tracer if it failed to build.
+pub fn install_tracer(exporter: Result<SpanExporter, TraceError>) -> Option<SdkTracerProvider> {
+    match exporter {
+        Ok(exporter) => {
+            let provider = SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .build();
+            global::set_tracer_provider(provider.clone());
+            Some(provider)
+        }
+        Err(error) => {
+            warn!(
+                "Failed to set up trace export, tracing is disabled: {}",
+                error
+            );
+            global::set_tracer_provider(NoopTracerProvider::new());
+            None
+        }
+    }
+}
+
+/// Exports request metrics to the same collector as the traces when
+/// `OTEL_METRICS_ENABLED=true` and `OTEL_EXPORTER_OTLP_ENDPOINT` is set.
+///
+/// Like tracing, a failure to build the exporter only disables the metrics.
+/// Keep the returned provider alive for as long as metrics should be exported.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_init_metrics() {
+/// let meter_provider = init_metrics();
+/// # }
+/// ```
+pub fn init_metrics() -> Option<SdkMeterProvider> {
+    if !config::env_flag("OTEL_METRICS_ENABLED") {
+        return None;
+    }
+    let Ok(endpoint) = env::var("OTEL_EXPORTER_OTLP_ENDPOINT") else {
+        warn!("OTEL_METRICS_ENABLED is set without OTEL_EXPORTER_OTLP_ENDPOINT, metrics are not exported");
+        return None;
+    };
+    info!("Exporting metrics to {}", endpoint);
+    // The exporter reads the endpoint itself, appending `/v1/metrics`.
+    install_meter(MetricExporter::builder().with_http().build())
+}
+
+/// Installs a meter provider pushing to `exporter` periodically, or none if it failed to build.
+pub fn install_meter(exporter: MetricResult<MetricExporter>) -> Option<SdkMeterProvider> {
+    match exporter {
+        Ok(exporter) => {
+            let provider = SdkMeterProvider::builder()
+                .with_periodic_exporter(exporter)
+                .build();
+            global::set_meter_provider(provider.clone());
+            Some(provider)
+        }
+        Err(error) => {
+            warn!(
+                "Failed to set up metric export, OpenTelemetry metrics are disabled: {}",
+                error
+            );
+            None
+        }
+    }
+}
+
+/// OpenTelemetry instruments recorded for every request, next to the Prometheus counters.
+///
+/// They follow the OpenTelemetry HTTP conventions, with dotted names that do
+/// not collide with the Prometheus ones when both are scraped into one system.
+pub struct RequestMetrics {
+    requests: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+impl RequestMetrics {
+    pub fn new(meter: &Meter) -> Self {
+        RequestMetrics {
+            requests: meter
+                .u64_counter("http.server.request.count")
+                .with_description("Requests served, by method, route and status")
+                .build(),
+            duration: meter
+                .f64_histogram("http.server.request.duration")
+                .with_description("Time taken to serve requests")
+                .with_unit("s")
+                .build(),
+        }
+    }
+
+    /// Records a request to `path`, labelled by its route.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_record() {
+    /// let metrics = RequestMetrics::new(&global::meter("rust-alloy"));
+    /// metrics.record(&Method::GET, "/health", StatusCode::OK, Duration::from_millis(2));
+    /// # }
+    /// ```
+    pub fn record(&self, method: &Method, path: &str, status: StatusCode, elapsed: Duration) {
+        let attributes = [
+            KeyValue::new("http.request.method", method.to_string()),
+            KeyValue::new("http.route", metrics::route_label(path)),
+            KeyValue::new("http.response.status_code", i64::from(status.as_u16())),
+        ];
+        self.requests.add(1, &attributes);
+        self.duration.record(elapsed.as_secs_f64(), &attributes);
+    }
+}
+
+/// Span covering a request from routing to its response, rejections included.
+pub struct RequestSpan(BoxedSpan);
+
+impl RequestSpan {
+    /// Starts a span named after the method and route of the request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_request_span_start() {
+    /// let span = RequestSpan::start(&global::tracer("rust-alloy"), &Method::GET, "/health");
+    /// span.finish(StatusCode::OK);
+    /// # }
+    /// ```
+    pub fn start(tracer: &BoxedTracer, method: &Method, path: &str) -> Self {
+        let mut span = tracer.start(format!("{} {}", method, metrics::route_label(path)));
+        span.set_attribute(KeyValue::new("http.method", method.to_string()));
+        span.set_attribute(KeyValue::new("http.target", path.to_string()));
+        RequestSpan(span)
+    }
+
+    /// Records the response status and ends the span; `5xx` responses mark it as failed.
+    pub fn finish(mut self, status: StatusCode) {
+        self.0.set_attribute(KeyValue::new(
+            "http.status_code",
+            i64::from(status.as_u16()),
+        ));
+        if status.is_server_error() {
+            self.0.set_status(Status::error(status.to_string()));
+        }
+        self.0.end();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::error::OTelSdkResult;
+    use opentelemetry_sdk::metrics::data::{self, ResourceMetrics};
+    use opentelemetry_sdk::metrics::reader::MetricReader;
+    use opentelemetry_sdk::metrics::{InstrumentKind, ManualReader, Pipeline, Temporality};
+    use opentelemetry_sdk::Resource;
+    use std::sync::{Arc, Weak};
+
+    /// In-memory reader that tests keep a handle on after handing it to a meter provider.
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct SharedReader(Arc<ManualReader>);
+
+    impl SharedReader {
+        /// Count and sum of the histogram `name`, over every data point.
+        pub(crate) fn histogram(&self, name: &str) -> Option<(u64, f64)> {
+            let mut collected = ResourceMetrics {
+                resource: Resource::builder_empty().build(),
+                scope_metrics: Vec::new(),
+            };
+            self.0.collect(&mut collected).unwrap();
+            let metric = collected
+                .scope_metrics
+                .iter()
+                .flat_map(|scope| &scope.metrics)
+                .find(|metric| metric.name == name)?;
+            let histogram = metric
+                .data
+                .as_any()
+                .downcast_ref::<data::Histogram<f64>>()?;
+            Some(
+                histogram
+                    .data_points
+                    .iter()
+                    .fold((0, 0.0), |(count, sum), point| {
+                        (count + point.count, sum + point.sum)
+                    }),
+            )
+        }
+    }
+
+    impl MetricReader for SharedReader {
+        fn register_pipeline(&self, pipeline: Weak<Pipeline>) {
+            self.0.register_pipeline(pipeline)
+        }
+
+        fn collect(&self, rm: &mut ResourceMetrics) -> MetricResult<()> {
+            self.0.collect(rm)
+        }
+
+        fn force_flush(&self) -> OTelSdkResult {
+            self.0.force_flush()
+        }
+
+        fn shutdown(&self) -> OTelSdkResult {
+            self.0.shutdown()
+        }
+
+        fn temporality(&self, kind: InstrumentKind) -> Temporality {
+            self.0.temporality(kind)
+        }
+    }
+
+    /// Request metrics recorded into `reader`, with the provider that must outlive them.
+    pub(crate) fn request_metrics(reader: &SharedReader) -> (SdkMeterProvider, RequestMetrics) {
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+        let metrics = RequestMetrics::new(&provider.meter("test"));
+        (provider, metrics)
+    }
+
+    #[test]
+    fn test_request_metrics_record_latency() {
+        let reader = SharedReader::default();
+        let (_provider, metrics) = request_metrics(&reader);
+        metrics.record(
+            &Method::GET,
+            "/balance/0x00",
+            StatusCode::OK,
+            Duration::from_millis(250),
+        );
+        metrics.record(
+            &Method::GET,
+            "/health",
+            StatusCode::OK,
+            Duration::from_millis(50),
+        );
+
+        let (count, sum) = reader.histogram("http.server.request.duration").unwrap();
+        assert_eq!(count, 2);
+        assert!((sum - 0.3).abs() < 1e-9, "{}", sum);
+    }
+}
@@ -0,0 +1,356 @@
+// Nothing in the server itself uses the mock provider.
+#![cfg_attr(not(test), allow(dead_code))]
+
+use alloy::eips::{BlockId, BlockNumberOrTag};
+use alloy::network::{Ethereum, TransactionBuilder};
+use alloy::providers::{
+    Caller, EthCall, EthCallManyParams, EthCallParams, Provider, ProviderCall, RootProvider,
+    RpcWithBlock,
+};
+use alloy::rpc::client::NoParams;
+use alloy::rpc::json_rpc::{ErrorPayload, RpcError};
+use alloy::rpc::types::{Block, BlockTransactionsKind, Transaction, TransactionRequest};
+use alloy::transports::TransportResult;
+use alloy_primitives::{Address, Bytes, B256, U128, U256, U64};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Endpoint of the root provider; nothing listens there, so methods without
+/// a canned response fail as if the node were down.
+const UNREACHABLE_URL: &str = "http://127.0.0.1:1";
+
+/// Configures a [`MockProvider`] with canned responses per method.
+///
+/// Addresses without a configured balance, nonce or storage answer zero, blocks
+/// and transactions that were not added do not exist, and calls without a
+/// canned output revert.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_mock_provider_builder() {
+/// let provider = MockProviderBuilder::new()
+///     .balance(address, U256::from(1000))
+///     .block_number(42)
+///     .build();
+/// # }
+/// ```
+#[derive(Default)]
+pub struct MockProviderBuilder {
+    balances: HashMap<Address, U256>,
+    nonces: HashMap<Address, u64>,
+    blocks: HashMap<u64, Block>,
+    block_number: u64,
+    chain_id: Option<u64>,
+    gas_price: Option<u128>,
+    call_outputs: HashMap<(Address, Bytes), Bytes>,
+    storage: HashMap<(Address, U256), U256>,
+    transactions: HashMap<B256, Transaction>,
+}
+
+impl MockProviderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Balance of `address` at every block.
+    pub fn balance(mut self, address: Address, balance: U256) -> Self {
+        self.balances.insert(address, balance);
+        self
+    }
+
+    /// Transaction count of `address` at every block.
+    pub fn nonce(mut self, address: Address, nonce: u64) -> Self {
+        self.nonces.insert(address, nonce);
+        self
+    }
+
+    /// Adds `block` under the number in its header.
+    pub fn block(mut self, block: Block) -> Self {
+        self.blocks.insert(block.header.number, block);
+        self
+    }
+
+    /// Number of the head block, also used to look up `latest` (default 0).
+    pub fn block_number(mut self, number: u64) -> Self {
+        self.block_number = number;
+        self
+    }
+
+    /// Chain id; without one, `eth_chainId` fails like an unreachable node.
+    pub fn chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    /// Gas price in wei; without one, `eth_gasPrice` fails like an unreachable node.
+    pub fn gas_price(mut self, gas_price: u128) -> Self {
+        self.gas_price = Some(gas_price);
+        self
+    }
+
+    /// Output of `eth_call` to `to` with `input`, at every block.
+    pub fn call_output(mut self, to: Address, input: Bytes, output: Bytes) -> Self {
+        self.call_outputs.insert((to, input), output);
+        self
+    }
+
+    /// Value of storage `slot` of `address`, at every block.
+    pub fn storage(mut self, address: Address, slot: U256, value: U256) -> Self {
+        self.storage.insert((address, slot), value);
+        self
+    }
+
+    /// Adds `transaction` under its hash.
+    pub fn transaction(mut self, transaction: Transaction) -> Self {
+        self.transactions
+            .insert(*transaction.inner.tx_hash(), transaction);
+        self
+    }
+
+    pub fn build(self) -> MockProvider {
+        MockProvider {
+            config: self,
+            calls: Mutex::new(HashMap::new()),
+            root: RootProvider::new_http(UNREACHABLE_URL.parse().expect("valid URL")),
+        }
+    }
+}
+
+/// Provider answering from a [`MockProviderBuilder`] and counting calls per JSON-RPC method.
+pub struct MockProvider {
+    config: MockProviderBuilder,
+    calls: Mutex<HashMap<&'static str, usize>>,
+    root: RootProvider,
+}
+
+impl MockProvider {
+    /// Number of times `method` (e.g. `eth_getBalance`) was called.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_calls() {
+    /// let provider = MockProviderBuilder::new().build();
+    /// assert_eq!(provider.calls("eth_getBalance"), 0);
+    /// # }
+    /// ```
+    pub fn calls(&self, method: &str) -> usize {
+        let calls = self.calls.lock().expect("mock calls poisoned");
+        calls.get(method).copied().unwrap_or(0)
+    }
+
+    /// Panics unless `method` was called exactly `expected` times.
+    pub fn assert_calls(&self, method: &str, expected: usize) {
+        let calls = self.calls(method);
+        assert_eq!(
+            calls, expected,
+            "expected {} calls to {}, got {}",
+            expected, method, calls
+        );
+    }
+
+    fn record(&self, method: &'static str) {
+        let mut calls = self.calls.lock().expect("mock calls poisoned");
+        *calls.entry(method).or_default() += 1;
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for MockProvider {
+    fn get_balance(&self, address: Address) -> RpcWithBlock<Address, U256> {
+        self.record("eth_getBalance");
+        let balance = self
+            .config
+            .balances
+            .get(&address)
+            .copied()
+            .unwrap_or_default();
+        RpcWithBlock::new_provider(move |_block_id| ProviderCall::ready(Ok(balance)))
+    }
+
+    fn get_transaction_count(&self, address: Address) -> RpcWithBlock<Address, U64, u64> {
+        self.record("eth_getTransactionCount");
+        let nonce = self
+            .config
+            .nonces
+            .get(&address)
+            .copied()
+            .unwrap_or_default();
+        RpcWithBlock::new_provider(move |_block_id| ProviderCall::ready(Ok(nonce)))
+    }
+
+    fn get_storage_at(&self, address: Address, key: U256) -> RpcWithBlock<(Address, U256), U256> {
+        self.record("eth_getStorageAt");
+        let value = self
+            .config
+            .storage
+            .get(&(address, key))
+            .copied()
+            .unwrap_or_default();
+        RpcWithBlock::new_provider(move |_block_id| ProviderCall::ready(Ok(value)))
+    }
+
+    fn get_block_number(&self) -> ProviderCall<NoParams, U64, u64> {
+        self.record("eth_blockNumber");
+        ProviderCall::ready(Ok(self.config.block_number))
+    }
+
+    fn get_chain_id(&self) -> ProviderCall<NoParams, U64, u64> {
+        self.record("eth_chainId");
+        match self.config.chain_id {
+            Some(chain_id) => ProviderCall::ready(Ok(chain_id)),
+            None => self.root.get_chain_id(),
+        }
+    }
+
+    fn get_gas_price(&self) -> ProviderCall<NoParams, U128, u128> {
+        self.record("eth_gasPrice");
+        match self.config.gas_price {
+            Some(gas_price) => ProviderCall::ready(Ok(gas_price)),
+            None => self.root.get_gas_price(),
+        }
+    }
+
+    async fn get_block(
+        &self,
+        block: BlockId,
+        _kind: BlockTransactionsKind,
+    ) -> TransportResult<Option<Block>> {
+        self.record("eth_getBlockByNumber");
+        let number = match block {
+            BlockId::Number(BlockNumberOrTag::Number(number)) => number,
+            BlockId::Number(BlockNumberOrTag::Latest) => self.config.block_number,
+            _ => return Ok(None),
+        };
+        Ok(self.config.blocks.get(&number).cloned())
+    }
+
+    fn get_transaction_by_hash(&self, hash: B256) -> ProviderCall<(B256,), Option<Transaction>> {
+        self.record("eth_getTransactionByHash");
+        ProviderCall::ready(Ok(self.config.transactions.get(&hash).cloned()))
+    }
+
+    fn call<'req>(&self, tx: &'req TransactionRequest) -> EthCall<'req, Ethereum, Bytes> {
+        self.record("eth_call");
+        let output = tx
+            .to()
+            .zip(tx.input())
+            .and_then(|(to, input)| self.config.call_outputs.get(&(to, input.clone())).cloned());
+        EthCall::call(CannedCaller(output), tx)
+    }
+
+    fn root(&self) -> &RootProvider {
+        &self.root
+    }
+}
+
+/// Answers one `eth_call` with its canned output, or reverts without one.
+struct CannedCaller(Option<Bytes>);
+
+impl Caller<Ethereum, Bytes> for CannedCaller {
+    fn call(
+        &self,
+        _params: EthCallParams<'_, Ethereum>,
+    ) -> TransportResult<ProviderCall<EthCallParams<'static, Ethereum>, Bytes>> {
+        let output = self.0.clone().ok_or_else(|| {
+            RpcError::ErrorResp(ErrorPayload {
+                code: 3,
+                message: "execution reverted".into(),
+                data: None,
+            })
+        });
+        Ok(ProviderCall::ready(output))
+    }
+
+    fn estimate_gas(
+        &self,
+        _params: EthCallParams<'_, Ethereum>,
+    ) -> TransportResult<ProviderCall<EthCallParams<'static, Ethereum>, Bytes>> {
+        unimplemented!("MockProvider does not support `estimate_gas`")
+    }
+
+    fn call_many(
+        &self,
+        _params: EthCallManyParams<'_>,
+    ) -> TransportResult<ProviderCall<EthCallManyParams<'static>, Bytes>> {
+        unimplemented!("MockProvider does not support `call_many`")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::consensus;
+    use alloy::rpc::types::Header;
+    use alloy_primitives::address;
+
+    const ALICE: Address = address!("1111111111111111111111111111111111111111");
+    const BOB: Address = address!("2222222222222222222222222222222222222222");
+
+    fn block(number: u64) -> Block {
+        Block {
+            header: Header::new(consensus::Header {
+                number,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_canned_responses() {
+        let provider = MockProviderBuilder::new()
+            .balance(ALICE, U256::from(1000))
+            .nonce(ALICE, 7)
+            .block(block(42))
+            .block_number(42)
+            .chain_id(1)
+            .gas_price(20)
+            .call_output(BOB, Bytes::from_static(&[1]), Bytes::from_static(&[2]))
+            .storage(BOB, U256::from(1), U256::from(3))
+            .build();
+
+        assert_eq!(provider.get_balance(ALICE).await.unwrap(), U256::from(1000));
+        assert_eq!(provider.get_balance(BOB).await.unwrap(), U256::ZERO);
+        assert_eq!(provider.get_transaction_count(ALICE).await.unwrap(), 7);
+        assert_eq!(provider.get_block_number().await.unwrap(), 42);
+        assert_eq!(provider.get_chain_id().await.unwrap(), 1);
+        assert_eq!(provider.get_gas_price().await.unwrap(), 20);
+        let slot = provider.get_storage_at(BOB, U256::from(1));
+        assert_eq!(slot.await.unwrap(), U256::from(3));
+        let unset = provider.get_storage_at(BOB, U256::from(2));
+        assert_eq!(unset.await.unwrap(), U256::ZERO);
+        let call = TransactionRequest::default()
+            .to(BOB)
+            .input(Bytes::from_static(&[1]).into());
+        assert_eq!(provider.call(&call).await.unwrap().as_ref(), &[2]);
+        let unknown = TransactionRequest::default().to(ALICE);
+        assert!(matches!(
+            provider.call(&unknown).await,
+            Err(RpcError::ErrorResp(_))
+        ));
+
+        let latest = BlockId::Number(BlockNumberOrTag::Latest);
+        let head = provider.get_block(latest, BlockTransactionsKind::Hashes);
+        assert_eq!(head.await.unwrap().unwrap().header.number, 42);
+        let missing = BlockId::Number(BlockNumberOrTag::Number(41));
+        let missing = provider.get_block(missing, BlockTransactionsKind::Hashes);
+        assert!(missing.await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_counts_calls_per_method() {
+        let provider = MockProviderBuilder::new().build();
+        provider.get_balance(ALICE).await.unwrap();
+        provider.get_balance(BOB).await.unwrap();
+        provider.get_block_number().await.unwrap();
+
+        provider.assert_calls("eth_getBalance", 2);
+        provider.assert_calls("eth_blockNumber", 1);
+        assert_eq!(provider.calls("eth_getTransactionCount"), 0);
+        // Without a configured chain id the call reaches the unreachable node.
+        assert!(provider.get_chain_id().await.is_err());
+        provider.assert_calls("eth_chainId", 1);
+    }
+}
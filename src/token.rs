@@ -0,0 +1,180 @@
+use crate::error::AppError;
+use alloy::eips::BlockId;
+use alloy::providers::Provider;
+use alloy::rpc::json_rpc::RpcError;
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use alloy_primitives::{Address, Bytes, U256};
+use log::debug;
+
+/// Decimals assumed for tokens without a `decimals()` function.
+pub const DEFAULT_DECIMALS: u8 = 18;
+
+sol! {
+    /// ERC-20 `balanceOf`.
+    function balanceOf(address owner) external view returns (uint256 balance);
+
+    /// ERC-20 `decimals`, optional in the standard.
+    function decimals() external view returns (uint8 decimals);
+}
+
+/// Decimals of a token, and whether they were assumed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenDecimals {
+    pub decimals: u8,
+    /// The token has no `decimals()` function, so [`DEFAULT_DECIMALS`] are assumed.
+    pub defaulted: bool,
+}
+
+/// `eth_call` request reading the ERC-20 balance of `owner` from `token`.
+pub fn balance_of_request(token: Address, owner: Address) -> TransactionRequest {
+    let input = balanceOfCall { owner }.abi_encode();
+    TransactionRequest::default()
+        .to(token)
+        .input(Bytes::from(input).into())
+}
+
+/// Decodes the output of an ERC-20 `balanceOf` call.
+pub fn decode_balance(output: &[u8]) -> Result<U256, AppError> {
+    balanceOfCall::abi_decode_returns(output, true)
+        .map(|decoded| decoded.balance)
+        .map_err(|error| AppError::Decode(format!("balanceOf returned {}", error)))
+}
+
+/// `eth_call` request reading the ERC-20 decimals of `token`.
+pub fn decimals_request(token: Address) -> TransactionRequest {
+    TransactionRequest::default()
+        .to(token)
+        .input(Bytes::from(decimalsCall {}.abi_encode()).into())
+}
+
+/// Decodes the output of an ERC-20 `decimals` call, `None` when it is not a `uint8`.
+pub fn decode_decimals(output: &[u8]) -> Option<u8> {
+    decimalsCall::abi_decode_returns(output, true)
+        .map(|decoded| decoded.decimals)
+        .ok()
+}
+
+/// Decimals of `token`, falling back to [`DEFAULT_DECIMALS`] when the call
+/// reverts or does not return a `uint8`, as for tokens without `decimals()`.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_token_decimals() {
+/// let decimals = token_decimals(provider.as_ref(), usdc).await.unwrap();
+/// assert_eq!(decimals, TokenDecimals { decimals: 6, defaulted: false });
+/// # }
+/// ```
+pub async fn token_decimals(
+    provider: &dyn Provider,
+    token: Address,
+) -> Result<TokenDecimals, AppError> {
+    let decimals = match provider.call(&decimals_request(token)).await {
+        Ok(output) => decode_decimals(&output),
+        Err(RpcError::ErrorResp(payload)) => {
+            debug!("decimals() of {} failed: {}", token, payload.message);
+            None
+        }
+        Err(error) => return Err(error.into()),
+    };
+    Ok(match decimals {
+        Some(decimals) => TokenDecimals {
+            decimals,
+            defaulted: false,
+        },
+        None => TokenDecimals {
+            decimals: DEFAULT_DECIMALS,
+            defaulted: true,
+        },
+    })
+}
+
+/// ERC-20 balance of `owner` in `token` at `block_id`.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_token_balance() {
+/// let balance = token_balance(provider.as_ref(), token, owner, BlockId::latest()).await.unwrap();
+/// # }
+/// ```
+pub async fn token_balance(
+    provider: &dyn Provider,
+    token: Address,
+    owner: Address,
+    block_id: BlockId,
+) -> Result<U256, AppError> {
+    let request = balance_of_request(token, owner);
+    let output = provider.call(&request).block(block_id).await?;
+    decode_balance(&output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockProviderBuilder;
+    use alloy::sol_types::SolValue;
+
+    #[test]
+    fn test_balance_of_request() {
+        let token = Address::repeat_byte(0x70);
+        let owner = Address::repeat_byte(0x01);
+        let request = balance_of_request(token, owner);
+
+        let input = request.input.input().unwrap();
+        assert_eq!(&input[..4], balanceOfCall::SELECTOR.as_slice());
+        assert_eq!(&input[16..36], owner.as_slice());
+        assert_eq!(request.to, Some(token.into()));
+    }
+
+    #[test]
+    fn test_decode_balance() {
+        let output = U256::from(5000).abi_encode();
+        assert_eq!(decode_balance(&output).unwrap(), U256::from(5000));
+        assert!(matches!(decode_balance(&[0x01]), Err(AppError::Decode(_))));
+    }
+
+    #[tokio::test]
+    async fn test_token_decimals() {
+        let usdc = Address::repeat_byte(0x60);
+        let output = U256::from(6).abi_encode().into();
+        let provider = MockProviderBuilder::new()
+            .call_output(
+                usdc,
+                decimals_request(usdc).input.into_input().unwrap(),
+                output,
+            )
+            .build();
+        assert_eq!(
+            token_decimals(&provider, usdc).await.unwrap(),
+            TokenDecimals {
+                decimals: 6,
+                defaulted: false
+            }
+        );
+        // The call to a token without `decimals()` reverts.
+        assert_eq!(
+            token_decimals(&provider, Address::repeat_byte(0x70))
+                .await
+                .unwrap(),
+            TokenDecimals {
+                decimals: DEFAULT_DECIMALS,
+                defaulted: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode_decimals() {
+        let input = decimals_request(Address::repeat_byte(0x70)).input;
+        assert_eq!(
+            input.input().unwrap().as_ref(),
+            decimalsCall::SELECTOR.as_slice()
+        );
+        assert_eq!(decode_decimals(&U256::from(6).abi_encode()), Some(6));
+        assert_eq!(decode_decimals(&U256::from(5000).abi_encode()), None);
+        assert_eq!(decode_decimals(&[]), None);
+    }
+}
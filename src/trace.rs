@@ -0,0 +1,184 @@
+use crate::config;
+use crate::error::AppError;
+use alloy::providers::Provider;
+use alloy_primitives::Address;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+/// Settings of the trace routes, which only some clients (Erigon, OpenEthereum) support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceConfig {
+    /// Widest block range accepted by `/trace-filter`.
+    pub max_range: u64,
+}
+
+/// Reads the trace route settings; returns `None` unless `ENABLE_TRACE_ROUTES=true`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_trace_config() {
+/// let config = get_trace_config();
+/// assert!(config.is_none());
+/// # }
+/// ```
+pub fn get_trace_config() -> Option<TraceConfig> {
+    if !config::env_flag("ENABLE_TRACE_ROUTES") {
+        return None;
+    }
+    Some(TraceConfig {
+        max_range: config::env_or("TRACE_MAX_BLOCK_RANGE", 100),
+    })
+}
+
+/// Body of `/trace-filter`; both block bounds are inclusive.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TraceFilterRequest {
+    pub from_block: u64,
+    pub to_block: u64,
+    pub from_address: Option<Address>,
+    pub to_address: Option<Address>,
+}
+
+/// Params of `trace_filter`, in the node's camel case with hex block numbers.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceFilterParams {
+    from_block: String,
+    to_block: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    from_address: Vec<Address>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    to_address: Vec<Address>,
+}
+
+/// Forwards a filter to the node's `trace_filter`, returning its traces untouched.
+///
+/// # Examples
+///
+/// ```rust
+/// # async fn test_trace_filter() {
+/// let request = TraceFilterRequest { from_block: 1, to_block: 2, from_address: None, to_address: None };
+/// let traces = trace_filter(provider.as_ref(), &request, &config).await.unwrap();
+/// # }
+/// ```
+pub async fn trace_filter(
+    provider: &dyn Provider,
+    request: &TraceFilterRequest,
+    config: &TraceConfig,
+) -> Result<Box<RawValue>, AppError> {
+    if request.from_block > request.to_block {
+        return Err(AppError::InvalidRequest(format!(
+            "from_block {} is after to_block {}",
+            request.from_block, request.to_block
+        )));
+    }
+    // `0..=u64::MAX` has one block more than a `u64` can count.
+    let span = (request.to_block - request.from_block).saturating_add(1);
+    if span > config.max_range {
+        return Err(AppError::InvalidRequest(format!(
+            "block range of {} blocks exceeds the maximum of {}",
+            span, config.max_range
+        )));
+    }
+
+    let params = [TraceFilterParams {
+        from_block: format!("{:#x}", request.from_block),
+        to_block: format!("{:#x}", request.to_block),
+        from_address: request.from_address.into_iter().collect(),
+        to_address: request.to_address.into_iter().collect(),
+    }];
+    let params = serde_json::value::to_raw_value(&params)
+        .map_err(|error| AppError::Internal(error.to_string()))?;
+
+    info!(
+        "Querying traces from block {} to {}",
+        request.from_block, request.to_block
+    );
+    provider
+        .raw_request_dyn("trace_filter".into(), &params)
+        .await
+        .map_err(AppError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::providers::RootProvider;
+    use alloy::transports::TransportResult;
+    use alloy_primitives::address;
+    use std::borrow::Cow;
+    use std::sync::Mutex;
+
+    const CANNED_TRACES: &str =
+        r#"[{"action":{"callType":"call","value":"0x0"},"blockNumber":5,"type":"call"}]"#;
+
+    /// Provider answering `trace_filter` with canned traces, recording the params it got.
+    #[derive(Default)]
+    struct TraceProvider {
+        params: Mutex<Vec<serde_json::Value>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for TraceProvider {
+        async fn raw_request_dyn(
+            &self,
+            method: Cow<'static, str>,
+            params: &RawValue,
+        ) -> TransportResult<Box<RawValue>> {
+            assert_eq!(method, "trace_filter");
+            self.params
+                .lock()
+                .unwrap()
+                .push(serde_json::from_str(params.get()).unwrap());
+            Ok(RawValue::from_string(CANNED_TRACES.to_string()).unwrap())
+        }
+
+        fn root(&self) -> &RootProvider {
+            unimplemented!("TraceProvider does not support `root`")
+        }
+    }
+
+    fn request(from_block: u64, to_block: u64) -> TraceFilterRequest {
+        TraceFilterRequest {
+            from_block,
+            to_block,
+            from_address: Some(address!("0000000000000000000000000000000000000001")),
+            to_address: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trace_filter_forwards_to_the_node() {
+        let provider = TraceProvider::default();
+        let config = TraceConfig { max_range: 10 };
+        let traces = trace_filter(&provider, &request(5, 14), &config)
+            .await
+            .unwrap();
+
+        assert_eq!(traces.get(), CANNED_TRACES);
+        assert_eq!(
+            provider.params.lock().unwrap()[0],
+            serde_json::json!([{
+                "fromBlock": "0x5",
+                "toBlock": "0xe",
+                "fromAddress": ["0x0000000000000000000000000000000000000001"],
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trace_filter_caps_the_block_range() {
+        let provider = TraceProvider::default();
+        let config = TraceConfig { max_range: 10 };
+        for (from, to) in [(5, 15), (6, 5), (0, u64::MAX)] {
+            let error = trace_filter(&provider, &request(from, to), &config)
+                .await
+                .unwrap_err();
+            assert!(matches!(error, AppError::InvalidRequest(_)));
+        }
+        assert!(provider.params.lock().unwrap().is_empty());
+    }
+}
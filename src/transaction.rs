@@ -0,0 +1,248 @@
+use alloy::consensus::Transaction as _;
+use alloy::eips::eip2718::Encodable2718;
+use alloy::rpc::types::{Transaction, TransactionReceipt};
+use alloy_primitives::{Address, Bytes, B256};
+use serde::Serialize;
+
+/// Transaction summary returned by `/tx/{hash}`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TransactionResponse {
+    pub hash: B256,
+    pub from: Address,
+    /// `None` for contract creations.
+    pub to: Option<Address>,
+    pub value: String,
+    pub nonce: u64,
+    pub gas_limit: u64,
+    pub input: Bytes,
+    /// `None` while the transaction is pending.
+    pub block_number: Option<u64>,
+    pub transaction_index: Option<u64>,
+}
+
+impl From<&Transaction> for TransactionResponse {
+    fn from(tx: &Transaction) -> Self {
+        TransactionResponse {
+            hash: *tx.inner.tx_hash(),
+            from: tx.from,
+            to: tx.inner.to(),
+            value: tx.inner.value().to_string(),
+            nonce: tx.inner.nonce(),
+            gas_limit: tx.inner.gas_limit(),
+            input: tx.inner.input().clone(),
+            block_number: tx.block_number,
+            transaction_index: tx.transaction_index,
+        }
+    }
+}
+
+/// Receipt summary returned by `/receipt/{hash}`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ReceiptResponse {
+    pub transaction_hash: B256,
+    pub block_hash: Option<B256>,
+    pub block_number: Option<u64>,
+    pub transaction_index: Option<u64>,
+    pub from: Address,
+    pub to: Option<Address>,
+    /// `true` when the transaction succeeded.
+    pub status: bool,
+    pub gas_used: u64,
+    pub effective_gas_price: String,
+    /// Address of the created contract, for contract creations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract_address: Option<Address>,
+    pub log_count: usize,
+}
+
+impl From<&TransactionReceipt> for ReceiptResponse {
+    fn from(receipt: &TransactionReceipt) -> Self {
+        ReceiptResponse {
+            transaction_hash: receipt.transaction_hash,
+            block_hash: receipt.block_hash,
+            block_number: receipt.block_number,
+            transaction_index: receipt.transaction_index,
+            from: receipt.from,
+            to: receipt.to,
+            status: receipt.status(),
+            gas_used: receipt.gas_used,
+            effective_gas_price: receipt.effective_gas_price.to_string(),
+            contract_address: receipt.contract_address,
+            log_count: receipt.inner.logs().len(),
+        }
+    }
+}
+
+/// Where a transaction is, as returned by `/tx/{hash}/status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxStatus {
+    /// Known to the node but not in a block yet.
+    Pending,
+    Mined,
+    /// Never seen by the node, or dropped from its mempool.
+    Unknown,
+}
+
+/// Status summary returned by `/tx/{hash}/status`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TxStatusResponse {
+    pub hash: B256,
+    pub status: TxStatus,
+    /// Block including the transaction, once mined.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<u64>,
+}
+
+impl TxStatusResponse {
+    /// A receipt means mined; otherwise a transaction without a block is pending.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_tx_status_response() {
+    /// let status = TxStatusResponse::new(hash, None, None);
+    /// assert_eq!(status.status, TxStatus::Unknown);
+    /// # }
+    /// ```
+    pub fn new(hash: B256, receipt: Option<&TransactionReceipt>, tx: Option<&Transaction>) -> Self {
+        let (status, block_number) = match (receipt, tx) {
+            (Some(receipt), _) => (TxStatus::Mined, receipt.block_number),
+            (None, Some(tx)) if tx.block_number.is_some() => (TxStatus::Mined, tx.block_number),
+            (None, Some(_)) => (TxStatus::Pending, None),
+            (None, None) => (TxStatus::Unknown, None),
+        };
+        TxStatusResponse {
+            hash,
+            status,
+            block_number,
+        }
+    }
+}
+
+/// Raw transaction bytes: the RLP encoding, prefixed by the type byte for typed (EIP-2718)
+/// transactions, as returned by `eth_getRawTransactionByHash`.
+pub fn encode_transaction(tx: &Transaction) -> Bytes {
+    tx.inner.encoded_2718().into()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use alloy::consensus::{
+        Eip658Value, Receipt, ReceiptEnvelope, ReceiptWithBloom, SignableTransaction, TxEnvelope,
+        TxLegacy,
+    };
+    use alloy::eips::eip2718::Decodable2718;
+    use alloy_primitives::{PrimitiveSignature, TxKind, U256};
+
+    /// A signed legacy transfer included in block 1000.
+    pub fn dummy_transaction() -> Transaction {
+        dummy_call(Bytes::new())
+    }
+
+    /// `dummy_transaction`, calling its recipient with `input`.
+    pub fn dummy_call(input: Bytes) -> Transaction {
+        let tx = TxLegacy {
+            chain_id: Some(1),
+            nonce: 7,
+            gas_price: 1_000_000_000,
+            gas_limit: 21_000,
+            to: TxKind::Call(Address::repeat_byte(0x22)),
+            value: U256::from(1000),
+            input,
+        };
+        Transaction {
+            inner: TxEnvelope::Legacy(tx.into_signed(PrimitiveSignature::test_signature())),
+            block_hash: None,
+            block_number: Some(1000),
+            transaction_index: Some(0),
+            effective_gas_price: None,
+            from: Address::repeat_byte(0x11),
+        }
+    }
+
+    /// A successful receipt of `dummy_transaction`.
+    pub fn dummy_receipt() -> TransactionReceipt {
+        let tx = dummy_transaction();
+        TransactionReceipt {
+            inner: ReceiptEnvelope::Legacy(ReceiptWithBloom {
+                receipt: Receipt {
+                    status: Eip658Value::Eip658(true),
+                    cumulative_gas_used: 21_000,
+                    logs: Vec::new(),
+                },
+                logs_bloom: Default::default(),
+            }),
+            transaction_hash: *tx.inner.tx_hash(),
+            transaction_index: Some(0),
+            block_hash: None,
+            block_number: Some(1000),
+            gas_used: 21_000,
+            effective_gas_price: 1_000_000_000,
+            blob_gas_used: None,
+            blob_gas_price: None,
+            from: tx.from,
+            to: tx.inner.to(),
+            contract_address: None,
+        }
+    }
+
+    #[test]
+    fn test_tx_status_prefers_the_receipt() {
+        let mut tx = dummy_transaction();
+        let hash = *tx.inner.tx_hash();
+        let receipt = dummy_receipt();
+
+        let mined = TxStatusResponse::new(hash, Some(&receipt), Some(&tx));
+        assert_eq!(
+            (mined.status, mined.block_number),
+            (TxStatus::Mined, Some(1000))
+        );
+        // Included in a block whose receipt the node has not indexed yet.
+        let included = TxStatusResponse::new(hash, None, Some(&tx));
+        assert_eq!(included.status, TxStatus::Mined);
+
+        tx.block_number = None;
+        let pending = TxStatusResponse::new(hash, None, Some(&tx));
+        assert_eq!(
+            (pending.status, pending.block_number),
+            (TxStatus::Pending, None)
+        );
+        assert_eq!(
+            TxStatusResponse::new(hash, None, None).status,
+            TxStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn test_receipt_response_from_receipt() {
+        let response = ReceiptResponse::from(&dummy_receipt());
+        assert_eq!(
+            response.transaction_hash,
+            *dummy_transaction().inner.tx_hash()
+        );
+        assert!(response.status);
+        assert_eq!(response.gas_used, 21_000);
+        assert_eq!(response.effective_gas_price, "1000000000");
+        assert_eq!(response.log_count, 0);
+    }
+
+    #[test]
+    fn test_transaction_response_from_transaction() {
+        let tx = dummy_transaction();
+        let response = TransactionResponse::from(&tx);
+        assert_eq!(response.hash, *tx.inner.tx_hash());
+        assert_eq!(response.to, Some(Address::repeat_byte(0x22)));
+        assert_eq!(response.value, "1000");
+        assert_eq!(response.nonce, 7);
+    }
+
+    #[test]
+    fn test_encode_transaction_round_trips() {
+        let tx = dummy_transaction();
+        let encoded = encode_transaction(&tx);
+        let decoded = TxEnvelope::decode_2718(&mut encoded.as_ref()).unwrap();
+        assert_eq!(decoded, tx.inner);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::config;
+use std::env;
+use std::path::PathBuf;
+use warp::filters::BoxedFilter;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+/// Page served at `/ui` when no `WEB_ROOT` file shadows it.
+const INDEX_HTML: &str = include_str!("../static/index.html");
+
+/// Settings of the built-in web UI.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UiConfig {
+    /// Directory whose files are served under `/ui/`.
+    pub web_root: Option<PathBuf>,
+}
+
+/// Reads the UI settings; returns `None` unless `ENABLE_UI=true`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_get_ui_config() {
+/// let ui = get_ui_config();
+/// assert!(ui.is_none());
+/// # }
+/// ```
+pub fn get_ui_config() -> Option<UiConfig> {
+    if !config::env_flag("ENABLE_UI") {
+        return None;
+    }
+    Some(UiConfig {
+        web_root: env::var("WEB_ROOT").ok().map(PathBuf::from),
+    })
+}
+
+/// Routes serving the UI, or rejecting everything as not found when disabled.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_ui_routes() {
+/// let routes = ui_routes(Some(UiConfig::default()));
+/// # }
+/// ```
+pub fn ui_routes(config: Option<UiConfig>) -> BoxedFilter<(Response,)> {
+    let Some(config) = config else {
+        return warp::any()
+            .and_then(|| async { Err::<Response, _>(warp::reject::not_found()) })
+            .boxed();
+    };
+
+    let index = warp::path("ui")
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(|| warp::reply::html(INDEX_HTML).into_response());
+
+    match config.web_root {
+        Some(web_root) => warp::path("ui")
+            .and(warp::get())
+            .and(warp::fs::dir(web_root))
+            .map(|file: warp::fs::File| file.into_response())
+            .or(index)
+            .unify()
+            .boxed(),
+        None => index.boxed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::http::StatusCode;
+    use warp::test::request;
+
+    #[tokio::test]
+    async fn test_ui_serves_embedded_index_when_enabled() {
+        let routes = ui_routes(Some(UiConfig::default()));
+
+        let resp = request().method("GET").path("/ui").reply(&routes).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = String::from_utf8(resp.body().to_vec()).unwrap();
+        assert!(body.contains("<form id=\"balance-form\">"));
+    }
+
+    #[tokio::test]
+    async fn test_ui_serves_files_from_web_root() {
+        let routes = ui_routes(Some(UiConfig {
+            web_root: Some(PathBuf::from(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/static"
+            ))),
+        }));
+
+        let resp = request()
+            .method("GET")
+            .path("/ui/index.html")
+            .reply(&routes)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_ui_is_not_found_when_disabled() {
+        let routes = ui_routes(None);
+
+        let resp = request().method("GET").path("/ui").reply(&routes).await;
+
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
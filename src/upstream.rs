@@ -0,0 +1,174 @@
+use crate::error::AppError;
+use alloy::providers::Provider;
+use log::info;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+/// Builds a provider for an allowlisted upstream URL.
+pub type ProviderFactory = Box<dyn Fn(&str) -> anyhow::Result<Arc<dyn Provider>> + Send + Sync>;
+
+/// Upstreams clients may select per request with the `X-RPC-URL` header.
+pub struct UpstreamAllowlist {
+    allowed: HashSet<String>,
+    factory: ProviderFactory,
+    providers: Mutex<HashMap<String, Arc<dyn Provider>>>,
+}
+
+/// Normalizes a URL so `http://node:8545` and `http://node:8545/` compare equal.
+fn normalize(url: &str) -> Option<String> {
+    Url::parse(url.trim()).ok().map(String::from)
+}
+
+/// Scheme, host and port of a URL, leaving out the path and query where
+/// hosted RPC URLs usually carry their API key; for logs and error messages.
+pub fn origin(url: &str) -> String {
+    Url::parse(url.trim())
+        .map(|url| url.origin().ascii_serialization())
+        .unwrap_or_else(|_| "(invalid URL)".to_string())
+}
+
+impl UpstreamAllowlist {
+    pub fn new<I, S>(urls: I, factory: ProviderFactory) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        UpstreamAllowlist {
+            allowed: urls
+                .into_iter()
+                .filter_map(|url| normalize(url.as_ref()))
+                .collect(),
+            factory,
+            providers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Provider for `url`, built on first use and reused afterwards.
+    ///
+    /// A provider that fails to build is not kept, so the next request tries again.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn test_provider_for() {
+    /// let allowlist = UpstreamAllowlist::new(["http://node:8545"], factory);
+    /// let provider = allowlist.provider_for("http://node:8545/").unwrap();
+    /// # }
+    /// ```
+    pub fn provider_for(&self, url: &str) -> Result<Arc<dyn Provider>, AppError> {
+        let normalized = normalize(url)
+            .filter(|normalized| self.allowed.contains(normalized))
+            .ok_or_else(|| AppError::UpstreamNotAllowed(origin(url)))?;
+
+        let mut providers = self.providers.lock().expect("upstream providers poisoned");
+        if let Some(provider) = providers.get(&normalized) {
+            return Ok(provider.clone());
+        }
+        info!(
+            "Connecting to allowlisted upstream: {}",
+            origin(&normalized)
+        );
+        let provider = (self.factory)(&normalized).map_err(|error| {
+            AppError::Internal(format!(
+                "Failed to build the upstream provider: {:#}",
+                error
+            ))
+        })?;
+        providers.insert(normalized, provider.clone());
+        Ok(provider)
+    }
+}
+
+/// Retrieves the URLs clients may select from the comma-separated `RPC_URL_ALLOWLIST`.
+pub fn get_rpc_url_allowlist() -> Vec<String> {
+    env::var("RPC_URL_ALLOWLIST")
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::providers::RootProvider;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct NullProvider;
+
+    impl Provider for NullProvider {
+        fn root(&self) -> &RootProvider {
+            unimplemented!("NullProvider does not support `root`")
+        }
+    }
+
+    fn allowlist(built: Arc<AtomicUsize>) -> UpstreamAllowlist {
+        UpstreamAllowlist::new(
+            ["http://node-a:8545"],
+            Box::new(move |_url| {
+                built.fetch_add(1, Ordering::Relaxed);
+                Ok(Arc::new(NullProvider))
+            }),
+        )
+    }
+
+    #[test]
+    fn test_allowlisted_provider_is_built_once() {
+        let built = Arc::new(AtomicUsize::new(0));
+        let allowlist = allowlist(built.clone());
+
+        let first = allowlist.provider_for("http://node-a:8545").unwrap();
+        let second = allowlist.provider_for("http://node-a:8545/").unwrap();
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(built.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_unlisted_url_is_forbidden() {
+        let built = Arc::new(AtomicUsize::new(0));
+        let allowlist = allowlist(built.clone());
+
+        for url in ["http://node-b:8545", "not a url"] {
+            let error = allowlist.provider_for(url).err().unwrap();
+            assert!(matches!(error, AppError::UpstreamNotAllowed(_)));
+        }
+        assert_eq!(built.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_failed_provider_is_not_kept() {
+        let built = Arc::new(AtomicUsize::new(0));
+        let counted = built.clone();
+        let allowlist = UpstreamAllowlist::new(
+            ["http://node-a:8545"],
+            Box::new(move |_url| {
+                counted.fetch_add(1, Ordering::Relaxed);
+                anyhow::bail!("no client")
+            }),
+        );
+
+        for _ in 0..2 {
+            let error = allowlist.provider_for("http://node-a:8545").err().unwrap();
+            assert!(matches!(error, AppError::Internal(_)));
+        }
+        assert_eq!(built.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_origin_leaves_out_credentials() {
+        assert_eq!(
+            origin("https://eth.example.com/v3/secret-key?token=abc"),
+            "https://eth.example.com"
+        );
+        assert_eq!(origin("http://node-a:8545/"), "http://node-a:8545");
+        assert_eq!(origin("not a url"), "(invalid URL)");
+    }
+}
@@ -0,0 +1,104 @@
+use alloy_primitives::Address;
+use serde::Serialize;
+
+/// Result of validating an address typed by a user.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ValidationResponse {
+    pub valid: bool,
+    /// EIP-55 form of the address, whenever it could be parsed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksummed: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Parses `input` as an address and verifies its EIP-55 checksum.
+///
+/// All-lowercase and all-uppercase addresses carry no checksum and are valid;
+/// mixed-case ones must match theirs exactly.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_validate_address() {
+/// let response = validate_address("0xd8da6bf26964af9d7eed9e03e53415d37aa96045");
+/// assert!(response.valid);
+/// assert_eq!(response.checksummed.unwrap(), "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045");
+/// # }
+/// ```
+pub fn validate_address(input: &str) -> ValidationResponse {
+    let address = match input.parse::<Address>() {
+        Ok(address) => address,
+        Err(error) => {
+            return ValidationResponse {
+                valid: false,
+                checksummed: None,
+                reason: Some(format!("not an address: {}", error)),
+            }
+        }
+    };
+
+    let checksummed = address.to_checksum(None);
+    let digits = input.strip_prefix("0x").unwrap_or(input);
+    let mixed_case = digits.chars().any(|c| c.is_ascii_lowercase())
+        && digits.chars().any(|c| c.is_ascii_uppercase());
+    let reason = (mixed_case && digits != &checksummed[2..])
+        .then(|| "mixed-case address does not match its EIP-55 checksum".to_string());
+
+    ValidationResponse {
+        valid: reason.is_none(),
+        checksummed: Some(checksummed),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECKSUMMED: &str = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045";
+
+    #[test]
+    fn test_checksummed_address_is_valid() {
+        let response = validate_address(CHECKSUMMED);
+        assert!(response.valid);
+        assert_eq!(response.checksummed.as_deref(), Some(CHECKSUMMED));
+        assert_eq!(response.reason, None);
+    }
+
+    #[test]
+    fn test_single_case_addresses_are_valid_and_rechecksummed() {
+        let lowercase = CHECKSUMMED.to_lowercase();
+        let uppercase = format!("0x{}", CHECKSUMMED[2..].to_uppercase());
+        for input in [lowercase.as_str(), uppercase.as_str(), &lowercase[2..]] {
+            let response = validate_address(input);
+            assert!(response.valid, "{}", input);
+            assert_eq!(
+                response.checksummed.as_deref(),
+                Some(CHECKSUMMED),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_bad_checksum_is_invalid() {
+        // Flip the case of the first letter.
+        let input = CHECKSUMMED.replacen('d', "D", 1);
+        let response = validate_address(&input);
+        assert!(!response.valid);
+        assert_eq!(response.checksummed.as_deref(), Some(CHECKSUMMED));
+        assert!(response.reason.unwrap().contains("EIP-55"));
+    }
+
+    #[test]
+    fn test_malformed_input_is_invalid() {
+        for input in ["", "0x1234", "hello", &format!("{}00", CHECKSUMMED)] {
+            let response = validate_address(input);
+            assert!(!response.valid, "{}", input);
+            assert_eq!(response.checksummed, None, "{}", input);
+            assert!(response.reason.is_some(), "{}", input);
+        }
+    }
+}
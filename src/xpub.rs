@@ -0,0 +1,91 @@
+use crate::error::AppError;
+use alloy::signers::utils::public_key_to_address;
+use alloy_primitives::Address;
+use coins_bip32::enc::{MainnetEncoder, XKeyEncoder};
+use coins_bip32::prelude::{Parent, XPub};
+
+/// Addresses derived when `?count=` is not given, the BIP-44 gap limit.
+pub const DEFAULT_XPUB_ADDRESSES: u32 = 20;
+
+/// Most addresses derived from one extended public key per request.
+pub const MAX_XPUB_ADDRESSES: u32 = 100;
+
+/// Length of a base58check-encoded mainnet extended public key.
+const XPUB_LEN: usize = 111;
+
+/// Parses a base58check extended public key (`xpub...`).
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_parse_xpub() {
+/// assert!(parse_xpub("xpub-not-really").is_err());
+/// # }
+/// ```
+pub fn parse_xpub(xpub: &str) -> Result<XPub, AppError> {
+    // The decoder panics on inputs shorter than a checksum, so the shape is checked first.
+    if !xpub.starts_with("xpub") || xpub.len() != XPUB_LEN {
+        return Err(AppError::InvalidRequest(format!(
+            "invalid xpub: expected {} characters starting with xpub",
+            XPUB_LEN
+        )));
+    }
+    MainnetEncoder::xpub_from_base58(xpub)
+        .map_err(|error| AppError::InvalidRequest(format!("invalid xpub: {}", error)))
+}
+
+/// The first `count` receiving addresses of an account-level key
+/// (`m/44'/60'/0'`), i.e. the non-hardened children `0/0` to `0/(count - 1)`.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn test_derive_addresses() {
+/// let addresses = derive_addresses(&parse_xpub(xpub).unwrap(), 5).unwrap();
+/// assert_eq!(addresses.len(), 5);
+/// # }
+/// ```
+pub fn derive_addresses(xpub: &XPub, count: u32) -> Result<Vec<Address>, AppError> {
+    let derive = |key: &XPub, index: u32| {
+        key.derive_child(index).map_err(|error| {
+            AppError::InvalidRequest(format!("cannot derive {}: {}", index, error))
+        })
+    };
+    let external = derive(xpub, 0)?;
+    (0..count)
+        .map(|index| Ok(public_key_to_address(derive(&external, index)?.as_ref())))
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloy_primitives::address;
+
+    /// Account key `m/44'/60'/0'` of the `test test ... junk` development mnemonic.
+    pub(crate) const XPUB: &str = "xpub6Ce9NcJvTk36xtLSrJLZqE7wtgA5deCeYs7rSQtreh4cj6ByPtrg9sD7V2FNFLPnf8heNP3FGkeV9qwfzvZNSd54JoNXVsXFYSYwHsnJxqP";
+
+    /// Its first receiving addresses, the well-known development accounts.
+    pub(crate) const ADDRESSES: [Address; 3] = [
+        address!("f39Fd6e51aad88F6F4ce6aB8827279cffFb92266"),
+        address!("70997970C51812dc3A010C7d01b50e0d17dc79C8"),
+        address!("3C44CdDdB6a900fa2b585dd299e03d12FA4293BC"),
+    ];
+
+    #[test]
+    fn test_derives_the_receiving_addresses() {
+        let xpub = parse_xpub(XPUB).unwrap();
+        assert_eq!(derive_addresses(&xpub, 3).unwrap(), ADDRESSES);
+        assert!(derive_addresses(&xpub, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rejects_malformed_xpubs() {
+        // Altered last character, so the checksum no longer matches.
+        let tampered = format!("{}Q", &XPUB[..XPUB.len() - 1]);
+        let zpub = "zpub6rJfywekm784fUigX1upFQJxEcSyWtBeP6AJ1CgdQhpNqHpRuDBoPzXPXSAYF9hdUQwFsLENC5MavRAoSKPQ36SG3UmNfhAE5tgE4xXG1d6";
+        for xpub in ["", "xpub", "0x1234", zpub, tampered.as_str()] {
+            assert!(matches!(parse_xpub(xpub), Err(AppError::InvalidRequest(_))));
+        }
+    }
+}